@@ -0,0 +1,235 @@
+//! Append-only record of every settings change.
+//!
+//! Each change is one JSON line in `/etc/survon/config/config-history.jsonl`,
+//! numbered in order, so a bad change can be looked up and reverted by id.
+//! Entries are never rewritten; a revert is itself a new entry.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::schema::Value;
+
+/// Who made a change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// survon.sh's menu.
+    Menu,
+    Installer,
+    /// `survon config` typed by hand.
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::Menu => "menu",
+            Source::Installer => "installer",
+            Source::Cli => "cli",
+        })
+    }
+}
+
+/// A change to one setting that has not been recorded yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub key: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+    /// The entry this change undoes, for reverts.
+    pub reverts: Option<u64>,
+}
+
+/// A recorded change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: u64,
+    /// Seconds since the epoch.
+    pub time: u64,
+    pub source: Source,
+    pub key: String,
+    /// `None` when the change set a key that was not set before.
+    pub old: Option<Value>,
+    /// `None` when the change removed the key.
+    pub new: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverts: Option<u64>,
+}
+
+/// The history file.
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        History { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every entry, oldest first. A missing file is an empty history.
+    pub fn entries(&self) -> Result<Vec<Entry>> {
+        Self::parse(&self.path, &self.read()?)
+    }
+
+    pub fn entry(&self, id: u64) -> Result<Entry> {
+        self.entries()?
+            .into_iter()
+            .find(|e| e.id == id)
+            .ok_or(Error::NoSuchChange(id))
+    }
+
+    fn read(&self) -> Result<String> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => Ok(text),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(Error::io(&self.path, e)),
+        }
+    }
+
+    fn parse(path: &Path, text: &str) -> Result<Vec<Entry>> {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(n, line)| {
+                serde_json::from_str(line).map_err(|e| Error::Parse {
+                    path: path.to_path_buf(),
+                    message: format!("line {}: {e}", n + 1),
+                })
+            })
+            .collect()
+    }
+
+    /// `existing` history text with `changes` appended, numbered after the
+    /// last entry. For callers that write the file themselves.
+    pub fn extend(&self, existing: &str, changes: &[Change], source: Source) -> Result<String> {
+        let first = Self::parse(&self.path, existing)?
+            .last()
+            .map_or(1, |e| e.id + 1);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut text = existing.to_string();
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        for (id, change) in (first..).zip(changes) {
+            let entry = Entry {
+                id,
+                time,
+                source,
+                key: change.key.clone(),
+                old: change.old.clone(),
+                new: change.new.clone(),
+                reverts: change.reverts,
+            };
+            text.push_str(&serde_json::to_string(&entry).expect("entries serialize"));
+            text.push('\n');
+        }
+        Ok(text)
+    }
+
+    /// Appends `changes` to the file.
+    pub fn append(&self, changes: &[Change], source: Source) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let existing = self.read()?;
+        let text = self.extend(&existing, changes, source)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| Error::io(&self.path, e))?;
+        let new = &text[existing.len()..];
+        file.write_all(new.as_bytes())
+            .map_err(|e| Error::io(&self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use survon_test_support::Scratch;
+
+    fn text(value: &str) -> Option<Value> {
+        Some(Value::Text(value.into()))
+    }
+
+    #[test]
+    fn saves_append_numbered_entries() {
+        let dir = Scratch::new("append");
+        let path = dir.join("config.toml");
+        let mut config = Config::load(&path).unwrap();
+        config.set("LOG_LEVEL", "debug").unwrap();
+        config.save(Source::Installer).unwrap();
+        config.set("LOG_LEVEL", "debug").unwrap();
+        config.set("LOG_LEVEL", "warn").unwrap();
+        config.unset("DEBUG");
+        config.save(Source::Cli).unwrap();
+
+        let entries = config.history().entries().unwrap();
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|e| (e.id, e.source, e.old.clone(), e.new.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (1, Source::Installer, None, text("debug")),
+                (2, Source::Cli, text("debug"), text("warn")),
+            ]
+        );
+    }
+
+    #[test]
+    fn reverts_restore_the_old_value_as_a_new_entry() {
+        let dir = Scratch::new("revert");
+        let path = dir.join("config.toml");
+        let mut config = Config::load(&path).unwrap();
+        config.set("COUNCIL_STRATEGY", "botany").unwrap();
+        config.save(Source::Cli).unwrap();
+        config.set("COUNCIL_STRATEGY", "survival").unwrap();
+        config.save(Source::Cli).unwrap();
+
+        let mut config = Config::load(&path).unwrap();
+        let entry = config.history().entry(2).unwrap();
+        assert_eq!(config.revert(&entry).unwrap(), text("survival"));
+        config.save(Source::Cli).unwrap();
+        assert_eq!(config.get("COUNCIL_STRATEGY"), text("botany").as_ref());
+        let last = config.history().entry(3).unwrap();
+        assert_eq!(
+            (last.old, last.new, last.reverts),
+            (text("survival"), text("botany"), Some(2))
+        );
+
+        // Reverting the first change removes the key it added.
+        let first = config.history().entry(1).unwrap();
+        config.revert(&first).unwrap();
+        assert_eq!(config.get("COUNCIL_STRATEGY"), None);
+        assert!(matches!(
+            config.history().entry(9),
+            Err(Error::NoSuchChange(9))
+        ));
+    }
+
+    #[test]
+    fn damaged_lines_are_reported() {
+        let history = History::new("history.jsonl");
+        let err = history
+            .extend("{\"id\":1}\n", &[], Source::Cli)
+            .unwrap_err();
+        assert!(err.to_string().contains("line 1"), "{err}");
+    }
+}
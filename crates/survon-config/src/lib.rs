@@ -0,0 +1,37 @@
+//! Survon's settings store.
+//!
+//! Settings live in one TOML file, `/etc/survon/config/config.toml`, instead
+//! of `export` lines scattered through `~/.bashrc`. Known keys are typed by
+//! the [`schema`] and checked whenever they are set or loaded. Every save
+//! also writes `/etc/survon/config/survon.env`, which systemd units read with
+//! `EnvironmentFile=` and the launchers source before starting the runtime:
+//!
+//! ```toml
+//! COUNCIL_STRATEGY = "survival"
+//! DEBUG = false
+//! LLM_MODEL_NAME = "phi3-mini.gguf"
+//! LLM_MODEL_PATH = "/home/survon/bundled/models/phi3-mini.gguf"
+//! ```
+//!
+//! Every change is also appended to the [`history`], with who made it.
+
+pub mod error;
+pub mod history;
+pub mod schema;
+mod store;
+
+pub use error::{Error, Result};
+pub use history::{History, Source};
+pub use schema::{Kind, Value};
+pub use store::Config;
+
+/// The settings directory, writable by the installing user. It sits apart
+/// from the rest of `/etc/survon`, which stays root-owned because it holds
+/// the keys trusted to sign updates.
+pub const CONFIG_DIR: &str = "/etc/survon/config";
+pub const CONFIG_PATH: &str = "/etc/survon/config/config.toml";
+/// Written next to the config file on every save.
+pub const ENV_FILE_NAME: &str = "survon.env";
+pub const ENV_PATH: &str = "/etc/survon/config/survon.env";
+/// Kept next to the config file; see [`history`].
+pub const HISTORY_FILE_NAME: &str = "config-history.jsonl";
@@ -0,0 +1,424 @@
+//! The settings Survon components read: each one's type, allowed values,
+//! default, and which component reads it.
+//!
+//! Keys not listed here can still be stored with [`parse_custom`]; they are
+//! kept as plain text and exported like the rest, so a module can grow a
+//! setting before the schema knows about it. [`parse`] refuses them, which
+//! catches typos in interactive use.
+
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Strategies a council seat can run.
+pub const COUNCIL_STRATEGIES: &[&str] = &[
+    "librarian",
+    "medicine",
+    "mechanical",
+    "botany",
+    "veterinary",
+    "building",
+    "survival",
+];
+
+pub const LOG_LEVELS: &[&str] = &["error", "warn", "info", "debug", "trace"];
+
+/// What the boot selector can start by itself; `last` is whatever was
+/// chosen at the previous boot.
+pub const BOOT_TARGETS: &[&str] = &["runtime", "council-seat", "menu", "shell", "last"];
+
+/// How the runtime and council seat are started: by the boot selector on
+/// the console, or as systemd services.
+pub const LAUNCH_MODES: &[&str] = &["console", "systemd"];
+
+/// The type of a setting's value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    /// An absolute path.
+    Path,
+    /// Written as true/false; yes/no, on/off and 1/0 are accepted too.
+    Bool,
+    /// One of a fixed set of words.
+    Enum(&'static [&'static str]),
+    /// A whole number in `min..=max`, stored as text like any other
+    /// value that is not a bool.
+    Number {
+        min: u64,
+        max: u64,
+    },
+}
+
+impl Kind {
+    /// Parses `raw` as given on the command line.
+    pub fn parse(self, raw: &str) -> Result<Value, String> {
+        check_text(raw)?;
+        match self {
+            Kind::Text => Ok(Value::Text(raw.to_string())),
+            Kind::Path if Path::new(raw).is_absolute() => Ok(Value::Text(raw.to_string())),
+            Kind::Path => Err(format!("`{raw}` is not an absolute path")),
+            Kind::Bool => match raw.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Ok(Value::Bool(true)),
+                "false" | "no" | "off" | "0" => Ok(Value::Bool(false)),
+                _ => Err(format!("`{raw}` is not true or false")),
+            },
+            Kind::Enum(values) if values.contains(&raw) => Ok(Value::Text(raw.to_string())),
+            Kind::Enum(values) => Err(
+                match values.iter().find(|v| v.eq_ignore_ascii_case(raw.trim())) {
+                    Some(close) => format!("`{raw}` is not allowed; did you mean `{close}`?"),
+                    None => format!("`{raw}` is not one of {}", values.join(", ")),
+                },
+            ),
+            Kind::Number { min, max } => match raw.trim().parse::<u64>() {
+                Ok(n) if (min..=max).contains(&n) => Ok(Value::Text(n.to_string())),
+                _ => Err(format!("`{raw}` is not a whole number from {min} to {max}")),
+            },
+        }
+    }
+
+    /// Checks a value read back from the config file.
+    pub fn check(self, value: &Value) -> Result<(), String> {
+        match (self, value) {
+            (Kind::Bool, Value::Bool(_)) => Ok(()),
+            (_, Value::Bool(b)) => Err(format!("expected {self}, found `{b}`")),
+            (_, Value::Text(raw)) => self.parse(raw).map(drop),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Text => f.write_str("text"),
+            Kind::Path => f.write_str("an absolute path"),
+            Kind::Bool => f.write_str("true or false"),
+            Kind::Enum(values) => write!(f, "one of {}", values.join(", ")),
+            Kind::Number { min, max } => write!(f, "a whole number from {min} to {max}"),
+        }
+    }
+}
+
+/// A known setting.
+#[derive(Debug, Clone, Copy)]
+pub struct Key {
+    pub name: &'static str,
+    pub kind: Kind,
+    /// What the reading component assumes when the key is not set.
+    pub default: Option<&'static str>,
+    pub description: &'static str,
+    /// The components that read the key from their environment.
+    pub read_by: &'static [&'static str],
+}
+
+impl Key {
+    pub fn default_value(&self) -> Option<Value> {
+        self.default.map(|raw| {
+            self.kind
+                .parse(raw)
+                .expect("schema defaults are valid for their keys")
+        })
+    }
+}
+
+const RUNTIME: &str = "runtime-base-rust";
+const COUNCIL_SEAT: &str = "survon-council-seat";
+const BOOT_SELECTOR: &str = "boot selector";
+const INSTALLER: &str = "survon-installer";
+const SUPERVISOR: &str = "supervisor";
+const MODULE_MANAGER: &str = "survon module";
+
+pub const KEYS: &[Key] = &[
+    Key {
+        name: "LLM_MODEL_NAME",
+        kind: Kind::Text,
+        default: Some("phi3-mini.gguf"),
+        description: "File name of the GGUF model the summarizer loads.",
+        read_by: &[RUNTIME],
+    },
+    Key {
+        name: "LLM_MODEL_PATH",
+        kind: Kind::Path,
+        default: Some("/home/survon/bundled/models/phi3-mini.gguf"),
+        description: "Full path of the GGUF model; overrides LLM_MODEL_NAME.",
+        read_by: &[RUNTIME],
+    },
+    Key {
+        name: "DEBUG",
+        kind: Kind::Bool,
+        default: Some("false"),
+        description: "Write debug logs to ./logs/debug.log (cleared on each start).",
+        read_by: &[RUNTIME],
+    },
+    Key {
+        name: "COUNCIL_STRATEGY",
+        kind: Kind::Enum(COUNCIL_STRATEGIES),
+        default: Some("librarian"),
+        description: "Which expertise the council seat answers with.",
+        read_by: &[COUNCIL_SEAT],
+    },
+    Key {
+        name: "DATABASE_PATH",
+        kind: Kind::Path,
+        default: None,
+        description: "Knowledge database the council seat searches.",
+        read_by: &[COUNCIL_SEAT],
+    },
+    Key {
+        name: "LOG_LEVEL",
+        kind: Kind::Enum(LOG_LEVELS),
+        default: Some("info"),
+        description: "Least severe log messages the council seat writes.",
+        read_by: &[COUNCIL_SEAT],
+    },
+    Key {
+        name: "TERM",
+        kind: Kind::Text,
+        default: Some("xterm-256color"),
+        description: "Terminal type; the runtime's TUI needs 256 colors.",
+        read_by: &[RUNTIME, "login shells"],
+    },
+    Key {
+        name: "LAUNCH_MODE",
+        kind: Kind::Enum(LAUNCH_MODES),
+        default: Some("console"),
+        description: "console: autologin and the boot selector; systemd: services that restart the runtime and council seat. Switch with `survon launch-mode`.",
+        read_by: &[INSTALLER],
+    },
+    Key {
+        name: "BOOT_TARGET",
+        kind: Kind::Enum(BOOT_TARGETS),
+        default: Some("runtime"),
+        description: "What the console starts when the boot countdown runs out.",
+        read_by: &[BOOT_SELECTOR],
+    },
+    Key {
+        name: "BOOT_TIMEOUT",
+        kind: Kind::Number { min: 0, max: 600 },
+        default: Some("5"),
+        description: "Seconds the boot selector counts down; 0 starts BOOT_TARGET at once.",
+        read_by: &[BOOT_SELECTOR],
+    },
+    Key {
+        name: "CRASH_LOOP_LIMIT",
+        kind: Kind::Number { min: 0, max: 20 },
+        default: Some("3"),
+        description: "Failed runtime starts in a row after which the unit boots safe mode; 0 never does.",
+        read_by: &[BOOT_SELECTOR],
+    },
+    Key {
+        name: "CRASH_LOOP_SECONDS",
+        kind: Kind::Number { min: 0, max: 3600 },
+        default: Some("30"),
+        description: "A runtime that exits sooner than this counts as a failed start, even if it exits cleanly.",
+        read_by: &[BOOT_SELECTOR],
+    },
+    Key {
+        name: "RESTART_LIMIT",
+        kind: Kind::Number { min: 0, max: 100 },
+        default: Some("5"),
+        description: "Times in a row the runtime or council seat is restarted after failing, when launched from the menu or `survon`; 0 never restarts it.",
+        read_by: &[SUPERVISOR],
+    },
+    Key {
+        name: "MODULE_INDEXES",
+        kind: Kind::Text,
+        default: None,
+        description: "Signed module indexes to search and upgrade from, separated by spaces: directories or http(s):// mirrors. Indexes on USB drives are found without it.",
+        read_by: &[MODULE_MANAGER],
+    },
+];
+
+/// The schema entry for `name`, if it is a known setting.
+pub fn key(name: &str) -> Option<&'static Key> {
+    KEYS.iter().find(|k| k.name == name)
+}
+
+/// A stored setting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    Text(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Text(s) => f.write_str(s),
+        }
+    }
+}
+
+/// Like [`key`], but an unknown `name` is an error suggesting the setting
+/// that was probably meant.
+pub fn require(name: &str) -> Result<&'static Key> {
+    key(name).ok_or_else(|| unknown(name))
+}
+
+/// Parses `raw` for known setting `name`.
+pub fn parse(name: &str, raw: &str) -> Result<Value> {
+    require(name)?
+        .kind
+        .parse(raw)
+        .map_err(|reason| Error::invalid(name, reason))
+}
+
+/// Like [`parse`], but stores an unknown `name` as text instead of
+/// refusing it.
+pub fn parse_custom(name: &str, raw: &str) -> Result<Value> {
+    check_name(name)?;
+    match key(name) {
+        Some(_) => parse(name, raw),
+        None => Kind::Text
+            .parse(raw)
+            .map_err(|reason| Error::invalid(name, reason)),
+    }
+}
+
+fn unknown(name: &str) -> Error {
+    let close = KEYS
+        .iter()
+        .find(|k| {
+            k.name.eq_ignore_ascii_case(name)
+                || (name.len() >= 3 && k.name.contains(&name.to_ascii_uppercase()))
+        })
+        .map(|k| k.name);
+    Error::UnknownKey {
+        key: name.to_string(),
+        suggestion: close,
+    }
+}
+
+/// Setting names become environment variables, so they must be valid
+/// shell identifiers.
+pub fn check_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::invalid(name, "not a valid variable name"))
+    }
+}
+
+fn check_text(raw: &str) -> Result<(), String> {
+    if raw.chars().any(char::is_control) {
+        Err("value may not contain newlines or other control characters".into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(name: &str, raw: &str) -> String {
+        match parse(name, raw) {
+            Err(Error::Invalid { reason, .. }) => reason,
+            other => panic!("{name}={raw}: expected Invalid, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn bools_accept_the_usual_spellings() {
+        for raw in ["true", "YES", "on", "1"] {
+            assert_eq!(parse("DEBUG", raw).unwrap(), Value::Bool(true));
+        }
+        for raw in ["false", "No", "off", "0"] {
+            assert_eq!(parse("DEBUG", raw).unwrap(), Value::Bool(false));
+        }
+        assert_eq!(reason("DEBUG", "maybe"), "`maybe` is not true or false");
+    }
+
+    #[test]
+    fn enums_suggest_the_right_case() {
+        assert_eq!(
+            parse("COUNCIL_STRATEGY", "botany").unwrap(),
+            Value::Text("botany".into())
+        );
+        assert_eq!(
+            reason("COUNCIL_STRATEGY", "Botany"),
+            "`Botany` is not allowed; did you mean `botany`?"
+        );
+        assert!(reason("LOG_LEVEL", "loud").starts_with("`loud` is not one of error, warn"));
+    }
+
+    #[test]
+    fn numbers_are_range_checked() {
+        assert_eq!(
+            parse("BOOT_TIMEOUT", " 10 ").unwrap(),
+            Value::Text("10".into())
+        );
+        assert!(parse("BOOT_TIMEOUT", "601").is_err());
+        assert!(parse("BOOT_TIMEOUT", "-1").is_err());
+        assert!(parse("BOOT_TIMEOUT", "soon").is_err());
+    }
+
+    #[test]
+    fn paths_must_be_absolute() {
+        assert!(parse("DATABASE_PATH", "/home/survon/db").is_ok());
+        assert_eq!(
+            reason("DATABASE_PATH", "db"),
+            "`db` is not an absolute path"
+        );
+    }
+
+    #[test]
+    fn control_characters_are_refused() {
+        assert!(parse("TERM", "xterm\nDEBUG=true").is_err());
+        assert!(parse_custom("GREETING", "hi\tthere").is_err());
+    }
+
+    #[test]
+    fn unknown_keys_suggest_a_known_one() {
+        assert!(matches!(
+            parse("debug", "true"),
+            Err(Error::UnknownKey {
+                suggestion: Some("DEBUG"),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("strategy", "botany"),
+            Err(Error::UnknownKey {
+                suggestion: Some("COUNCIL_STRATEGY"),
+                ..
+            })
+        ));
+        assert_eq!(
+            parse_custom("MY_SETTING", "1").unwrap(),
+            Value::Text("1".into())
+        );
+    }
+
+    #[test]
+    fn names_are_shell_identifiers() {
+        assert!(check_name("_PRIVATE2").is_ok());
+        for name in ["", "2FAST", "MY-SETTING", "A B"] {
+            assert!(check_name(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        for key in KEYS {
+            key.default_value();
+        }
+    }
+
+    #[test]
+    fn stored_values_are_checked_on_load() {
+        assert!(crate::Config::parse("config.toml", "DEBUG = \"maybe\"\n").is_err());
+        assert!(crate::Config::parse("config.toml", "BOOT_TIMEOUT = 9000\n").is_err());
+        assert!(crate::Config::parse("config.toml", "LOG_LEVEL = true\n").is_err());
+        assert!(crate::Config::parse("config.toml", "\"BAD-NAME\" = \"x\"\n").is_err());
+    }
+}
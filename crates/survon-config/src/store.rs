@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::history::{Change, Entry, History, Source};
+use crate::schema::{self, Value};
+
+const TOML_HEADER: &str = "\
+# Survon settings. Change them with `survon config set KEY VALUE`, or edit
+# this file and run `survon config export` to refresh the environment file.
+";
+
+/// The settings in one config file.
+#[derive(Debug, Clone)]
+pub struct Config {
+    path: PathBuf,
+    values: BTreeMap<String, Value>,
+    /// Changes since the config was loaded, not yet in the history.
+    changes: Vec<Change>,
+}
+
+impl Config {
+    /// Reads `path`; a missing file is an empty config.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::io(&path, e)),
+        };
+        Config::parse(path, &text)
+    }
+
+    /// Parses `text` as the contents of `path`, checking every value
+    /// against the schema.
+    pub fn parse(path: impl Into<PathBuf>, text: &str) -> Result<Self> {
+        let path = path.into();
+        let fail = |message: String| Error::Parse {
+            path: path.clone(),
+            message,
+        };
+        let table: toml::Table = toml::from_str(text).map_err(|e| fail(e.to_string()))?;
+        let mut values = BTreeMap::new();
+        for (name, value) in table {
+            let value = match value {
+                toml::Value::Boolean(b) => Value::Bool(b),
+                toml::Value::String(s) => Value::Text(s),
+                toml::Value::Integer(i) => Value::Text(i.to_string()),
+                toml::Value::Float(x) => Value::Text(x.to_string()),
+                other => {
+                    return Err(fail(format!(
+                        "{name}: expected a single value, found {}",
+                        other.type_str()
+                    )))
+                }
+            };
+            schema::check_name(&name).map_err(|e| fail(e.to_string()))?;
+            if let Some(key) = schema::key(&name) {
+                key.kind
+                    .check(&value)
+                    .map_err(|reason| fail(format!("{name}: {reason}")))?;
+            }
+            values.insert(name, value);
+        }
+        Ok(Config {
+            path,
+            values,
+            changes: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The environment file kept next to the config file.
+    pub fn env_path(&self) -> PathBuf {
+        self.path.with_file_name(crate::ENV_FILE_NAME)
+    }
+
+    /// The history kept next to the config file.
+    pub fn history(&self) -> History {
+        History::new(self.path.with_file_name(crate::HISTORY_FILE_NAME))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// The stored value of `name`, or its schema default.
+    pub fn effective(&self, name: &str) -> Option<Value> {
+        self.get(name)
+            .cloned()
+            .or_else(|| schema::key(name)?.default_value())
+    }
+
+    /// Validates `raw` for known setting `name` and stores it, returning
+    /// the value it replaced.
+    pub fn set(&mut self, name: &str, raw: &str) -> Result<Option<Value>> {
+        let value = schema::parse(name, raw)?;
+        Ok(self.store(name, Some(value), None))
+    }
+
+    /// Like [`Config::set`], but also stores settings the schema does not
+    /// know, as text.
+    pub fn set_custom(&mut self, name: &str, raw: &str) -> Result<Option<Value>> {
+        let value = schema::parse_custom(name, raw)?;
+        Ok(self.store(name, Some(value), None))
+    }
+
+    /// Removes `name`, returning the value it had.
+    pub fn unset(&mut self, name: &str) -> Option<Value> {
+        self.store(name, None, None)
+    }
+
+    /// Puts `entry`'s key back to the value it had before that change.
+    pub fn revert(&mut self, entry: &Entry) -> Result<Option<Value>> {
+        if let (Some(key), Some(old)) = (schema::key(&entry.key), &entry.old) {
+            key.kind
+                .check(old)
+                .map_err(|reason| Error::invalid(&entry.key, reason))?;
+        }
+        Ok(self.store(&entry.key, entry.old.clone(), Some(entry.id)))
+    }
+
+    fn store(&mut self, name: &str, value: Option<Value>, reverts: Option<u64>) -> Option<Value> {
+        let old = match &value {
+            Some(value) => self.values.insert(name.to_string(), value.clone()),
+            None => self.values.remove(name),
+        };
+        if old != value {
+            self.changes.push(Change {
+                key: name.to_string(),
+                old: old.clone(),
+                new: value,
+                reverts,
+            });
+        }
+        old
+    }
+
+    /// The changes made since loading, which the caller must now record.
+    /// [`Config::save`] does this itself.
+    pub fn take_changes(&mut self) -> Vec<Change> {
+        std::mem::take(&mut self.changes)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let body = toml::to_string(&self.values).expect("settings serialize as a flat table");
+        format!("{TOML_HEADER}\n{body}")
+    }
+
+    /// The settings as `KEY="value"` lines, which both systemd's
+    /// `EnvironmentFile=` and a shell's `.` read the same way.
+    pub fn to_env(&self) -> String {
+        let mut env = format!(
+            "# Generated from {}; change settings with `survon config`.\n",
+            self.path.display()
+        );
+        for (name, value) in &self.values {
+            env.push_str(&format!("{name}=\"{}\"\n", escape(&value.to_string())));
+        }
+        env
+    }
+
+    /// Writes the config file and regenerates the environment file, each
+    /// through a rename so readers never see a partial file, then records
+    /// the changes as made by `source`.
+    pub fn save(&mut self, source: Source) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+        }
+        write_atomic(&self.path, &self.to_toml())?;
+        self.export()?;
+        let changes = self.take_changes();
+        self.history().append(&changes, source)
+    }
+
+    /// Regenerates the environment file alone.
+    pub fn export(&self) -> Result<()> {
+        write_atomic(&self.env_path(), &self.to_env())
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let staged = path.with_extension("new");
+    fs::write(&staged, contents).map_err(|e| Error::io(&staged, e))?;
+    fs::rename(&staged, path).map_err(|e| Error::io(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use survon_test_support::Scratch;
+
+    #[test]
+    fn reads_single_values() {
+        let config = Config::parse(
+            "config.toml",
+            "DEBUG = true\nLLM_MODEL_NAME = \"phi3-mini.gguf\"\nRETRIES = 3\n",
+        )
+        .unwrap();
+        assert_eq!(config.get("DEBUG"), Some(&Value::Bool(true)));
+        assert_eq!(
+            config.get("LLM_MODEL_NAME"),
+            Some(&Value::Text("phi3-mini.gguf".into()))
+        );
+        assert_eq!(config.get("RETRIES"), Some(&Value::Text("3".into())));
+        assert!(Config::parse("config.toml", "PATHS = [\"/a\"]\n").is_err());
+    }
+
+    #[test]
+    fn unset_keys_fall_back_to_their_default() {
+        let config = Config::parse("config.toml", "").unwrap();
+        assert_eq!(config.get("DEBUG"), None);
+        assert_eq!(config.effective("DEBUG"), Some(Value::Bool(false)));
+        assert_eq!(config.effective("DATABASE_PATH"), None);
+    }
+
+    #[test]
+    fn env_file_quotes_for_the_shell() {
+        let mut config = Config::parse("/etc/survon/config/config.toml", "").unwrap();
+        config
+            .set_custom("GREETING", "say \"hi\" to $USER")
+            .unwrap();
+        config.set("DEBUG", "yes").unwrap();
+        let env = config.to_env();
+        assert!(env.contains("DEBUG=\"true\"\n"), "{env}");
+        assert!(env.contains(r#"GREETING="say \"hi\" to \$USER""#), "{env}");
+    }
+
+    #[test]
+    fn saved_settings_load_back() {
+        let dir = Scratch::new("save");
+        let path = dir.join("config.toml");
+        let mut config = Config::load(&path).unwrap();
+        config.set("COUNCIL_STRATEGY", "survival").unwrap();
+        config.set("DEBUG", "on").unwrap();
+        config.save(Source::Cli).unwrap();
+
+        let loaded = Config::load(&path).unwrap();
+        assert_eq!(loaded.iter().count(), 2);
+        assert_eq!(
+            loaded.get("COUNCIL_STRATEGY"),
+            Some(&Value::Text("survival".into()))
+        );
+        assert_eq!(loaded.get("DEBUG"), Some(&Value::Bool(true)));
+        assert!(fs::read_to_string(loaded.env_path())
+            .unwrap()
+            .contains("COUNCIL_STRATEGY=\"survival\""));
+    }
+}
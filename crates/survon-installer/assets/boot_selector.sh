@@ -0,0 +1,22 @@
+#!/bin/bash
+# boot_selector.sh - Console entry point, started from .bashrc on login.
+#
+# The boot selector itself is `survon boot` (default target and countdown
+# come from BOOT_TARGET and BOOT_TIMEOUT; see `survon config describe`).
+# This script only falls back to starting the runtime if the survon
+# command is missing.
+
+SURVON="/home/survon/.local/bin/survon"
+
+if [ -x "$SURVON" ]; then
+    exec "$SURVON" boot
+fi
+
+clear
+echo "Starting Survon Runtime..."
+cd /home/survon
+# Settings from /etc/survon/config/config.toml (see `survon config list`)
+if [ -f /etc/survon/config/survon.env ]; then
+    set -a; . /etc/survon/config/survon.env; set +a
+fi
+exec /usr/local/bin/runtime-base-rust
@@ -0,0 +1,116 @@
+//! Edits to the user's `~/.bashrc`, which starts the boot flow and loads
+//! the Survon settings into login shells.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::fsutil;
+use crate::system::System;
+
+pub fn contains(path: &Path, needle: &str) -> Result<bool> {
+    Ok(fsutil::read_or_empty(path)?.contains(needle))
+}
+
+/// Appends `block` unless a line containing `marker` is already present.
+/// Returns whether anything was written.
+pub fn append_once(system: &System, path: &Path, marker: &str, block: &str) -> Result<bool> {
+    system.edit_file(path, |contents| {
+        if contents.contains(marker) {
+            return contents.to_string();
+        }
+        let mut contents = contents.to_string();
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push('\n');
+        contents.push_str(block);
+        if !block.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents
+    })
+}
+
+/// Replaces the text `old` with `new`, if it is there. Returns whether
+/// anything was written.
+pub fn replace(system: &System, path: &Path, old: &str, new: &str) -> Result<bool> {
+    system.edit_file(path, |contents| contents.replacen(old, new, 1))
+}
+
+/// Every `export KEY=value` line, with one level of quotes removed from the
+/// value. A key exported twice keeps its last value, as in the shell.
+pub fn exports(path: &Path) -> Result<BTreeMap<String, String>> {
+    Ok(fsutil::read_or_empty(path)?
+        .lines()
+        .filter_map(|l| l.trim_start().strip_prefix("export ")?.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), unquote(value.trim()).to_string()))
+        .collect())
+}
+
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+        .unwrap_or(value)
+}
+
+/// Comments earlier installs wrote directly above the exports they added.
+const INSTALLER_COMMENTS: &[&str] = &["# Enable 256 color support for terminal"];
+
+/// Removes the `export` lines for `keys`, each with the comment line an
+/// earlier install wrote directly above it, if any. Comments of the user's
+/// own stay.
+pub fn remove_exports(system: &System, path: &Path, keys: &[&str]) -> Result<bool> {
+    system.edit_file(path, |contents| without_exports(contents, keys))
+}
+
+fn without_exports(contents: &str, keys: &[&str]) -> String {
+    let exported = |line: &str| {
+        line.trim_start()
+            .strip_prefix("export ")
+            .and_then(|rest| rest.split_once('='))
+            .is_some_and(|(key, _)| keys.contains(&key.trim()))
+    };
+    let mut kept: Vec<&str> = Vec::new();
+    for line in contents.lines() {
+        if exported(line) {
+            if kept
+                .last()
+                .is_some_and(|l| INSTALLER_COMMENTS.contains(&l.trim()))
+            {
+                kept.pop();
+            }
+        } else {
+            kept.push(line);
+        }
+    }
+    kept.iter().flat_map(|l| [*l, "\n"]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_exports_with_only_the_installers_comments() {
+        let bashrc = "\
+alias ll='ls -l'
+
+# Enable 256 color support for terminal
+export TERM=xterm-256color
+# my strategy, keep it
+export COUNCIL_STRATEGY=librarian
+export EDITOR=vim
+";
+        assert_eq!(
+            without_exports(bashrc, &["TERM", "COUNCIL_STRATEGY"]),
+            "\
+alias ll='ls -l'
+
+# my strategy, keep it
+export EDITOR=vim
+"
+        );
+    }
+}
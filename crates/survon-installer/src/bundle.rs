@@ -0,0 +1,119 @@
+//! Exporting and importing offline update bundles.
+//!
+//! The format lives in [`survon_update::bundle`]; this module decides what
+//! goes into a bundle and which installer steps use it. An export collects
+//! exactly the URLs those steps would download. An import verifies the
+//! bundle, redirects those URLs to it and re-runs the steps, so a unit
+//! updated from a USB drive ends up just like one updated online.
+
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use survon_update::bundle::{Bundle, BundleBuilder};
+use survon_update::runtime::{RUNTIME_NAME, RUNTIME_RELEASES_URL};
+use survon_update::Version;
+
+use crate::error::Result;
+use crate::models;
+use crate::self_update::{COMPANION_BINARY, RELEASES_URL};
+use crate::step::StepId;
+use crate::steps::{AUDIO_ARCHIVE_URL, RUNTIME_TARBALL_URL, SURVON_SH_URL};
+
+/// Architectures exported when none are asked for.
+pub const DEFAULT_ARCHS: [&str; 2] = ["armv7", "aarch64"];
+
+/// What to put in an exported bundle.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub out_dir: PathBuf,
+    /// Minisign secret key to sign the bundle with.
+    pub key: PathBuf,
+    /// The key's password; asked for on the terminal when `None`.
+    pub password: Option<String>,
+    pub version: Version,
+    pub archs: Vec<String>,
+    /// URLs of the models to carry.
+    pub models: Vec<String>,
+    pub audio: bool,
+}
+
+/// Downloads everything `opts` asks for and writes the signed bundle to
+/// `opts.out_dir`, returning the archive's path.
+pub fn export(opts: &ExportOptions) -> Result<PathBuf> {
+    let scratch = env::temp_dir().join(format!("survon-bundle-{}", std::process::id()));
+    let result = build(opts, &scratch);
+    let _ = std::fs::remove_dir_all(&scratch);
+    result
+}
+
+fn build(opts: &ExportOptions, scratch: &Path) -> Result<PathBuf> {
+    let mut builder = BundleBuilder::new(scratch)?;
+    let assets = |name: &str| -> Vec<String> {
+        opts.archs
+            .iter()
+            .map(|arch| format!("{name}-{arch}"))
+            .collect()
+    };
+
+    println!("Adding survon-installer release...");
+    let installer = [assets("survon-installer"), assets(COMPANION_BINARY)].concat();
+    builder.add_release(RELEASES_URL, "installer", &installer)?;
+    println!("Adding {RUNTIME_NAME} release...");
+    let runtime = builder.add_release(RUNTIME_RELEASES_URL, "runtime", &assets(RUNTIME_NAME))?;
+    println!("  runtime {}", runtime.version);
+    println!("Adding runtime modules and survon.sh...");
+    builder.add_download(RUNTIME_TARBALL_URL, "files/runtime-base-rust.tar.gz")?;
+    builder.add_download(SURVON_SH_URL, "files/survon.sh")?;
+    for url in &opts.models {
+        let name = models::url_file_name(url);
+        println!("Adding model {name} (this may take a while)...");
+        builder.add_download(url, &format!("files/models/{name}"))?;
+    }
+    if opts.audio {
+        println!("Adding jukebox audio...");
+        builder.add_download(AUDIO_ARCHIVE_URL, "files/BigBandMix.zip")?;
+    }
+
+    println!("Signing bundle...");
+    Ok(builder.finish(
+        &opts.version,
+        &opts.key,
+        opts.password.clone(),
+        &opts.out_dir,
+    )?)
+}
+
+/// The steps that download something `bundle` carries, and so have to run
+/// again for an import to take effect.
+pub fn steps_to_redo(bundle: &Bundle) -> HashSet<StepId> {
+    let fixed = [
+        (RUNTIME_RELEASES_URL, StepId::FetchBinary),
+        (RUNTIME_TARBALL_URL, StepId::DownloadRuntime),
+        (SURVON_SH_URL, StepId::FetchSurvonSh),
+        (AUDIO_ARCHIVE_URL, StepId::DownloadJukeboxAudio),
+    ];
+    let mut steps: HashSet<StepId> = fixed
+        .into_iter()
+        .filter(|(url, _)| bundle.manifest().sources.contains_key(*url))
+        .map(|(_, step)| step)
+        .collect();
+    if bundle
+        .manifest()
+        .sources
+        .values()
+        .any(|path| path.starts_with("files/models/"))
+    {
+        steps.insert(StepId::ModelSelection);
+    }
+    steps
+}
+
+/// The URL of the model `arg` names: `default`, a catalog model or a URL.
+pub fn model_url(arg: &str) -> &str {
+    if arg == "default" {
+        models::default_model().url
+    } else {
+        models::known(arg).map_or(arg, |known| known.url)
+    }
+}
@@ -0,0 +1,210 @@
+//! Descriptions of the mutations the installer makes to the system.
+//!
+//! Every mutating call on [`System`](crate::system::System) is recorded as a
+//! [`Change`]. In plan mode that record is all that happens, which is how
+//! `--plan` can list exactly what a run would do without doing it.
+
+use std::fmt;
+use std::path::PathBuf;
+
+/// New files longer than this are summarised instead of printed in full.
+const MAX_NEW_FILE_LINES: usize = 15;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Creates or rewrites a file; `before` is `None` when it did not exist.
+    WriteFile {
+        path: PathBuf,
+        before: Option<String>,
+        after: String,
+    },
+    CopyFile {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    CopyTree {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Remove {
+        path: PathBuf,
+    },
+    CreateDir {
+        path: PathBuf,
+    },
+    Symlink {
+        target: PathBuf,
+        link: PathBuf,
+    },
+    SetMode {
+        path: PathBuf,
+        mode: u32,
+    },
+    InstallPackages(Vec<String>),
+    RemovePackages(Vec<String>),
+    UpgradePackages,
+    Service {
+        action: String,
+        unit: String,
+    },
+    AddToGroup {
+        user: String,
+        group: String,
+    },
+    RemoveFromGroup {
+        user: String,
+        group: String,
+    },
+    Download {
+        url: String,
+        dest: PathBuf,
+    },
+    /// Any other command with side effects.
+    Command(String),
+}
+
+impl Change {
+    /// Kind tag shown in the plan's left-hand column.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Change::WriteFile { .. }
+            | Change::CopyFile { .. }
+            | Change::CopyTree { .. }
+            | Change::Move { .. }
+            | Change::Remove { .. }
+            | Change::CreateDir { .. }
+            | Change::Symlink { .. }
+            | Change::SetMode { .. } => "file",
+            Change::InstallPackages(_) | Change::RemovePackages(_) | Change::UpgradePackages => {
+                "package"
+            }
+            Change::Service { .. } => "service",
+            Change::AddToGroup { .. } | Change::RemoveFromGroup { .. } => "group",
+            Change::Download { .. } => "download",
+            Change::Command(_) => "command",
+        }
+    }
+
+    /// `+` creates, `-` removes, `~` modifies.
+    fn sign(&self) -> char {
+        match self {
+            Change::WriteFile { before: None, .. }
+            | Change::CopyFile { .. }
+            | Change::CopyTree { .. }
+            | Change::CreateDir { .. }
+            | Change::Symlink { .. }
+            | Change::InstallPackages(_)
+            | Change::AddToGroup { .. }
+            | Change::Download { .. } => '+',
+            Change::Remove { .. } | Change::RemovePackages(_) | Change::RemoveFromGroup { .. } => {
+                '-'
+            }
+            _ => '~',
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:<8} ", self.sign(), self.kind())?;
+        match self {
+            Change::WriteFile {
+                path,
+                before,
+                after,
+            } => {
+                write!(f, "{}", path.display())?;
+                if before.is_none() && after.lines().count() > MAX_NEW_FILE_LINES {
+                    return write!(f, " (new file, {} lines)", after.lines().count());
+                }
+                for line in diff_lines(before.as_deref().unwrap_or(""), after) {
+                    write!(f, "\n      {line}")?;
+                }
+                Ok(())
+            }
+            Change::CopyFile { from, to } => {
+                write!(f, "copy {} -> {}", from.display(), to.display())
+            }
+            Change::CopyTree { from, to } => {
+                write!(f, "copy {}/* -> {}", from.display(), to.display())
+            }
+            Change::Move { from, to } => write!(f, "move {} -> {}", from.display(), to.display()),
+            Change::Remove { path } => write!(f, "remove {}", path.display()),
+            Change::CreateDir { path } => write!(f, "mkdir {}", path.display()),
+            Change::Symlink { target, link } => {
+                write!(f, "link {} -> {}", link.display(), target.display())
+            }
+            Change::SetMode { path, mode } => write!(f, "chmod {mode:o} {}", path.display()),
+            Change::InstallPackages(packages) => write!(f, "apt install {}", packages.join(" ")),
+            Change::RemovePackages(packages) => write!(f, "apt remove {}", packages.join(" ")),
+            Change::UpgradePackages => f.write_str("apt update && apt upgrade"),
+            Change::Service { action, unit } => write!(f, "systemctl {action} {unit}"),
+            Change::AddToGroup { user, group } => write!(f, "add {user} to group {group}"),
+            Change::RemoveFromGroup { user, group } => {
+                write!(f, "remove {user} from group {group}")
+            }
+            Change::Download { url, dest } => write!(f, "{url} -> {}", dest.display()),
+            Change::Command(command) => f.write_str(command),
+        }
+    }
+}
+
+/// Line diff of `before` and `after`, as `-`/`+` lines for what changed.
+/// Config files are small, so a plain LCS table is fine.
+pub fn diff_lines(before: &str, after: &str) -> Vec<String> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out.push(format!("+{}", b[j]));
+            j += 1;
+        } else {
+            out.push(format!("-{}", a[i]));
+            i += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_lists_only_changed_lines() {
+        let before = "a\nb\nc\n";
+        let after = "a\nB\nc\nd\n";
+        assert_eq!(diff_lines(before, after), ["+B", "-b", "+d"]);
+    }
+
+    #[test]
+    fn identical_text_has_no_diff() {
+        assert!(diff_lines("a\nb\n", "a\nb\n").is_empty());
+    }
+
+    #[test]
+    fn diff_against_nothing() {
+        assert_eq!(diff_lines("", "x\ny"), ["+x", "+y"]);
+        assert_eq!(diff_lines("x\ny", ""), ["-x", "-y"]);
+    }
+}
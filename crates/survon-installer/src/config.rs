@@ -0,0 +1,108 @@
+//! Edits to the Survon settings store (see [`survon_config`]), which is where
+//! the runtime, the council seat and their launchers pick up their
+//! environment. Writes go through [`System`](crate::system::System) so they
+//! show up in plans and can be rolled back.
+
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use survon_config::{schema, Config, Source, ENV_FILE_NAME, HISTORY_FILE_NAME};
+
+use crate::context::Context;
+use crate::error::Result;
+use crate::fsutil;
+
+/// Where earlier installs kept the settings files, in a directory owned by
+/// the installing user. It also holds the trusted keys, so it goes back to
+/// root once the settings have moved out.
+const LEGACY_DIR: &str = "/etc/survon";
+
+/// Loads the settings, reading an earlier install's config file if nothing
+/// has been saved in the current location yet.
+pub fn load(ctx: &Context) -> Result<Config> {
+    match legacy_config(ctx) {
+        Some(legacy) => Ok(Config::parse(
+            ctx.config_path(),
+            &fsutil::read_or_empty(&legacy)?,
+        )?),
+        None => Ok(Config::load(ctx.config_path())?),
+    }
+}
+
+/// An earlier install's config file, while the current one does not exist.
+fn legacy_config(ctx: &Context) -> Option<PathBuf> {
+    let path = ctx.config_path();
+    let legacy = Path::new(LEGACY_DIR).join(path.file_name()?);
+    (legacy != path && !path.exists() && legacy.is_file()).then_some(legacy)
+}
+
+/// Whether `key` is currently set to `raw`, compared as typed values.
+pub fn has(ctx: &Context, key: &str, raw: &str) -> Result<bool> {
+    let value = schema::parse_custom(key, raw)?;
+    Ok(load(ctx)?.get(key) == Some(&value))
+}
+
+/// Stores every `(key, value)` pair, validating each, and regenerates the
+/// environment file.
+pub fn set<K, V>(ctx: &Context, settings: impl IntoIterator<Item = (K, V)>) -> Result<()>
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut config = load(ctx)?;
+    for (key, value) in settings {
+        config.set(key.as_ref(), value.as_ref())?;
+    }
+    save(ctx, &mut config)
+}
+
+/// Writes `config` and its environment file and records its changes in the
+/// history, creating the settings directory owned by the installing user so
+/// `survon config` works without root.
+pub fn save(ctx: &Context, config: &mut Config) -> Result<()> {
+    if let Some(dir) = config.path().parent() {
+        ctx.system.create_user_dir(dir, &ctx.user)?;
+    }
+    let legacy = legacy_config(ctx);
+    if legacy.is_some() {
+        ctx.say(format!(
+            "Moving settings from {LEGACY_DIR} to {}.",
+            config.path().parent().unwrap_or(config.path()).display()
+        ));
+        let history = Path::new(LEGACY_DIR).join(HISTORY_FILE_NAME);
+        if history.is_file() {
+            ctx.system.rename(&history, config.history().path())?;
+        }
+    }
+    let toml = config.to_toml();
+    ctx.system.edit_file(config.path(), |_| toml)?;
+    let env = config.to_env();
+    ctx.system.edit_file(&config.env_path(), |_| env)?;
+    let history = config.history();
+    let changes = config.take_changes();
+    if !changes.is_empty() {
+        let existing = fsutil::read_or_empty(history.path())?;
+        let updated = history.extend(&existing, &changes, Source::Installer)?;
+        ctx.system.edit_file(history.path(), |_| updated)?;
+    }
+    if let Some(legacy) = legacy {
+        ctx.system.remove(&legacy)?;
+        ctx.system
+            .remove(&Path::new(LEGACY_DIR).join(ENV_FILE_NAME))?;
+    }
+    reclaim_legacy_dir(ctx)
+}
+
+/// Hands [`LEGACY_DIR`] back to root if an earlier install left it owned by
+/// the installing user.
+fn reclaim_legacy_dir(ctx: &Context) -> Result<()> {
+    let dir = Path::new(LEGACY_DIR);
+    if fs::metadata(dir).is_ok_and(|meta| meta.uid() != 0) {
+        ctx.system
+            .run(ctx.system.privileged("chown").arg("root:root").arg(dir))?;
+        ctx.system
+            .run(ctx.system.privileged("chmod").arg("0755").arg(dir))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use crate::error::{Error, Result};
+use crate::provision::Provision;
+use crate::system::{Mode, System};
+
+/// State shared by all steps of one installer run.
+#[derive(Debug)]
+pub struct Context {
+    pub home: PathBuf,
+    pub user: String,
+    pub system: System,
+    pub prompt: Prompt,
+    /// Answers for an unattended install. Steps take their answers from
+    /// here instead of prompting when it is set.
+    pub provision: Option<Provision>,
+    /// Hostname chosen by the hostname step, if it ran.
+    pub hostname: Option<String>,
+    /// Artifacts registered by the step currently applying.
+    artifacts: Vec<PathBuf>,
+}
+
+impl Context {
+    pub fn from_env(mode: Mode) -> Self {
+        let home = env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/home/survon"));
+        let user = env::var("USER").unwrap_or_else(|_| "survon".to_string());
+        Context {
+            home,
+            user,
+            system: System::new(mode),
+            prompt: Prompt::default(),
+            provision: None,
+            hostname: None,
+            artifacts: Vec::new(),
+        }
+    }
+
+    /// Prints a progress message. Silent in plan mode, where nothing the
+    /// message could describe actually happens.
+    pub fn say(&self, message: impl AsRef<str>) {
+        if !self.system.is_planning() {
+            println!("{}", message.as_ref());
+            self.system.log().write(message.as_ref());
+        }
+    }
+
+    /// Registers a file or directory produced by the running step. Its
+    /// fingerprint goes into the install journal when the step completes.
+    pub fn artifact(&mut self, path: impl Into<PathBuf>) {
+        self.artifacts.push(path.into());
+    }
+
+    pub(crate) fn take_artifacts(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.artifacts)
+    }
+
+    /// Runs unattended, answering from `provision`. Any prompt that is
+    /// still reached becomes an error instead of blocking on the terminal.
+    pub fn provisioned(mut self, provision: Provision) -> Self {
+        self.prompt = Prompt { unattended: true };
+        self.provision = Some(provision);
+        self
+    }
+
+    /// The Survon settings file; see [`crate::config`].
+    pub fn config_path(&self) -> PathBuf {
+        PathBuf::from(survon_config::CONFIG_PATH)
+    }
+
+    pub fn bashrc(&self) -> PathBuf {
+        self.home.join(".bashrc")
+    }
+
+    pub fn modules_dir(&self) -> PathBuf {
+        self.home.join("modules")
+    }
+}
+
+/// Asks the user questions on the controlling terminal.
+///
+/// Reads from `/dev/tty` so prompts still work when the installer was started
+/// from `curl ... | bash` and stdin is the script itself.
+#[derive(Debug, Default)]
+pub struct Prompt {
+    unattended: bool,
+}
+
+impl Prompt {
+    pub fn ask(&self, question: &str) -> Result<String> {
+        if self.unattended {
+            return Err(Error::Input(format!(
+                "`{}` needs an answer, but the installer is running unattended",
+                question.trim_end_matches([':', ' '])
+            )));
+        }
+        print!("{question}");
+        io::stdout().flush().map_err(Error::Prompt)?;
+
+        let mut line = String::new();
+        match File::open("/dev/tty") {
+            Ok(tty) => BufReader::new(tty).read_line(&mut line),
+            Err(_) => io::stdin().lock().read_line(&mut line),
+        }
+        .map_err(Error::Prompt)?;
+        Ok(line.trim().to_string())
+    }
+
+    pub fn ask_default(&self, question: &str, default: &str) -> Result<String> {
+        let answer = self.ask(&format!("{question} [default: {default}]: "))?;
+        Ok(if answer.is_empty() {
+            default.to_string()
+        } else {
+            answer
+        })
+    }
+
+    pub fn confirm(&self, question: &str) -> Result<bool> {
+        let answer = self.ask(&format!("{question} (y/n): "))?;
+        Ok(matches!(answer.as_str(), "y" | "Y"))
+    }
+}
@@ -0,0 +1,81 @@
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::step::StepId;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to start `{command}`: {source}")]
+    Spawn { command: String, source: io::Error },
+
+    #[error("`{command}` failed ({status}){}", stderr_tail(.stderr))]
+    Command {
+        command: String,
+        status: String,
+        stderr: String,
+    },
+
+    #[error("{}: {source}", .path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("could not read answer: {0}")]
+    Prompt(io::Error),
+
+    #[error("invalid input: {0}")]
+    Input(String),
+
+    #[error("verification failed: {0}")]
+    Verify(String),
+
+    #[error("install journal: {0}")]
+    Journal(String),
+
+    #[error("rollback ledger: {0}")]
+    Ledger(String),
+
+    #[error("{}: {} problem(s):{}", .path.display(), .problems.len(), bullet_list(.problems))]
+    Provision {
+        path: PathBuf,
+        problems: Vec<String>,
+    },
+
+    #[error(transparent)]
+    Update(#[from] survon_update::Error),
+
+    #[error(transparent)]
+    Config(#[from] survon_config::Error),
+
+    #[error("step `{step}` depends on unknown step `{missing}`")]
+    UnknownDependency { step: StepId, missing: StepId },
+
+    #[error("dependency cycle between steps: {0}")]
+    DependencyCycle(String),
+}
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+fn bullet_list(items: &[String]) -> String {
+    items.iter().map(|i| format!("\n  - {i}")).collect()
+}
+
+/// Last few lines of a subprocess' stderr, appended to command errors so a
+/// failed step says why without the whole transcript.
+fn stderr_tail(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.is_empty() {
+        return String::new();
+    }
+    let start = lines.len().saturating_sub(3);
+    format!(": {}", lines[start..].join(" | "))
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+
+/// Recursively copies `src` into `dest`, overwriting files that exist in both.
+pub fn copy_dir_all(src: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest).map_err(|e| Error::io(dest, e))?;
+    for entry in fs::read_dir(src).map_err(|e| Error::io(src, e))? {
+        let entry = entry.map_err(|e| Error::io(src, e))?;
+        let from = entry.path();
+        let to = dest.join(entry.file_name());
+        if from.is_dir() {
+            copy_dir_all(&from, &to)?;
+        } else {
+            fs::copy(&from, &to).map_err(|e| Error::io(&from, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Paths of the files under `dir`, relative to it.
+pub fn files_under(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| Error::io(dir, e))? {
+        let entry = entry.map_err(|e| Error::io(dir, e))?;
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(
+                files_under(&path)?
+                    .into_iter()
+                    .map(|f| Path::new(&entry.file_name()).join(f)),
+            );
+        } else {
+            files.push(PathBuf::from(entry.file_name()));
+        }
+    }
+    Ok(files)
+}
+
+/// The SHA-256 of every file under `dir`, by its path relative to `dir`.
+pub fn hash_tree(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    for file in files_under(dir)? {
+        let hash = sha256_file(&dir.join(&file))?;
+        hashes.insert(file.to_string_lossy().into_owned(), hash);
+    }
+    Ok(hashes)
+}
+
+/// The files of `hashes` that are missing under `dir` or no longer have
+/// the recorded SHA-256, e.g. because someone edited them.
+pub fn changed_files(dir: &Path, hashes: &BTreeMap<String, String>) -> Vec<String> {
+    hashes
+        .iter()
+        .filter(|(file, hash)| sha256_file(&dir.join(file)).ok().as_ref() != Some(*hash))
+        .map(|(file, _)| file.clone())
+        .collect()
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// A file's contents, or the empty string if it does not exist.
+pub fn read_or_empty(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(s),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(Error::io(path, e)),
+    }
+}
+
+/// Hex-encoded SHA-256 of a file's contents.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| Error::io(path, e))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub fn sha256_str(contents: &str) -> String {
+    hex::encode(Sha256::digest(contents.as_bytes()))
+}
@@ -0,0 +1,297 @@
+//! Persistent record of installer progress.
+//!
+//! Each step's last status is stored together with the inputs it ran with and
+//! fingerprints of the files it produced. A later run reuses a completion only
+//! while the inputs are unchanged and every artifact is still intact, so a
+//! re-install after a power cut or a failed download picks up where it
+//! stopped instead of starting over.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::step::StepId;
+use crate::system::System;
+
+pub const DEFAULT_STATE_DIR: &str = "/var/lib/survon";
+const JOURNAL_FILE: &str = "install-journal.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// Started but never finished; the previous run was interrupted.
+    Started,
+    Completed,
+    Failed,
+}
+
+/// Fingerprint of a file or directory a step produced.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub path: PathBuf,
+    /// `None` for directories, which are only checked for existence.
+    pub sha256: Option<String>,
+    pub size: u64,
+    pub modified: u64,
+}
+
+impl Artifact {
+    pub fn capture(path: &Path) -> Result<Self> {
+        let meta = fs::metadata(path).map_err(|e| Error::io(path, e))?;
+        let sha256 = if meta.is_dir() {
+            None
+        } else {
+            Some(fsutil::sha256_file(path)?)
+        };
+        Ok(Artifact {
+            path: path.to_path_buf(),
+            sha256,
+            size: meta.len(),
+            modified: mtime(&meta),
+        })
+    }
+
+    /// Whether the artifact on disk still matches the fingerprint. Files whose
+    /// size and mtime are unchanged are trusted without re-hashing, which
+    /// keeps resuming cheap for multi-gigabyte models.
+    pub fn is_intact(&self) -> bool {
+        let Ok(meta) = fs::metadata(&self.path) else {
+            return false;
+        };
+        let Some(expected) = &self.sha256 else {
+            return meta.is_dir();
+        };
+        if meta.len() != self.size {
+            return false;
+        }
+        if mtime(&meta) == self.modified {
+            return true;
+        }
+        fsutil::sha256_file(&self.path).is_ok_and(|actual| &actual == expected)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub status: Status,
+    pub updated_at: u64,
+    #[serde(default)]
+    pub inputs: BTreeMap<String, String>,
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(default)]
+    steps: BTreeMap<String, Entry>,
+}
+
+impl Journal {
+    /// Loads the journal from `state_dir`, or starts an empty one if this is
+    /// the first run.
+    pub fn open(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(JOURNAL_FILE);
+        let mut journal: Journal = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| {
+                Error::Journal(format!(
+                    "{} is unreadable ({e}); re-run with --fresh to start over",
+                    path.display()
+                ))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Journal::default(),
+            Err(e) => return Err(Error::io(&path, e)),
+        };
+        journal.path = path;
+        Ok(journal)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Forgets every recorded step.
+    pub fn reset(&mut self) -> Result<()> {
+        self.steps.clear();
+        self.save()
+    }
+
+    pub fn entry(&self, id: StepId) -> Option<&Entry> {
+        self.steps.get(id.as_str())
+    }
+
+    /// The previous completion of `id`, if it ran with the same `inputs` and
+    /// all of its artifacts are still intact.
+    pub fn reusable(&self, id: StepId, inputs: &BTreeMap<String, String>) -> Option<&Entry> {
+        self.entry(id).filter(|e| {
+            e.status == Status::Completed
+                && &e.inputs == inputs
+                && e.artifacts.iter().all(Artifact::is_intact)
+        })
+    }
+
+    pub fn started(&mut self, id: StepId, inputs: BTreeMap<String, String>) -> Result<()> {
+        self.record(id, Status::Started, inputs, Vec::new(), None)
+    }
+
+    pub fn completed(
+        &mut self,
+        id: StepId,
+        inputs: BTreeMap<String, String>,
+        artifacts: Vec<Artifact>,
+    ) -> Result<()> {
+        self.record(id, Status::Completed, inputs, artifacts, None)
+    }
+
+    pub fn failed(
+        &mut self,
+        id: StepId,
+        inputs: BTreeMap<String, String>,
+        error: String,
+    ) -> Result<()> {
+        self.record(id, Status::Failed, inputs, Vec::new(), Some(error))
+    }
+
+    fn record(
+        &mut self,
+        id: StepId,
+        status: Status,
+        inputs: BTreeMap<String, String>,
+        artifacts: Vec<Artifact>,
+        error: Option<String>,
+    ) -> Result<()> {
+        self.steps.insert(
+            id.as_str().to_string(),
+            Entry {
+                status,
+                updated_at: now(),
+                inputs,
+                artifacts,
+                error,
+            },
+        );
+        self.save()
+    }
+
+    /// Writes the journal atomically so a power cut mid-write leaves the
+    /// previous version in place.
+    fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| Error::Journal(e.to_string()))?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| Error::io(&tmp, e))?;
+        fs::rename(&tmp, &self.path).map_err(|e| Error::io(&self.path, e))
+    }
+}
+
+/// Creates the state directory owned by `user`, so the installer can keep
+/// its journal there without running as root.
+pub fn ensure_state_dir(system: &System, dir: &Path, user: &str) -> Result<()> {
+    if writable(dir) {
+        return Ok(());
+    }
+    system.run(
+        system
+            .privileged("install")
+            .args(["-d", "-m", "0755", "-o", user])
+            .arg(dir),
+    )?;
+    Ok(())
+}
+
+fn writable(dir: &Path) -> bool {
+    let probe = dir.join(".write-test");
+    let ok = fs::write(&probe, b"").is_ok();
+    let _ = fs::remove_file(&probe);
+    ok
+}
+
+fn mtime(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use survon_test_support::Scratch;
+
+    fn inputs(url: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([("url".to_string(), url.to_string())])
+    }
+
+    #[test]
+    fn completions_are_reused_with_the_same_inputs() {
+        let dir = Scratch::new("inputs");
+        let mut journal = Journal::open(&dir).unwrap();
+        journal
+            .completed(StepId::DownloadRuntime, inputs("a"), Vec::new())
+            .unwrap();
+
+        let journal = Journal::open(&dir).unwrap();
+        assert!(journal
+            .reusable(StepId::DownloadRuntime, &inputs("a"))
+            .is_some());
+        assert!(journal
+            .reusable(StepId::DownloadRuntime, &inputs("b"))
+            .is_none());
+        assert!(journal.reusable(StepId::Hostname, &inputs("a")).is_none());
+    }
+
+    #[test]
+    fn unfinished_steps_are_not_reused() {
+        let dir = Scratch::new("status");
+        let mut journal = Journal::open(&dir).unwrap();
+        journal.started(StepId::AptUpdate, inputs("a")).unwrap();
+        journal
+            .failed(StepId::InstallDeps, inputs("a"), "no network".into())
+            .unwrap();
+        assert!(journal.reusable(StepId::AptUpdate, &inputs("a")).is_none());
+        assert!(journal
+            .reusable(StepId::InstallDeps, &inputs("a"))
+            .is_none());
+    }
+
+    #[test]
+    fn changed_artifacts_are_not_reused() {
+        let dir = Scratch::new("artifacts");
+        let model = dir.join("model.gguf");
+        fs::write(&model, "weights").unwrap();
+        let mut journal = Journal::open(&dir).unwrap();
+        journal
+            .completed(
+                StepId::ModelSelection,
+                inputs("a"),
+                vec![Artifact::capture(&model).unwrap()],
+            )
+            .unwrap();
+        assert!(journal
+            .reusable(StepId::ModelSelection, &inputs("a"))
+            .is_some());
+
+        fs::write(&model, "truncated").unwrap();
+        assert!(journal
+            .reusable(StepId::ModelSelection, &inputs("a"))
+            .is_none());
+        fs::remove_file(&model).unwrap();
+        assert!(journal
+            .reusable(StepId::ModelSelection, &inputs("a"))
+            .is_none());
+    }
+}
@@ -0,0 +1,38 @@
+//! Survon OS installer.
+//!
+//! Provisions a Raspberry Pi running Pi OS Lite into a Survon unit. Each piece
+//! of work is a [`Step`] with check/apply/verify phases, and the [`Pipeline`]
+//! runs them in dependency order, reporting the real outcome of every step.
+//! Progress is kept in a [`Journal`] so re-runs resume instead of redoing
+//! finished work, and every change a live run makes is recorded in a
+//! rollback [`Ledger`] so the machine can be returned to its pre-Survon
+//! state.
+
+pub mod bashrc;
+pub mod bundle;
+pub mod change;
+pub mod config;
+pub mod context;
+pub mod error;
+pub mod fsutil;
+pub mod journal;
+pub mod log;
+pub mod merge;
+pub mod models;
+pub mod pipeline;
+pub mod provision;
+pub mod rollback;
+pub mod self_update;
+pub mod step;
+pub mod steps;
+pub mod system;
+pub mod units;
+
+pub use change::Change;
+pub use context::Context;
+pub use error::{Error, Result};
+pub use journal::Journal;
+pub use pipeline::{Outcome, Pipeline, Report, RunOptions};
+pub use provision::Provision;
+pub use rollback::Ledger;
+pub use step::{Check, Step, StepId};
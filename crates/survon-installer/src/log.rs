@@ -0,0 +1,334 @@
+//! Per-step log files and the `--json` progress stream.
+//!
+//! A live run gets its own directory under `<state dir>/logs`, named after
+//! the time it started, with one file per step. Every line is timestamped
+//! and every subprocess the step runs is logged with its full stdout and
+//! stderr, so a failed step can be diagnosed after the fact. `logs/latest`
+//! points at the newest run.
+//!
+//! Independently of the files, [`Event`]s describing the run can be written
+//! as JSON lines for an outer tool (or the TUI) to render.
+
+use std::cell::{Cell, RefCell};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::fd::AsFd;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::journal::now;
+use crate::pipeline::Outcome;
+use crate::step::StepId;
+
+const LOG_DIR: &str = "logs";
+const LATEST: &str = "latest";
+
+/// One entry of the `--json` progress stream.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    RunStarted {
+        plan: bool,
+        steps: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        log_dir: Option<&'a Path>,
+    },
+    StepStarted {
+        step: StepId,
+        title: &'a str,
+        index: usize,
+        total: usize,
+        /// Share of the run's steps finished before this one.
+        percent: u8,
+    },
+    /// Progress within a step, currently from downloads.
+    Progress {
+        step: StepId,
+        percent: u8,
+    },
+    StepFinished {
+        step: StepId,
+        status: &'a str,
+    },
+    StepFailed {
+        step: StepId,
+        reason: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        log: Option<&'a Path>,
+    },
+    RunFinished {
+        ok: bool,
+    },
+}
+
+/// Where a run's log lines and events go. Either half may be switched off:
+/// plan runs keep no log files, and events are only written with `--json`.
+#[derive(Debug, Default)]
+pub struct Log {
+    dir: Option<PathBuf>,
+    file: RefCell<Option<(PathBuf, File)>>,
+    events: RefCell<Option<File>>,
+    step: Cell<Option<StepId>>,
+    percent: Cell<Option<u8>>,
+}
+
+impl Log {
+    /// Starts keeping step logs in a fresh run directory under `state_dir`.
+    pub fn keep_files(&mut self, state_dir: &Path) -> Result<()> {
+        let logs = state_dir.join(LOG_DIR);
+        let dir = logs.join(timestamp(now()).replace(':', ""));
+        fs::create_dir_all(&dir).map_err(|e| Error::io(&dir, e))?;
+        let latest = logs.join(LATEST);
+        let _ = fs::remove_file(&latest);
+        let _ = std::os::unix::fs::symlink(&dir, &latest);
+        self.dir = Some(dir);
+        Ok(())
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Sends events to stdout as JSON lines. From here on anything else the
+    /// process prints to stdout goes to stderr instead, so the stream stays
+    /// machine-readable whatever the steps print.
+    pub fn stream_events(&mut self) -> io::Result<()> {
+        io::stdout().flush()?;
+        let events = io::stdout().as_fd().try_clone_to_owned()?;
+        // SAFETY: dup2 on the process's own standard descriptors.
+        if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.events.replace(Some(File::from(events)));
+        Ok(())
+    }
+
+    pub fn event(&self, event: &Event) {
+        if let Some(out) = self.events.borrow_mut().as_mut() {
+            if let Ok(json) = serde_json::to_string(event) {
+                let _ = writeln!(out, "{json}");
+            }
+        }
+    }
+
+    /// Opens the log file for step `index` of `total` and announces it.
+    pub fn begin_step(&self, index: usize, total: usize, step: StepId, title: &str) {
+        self.step.set(Some(step));
+        self.percent.set(None);
+        if let Some(dir) = &self.dir {
+            let path = dir.join(format!("{index:02}-{step}.log"));
+            if let Ok(file) = File::create(&path) {
+                self.file.replace(Some((path, file)));
+            }
+        }
+        self.write(&format!("== {title} ({step})"));
+        self.event(&Event::StepStarted {
+            step,
+            title,
+            index,
+            total,
+            percent: ((index - 1) * 100 / total.max(1)) as u8,
+        });
+    }
+
+    /// Records how the current step ended and closes its log file.
+    pub fn end_step(&self, outcome: &Outcome) {
+        let Some(step) = self.step.take() else {
+            return;
+        };
+        self.write(&format!("== {outcome}"));
+        let path = self.file.take().map(|(path, _)| path);
+        match outcome {
+            Outcome::Failed(_) | Outcome::Blocked(_) => self.event(&Event::StepFailed {
+                step,
+                reason: outcome.to_string(),
+                log: path.as_deref(),
+            }),
+            _ => self.event(&Event::StepFinished {
+                step,
+                status: outcome.status(),
+            }),
+        }
+    }
+
+    /// The log file of the step currently running.
+    pub fn step_log(&self) -> Option<PathBuf> {
+        self.file.borrow().as_ref().map(|(path, _)| path.clone())
+    }
+
+    /// Appends a timestamped line to the current step's log.
+    pub fn write(&self, line: &str) {
+        if let Some((_, file)) = self.file.borrow_mut().as_mut() {
+            let _ = writeln!(file, "[{}] {line}", timestamp(now()));
+        }
+    }
+
+    /// Reports progress within the current step, once per whole percent.
+    pub fn progress(&self, percent: f32) {
+        let percent = percent.clamp(0.0, 100.0) as u8;
+        let Some(step) = self.step.get() else {
+            return;
+        };
+        if self.percent.replace(Some(percent)) != Some(percent) {
+            self.event(&Event::Progress { step, percent });
+        }
+    }
+}
+
+/// RFC 3339 UTC timestamp of `secs` since the epoch.
+pub fn timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from day count (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process::Command;
+    use survon_test_support::Scratch;
+
+    /// Set when the test binary re-runs itself to test `stream_events`.
+    const CHILD: &str = "SURVON_LOG_TEST_CHILD";
+
+    fn json(event: &Event) -> String {
+        serde_json::to_string(event).unwrap()
+    }
+
+    #[test]
+    fn events_serialize_with_kebab_case_tags() {
+        assert_eq!(
+            json(&Event::RunStarted {
+                plan: true,
+                steps: 3,
+                log_dir: None,
+            }),
+            r#"{"event":"run-started","plan":true,"steps":3}"#
+        );
+        assert_eq!(
+            json(&Event::StepStarted {
+                step: StepId::AptUpdate,
+                title: "Update package lists",
+                index: 2,
+                total: 4,
+                percent: 25,
+            }),
+            r#"{"event":"step-started","step":"apt-update","title":"Update package lists","index":2,"total":4,"percent":25}"#
+        );
+        assert_eq!(
+            json(&Event::StepFailed {
+                step: StepId::Hostname,
+                reason: "no network".into(),
+                log: Some(Path::new("/var/lib/survon/logs/latest/01-hostname.log")),
+            }),
+            r#"{"event":"step-failed","step":"hostname","reason":"no network","log":"/var/lib/survon/logs/latest/01-hostname.log"}"#
+        );
+    }
+
+    #[test]
+    fn steps_log_to_files_and_events_to_one_line_each() {
+        let dir = Scratch::new("log");
+        let mut log = Log::default();
+        log.keep_files(&dir).unwrap();
+        let events = dir.join("events.jsonl");
+        log.events.replace(Some(File::create(&events).unwrap()));
+
+        log.begin_step(1, 2, StepId::Hostname, "Set the hostname");
+        log.write("hostnamectl set-hostname survon");
+        log.progress(10.2);
+        log.progress(10.9);
+        log.progress(55.0);
+        log.end_step(&Outcome::Applied);
+        log.begin_step(2, 2, StepId::AptUpdate, "Update package lists");
+        log.end_step(&Outcome::Failed(Error::Input("no network".into())));
+        log.event(&Event::RunFinished { ok: false });
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&events)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let names: Vec<&str> = lines.iter().map(|e| e["event"].as_str().unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                "step-started",
+                "progress",
+                "progress",
+                "step-finished",
+                "step-started",
+                "step-failed",
+                "run-finished"
+            ]
+        );
+        assert_eq!(lines[2]["percent"], 55);
+        assert_eq!(lines[3]["status"], "applied");
+        assert_eq!(lines[4]["percent"], 50);
+
+        let run = log.dir().unwrap();
+        assert_eq!(fs::read_link(dir.join(LOG_DIR).join(LATEST)).unwrap(), run);
+        let hostname = fs::read_to_string(run.join("01-hostname.log")).unwrap();
+        let hostname: Vec<&str> = hostname.lines().collect();
+        assert_eq!(hostname.len(), 3);
+        assert!(hostname[0].ends_with("] == Set the hostname (hostname)"));
+        assert!(hostname[1].ends_with("] hostnamectl set-hostname survon"));
+        assert!(hostname[2].ends_with("] == applied"));
+        assert_eq!(
+            lines[5]["log"],
+            run.join("02-apt-update.log").to_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn streaming_events_moves_other_output_to_stderr() {
+        if env::var_os(CHILD).is_some() {
+            let mut log = Log::default();
+            log.stream_events().unwrap();
+            println!("human output");
+            Command::new("echo")
+                .arg("subprocess output")
+                .status()
+                .unwrap();
+            log.event(&Event::RunFinished { ok: true });
+            return;
+        }
+
+        let output = Command::new(env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "log::tests::streaming_events_moves_other_output_to_stderr",
+                "--nocapture",
+                "--quiet",
+            ])
+            .env(CHILD, "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        // The test harness's own banner precedes the switch.
+        let events: Vec<&str> = stdout.lines().filter(|l| l.starts_with('{')).collect();
+        assert_eq!(events, [r#"{"event":"run-finished","ok":true}"#]);
+        assert!(!stdout.contains("output"), "{stdout}");
+        assert!(stderr.contains("human output\n"), "{stderr}");
+        assert!(stderr.contains("subprocess output\n"), "{stderr}");
+    }
+}
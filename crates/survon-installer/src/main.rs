@@ -0,0 +1,713 @@
+use std::env;
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use survon_update::arch;
+use survon_update::bundle::{trusted_keys, Bundle, MEDIA_DIRS};
+use survon_update::runtime::{RuntimeUpdate, Store, RUNTIME_RELEASES_URL};
+use survon_update::Version;
+
+use survon_installer::bundle::{self, ExportOptions};
+use survon_installer::journal::{self, Journal};
+use survon_installer::log::{self, Event};
+use survon_installer::provision::RebootPolicy;
+use survon_installer::rollback::{self, RollbackOptions};
+use survon_installer::self_update::{self, SelfUpdate};
+use survon_installer::steps::update_runtime;
+use survon_installer::system::Mode;
+use survon_installer::{Context, Ledger, Pipeline, Provision, Report, RunOptions, StepId};
+
+/// Flags install.sh accepted that no longer map to a step. Still accepted so
+/// existing invocations keep working.
+const LEGACY_SKIP_FLAGS: [&str; 2] = ["skip-ble-config", "skip-update-check"];
+
+/// Tells a restarted `bundle import` which already verified, unpacked
+/// bundle to continue with.
+const UNPACKED_BUNDLE_ENV: &str = "SURVON_UNPACKED_BUNDLE";
+
+fn cli() -> Command {
+    let mut cmd = Command::new("survon-installer")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Installs and configures Survon OS on a Raspberry Pi")
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("self-update")
+                .about("Install the latest signed release of the installer")
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .action(ArgAction::SetTrue)
+                        .help("Only report whether a newer release exists"),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .value_name("URL")
+                        .default_value(self_update::RELEASES_URL)
+                        .help("Where the release manifest and assets are published"),
+                ),
+        )
+        .subcommand(
+            Command::new("runtime")
+                .about("Manage the installed Survon runtime versions")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("update")
+                        .about("Install and switch to the latest signed runtime release")
+                        .arg(
+                            Arg::new("check")
+                                .long("check")
+                                .action(ArgAction::SetTrue)
+                                .help("Only report whether a newer release exists"),
+                        )
+                        .arg(
+                            Arg::new("from")
+                                .long("from")
+                                .value_name("URL")
+                                .default_value(RUNTIME_RELEASES_URL)
+                                .help("Where the release manifest and assets are published"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("status").about("Show the current and installed runtime versions"),
+                ),
+        )
+        .subcommand(
+            Command::new("bundle")
+                .about("Carry updates to units without internet access")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("export")
+                        .about("Download everything an update needs into one signed archive")
+                        .arg(
+                            Arg::new("key")
+                                .long("key")
+                                .value_name("FILE")
+                                .required(true)
+                                .value_parser(value_parser!(PathBuf))
+                                .help("Minisign secret key to sign the bundle with (password from $SURVON_BUNDLE_PASSWORD or the terminal)"),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .value_name("DIR")
+                                .default_value(".")
+                                .value_parser(value_parser!(PathBuf))
+                                .help("Directory to write survon-bundle-<version>.tar to"),
+                        )
+                        .arg(
+                            Arg::new("arch")
+                                .long("arch")
+                                .value_name("ARCH")
+                                .action(ArgAction::Append)
+                                .value_parser(["armv7", "aarch64"])
+                                .help("Architecture to carry binaries for (default: both)"),
+                        )
+                        .arg(
+                            Arg::new("model")
+                                .long("model")
+                                .value_name("MODEL")
+                                .action(ArgAction::Append)
+                                .help("GGUF model to carry: a URL, a catalog model such as tinyllama, or `default` for the recommended one"),
+                        )
+                        .arg(
+                            Arg::new("audio")
+                                .long("audio")
+                                .action(ArgAction::SetTrue)
+                                .help("Carry the jukebox audio"),
+                        )
+                        .arg(
+                            Arg::new("bundle-version")
+                                .long("bundle-version")
+                                .value_name("VERSION")
+                                .value_parser(value_parser!(Version))
+                                .help("Version to give the bundle (default: today's date, e.g. 2026.10.18)"),
+                        ),
+                )
+                .subcommand(install_args(
+                    Command::new("import")
+                        .about("Verify a bundle and update this unit from it")
+                        .arg(
+                            Arg::new("path")
+                                .value_name("PATH")
+                                .value_parser(value_parser!(PathBuf))
+                                .help("Bundle archive or unpacked directory (default: newest on a mounted drive)"),
+                        ),
+                )),
+        )
+        .subcommand(
+            Command::new("rollback")
+                .visible_alias("uninstall")
+                .about("Restore every file, setting and service the installer changed")
+                .arg(
+                    Arg::new("plan")
+                        .long("plan")
+                        .action(ArgAction::SetTrue)
+                        .help("Print what would be restored, without restoring it"),
+                )
+                .arg(
+                    Arg::new("remove-packages")
+                        .long("remove-packages")
+                        .action(ArgAction::SetTrue)
+                        .help("Also remove the apt packages the installer added"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .short('y')
+                        .long("yes")
+                        .action(ArgAction::SetTrue)
+                        .help("Do not ask for confirmation"),
+                ),
+        )
+        .arg(
+            Arg::new("state-dir")
+                .long("state-dir")
+                .value_name("DIR")
+                .value_parser(value_parser!(PathBuf))
+                .default_value(journal::DEFAULT_STATE_DIR)
+                .global(true)
+                .help("Where the install journal and rollback ledger are kept"),
+        );
+    for flag in LEGACY_SKIP_FLAGS {
+        cmd = cmd.arg(
+            Arg::new(flag)
+                .long(flag)
+                .action(ArgAction::SetTrue)
+                .hide(true),
+        );
+    }
+    install_args(cmd)
+}
+
+/// Options of an installation run, shared by a plain run and `bundle
+/// import`.
+fn install_args(cmd: Command) -> Command {
+    let mut cmd = cmd
+        .arg(
+            Arg::new("redo")
+                .long("redo")
+                .value_name("STEP")
+                .action(ArgAction::Append)
+                .value_parser(StepId::ALL.map(|id| id.as_str()))
+                .help("Run STEP even if a previous run completed it"),
+        )
+        .arg(
+            Arg::new("plan")
+                .long("plan")
+                .action(ArgAction::SetTrue)
+                .help("Print every change the run would make, without making any"),
+        )
+        .arg(
+            Arg::new("provision")
+                .long("provision")
+                .value_name("FILE")
+                .value_parser(value_parser!(PathBuf))
+                .help("Install unattended, taking every answer from a TOML or YAML file"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Write progress events to stdout as JSON lines; other output goes to stderr"),
+        )
+        .arg(
+            Arg::new("fresh")
+                .long("fresh")
+                .action(ArgAction::SetTrue)
+                .help("Ignore the install journal and run every step"),
+        );
+    for id in StepId::ALL {
+        cmd = cmd.arg(
+            Arg::new(id.skip_flag())
+                .long(id.skip_flag())
+                .aliases(id.aliases().iter().map(|alias| format!("skip-{alias}")))
+                .action(ArgAction::SetTrue)
+                .help(format!("Skip the {id} step")),
+        );
+    }
+    cmd
+}
+
+fn run_options(matches: &ArgMatches, provision: Option<&Provision>) -> RunOptions {
+    let mut opts = RunOptions {
+        skip: StepId::ALL
+            .into_iter()
+            .filter(|id| matches.get_flag(&id.skip_flag()))
+            .collect(),
+        redo: matches
+            .get_many::<String>("redo")
+            .into_iter()
+            .flatten()
+            .filter_map(|s| s.parse().ok())
+            .collect(),
+    };
+    if let Some(provision) = provision {
+        opts.skip.extend(&provision.skip);
+    }
+    // A plan must not reset the journal, so --fresh just means "redo all".
+    if matches.get_flag("plan") && matches.get_flag("fresh") {
+        opts.redo.extend(StepId::ALL);
+    }
+    opts
+}
+
+fn state_dir(matches: &ArgMatches) -> &PathBuf {
+    matches
+        .get_one::<PathBuf>("state-dir")
+        .expect("state-dir has a default")
+}
+
+/// Opens the install journal and, for a live run, attaches the rollback
+/// ledger to `ctx.system`.
+fn open_journal(ctx: &Context, matches: &ArgMatches) -> survon_installer::Result<Journal> {
+    let state_dir = state_dir(matches);
+    if ctx.system.is_planning() {
+        return Journal::open(state_dir);
+    }
+    journal::ensure_state_dir(&ctx.system, state_dir, &ctx.user)?;
+    ctx.system.take_changes();
+    ctx.system.attach_ledger(Ledger::open(state_dir)?);
+    let mut journal = Journal::open(state_dir)?;
+    if matches.get_flag("fresh") {
+        journal.reset()?;
+    }
+    Ok(journal)
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    match matches.subcommand() {
+        Some(("rollback", sub)) => return run_rollback(sub),
+        Some(("self-update", sub)) => return run_self_update(sub),
+        Some(("runtime", sub)) => return run_runtime(sub),
+        Some(("bundle", sub)) => return run_bundle(sub),
+        _ => {}
+    }
+    install(&matches, None)
+}
+
+/// Runs the installation pipeline with the options in `matches`, taking
+/// downloads from `bundle` where it has them.
+fn install(matches: &ArgMatches, bundle: Option<Bundle>) -> ExitCode {
+    // Validated before anything else so a bad file changes nothing.
+    let provision = match matches
+        .get_one::<PathBuf>("provision")
+        .map(|p| Provision::load(p))
+    {
+        Some(Ok(provision)) => Some(provision),
+        Some(Err(e)) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+        None => None,
+    };
+    let mut opts = run_options(matches, provision.as_ref());
+
+    let mode = if matches.get_flag("plan") {
+        Mode::Plan
+    } else {
+        Mode::Live
+    };
+    let mut ctx = Context::from_env(mode);
+    if let Some(bundle) = bundle {
+        opts.redo.extend(bundle::steps_to_redo(&bundle));
+        ctx.system.use_bundle(bundle);
+    }
+    if matches.get_flag("json") {
+        if let Err(e) = ctx.system.log_mut().stream_events() {
+            eprintln!("Could not set up the JSON event stream: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Some(provision) = provision {
+        println!(
+            "Provisioning from {} (unattended)",
+            provision.path.display()
+        );
+        ctx = ctx.provisioned(provision);
+    }
+    if mode == Mode::Plan {
+        println!("Planning installation (nothing will be changed)...");
+    } else {
+        println!("Starting installation...");
+    }
+    let mut journal = match open_journal(&ctx, matches) {
+        Ok(journal) => journal,
+        Err(e) => {
+            eprintln!("Could not open install journal: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if mode == Mode::Live {
+        if let Err(e) = ctx.system.log_mut().keep_files(state_dir(matches)) {
+            eprintln!("Warning: could not create log directory: {e}");
+        }
+    }
+
+    let pipeline = Pipeline::standard();
+    ctx.system.log().event(&Event::RunStarted {
+        plan: mode == Mode::Plan,
+        steps: pipeline.steps().count(),
+        log_dir: ctx.system.log().dir(),
+    });
+    let report = pipeline.run(&mut ctx, &mut journal, &opts);
+    ctx.system.log().event(&Event::RunFinished {
+        ok: report.succeeded(),
+    });
+    if mode == Mode::Plan {
+        return print_plan_summary(&report);
+    }
+
+    println!("==========================================");
+    if let Some(dir) = ctx.system.log().dir() {
+        println!("Step logs: {}", dir.display());
+    }
+    if !report.succeeded() {
+        println!("Survon OS installation finished with errors:");
+        for step in report.failures() {
+            println!("  - {} ({}): {}", step.title, step.id, step.outcome);
+        }
+        println!("==========================================");
+        println!(
+            "Fix the problems above and re-run the installer; finished steps will not be repeated."
+        );
+        return ExitCode::FAILURE;
+    }
+
+    println!("Survon OS installed successfully!");
+    println!("==========================================");
+    println!();
+    let hostname = ctx
+        .hostname
+        .clone()
+        .unwrap_or_else(|| current_hostname(&ctx));
+    println!("Your system is accessible at: {hostname}.local");
+    println!();
+    println!("IMPORTANT: You need to REBOOT for BLE changes to take effect");
+    println!();
+    println!("After reboot:");
+    println!("  - User will be in 'bluetooth' group");
+    println!("  - BlueZ experimental features enabled");
+    println!("  - BLE should work with btleplug");
+    println!();
+    println!("If you still see DBus errors after reboot:");
+    println!("  1. Check BlueZ version: bluetoothctl --version");
+    println!("  2. Should be 5.50 or higher");
+    println!("  3. Run: sudo systemctl status bluetooth");
+    println!();
+
+    let reboot = match ctx.provision.as_ref().map(|p| p.reboot) {
+        Some(RebootPolicy::Always) => Ok(true),
+        Some(RebootPolicy::Never) => {
+            println!("Not rebooting (reboot = \"never\" in the provisioning file).");
+            Ok(false)
+        }
+        None => ctx.prompt.confirm("Reboot now?"),
+    };
+    match reboot {
+        Ok(true) => {
+            if let Err(e) = ctx.system.run(&mut ctx.system.privileged("reboot")) {
+                eprintln!("Could not reboot: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        Ok(false) => {}
+        Err(e) => eprintln!("{e}"),
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_self_update(matches: &ArgMatches) -> ExitCode {
+    let from = matches
+        .get_one::<String>("from")
+        .expect("from has a default");
+    match self_update::self_update(from, matches.get_flag("check")) {
+        Ok(SelfUpdate::UpToDate(version)) => {
+            println!("survon-installer {version} is up to date.");
+            ExitCode::SUCCESS
+        }
+        Ok(SelfUpdate::Available { from, to }) => {
+            println!("survon-installer {to} is available (installed: {from}).");
+            ExitCode::SUCCESS
+        }
+        Ok(SelfUpdate::Updated { from, to }) => {
+            println!("Updated survon-installer {from} -> {to}.");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Self-update failed: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_runtime(matches: &ArgMatches) -> ExitCode {
+    let store = Store::default();
+    let Some(("update", sub)) = matches.subcommand() else {
+        let current = store.current();
+        match &current {
+            Some(version) => println!("Current runtime: {version}"),
+            None => println!("No runtime installed under {}", store.root().display()),
+        }
+        if let Some(previous) = store.previous() {
+            println!("Previous runtime: {previous}");
+        }
+        for version in store.installed().unwrap_or_default() {
+            let mark = if Some(&version) == current.as_ref() {
+                "*"
+            } else {
+                " "
+            };
+            println!("  {mark} {version}  {}", store.binary(&version).display());
+        }
+        return ExitCode::SUCCESS;
+    };
+
+    let from = sub.get_one::<String>("from").expect("from has a default");
+    let ctx = Context::from_env(Mode::Live);
+    let result = if sub.get_flag("check") {
+        store
+            .update(from, arch::system(), true)
+            .map(Some)
+            .map_err(Into::into)
+    } else {
+        println!("Updating the Survon runtime...");
+        update_runtime(&ctx.system, &ctx.user, from)
+    };
+    let previous =
+        |from: Option<Version>| from.map_or_else(|| "none".to_string(), |v| v.to_string());
+    match result {
+        Ok(Some(RuntimeUpdate::UpToDate(version))) => {
+            println!("Runtime {version} is up to date.");
+            ExitCode::SUCCESS
+        }
+        Ok(Some(RuntimeUpdate::Available { from, to })) => {
+            println!("Runtime {to} is available (installed: {}).", previous(from));
+            ExitCode::SUCCESS
+        }
+        Ok(Some(RuntimeUpdate::Updated { from, to })) => {
+            println!("Updated runtime {} -> {to}.", previous(from));
+            ExitCode::SUCCESS
+        }
+        Ok(None) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Runtime update failed: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_bundle(matches: &ArgMatches) -> ExitCode {
+    match matches.subcommand() {
+        Some(("export", sub)) => run_bundle_export(sub),
+        Some(("import", sub)) => run_bundle_import(sub),
+        _ => unreachable!("bundle requires a subcommand"),
+    }
+}
+
+fn run_bundle_export(matches: &ArgMatches) -> ExitCode {
+    let version = matches
+        .get_one::<Version>("bundle-version")
+        .cloned()
+        .unwrap_or_else(|| {
+            let today = log::timestamp(journal::now());
+            let mut date = today[..10].split('-').map(|n| n.parse().unwrap_or(0));
+            let mut next = || date.next().unwrap_or(0);
+            Version::new(next(), next(), next())
+        });
+    let opts = ExportOptions {
+        out_dir: matches
+            .get_one::<PathBuf>("out")
+            .cloned()
+            .unwrap_or_default(),
+        key: matches
+            .get_one::<PathBuf>("key")
+            .cloned()
+            .expect("key is required"),
+        password: env::var("SURVON_BUNDLE_PASSWORD").ok(),
+        version,
+        archs: match matches.get_many::<String>("arch") {
+            Some(archs) => archs.cloned().collect(),
+            None => bundle::DEFAULT_ARCHS.map(String::from).to_vec(),
+        },
+        models: matches
+            .get_many::<String>("model")
+            .into_iter()
+            .flatten()
+            .map(|m| bundle::model_url(m).to_string())
+            .collect(),
+        audio: matches.get_flag("audio"),
+    };
+    match bundle::export(&opts) {
+        Ok(archive) => {
+            println!("Bundle written to {}", archive.display());
+            println!(
+                "Copy it to a USB drive and run `survon-installer bundle import` on the unit."
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Export failed: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_bundle_import(matches: &ArgMatches) -> ExitCode {
+    let state_dir = state_dir(matches);
+    let plan = matches.get_flag("plan");
+    // Set when the updated installer re-runs the import; see below.
+    let unpacked = env::var_os(UNPACKED_BUNDLE_ENV).map(PathBuf::from);
+    let path = unpacked.clone().or_else(|| {
+        matches.get_one::<PathBuf>("path").cloned().or_else(|| {
+            let media = MEDIA_DIRS.map(Path::new);
+            Bundle::find(&media)
+        })
+    });
+    let Some(path) = path else {
+        eprintln!("No survon-bundle-*.tar found under /media or /mnt; pass the bundle's path.");
+        return ExitCode::FAILURE;
+    };
+
+    println!("Verifying bundle {}...", path.display());
+    let keys = trusted_keys();
+    let staging = state_dir.join("bundle");
+    let opened = if path.is_dir() {
+        Bundle::open(&path, &keys).map_err(Into::into)
+    } else {
+        let ctx = Context::from_env(Mode::Live);
+        journal::ensure_state_dir(&ctx.system, state_dir, &ctx.user)
+            .and_then(|()| Ok(Bundle::unpack(&path, &staging, &keys)?))
+    };
+    let bundle = match opened {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("Bundle rejected: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    println!(
+        "Bundle {} verified ({} files).",
+        bundle.manifest().version,
+        bundle.manifest().artifacts.len()
+    );
+
+    // Update the installer first so the steps below are the bundled ones.
+    // The path has to be looked up before the executable is replaced.
+    let exe = env::current_exe().unwrap_or_else(|_| PathBuf::from("survon-installer"));
+    let installer_url = bundle
+        .local_url(self_update::RELEASES_URL)
+        .filter(|_| !plan && unpacked.is_none());
+    if let Some(url) = installer_url {
+        match self_update::self_update(&url, false) {
+            Ok(SelfUpdate::Updated { from, to }) => {
+                println!("Updated survon-installer {from} -> {to}; continuing with it...");
+                let e = process::Command::new(exe)
+                    .args(env::args_os().skip(1))
+                    .env(UNPACKED_BUNDLE_ENV, bundle.dir())
+                    .exec();
+                eprintln!("Could not restart the installer: {e}");
+                return ExitCode::FAILURE;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Bundled installer rejected: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let unpacked_here = bundle.dir() == staging;
+    let status = install(matches, Some(bundle));
+    if unpacked_here && !plan {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    status
+}
+
+fn run_rollback(matches: &ArgMatches) -> ExitCode {
+    let plan = matches.get_flag("plan");
+    let ctx = Context::from_env(if plan { Mode::Plan } else { Mode::Live });
+    let state_dir = state_dir(matches);
+    let mut ledger = match Ledger::open(state_dir) {
+        Ok(ledger) => ledger,
+        Err(e) => {
+            eprintln!("Could not open rollback ledger: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if ledger.is_empty() {
+        println!("Nothing to roll back: the installer has not changed anything yet.");
+        return ExitCode::SUCCESS;
+    }
+
+    let steps = ledger.transactions().len();
+    if plan {
+        println!("Planning rollback of {steps} installer step(s) (nothing will be changed)...");
+    } else {
+        println!(
+            "This restores everything {steps} installer step(s) changed to its pre-Survon state."
+        );
+        if !matches.get_flag("yes") {
+            match ctx.prompt.confirm("Continue?") {
+                Ok(true) => {}
+                Ok(false) => return ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+
+    let opts = RollbackOptions {
+        remove_packages: matches.get_flag("remove-packages"),
+    };
+    let ok = rollback::rollback(&ctx.system, &mut ledger, &opts);
+    println!("==========================================");
+    if plan {
+        println!("Nothing was modified.");
+        return ExitCode::SUCCESS;
+    }
+    if !ok {
+        println!("Rollback finished with errors; run it again to retry the failed items.");
+        return ExitCode::FAILURE;
+    }
+    // The journal describes work that no longer exists.
+    if let Err(e) = Journal::open(state_dir).and_then(|mut j| j.reset()) {
+        eprintln!("Warning: could not reset install journal: {e}");
+    }
+    println!(
+        "Survon changes rolled back. Reboot for the hostname and group changes to take effect."
+    );
+    ExitCode::SUCCESS
+}
+
+fn print_plan_summary(report: &Report) -> ExitCode {
+    let changes = report.changes().count();
+    let steps = report
+        .steps
+        .iter()
+        .filter(|s| !s.changes.is_empty())
+        .count();
+    println!("==========================================");
+    println!("Plan: {changes} change(s) across {steps} step(s). Nothing was modified.");
+    if report.succeeded() {
+        return ExitCode::SUCCESS;
+    }
+    println!("Some steps could not be planned:");
+    for step in report.failures() {
+        println!("  - {} ({}): {}", step.title, step.id, step.outcome);
+    }
+    ExitCode::FAILURE
+}
+
+fn current_hostname(ctx: &Context) -> String {
+    ctx.system
+        .probe(&mut ctx.system.command("hostname"))
+        .map(|h| h.trim().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,422 @@
+//! Three-way merging of YAML files, so that a module config edited on the
+//! unit can take upstream changes without losing the edits.
+//!
+//! The merge works on the parsed documents, key by key: a key changed on one
+//! side only takes that side's value, nested mappings are merged the same
+//! way, and a key changed differently on both sides keeps the local value
+//! and is reported as a [`Conflict`]. Lists and scalars are merged whole.
+//!
+//! The result is written back by patching the local text: only the
+//! top-level entries that changed are rewritten, so comments and quoting
+//! elsewhere in the file survive.
+
+use std::fmt;
+
+use serde_yaml::{Mapping, Value};
+
+/// A change made both locally and upstream that could not be combined.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict {
+    /// Both sides changed `key` (dotted for nested mappings), differently.
+    /// `None` is a removed key.
+    Key {
+        key: String,
+        local: Option<Value>,
+        shipped: Option<Value>,
+    },
+    /// A version of the file is not YAML, so it was not merged at all.
+    Unparsable { which: &'static str, error: String },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conflict::Key {
+                key,
+                local,
+                shipped,
+            } => write!(
+                f,
+                "{}: kept {}, shipped {}",
+                if key.is_empty() {
+                    "the whole file"
+                } else {
+                    key
+                },
+                describe(local.as_ref()),
+                describe(shipped.as_ref())
+            ),
+            Conflict::Unparsable { which, error } => {
+                write!(f, "the {which} file is not valid YAML ({error})")
+            }
+        }
+    }
+}
+
+/// The result of [`merge`].
+#[derive(Debug, Clone)]
+pub struct Merged {
+    pub text: String,
+    /// What was kept local although upstream changed it too.
+    pub conflicts: Vec<Conflict>,
+    /// Whether the local text could not be patched and was written out
+    /// anew, without its comments and layout.
+    pub rewritten: bool,
+}
+
+/// Merges the changes between `base`, the file as last shipped, and
+/// `shipped`, the file as shipped now, into `local`. Without a `base` every
+/// key the two differ on is a conflict.
+///
+/// When one side's version wins outright its text is returned as is, with
+/// its comments and layout. Otherwise the local text is patched (see
+/// [`patch`]), or written out anew if that fails.
+pub fn merge(base: Option<&str>, local: &str, shipped: &str) -> Merged {
+    let unchanged = |text: &str| Merged {
+        text: text.to_string(),
+        conflicts: Vec::new(),
+        rewritten: false,
+    };
+    if local == shipped || base == Some(local) {
+        return unchanged(shipped);
+    }
+    if base == Some(shipped) {
+        return unchanged(local);
+    }
+
+    let local_value = match serde_yaml::from_str::<Value>(local) {
+        Ok(value) => value,
+        Err(e) => return unparsable(local, "local", e),
+    };
+    let shipped_value = match serde_yaml::from_str::<Value>(shipped) {
+        Ok(value) => value,
+        Err(e) => return unparsable(local, "shipped", e),
+    };
+    let base_value = base.and_then(|base| serde_yaml::from_str::<Value>(base).ok());
+
+    let mut conflicts = Vec::new();
+    let merged = merge_value(
+        "",
+        base_value.as_ref(),
+        Some(&local_value),
+        Some(&shipped_value),
+        &mut conflicts,
+    )
+    .unwrap_or(Value::Null);
+    let mut rewritten = false;
+    let text = if merged == local_value {
+        local.to_string()
+    } else if merged == shipped_value {
+        shipped.to_string()
+    } else if let Some(text) = patch(local, &local_value, &merged) {
+        text
+    } else {
+        rewritten = true;
+        serde_yaml::to_string(&merged).expect("YAML values serialize")
+    };
+    Merged {
+        text,
+        conflicts,
+        rewritten,
+    }
+}
+
+/// Rewrites the top-level entries of `local` (parsed as `local_value`) that
+/// differ in `merged`, drops those `merged` lacks and appends those it adds,
+/// leaving every other line as it was. Returns `None` unless both are
+/// mappings and the patched text parses back to `merged`.
+fn patch(local: &str, local_value: &Value, merged: &Value) -> Option<String> {
+    let (Value::Mapping(before), Value::Mapping(after)) = (local_value, merged) else {
+        return None;
+    };
+    let lines: Vec<&str> = local.lines().collect();
+    let blocks = top_level_blocks(&lines)?;
+    if blocks.len() != before.len() || blocks.iter().any(|(key, _)| !before.contains_key(key)) {
+        return None;
+    }
+
+    let mut out = Vec::new();
+    let mut next = 0;
+    for (key, range) in &blocks {
+        out.extend(lines[next..range.start].iter().map(|line| line.to_string()));
+        next = range.end;
+        match after.get(key) {
+            Some(value) if before.get(key) == Some(value) => {
+                out.extend(lines[range.clone()].iter().map(|line| line.to_string()));
+            }
+            Some(value) => out.push(entry(key, value)),
+            None => {}
+        }
+    }
+    out.extend(lines[next..].iter().map(|line| line.to_string()));
+    for (key, value) in after {
+        if !before.contains_key(key) {
+            out.push(entry(key, value));
+        }
+    }
+
+    let mut text = out.join("\n");
+    text.push('\n');
+    let reparsed: Value = serde_yaml::from_str(&text).ok()?;
+    (reparsed == *merged).then_some(text)
+}
+
+/// The top-level keys of a block mapping and the lines each entry spans,
+/// in order: the key's line plus the indented or list lines after it.
+/// Comments and blank lines between entries belong to none of them.
+fn top_level_blocks(lines: &[&str]) -> Option<Vec<(Value, std::ops::Range<usize>)>> {
+    let continues = |line: &str| {
+        line.starts_with([' ', '\t']) || (line.starts_with('-') && !line.starts_with("---"))
+    };
+    let mut blocks: Vec<(Value, std::ops::Range<usize>)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if continues(line) {
+            let (_, range) = blocks.last_mut()?;
+            if range.end == i || lines[range.end..i].iter().all(|l| l.trim().is_empty()) {
+                range.end = i + 1;
+            }
+            continue;
+        }
+        if line.starts_with("---") || line.starts_with("...") {
+            continue;
+        }
+        let key = line
+            .find(": ")
+            .map(|end| &line[..end])
+            .or_else(|| line.strip_suffix(':'))?;
+        let key: Value = serde_yaml::from_str(key).ok()?;
+        blocks.push((key, i..i + 1));
+    }
+    Some(blocks)
+}
+
+/// One top-level entry as YAML, without a trailing newline.
+fn entry(key: &Value, value: &Value) -> String {
+    let mut mapping = Mapping::new();
+    mapping.insert(key.clone(), value.clone());
+    let text = serde_yaml::to_string(&mapping).expect("YAML values serialize");
+    text.trim_end_matches('\n').to_string()
+}
+
+fn unparsable(local: &str, which: &'static str, error: serde_yaml::Error) -> Merged {
+    Merged {
+        text: local.to_string(),
+        conflicts: vec![Conflict::Unparsable {
+            which,
+            error: error.to_string(),
+        }],
+        rewritten: false,
+    }
+}
+
+/// Merges one value, `None` meaning the key is absent on that side.
+fn merge_value(
+    key: &str,
+    base: Option<&Value>,
+    local: Option<&Value>,
+    shipped: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    if local == shipped || base == shipped {
+        return local.cloned();
+    }
+    if base == local {
+        return shipped.cloned();
+    }
+    if let (Some(Value::Mapping(local)), Some(Value::Mapping(shipped))) = (local, shipped) {
+        let base = base.and_then(Value::as_mapping);
+        return Some(Value::Mapping(merge_mapping(
+            key, base, local, shipped, conflicts,
+        )));
+    }
+    conflicts.push(Conflict::Key {
+        key: key.to_string(),
+        local: local.cloned(),
+        shipped: shipped.cloned(),
+    });
+    local.cloned()
+}
+
+/// Merges two mappings key by key, in the shipped order followed by keys
+/// only the local side has.
+fn merge_mapping(
+    prefix: &str,
+    base: Option<&Mapping>,
+    local: &Mapping,
+    shipped: &Mapping,
+    conflicts: &mut Vec<Conflict>,
+) -> Mapping {
+    let keys = shipped
+        .keys()
+        .chain(local.keys().filter(|k| !shipped.contains_key(*k)));
+    let mut merged = Mapping::new();
+    for key in keys {
+        let name = match prefix {
+            "" => key_name(key),
+            _ => format!("{prefix}.{}", key_name(key)),
+        };
+        let value = merge_value(
+            &name,
+            base.and_then(|base| base.get(key)),
+            local.get(key),
+            shipped.get(key),
+            conflicts,
+        );
+        if let Some(value) = value {
+            merged.insert(key.clone(), value);
+        }
+    }
+    merged
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        other => describe(Some(other)),
+    }
+}
+
+/// A value on one line, for messages.
+fn describe(value: Option<&Value>) -> String {
+    match value {
+        None => "nothing (removed)".to_string(),
+        Some(value) => serde_json::to_string(value).unwrap_or_else(|_| {
+            serde_yaml::to_string(value)
+                .unwrap_or_default()
+                .trim()
+                .replace('\n', " ")
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "\
+# Survon jukebox
+name: jukebox
+volume: 5
+playlist:
+- intro.ogg
+- outro.ogg
+bus:
+  device: hci0
+  retries: 3
+";
+
+    #[test]
+    fn a_side_that_wins_outright_keeps_its_text() {
+        let local = BASE.replace("volume: 5", "volume: 7 # too quiet");
+        let merged = merge(Some(BASE), &local, BASE);
+        assert_eq!(merged.text, local);
+        assert!(merged.conflicts.is_empty());
+
+        let shipped = BASE.replace("retries: 3", "retries: 4");
+        assert_eq!(merge(Some(BASE), BASE, &shipped).text, shipped);
+    }
+
+    #[test]
+    fn changes_on_both_sides_are_combined_keeping_comments() {
+        let local = BASE.replace("volume: 5", "volume: 7 # too quiet");
+        let shipped = BASE
+            .replace("retries: 3", "retries: 4")
+            .replace("- outro.ogg", "- outro.ogg\n- credits.ogg")
+            + "fade: true\n";
+        let merged = merge(Some(BASE), &local, &shipped);
+        assert!(merged.conflicts.is_empty());
+        assert!(!merged.rewritten);
+        assert_eq!(
+            merged.text,
+            "\
+# Survon jukebox
+name: jukebox
+volume: 7 # too quiet
+playlist:
+- intro.ogg
+- outro.ogg
+- credits.ogg
+bus:
+  device: hci0
+  retries: 4
+fade: true
+"
+        );
+    }
+
+    #[test]
+    fn keys_removed_upstream_go_unless_edited() {
+        let local = BASE.replace("volume: 5", "volume: 7");
+        let shipped = BASE.replace("volume: 5\n", "");
+        let merged = merge(Some(BASE), &local, &shipped);
+        assert_eq!(
+            merged.conflicts,
+            [Conflict::Key {
+                key: "volume".into(),
+                local: Some(Value::from(7)),
+                shipped: None,
+            }]
+        );
+        assert_eq!(merged.text, local);
+
+        let shipped = BASE.replace("  retries: 3\n", "");
+        let merged = merge(Some(BASE), &local, &shipped);
+        assert!(merged.conflicts.is_empty());
+        assert!(!merged.text.contains("retries"));
+        assert!(merged.text.contains("volume: 7"));
+    }
+
+    #[test]
+    fn conflicts_keep_the_local_value() {
+        let local = BASE.replace("retries: 3", "retries: 9");
+        let shipped = BASE.replace("retries: 3", "retries: 4");
+        let merged = merge(Some(BASE), &local, &shipped);
+        assert_eq!(merged.text, local);
+        assert_eq!(
+            merged.conflicts,
+            [Conflict::Key {
+                key: "bus.retries".into(),
+                local: Some(Value::from(9)),
+                shipped: Some(Value::from(4)),
+            }]
+        );
+        assert_eq!(
+            merged.conflicts[0].to_string(),
+            "bus.retries: kept 9, shipped 4"
+        );
+    }
+
+    #[test]
+    fn without_a_base_every_difference_conflicts() {
+        let local = BASE.replace("volume: 5", "volume: 7");
+        let merged = merge(None, &local, BASE);
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.text, local);
+    }
+
+    #[test]
+    fn unparsable_files_are_left_alone() {
+        let local = "volume: [5\n";
+        let merged = merge(Some(BASE), local, &BASE.replace("retries: 3", "retries: 4"));
+        assert_eq!(merged.text, local);
+        assert!(matches!(
+            merged.conflicts[..],
+            [Conflict::Unparsable { which: "local", .. }]
+        ));
+    }
+
+    #[test]
+    fn unpatchable_layouts_are_rewritten() {
+        let base = "{name: jukebox, volume: 5, fade: false}\n";
+        let local = "{name: jukebox, volume: 7, fade: false}\n";
+        let shipped = "{name: jukebox, volume: 5, fade: true}\n";
+        let merged = merge(Some(base), local, shipped);
+        assert!(merged.rewritten);
+        let value: Value = serde_yaml::from_str(&merged.text).unwrap();
+        assert_eq!(value["volume"], Value::from(7));
+        assert_eq!(value["fade"], Value::from(true));
+    }
+}
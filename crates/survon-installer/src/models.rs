@@ -0,0 +1,360 @@
+//! GGUF models for the survon_llm module: a catalog of known ones, and
+//! downloading them and switching the module between search-only and
+//! summarizer mode. Shared by the model step and `survon model`.
+//!
+//! Models live in `~/bundled/models`. The runtime loads the one
+//! `LLM_MODEL_PATH` names, but only when survon_llm's `config.yml` says
+//! `model: "summarizer"`; with `model: "search"` it answers from search
+//! alone.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::fsutil;
+
+/// Where models are kept, under the home directory.
+pub const MODEL_DIR: &str = "bundled/models";
+/// survon_llm's config, under the modules directory.
+pub const LLM_CONFIG: &str = "core/survon_llm/config.yml";
+pub const EXTENSION: &str = "gguf";
+/// The catalog entry recommended for a Pi 4 or 5.
+pub const DEFAULT_MODEL: &str = "phi3-mini";
+
+/// A model from the catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Known {
+    /// What to call it in `survon model`.
+    pub name: &'static str,
+    /// The file it is saved as in [`MODEL_DIR`].
+    pub file: &'static str,
+    pub description: &'static str,
+    pub url: &'static str,
+    pub quantization: &'static str,
+    /// The download size, roughly.
+    pub size_mb: u64,
+    /// The context length it was trained for, in tokens.
+    pub context: u32,
+    /// The least RAM it runs in alongside the runtime.
+    pub min_ram_mb: u64,
+    /// Checked after downloading when known; without it only curl's own
+    /// checks apply.
+    pub sha256: Option<&'static str>,
+}
+
+/// The models known to run on a Pi, smallest last.
+pub const CATALOG: &[Known] = &[
+    Known {
+        name: "phi3-mini-q4",
+        file: "phi3-mini-q4_k_m.gguf",
+        description: "Phi-3 mini 4k instruct, better answers than phi3-mini",
+        url: "https://huggingface.co/bartowski/Phi-3-mini-4k-instruct-GGUF/resolve/main/Phi-3-mini-4k-instruct-Q4_K_M.gguf",
+        quantization: "Q4_K_M",
+        size_mb: 2390,
+        context: 4096,
+        min_ram_mb: 4000,
+        sha256: None,
+    },
+    Known {
+        name: "phi3-mini",
+        file: "phi3-mini.gguf",
+        description: "Phi-3 mini 4k instruct",
+        url: "https://huggingface.co/bartowski/Phi-3-mini-4k-instruct-GGUF/resolve/main/Phi-3-mini-4k-instruct-Q3_K_S.gguf",
+        quantization: "Q3_K_S",
+        size_mb: 1680,
+        context: 4096,
+        min_ram_mb: 2000,
+        sha256: None,
+    },
+    Known {
+        name: "llama3.2-1b",
+        file: "llama-3.2-1b-instruct-q4_k_m.gguf",
+        description: "Llama 3.2 1B instruct",
+        url: "https://huggingface.co/bartowski/Llama-3.2-1B-Instruct-GGUF/resolve/main/Llama-3.2-1B-Instruct-Q4_K_M.gguf",
+        quantization: "Q4_K_M",
+        size_mb: 810,
+        context: 8192,
+        min_ram_mb: 1500,
+        sha256: None,
+    },
+    Known {
+        name: "tinyllama",
+        file: "tinyllama-1.1b-chat-q4_k_m.gguf",
+        description: "TinyLlama 1.1B chat",
+        url: "https://huggingface.co/TheBloke/TinyLlama-1.1B-Chat-v1.0-GGUF/resolve/main/tinyllama-1.1b-chat-v1.0.Q4_K_M.gguf",
+        quantization: "Q4_K_M",
+        size_mb: 670,
+        context: 2048,
+        min_ram_mb: 1200,
+        sha256: None,
+    },
+    Known {
+        name: "qwen2.5-0.5b",
+        file: "qwen2.5-0.5b-instruct-q4_k_m.gguf",
+        description: "Qwen2.5 0.5B instruct, fits a Pi 3B",
+        url: "https://huggingface.co/Qwen/Qwen2.5-0.5B-Instruct-GGUF/resolve/main/qwen2.5-0.5b-instruct-q4_k_m.gguf",
+        quantization: "Q4_K_M",
+        size_mb: 490,
+        context: 32768,
+        min_ram_mb: 900,
+        sha256: None,
+    },
+];
+
+/// The catalog entry called `name`, or saved as the file `name`.
+pub fn known(name: &str) -> Option<&'static Known> {
+    CATALOG.iter().find(|k| k.name == name || k.file == name)
+}
+
+pub fn default_model() -> &'static Known {
+    known(DEFAULT_MODEL).expect("the default model is in the catalog")
+}
+
+/// The model directory under `home`.
+pub fn dir(home: &Path) -> PathBuf {
+    home.join(MODEL_DIR)
+}
+
+/// Last path segment of `url`, without any query string or fragment.
+pub fn url_file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// A model file in the model directory.
+#[derive(Debug, Clone)]
+pub struct Installed {
+    pub file: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub known: Option<&'static Known>,
+}
+
+/// The models in the model directory under `home`, by file name.
+pub fn installed(home: &Path) -> Result<Vec<Installed>> {
+    let dir = dir(home);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::io(&dir, e)),
+    };
+    let mut models = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::io(&dir, e))?;
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != EXTENSION) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let file = entry.file_name().to_string_lossy().into_owned();
+        models.push(Installed {
+            known: known(&file),
+            size: metadata.len(),
+            file,
+            path,
+        });
+    }
+    models.sort_by(|a, b| a.file.cmp(&b.file));
+    Ok(models)
+}
+
+/// The settings that make the runtime load the model at `path`.
+pub fn settings(path: &Path) -> [(&'static str, String); 2] {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    [
+        ("LLM_MODEL_NAME", name),
+        ("LLM_MODEL_PATH", path.to_string_lossy().into_owned()),
+    ]
+}
+
+/// Downloads `url` into the model directory as `file`, unless a copy that
+/// checks out is already there, and checks it against `sha256` if given.
+pub fn download(ctx: &Context, url: &str, file: &str, sha256: Option<&str>) -> Result<PathBuf> {
+    check_file_name(file)?;
+    let dir = dir(&ctx.home);
+    ctx.system.create_dir_all(&dir)?;
+    let path = dir.join(file);
+    if path.is_file() && check(&path, sha256).is_ok() {
+        ctx.say(format!("{file} is already downloaded"));
+        return Ok(path);
+    }
+    ctx.system.download(url, &path)?;
+    if ctx.system.is_planning() {
+        return Ok(path);
+    }
+    if let Err(e) = check(&path, sha256) {
+        let _ = fs::remove_file(&path);
+        return Err(e);
+    }
+    Ok(path)
+}
+
+/// Checks that `file` can name a model in [`MODEL_DIR`]: a plain file name
+/// ending in `.gguf`.
+pub fn check_file_name(file: &str) -> Result<()> {
+    if file.is_empty() || file.contains('/') || file.starts_with('.') {
+        return Err(Error::Input(format!("`{file}` is not a model file name")));
+    }
+    if !file.ends_with(&format!(".{EXTENSION}")) {
+        return Err(Error::Input(format!(
+            "`{file}` is not a .{EXTENSION} model file"
+        )));
+    }
+    Ok(())
+}
+
+fn check(path: &Path, sha256: Option<&str>) -> Result<()> {
+    let Some(expected) = sha256 else {
+        return Ok(());
+    };
+    let actual = fsutil::sha256_file(path)?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(Error::Verify(format!(
+            "{} has SHA-256 {actual}, expected {expected}",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// How survon_llm answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LlmMode {
+    /// From search results alone, without a model.
+    Search,
+    /// Search results summarized by the model.
+    Summarizer,
+}
+
+impl LlmMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            LlmMode::Search => "search",
+            LlmMode::Summarizer => "summarizer",
+        }
+    }
+}
+
+/// The mode survon_llm's config under `modules_dir` is set to.
+pub fn llm_mode(modules_dir: &Path) -> Option<LlmMode> {
+    mode_in(&fs::read_to_string(modules_dir.join(LLM_CONFIG)).ok()?)
+}
+
+fn mode_in(config: &str) -> Option<LlmMode> {
+    let value = config
+        .lines()
+        .find_map(|line| line.strip_prefix("model:"))?;
+    match value.trim().trim_matches(['"', '\'']) {
+        "search" => Some(LlmMode::Search),
+        "summarizer" => Some(LlmMode::Summarizer),
+        _ => None,
+    }
+}
+
+/// Sets the top-level `model:` of survon_llm's config to `mode`, leaving the
+/// rest of the file as it is. Does nothing if the module is not installed.
+/// Returns whether the file changed.
+pub fn set_llm_mode(ctx: &Context, mode: LlmMode) -> Result<bool> {
+    let config = ctx.modules_dir().join(LLM_CONFIG);
+    if !config.exists() {
+        return Ok(false);
+    }
+    ctx.system.edit_file(&config, |contents| {
+        if mode_in(contents) == Some(mode) {
+            return contents.to_string();
+        }
+        let setting = format!("model: \"{}\"", mode.name());
+        let mut lines: Vec<&str> = contents.lines().collect();
+        match lines.iter().position(|line| line.starts_with("model:")) {
+            Some(i) => lines[i] = &setting,
+            None => lines.push(&setting),
+        }
+        lines.join("\n") + "\n"
+    })
+}
+
+pub fn total_ram_mb() -> u64 {
+    fs::read_to_string("/proc/meminfo")
+        .unwrap_or_default()
+        .lines()
+        .find_map(|l| l.strip_prefix("MemTotal:"))
+        .and_then(|v| v.split_whitespace().next())
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map_or(0, |kb| kb / 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_come_from_the_url_path() {
+        assert_eq!(
+            url_file_name("https://example.com/models/tiny.gguf?download=true#top"),
+            "tiny.gguf"
+        );
+        assert_eq!(
+            url_file_name("https://example.com/tiny.gguf#x"),
+            "tiny.gguf"
+        );
+        assert_eq!(url_file_name("tiny.gguf"), "tiny.gguf");
+        assert_eq!(url_file_name("https://example.com/models/"), "");
+    }
+
+    #[test]
+    fn model_files_must_be_gguf() {
+        check_file_name("tiny.gguf").unwrap();
+        for file in [
+            "",
+            "../tiny.gguf",
+            ".gguf",
+            "tiny.bin",
+            "tiny.gguf.exe",
+            "gguf",
+        ] {
+            assert!(
+                matches!(check_file_name(file), Err(Error::Input(_))),
+                "{file:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn catalog_entries_are_consistent() {
+        assert_eq!(default_model().name, DEFAULT_MODEL);
+        for model in CATALOG {
+            assert_eq!(known(model.name).map(|k| k.file), Some(model.file));
+            assert_eq!(known(model.file).map(|k| k.name), Some(model.name));
+            assert!(
+                model.file.ends_with(&format!(".{EXTENSION}")),
+                "{}",
+                model.file
+            );
+            assert!(
+                url_file_name(model.url).ends_with(EXTENSION),
+                "{}",
+                model.url
+            );
+        }
+        assert!(known("gpt-5").is_none());
+    }
+
+    #[test]
+    fn mode_is_the_top_level_model_key() {
+        let config = "name: survon_llm\nsettings:\n  model: search\nmodel: \"summarizer\"\n";
+        assert_eq!(mode_in(config), Some(LlmMode::Summarizer));
+        assert_eq!(mode_in("model: search\n"), Some(LlmMode::Search));
+        assert_eq!(mode_in("model: 'search'\n"), Some(LlmMode::Search));
+        assert_eq!(mode_in("model: chat\n"), None);
+        assert_eq!(mode_in("name: survon_llm\n  model: search\n"), None);
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use crate::change::Change;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::journal::{Artifact, Journal};
+use crate::step::{Check, Step, StepId};
+
+/// What happened to a single step during a run.
+#[derive(Debug)]
+pub enum Outcome {
+    /// `apply` ran and `verify` passed.
+    Applied,
+    /// `check` found nothing to do.
+    AlreadySatisfied(String),
+    /// Completed by an earlier run whose inputs and artifacts still hold.
+    Resumed,
+    /// Plan mode: `apply` was dry-run; the changes are in the report.
+    Planned,
+    /// Skipped through its `--skip-<step>` flag.
+    Skipped,
+    /// Not attempted because a dependency failed.
+    Blocked(StepId),
+    Failed(Error),
+}
+
+impl Outcome {
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            Outcome::Applied
+                | Outcome::AlreadySatisfied(_)
+                | Outcome::Resumed
+                | Outcome::Planned
+                | Outcome::Skipped
+        )
+    }
+
+    /// Short kebab-case name, as used in progress events.
+    pub fn status(&self) -> &'static str {
+        match self {
+            Outcome::Applied => "applied",
+            Outcome::AlreadySatisfied(_) => "already-satisfied",
+            Outcome::Resumed => "resumed",
+            Outcome::Planned => "planned",
+            Outcome::Skipped => "skipped",
+            Outcome::Blocked(_) => "blocked",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Applied => f.write_str("applied"),
+            Outcome::AlreadySatisfied(why) => write!(f, "already satisfied ({why})"),
+            Outcome::Resumed => f.write_str("completed in a previous run"),
+            Outcome::Planned => f.write_str("would apply"),
+            Outcome::Skipped => f.write_str("skipped"),
+            Outcome::Blocked(root) => write!(f, "blocked by failed step `{root}`"),
+            Outcome::Failed(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StepReport {
+    pub id: StepId,
+    pub title: &'static str,
+    pub outcome: Outcome,
+    /// Mutations the step made, or would make in plan mode.
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub steps: Vec<StepReport>,
+}
+
+impl Report {
+    pub fn succeeded(&self) -> bool {
+        self.steps.iter().all(|s| s.outcome.is_success())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &StepReport> {
+        self.steps.iter().filter(|s| !s.outcome.is_success())
+    }
+
+    pub fn changes(&self) -> impl Iterator<Item = &Change> {
+        self.steps.iter().flat_map(|s| &s.changes)
+    }
+}
+
+/// Per-run choices made on the command line.
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    /// Steps not to run at all (`--skip-<step>`).
+    pub skip: HashSet<StepId>,
+    /// Steps to run even if the journal says they are done (`--redo`).
+    pub redo: HashSet<StepId>,
+}
+
+/// Steps in an order that respects their declared dependencies.
+pub struct Pipeline {
+    steps: Vec<Box<dyn Step>>,
+}
+
+impl Pipeline {
+    /// Orders `steps` so every step runs after its dependencies. Steps with no
+    /// ordering constraint between them keep the order they were given in.
+    pub fn new(steps: Vec<Box<dyn Step>>) -> Result<Self> {
+        let known: HashSet<StepId> = steps.iter().map(|s| s.id()).collect();
+        for step in &steps {
+            if let Some(&missing) = step.depends_on().iter().find(|d| !known.contains(d)) {
+                return Err(Error::UnknownDependency {
+                    step: step.id(),
+                    missing,
+                });
+            }
+        }
+
+        let mut pending = steps;
+        let mut ordered: Vec<Box<dyn Step>> = Vec::with_capacity(pending.len());
+        let mut placed = HashSet::new();
+        while !pending.is_empty() {
+            let Some(next) = pending
+                .iter()
+                .position(|s| s.depends_on().iter().all(|d| placed.contains(d)))
+            else {
+                let cycle: Vec<String> = pending.iter().map(|s| s.id().to_string()).collect();
+                return Err(Error::DependencyCycle(cycle.join(", ")));
+            };
+            let step = pending.remove(next);
+            placed.insert(step.id());
+            ordered.push(step);
+        }
+        Ok(Pipeline { steps: ordered })
+    }
+
+    pub fn standard() -> Self {
+        Pipeline::new(crate::steps::all()).expect("built-in steps form a valid pipeline")
+    }
+
+    pub fn steps(&self) -> impl Iterator<Item = &dyn Step> {
+        self.steps.iter().map(|s| s.as_ref())
+    }
+
+    /// Runs every step, continuing past failures with the steps that do not
+    /// depend on the failed one. Progress is recorded in `journal` as it
+    /// happens so an interrupted run can be resumed.
+    ///
+    /// When `ctx.system` is planning, steps are dry-run: `check` and the
+    /// journal are consulted as usual, `apply` only records its changes,
+    /// and neither `verify` nor the journal update runs.
+    pub fn run(&self, ctx: &mut Context, journal: &mut Journal, opts: &RunOptions) -> Report {
+        let total = self.steps.len();
+        let mut failed: HashMap<StepId, StepId> = HashMap::new();
+        let mut report = Report::default();
+
+        for (n, step) in self.steps.iter().enumerate() {
+            let id = step.id();
+            println!("Step {}/{} - {}:", n + 1, total, step.title());
+            ctx.system.log().begin_step(n + 1, total, id, step.title());
+
+            let outcome = if opts.skip.contains(&id) {
+                Outcome::Skipped
+            } else if let Some(&root) = step.depends_on().iter().find_map(|d| failed.get(d)) {
+                Outcome::Blocked(root)
+            } else {
+                run_journaled(step.as_ref(), ctx, journal, opts.redo.contains(&id))
+            };
+            let changes = ctx.system.take_changes();
+
+            match &outcome {
+                Outcome::Applied => println!("Done."),
+                Outcome::AlreadySatisfied(why) => println!("Done. ({why})"),
+                Outcome::Resumed => println!("Done. (completed in a previous run)"),
+                Outcome::Planned if changes.is_empty() => println!("[Plan]. No changes"),
+                Outcome::Planned => {
+                    for change in &changes {
+                        println!("  {change}");
+                    }
+                }
+                Outcome::Skipped => println!("[Skipped]. Received flag --{}", id.skip_flag()),
+                Outcome::Blocked(root) => {
+                    failed.insert(id, *root);
+                    println!("[Blocked]. Depends on failed step `{root}`");
+                }
+                Outcome::Failed(e) => {
+                    failed.insert(id, id);
+                    println!("[Failed]. {e}");
+                    if let Some(log) = ctx.system.log().step_log() {
+                        println!("Full output: {}", log.display());
+                    }
+                }
+            }
+            ctx.system.log().end_step(&outcome);
+
+            report.steps.push(StepReport {
+                id,
+                title: step.title(),
+                outcome,
+                changes,
+            });
+        }
+        report
+    }
+}
+
+fn run_journaled(step: &dyn Step, ctx: &mut Context, journal: &mut Journal, redo: bool) -> Outcome {
+    let id = step.id();
+    let inputs: BTreeMap<String, String> = step
+        .inputs(ctx)
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+    if step.resumable() && !redo && journal.reusable(id, &inputs).is_some() {
+        return Outcome::Resumed;
+    }
+
+    if ctx.system.is_planning() {
+        let outcome = match step.check(ctx) {
+            Ok(Check::Satisfied(why)) => Outcome::AlreadySatisfied(why),
+            Ok(Check::Needed) => match step.apply(ctx) {
+                Ok(()) => Outcome::Planned,
+                Err(e) => Outcome::Failed(e),
+            },
+            Err(e) => Outcome::Failed(e),
+        };
+        ctx.take_artifacts();
+        return outcome;
+    }
+
+    warn_on_journal_error(journal.started(id, inputs.clone()));
+    ctx.system.begin_transaction(id);
+    ctx.take_artifacts();
+    let result = run_step(step, ctx).and_then(|outcome| {
+        let artifacts = ctx
+            .take_artifacts()
+            .iter()
+            .map(|p| Artifact::capture(p))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((outcome, artifacts))
+    });
+
+    match result {
+        Ok((outcome, artifacts)) => {
+            warn_on_journal_error(journal.completed(id, inputs, artifacts));
+            outcome
+        }
+        Err(e) => {
+            warn_on_journal_error(journal.failed(id, inputs, e.to_string()));
+            Outcome::Failed(e)
+        }
+    }
+}
+
+fn run_step(step: &dyn Step, ctx: &mut Context) -> Result<Outcome> {
+    if let Check::Satisfied(why) = step.check(ctx)? {
+        return Ok(Outcome::AlreadySatisfied(why));
+    }
+    step.apply(ctx)?;
+    step.verify(ctx)?;
+    Ok(Outcome::Applied)
+}
+
+/// A journal that cannot be written only costs resumability, so it must not
+/// fail the step that just ran.
+fn warn_on_journal_error(result: Result<()>) {
+    if let Err(e) = result {
+        eprintln!("Warning: could not update install journal: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fake(StepId, &'static [StepId]);
+
+    impl Step for Fake {
+        fn id(&self) -> StepId {
+            self.0
+        }
+
+        fn title(&self) -> &'static str {
+            "fake"
+        }
+
+        fn depends_on(&self) -> &'static [StepId] {
+            self.1
+        }
+
+        fn apply(&self, _ctx: &mut Context) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn order(steps: Vec<Fake>) -> Result<Vec<StepId>> {
+        let steps = steps
+            .into_iter()
+            .map(|s| Box::new(s) as Box<dyn Step>)
+            .collect();
+        Ok(Pipeline::new(steps)?.steps().map(|s| s.id()).collect())
+    }
+
+    #[test]
+    fn dependencies_run_first() {
+        let ordered = order(vec![
+            Fake(StepId::ModelSelection, &[StepId::DownloadRuntime]),
+            Fake(StepId::DownloadRuntime, &[StepId::InstallDeps]),
+            Fake(StepId::InstallDeps, &[]),
+        ])
+        .unwrap();
+        assert_eq!(
+            ordered,
+            [
+                StepId::InstallDeps,
+                StepId::DownloadRuntime,
+                StepId::ModelSelection
+            ]
+        );
+    }
+
+    #[test]
+    fn unconstrained_steps_keep_their_order() {
+        let ordered = order(vec![
+            Fake(StepId::Cleanup, &[]),
+            Fake(StepId::Hostname, &[]),
+            Fake(StepId::AptUpdate, &[StepId::Cleanup]),
+        ])
+        .unwrap();
+        assert_eq!(
+            ordered,
+            [StepId::Cleanup, StepId::Hostname, StepId::AptUpdate]
+        );
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let err = order(vec![
+            Fake(StepId::Hostname, &[]),
+            Fake(StepId::AptUpdate, &[StepId::InstallDeps]),
+            Fake(StepId::InstallDeps, &[StepId::AptUpdate]),
+        ])
+        .unwrap_err();
+        assert!(
+            matches!(&err, Error::DependencyCycle(steps) if steps == "apt-update, install-deps"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn unknown_dependencies_are_rejected() {
+        let err = order(vec![Fake(StepId::AptUpdate, &[StepId::Hostname])]).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::UnknownDependency {
+                step: StepId::AptUpdate,
+                missing: StepId::Hostname
+            }
+        ));
+    }
+
+    #[test]
+    fn standard_pipeline_is_valid() {
+        assert_eq!(Pipeline::standard().steps().count(), StepId::ALL.len());
+    }
+}
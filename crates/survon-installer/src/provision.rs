@@ -0,0 +1,477 @@
+//! Declarative provisioning files for unattended installs.
+//!
+//! A provisioning file answers every question the installer would otherwise
+//! ask on the terminal, so a fleet of Pis can be set up from one file:
+//!
+//! ```toml
+//! hostname = "survon-07"
+//! download_audio = true
+//! council_strategy = "survival"
+//! reboot = "always"
+//! skip = ["ble-test"]
+//!
+//! [model]
+//! source = "url"      # or default, none, or a catalog model such as tinyllama
+//! url = "https://example.com/models/tiny.gguf"
+//!
+//! [env]
+//! LOG_LEVEL = "debug"
+//! ```
+//!
+//! TOML and YAML are both accepted, chosen by the file extension. The whole
+//! file is validated before the installer touches anything, and every
+//! problem is reported at once rather than one per attempt.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use survon_config::schema;
+
+use crate::error::{Error, Result};
+use crate::models::{self, Known};
+use crate::step::StepId;
+use crate::steps::validate_hostname;
+
+pub use survon_config::schema::COUNCIL_STRATEGIES;
+
+/// Environment variables owned by other settings, which `env` must not set.
+const RESERVED_ENV: [(&str, &str); 3] = [
+    ("LLM_MODEL_NAME", "model"),
+    ("LLM_MODEL_PATH", "model"),
+    ("COUNCIL_STRATEGY", "council_strategy"),
+];
+
+/// Where the AI model comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelSource {
+    /// The recommended model, [`models::DEFAULT_MODEL`].
+    Default,
+    /// No model; the runtime uses search-only mode.
+    None,
+    /// A model from the [`models::CATALOG`].
+    Catalog(&'static Known),
+    /// A GGUF model downloaded from `url` and saved as `name`, checked
+    /// against `sha256` if given.
+    Url {
+        url: String,
+        name: String,
+        sha256: Option<String>,
+    },
+}
+
+impl fmt::Display for ModelSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelSource::Default => f.write_str("default"),
+            ModelSource::None => f.write_str("none"),
+            ModelSource::Catalog(known) => f.write_str(known.name),
+            ModelSource::Url { url, .. } => f.write_str(url),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebootPolicy {
+    /// Reboot as soon as the installation succeeds.
+    Always,
+    /// Leave rebooting to whoever provisions the unit.
+    Never,
+}
+
+/// A validated provisioning file.
+#[derive(Debug, Clone)]
+pub struct Provision {
+    pub path: PathBuf,
+    pub hostname: String,
+    pub model: ModelSource,
+    pub download_audio: bool,
+    pub council_strategy: Option<String>,
+    /// Extra settings for the settings store.
+    pub env: BTreeMap<String, String>,
+    pub reboot: RebootPolicy,
+    pub skip: BTreeSet<StepId>,
+}
+
+impl Provision {
+    /// Reads and validates `path`. Every problem found is reported in a
+    /// single [`Error::Provision`].
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let fail = |problems: Vec<String>| Error::Provision {
+            path: path.to_path_buf(),
+            problems,
+        };
+        let raw: RawProvision = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&text).map_err(|e| fail(vec![e.to_string()]))?,
+            Some("yml" | "yaml") => {
+                serde_yaml::from_str(&text).map_err(|e| fail(vec![e.to_string()]))?
+            }
+            _ => {
+                return Err(fail(vec![
+                    "unknown format; name the file .toml, .yaml or .yml".into(),
+                ]))
+            }
+        };
+        let mut problems = Vec::new();
+        let provision = raw.validate(path, &mut problems);
+        if problems.is_empty() {
+            Ok(provision)
+        } else {
+            Err(fail(problems))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawProvision {
+    hostname: Option<String>,
+    model: Option<RawModel>,
+    download_audio: Option<bool>,
+    council_strategy: Option<String>,
+    #[serde(default)]
+    env: BTreeMap<String, Scalar>,
+    reboot: Option<String>,
+    #[serde(default)]
+    skip: Vec<String>,
+    #[serde(flatten)]
+    unknown: BTreeMap<String, IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawModel {
+    source: Option<String>,
+    url: Option<String>,
+    name: Option<String>,
+    sha256: Option<String>,
+    #[serde(flatten)]
+    unknown: BTreeMap<String, IgnoredAny>,
+}
+
+/// Env values may be written as bare YAML/TOML numbers or booleans.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    String(String),
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+}
+
+impl fmt::Display for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scalar::String(s) => f.write_str(s),
+            Scalar::Bool(b) => write!(f, "{b}"),
+            Scalar::Int(i) => write!(f, "{i}"),
+            Scalar::Float(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+impl RawProvision {
+    /// Checks every field, pushing a message per problem. Invalid fields
+    /// fall back to their defaults so the rest can still be checked.
+    fn validate(self, path: &Path, problems: &mut Vec<String>) -> Provision {
+        for key in self.unknown.keys() {
+            problems.push(format!("unknown setting `{key}`"));
+        }
+
+        let hostname = self.hostname.unwrap_or_else(|| "survon".to_string());
+        if let Err(e) = validate_hostname(&hostname) {
+            problems.push(format!("hostname: {e}"));
+        }
+
+        let model = self
+            .model
+            .map_or(ModelSource::None, |m| m.validate(problems));
+
+        let council_strategy = self.council_strategy.filter(|s| {
+            let known = COUNCIL_STRATEGIES.contains(&s.as_str());
+            if !known {
+                problems.push(format!(
+                    "council_strategy: `{s}` is not one of {}",
+                    COUNCIL_STRATEGIES.join(", ")
+                ));
+            }
+            known
+        });
+
+        let mut env = BTreeMap::new();
+        for (key, value) in self.env {
+            let value = value.to_string();
+            if let Some((_, owner)) = RESERVED_ENV.iter().find(|(k, _)| *k == key) {
+                problems.push(format!("env.{key}: set it through `{owner}` instead"));
+            } else if let Err(e) = schema::parse_custom(&key, &value) {
+                problems.push(format!("env.{e}"));
+            } else {
+                env.insert(key, value);
+            }
+        }
+
+        let reboot = match self.reboot.as_deref() {
+            None | Some("never") => RebootPolicy::Never,
+            Some("always") => RebootPolicy::Always,
+            Some(other) => {
+                problems.push(format!("reboot: `{other}` is not one of always, never"));
+                RebootPolicy::Never
+            }
+        };
+
+        let skip = self
+            .skip
+            .iter()
+            .filter_map(|name| match name.parse() {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    problems.push(format!("skip: {e}"));
+                    None
+                }
+            })
+            .collect();
+
+        Provision {
+            path: path.to_path_buf(),
+            hostname,
+            model,
+            download_audio: self.download_audio.unwrap_or(false),
+            council_strategy,
+            env,
+            reboot,
+            skip,
+        }
+    }
+}
+
+impl RawModel {
+    fn validate(self, problems: &mut Vec<String>) -> ModelSource {
+        for key in self.unknown.keys() {
+            problems.push(format!("model: unknown setting `{key}`"));
+        }
+        let source =
+            self.source
+                .as_deref()
+                .unwrap_or(if self.url.is_some() { "url" } else { "none" });
+        if source != "url" && (self.url.is_some() || self.name.is_some() || self.sha256.is_some()) {
+            problems.push(format!(
+                "model: `url`, `name` and `sha256` only apply to source = \"url\", not \"{source}\""
+            ));
+        }
+        match source {
+            "default" => ModelSource::Default,
+            "none" => ModelSource::None,
+            "url" => {
+                let Some(url) = self.url else {
+                    problems.push("model: source = \"url\" needs a `url`".into());
+                    return ModelSource::None;
+                };
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    problems.push(format!("model.url: `{url}` is not an http(s) URL"));
+                }
+                let name = self
+                    .name
+                    .unwrap_or_else(|| models::url_file_name(&url).to_string());
+                if name.is_empty() || name.contains('/') || name.starts_with('.') {
+                    problems.push(format!(
+                        "model: cannot derive a file name from `{url}`; set `name`"
+                    ));
+                } else if models::check_file_name(&name).is_err() {
+                    problems.push(format!(
+                        "model: `{name}` is not a .{} file; set `name`",
+                        models::EXTENSION
+                    ));
+                }
+                if let Some(sha256) = &self.sha256 {
+                    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                        problems.push(format!("model.sha256: `{sha256}` is not a SHA-256"));
+                    }
+                }
+                ModelSource::Url {
+                    url,
+                    name,
+                    sha256: self.sha256,
+                }
+            }
+            other => match models::known(other) {
+                Some(known) => ModelSource::Catalog(known),
+                None => {
+                    let catalog: Vec<&str> = models::CATALOG.iter().map(|k| k.name).collect();
+                    problems.push(format!(
+                        "model.source: `{other}` is not one of default, none, url or a catalog model ({})",
+                        catalog.join(", ")
+                    ));
+                    ModelSource::None
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use survon_test_support::Scratch;
+
+    fn load(name: &str, text: &str) -> Result<Provision> {
+        let dir = Scratch::new("provision");
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        Provision::load(&path)
+    }
+
+    fn problems(text: &str) -> Vec<String> {
+        match load("unit.toml", text) {
+            Err(Error::Provision { problems, .. }) => problems,
+            other => panic!("expected provisioning problems, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn toml_and_yaml_read_the_same() {
+        let toml = load(
+            "unit.toml",
+            "hostname = \"survon-07\"\ndownload_audio = true\ncouncil_strategy = \"survival\"\n\
+             reboot = \"always\"\nskip = [\"ble-test\"]\n\n[model]\nsource = \"default\"\n\n\
+             [env]\nLOG_LEVEL = \"debug\"\nRETRIES = 3\n",
+        )
+        .unwrap();
+        let yaml = load(
+            "unit.yml",
+            "hostname: survon-07\ndownload_audio: true\ncouncil_strategy: survival\n\
+             reboot: always\nskip: [ble-test]\nmodel:\n  source: default\n\
+             env:\n  LOG_LEVEL: debug\n  RETRIES: 3\n",
+        )
+        .unwrap();
+        for provision in [toml, yaml] {
+            assert_eq!(provision.hostname, "survon-07");
+            assert!(provision.download_audio);
+            assert_eq!(provision.council_strategy.as_deref(), Some("survival"));
+            assert_eq!(provision.reboot, RebootPolicy::Always);
+            assert_eq!(provision.skip, BTreeSet::from([StepId::BleTest]));
+            assert_eq!(provision.model, ModelSource::Default);
+            assert_eq!(provision.env["LOG_LEVEL"], "debug");
+            assert_eq!(provision.env["RETRIES"], "3");
+        }
+
+        let defaults = load("empty.yaml", "{}").unwrap();
+        assert_eq!(defaults.hostname, "survon");
+        assert_eq!(defaults.model, ModelSource::None);
+        assert_eq!(defaults.reboot, RebootPolicy::Never);
+        assert!(!defaults.download_audio);
+
+        assert!(matches!(
+            load("unit.json", "{}"),
+            Err(Error::Provision { problems, .. }) if problems[0].starts_with("unknown format")
+        ));
+        assert!(matches!(
+            load("unit.toml", "hostname = "),
+            Err(Error::Provision { problems, .. }) if problems.len() == 1
+        ));
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let problems = problems(
+            "hostname = \"-survon\"\ncouncil_strategy = \"chaos\"\nreboot = \"sometimes\"\n\
+             skip = [\"ble-test\", \"coffee\"]\n",
+        );
+        assert_eq!(problems.len(), 4, "{problems:?}");
+        for (problem, field) in
+            problems
+                .iter()
+                .zip(["hostname:", "council_strategy:", "reboot:", "skip:"])
+        {
+            assert!(problem.starts_with(field), "{problem}");
+        }
+    }
+
+    #[test]
+    fn reserved_env_keys_are_rejected() {
+        assert_eq!(
+            problems("[env]\nLLM_MODEL_PATH = \"/tmp/x.gguf\"\nCOUNCIL_STRATEGY = \"survival\"\n"),
+            [
+                "env.COUNCIL_STRATEGY: set it through `council_strategy` instead",
+                "env.LLM_MODEL_PATH: set it through `model` instead",
+            ]
+        );
+        let problems = problems("[env]\n\"LOG LEVEL\" = \"debug\"\n");
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].starts_with("env.LOG LEVEL"));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert_eq!(
+            problems("hostnme = \"survon-07\"\n\n[model]\nsource = \"default\"\nmirror = \"x\"\n"),
+            [
+                "unknown setting `hostnme`",
+                "model: unknown setting `mirror`"
+            ]
+        );
+    }
+
+    #[test]
+    fn model_sources_are_checked() {
+        let provision = load(
+            "unit.toml",
+            "[model]\nurl = \"https://example.com/models/tiny.gguf\"\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            provision.model,
+            ModelSource::Url { url, name, .. }
+                if url == "https://example.com/models/tiny.gguf" && name == "tiny.gguf"
+        ));
+        let provision = load("unit.toml", "[model]\nsource = \"none\"\n").unwrap();
+        assert_eq!(provision.model, ModelSource::None);
+
+        for (model, problem) in [
+            ("source = \"url\"", "model: source = \"url\" needs a `url`"),
+            ("url = \"ftp://example.com/tiny.gguf\"", "model.url:"),
+            (
+                "url = \"https://example.com/\"",
+                "model: cannot derive a file name",
+            ),
+            (
+                "url = \"https://example.com/tiny.bin\"",
+                "model: `tiny.bin` is not a .gguf file",
+            ),
+            ("source = \"default\"\nname = \"tiny.gguf\"", "model: `url`"),
+            ("source = \"llama-9000\"", "model.source: `llama-9000`"),
+        ] {
+            let problems = problems(&format!("[model]\n{model}\n"));
+            assert_eq!(problems.len(), 1, "{model}: {problems:?}");
+            assert!(problems[0].starts_with(problem), "{model}: {problems:?}");
+        }
+    }
+
+    #[test]
+    fn catalog_models_and_checksums() {
+        let provision = load("unit.yaml", "model:\n  source: tinyllama\n").unwrap();
+        assert!(
+            matches!(provision.model, ModelSource::Catalog(known) if known.name == "tinyllama")
+        );
+
+        let sha256 = "ab".repeat(32);
+        let provision = load(
+            "unit.toml",
+            &format!("[model]\nurl = \"https://example.com/tiny.gguf?download=1\"\nsha256 = \"{sha256}\"\n"),
+        )
+        .unwrap();
+        assert_eq!(
+            provision.model,
+            ModelSource::Url {
+                url: "https://example.com/tiny.gguf?download=1".into(),
+                name: "tiny.gguf".into(),
+                sha256: Some(sha256),
+            }
+        );
+        assert_eq!(
+            problems("[model]\nurl = \"https://example.com/tiny.gguf\"\nsha256 = \"abc\"\n"),
+            ["model.sha256: `abc` is not a SHA-256"]
+        );
+    }
+}
@@ -0,0 +1,466 @@
+//! Reversible record of everything the installer changed.
+//!
+//! While a live run applies a step, [`System`] notes how to undo each
+//! mutation before making it: files are backed up, new paths are marked for
+//! deletion, and steps add undo commands for changes only they understand
+//! (the hostname, the boot behaviour). The undo actions of one step form a
+//! [`Transaction`] in the [`Ledger`], which is saved as it grows so a
+//! half-finished run can be rolled back too.
+//!
+//! Only the first record for any file, group or setting is kept. Re-running
+//! the installer therefore never replaces the pre-Survon backup with a copy
+//! of something Survon itself wrote, and [`rollback`] always lands on the
+//! state the machine was in before the first install.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::journal::now;
+use crate::step::StepId;
+use crate::system::System;
+
+const LEDGER_FILE: &str = "rollback.json";
+const BACKUP_DIR: &str = "backups";
+
+/// How to reverse one mutation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Undo {
+    /// Put back the file saved at `backup`.
+    Restore {
+        path: PathBuf,
+        backup: PathBuf,
+        root: bool,
+    },
+    /// Remove a file or directory that did not exist before.
+    Delete {
+        path: PathBuf,
+        root: bool,
+    },
+    SetMode {
+        path: PathBuf,
+        mode: u32,
+    },
+    LeaveGroup {
+        user: String,
+        group: String,
+    },
+    /// Restart or reload a unit once the files it reads are restored.
+    Service {
+        action: String,
+        unit: String,
+    },
+    /// Packages that were not installed before. Only removed on request,
+    /// since other software may have come to rely on them.
+    RemovePackages {
+        packages: Vec<String>,
+    },
+    /// A step-specific command. `key` names the setting it restores, so a
+    /// re-run does not record the setting a second time.
+    Command {
+        key: String,
+        argv: Vec<String>,
+        privileged: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        input: Option<String>,
+    },
+}
+
+impl Undo {
+    /// A step-specific undo command; see [`Undo::Command`].
+    pub fn command(key: &str, privileged: bool, argv: &[&str]) -> Self {
+        Undo::Command {
+            key: key.to_string(),
+            argv: argv.iter().map(|a| a.to_string()).collect(),
+            privileged,
+            input: None,
+        }
+    }
+
+    /// What this undo restores. Two undos with the same key restore the
+    /// same thing, and only the earlier one describes the pre-Survon state.
+    fn key(&self) -> String {
+        match self {
+            Undo::Restore { path, .. } | Undo::Delete { path, .. } | Undo::SetMode { path, .. } => {
+                format!("path:{}", path.display())
+            }
+            Undo::LeaveGroup { user, group } => format!("group:{user}:{group}"),
+            Undo::Service { action, unit } => format!("service:{action}:{unit}"),
+            Undo::RemovePackages { packages } => format!("packages:{}", packages.join(",")),
+            Undo::Command { key, .. } => format!("command:{key}"),
+        }
+    }
+}
+
+impl fmt::Display for Undo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Undo::Restore { path, .. } => write!(f, "restore {}", path.display()),
+            Undo::Delete { path, .. } => write!(f, "delete {}", path.display()),
+            Undo::SetMode { path, mode } => write!(f, "chmod {mode:o} {}", path.display()),
+            Undo::LeaveGroup { user, group } => write!(f, "remove {user} from group {group}"),
+            Undo::Service { action, unit } => write!(f, "systemctl {action} {unit}"),
+            Undo::RemovePackages { packages } => write!(f, "apt remove {}", packages.join(" ")),
+            Undo::Command { argv, .. } => f.write_str(&argv.join(" ")),
+        }
+    }
+}
+
+/// The undo actions recorded while one step ran, in the order they were
+/// recorded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub step: String,
+    pub started_at: u64,
+    pub undo: Vec<Undo>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Ledger {
+    #[serde(skip)]
+    dir: PathBuf,
+    #[serde(default)]
+    next_backup: u32,
+    #[serde(default)]
+    transactions: Vec<Transaction>,
+}
+
+impl Ledger {
+    /// Loads the ledger from `state_dir`, or starts an empty one.
+    pub fn open(state_dir: &Path) -> Result<Self> {
+        let path = state_dir.join(LEDGER_FILE);
+        let mut ledger: Ledger = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| Error::Ledger(format!("{} is unreadable ({e})", path.display())))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ledger::default(),
+            Err(e) => return Err(Error::io(&path, e)),
+        };
+        ledger.dir = state_dir.to_path_buf();
+        Ok(ledger)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.iter().all(|t| t.undo.is_empty())
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Starts the transaction that following undo actions belong to.
+    pub fn begin(&mut self, step: StepId) {
+        self.transactions.retain(|t| !t.undo.is_empty());
+        self.transactions.push(Transaction {
+            step: step.to_string(),
+            started_at: now(),
+            undo: Vec::new(),
+        });
+    }
+
+    /// Whether an earlier record already restores what `undo` would.
+    pub fn covers(&self, undo: &Undo) -> bool {
+        let key = undo.key();
+        self.transactions
+            .iter()
+            .flat_map(|t| &t.undo)
+            .any(|u| u.key() == key)
+    }
+
+    /// Adds `undo` to the current transaction unless it is already covered,
+    /// and saves the ledger.
+    pub fn push(&mut self, undo: Undo) -> Result<()> {
+        if self.covers(&undo) {
+            return Ok(());
+        }
+        match self.transactions.last_mut() {
+            Some(transaction) => transaction.undo.push(undo),
+            None => return Err(Error::Ledger("undo recorded outside a transaction".into())),
+        }
+        self.save()
+    }
+
+    /// Copies `path` into the backup directory and returns where it went.
+    pub fn back_up(&mut self, path: &Path) -> Result<PathBuf> {
+        let dir = self.dir.join(BACKUP_DIR);
+        fs::create_dir_all(&dir).map_err(|e| Error::io(&dir, e))?;
+        self.next_backup += 1;
+        let name = path
+            .file_name()
+            .map_or_else(|| "file".into(), |n| n.to_string_lossy());
+        let backup = dir.join(format!("{:04}-{name}", self.next_backup));
+        fs::copy(path, &backup).map_err(|e| Error::io(path, e))?;
+        Ok(backup)
+    }
+
+    /// Forgets every transaction and deletes the backups.
+    pub fn clear(&mut self) -> Result<()> {
+        self.transactions.clear();
+        self.next_backup = 0;
+        let dir = self.dir.join(BACKUP_DIR);
+        match fs::remove_dir_all(&dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Error::io(&dir, e)),
+        }
+        self.save()
+    }
+
+    /// Writes the ledger atomically, like the install journal.
+    fn save(&self) -> Result<()> {
+        let path = self.dir.join(LEDGER_FILE);
+        let json = serde_json::to_string_pretty(self).map_err(|e| Error::Ledger(e.to_string()))?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| Error::io(&tmp, e))?;
+        fs::rename(&tmp, &path).map_err(|e| Error::io(&path, e))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RollbackOptions {
+    /// Also remove the packages the installer added.
+    pub remove_packages: bool,
+}
+
+/// Reverts every transaction in `ledger`, newest first, through `system`.
+///
+/// Failures are reported and do not stop the rollback. Service restarts are
+/// deferred until every file is back in place, since restarting bluetoothd
+/// before its unit file is restored would only pick up the Survon version.
+/// In a live rollback the ledger keeps just the undos that failed, so
+/// running it again retries them. Returns whether everything was reverted.
+pub fn rollback(system: &System, ledger: &mut Ledger, opts: &RollbackOptions) -> bool {
+    let mut ok = true;
+    let mut services: Vec<(String, String)> = Vec::new();
+    let mut reload_units = false;
+    let mut remaining: Vec<Transaction> = Vec::new();
+
+    for transaction in ledger.transactions.iter().rev() {
+        if transaction.undo.is_empty() {
+            continue;
+        }
+        println!("Reverting {}:", transaction.step);
+        let mut failed = Vec::new();
+        for undo in transaction.undo.iter().rev() {
+            let result = match undo {
+                Undo::Service { action, unit } => {
+                    if !services.iter().any(|(_, u)| u == unit) {
+                        services.push((action.clone(), unit.clone()));
+                    }
+                    continue;
+                }
+                Undo::RemovePackages { packages } if !opts.remove_packages => {
+                    println!(
+                        "  kept packages {} (pass --remove-packages to remove them)",
+                        packages.join(" ")
+                    );
+                    continue;
+                }
+                _ => revert(system, undo),
+            };
+            if let Undo::Restore { path, .. } | Undo::Delete { path, .. } = undo {
+                reload_units |= is_unit_file(path);
+            }
+            report(system, undo, &result);
+            if result.is_err() {
+                ok = false;
+                failed.push(undo.clone());
+            }
+        }
+        if !failed.is_empty() {
+            failed.reverse();
+            remaining.insert(
+                0,
+                Transaction {
+                    undo: failed,
+                    ..transaction.clone()
+                },
+            );
+        }
+    }
+
+    if reload_units || !services.is_empty() {
+        println!("Restarting services:");
+    }
+    if reload_units {
+        let result = system
+            .run(system.privileged("systemctl").arg("daemon-reload"))
+            .map(drop);
+        ok &= result.is_ok();
+        report(
+            system,
+            &Undo::command("daemon-reload", true, &["systemctl", "daemon-reload"]),
+            &result,
+        );
+    }
+    for (action, unit) in services {
+        let result = system.service(&action, &unit);
+        ok &= result.is_ok();
+        report(system, &Undo::Service { action, unit }, &result);
+    }
+
+    if !system.is_planning() {
+        let saved = if remaining.is_empty() {
+            ledger.clear()
+        } else {
+            ledger.transactions = remaining;
+            ledger.save()
+        };
+        if let Err(e) = saved {
+            eprintln!("Warning: could not update rollback ledger: {e}");
+        }
+    }
+    ok
+}
+
+fn revert(system: &System, undo: &Undo) -> Result<()> {
+    match undo {
+        Undo::Restore { path, backup, root } => {
+            // Writing through a symlink the installer put there would
+            // clobber its target instead.
+            if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+                if *root {
+                    system.remove_root(path)?;
+                } else {
+                    system.remove(path)?;
+                }
+            }
+            match fs::read_to_string(backup) {
+                Ok(contents) if *root => system.write_root_file(path, &contents),
+                Ok(contents) => system.write_file(path, &contents),
+                Err(_) if *root => system.copy_root_file(backup, path),
+                Err(_) => system.copy_file(backup, path),
+            }
+        }
+        Undo::Delete { path, root: true } => system.remove_root(path),
+        Undo::Delete { path, root: false } => system.remove(path),
+        Undo::SetMode { path, mode } => system.set_mode(path, *mode),
+        Undo::LeaveGroup { user, group } => system.remove_from_group(user, group),
+        Undo::RemovePackages { packages } => {
+            let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
+            system.remove_packages(&packages)
+        }
+        Undo::Command {
+            argv,
+            privileged,
+            input,
+            ..
+        } => {
+            let Some((program, args)) = argv.split_first() else {
+                return Ok(());
+            };
+            let mut cmd = if *privileged {
+                system.privileged(program)
+            } else {
+                system.command(program)
+            };
+            cmd.args(args);
+            system
+                .run_with_input(&mut cmd, input.as_deref().map(str::as_bytes))
+                .map(drop)
+        }
+        Undo::Service { action, unit } => system.service(action, unit),
+    }
+}
+
+/// Prints what an undo changed (or would change, when planning).
+fn report(system: &System, undo: &Undo, result: &Result<()>) {
+    let changes = system.take_changes();
+    match result {
+        Ok(()) if changes.is_empty() => {}
+        Ok(()) => {
+            for change in changes {
+                println!("  {change}");
+            }
+        }
+        Err(e) => println!("  [Failed] {undo}: {e}"),
+    }
+}
+
+fn is_unit_file(path: &Path) -> bool {
+    ["/lib/systemd/", "/etc/systemd/", "/usr/lib/systemd/"]
+        .iter()
+        .any(|dir| path.starts_with(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::Mode;
+    use survon_test_support::Scratch;
+
+    fn delete(path: &Path) -> Undo {
+        Undo::Delete {
+            path: path.to_path_buf(),
+            root: false,
+        }
+    }
+
+    #[test]
+    fn the_first_record_of_a_path_wins() {
+        let dir = Scratch::new("first");
+        let file = dir.join("hosts");
+        let mut ledger = Ledger::open(&dir).unwrap();
+        ledger.begin(StepId::Hostname);
+        ledger.push(delete(&file)).unwrap();
+        ledger.begin(StepId::BluezConfig);
+        ledger
+            .push(Undo::Restore {
+                path: file.clone(),
+                backup: dir.join("backup"),
+                root: false,
+            })
+            .unwrap();
+        ledger
+            .push(Undo::command("hostname", true, &["hostname", "old"]))
+            .unwrap();
+
+        let ledger = Ledger::open(&dir).unwrap();
+        let undos: Vec<&Undo> = ledger.transactions().iter().flat_map(|t| &t.undo).collect();
+        assert_eq!(undos.len(), 2);
+        assert_eq!(undos[0], &delete(&file));
+        assert!(matches!(undos[1], Undo::Command { key, .. } if key == "hostname"));
+    }
+
+    #[test]
+    fn undos_need_a_transaction() {
+        let dir = Scratch::new("outside");
+        let mut ledger = Ledger::open(&dir).unwrap();
+        assert!(ledger.push(delete(&dir.join("x"))).is_err());
+        ledger.begin(StepId::Hostname);
+        ledger.begin(StepId::AptUpdate);
+        assert_eq!(ledger.transactions().len(), 1);
+        assert!(ledger.is_empty());
+    }
+
+    #[test]
+    fn rollback_restores_and_deletes() {
+        let dir = Scratch::new("rollback");
+        let edited = dir.join("config.txt");
+        let created = dir.join("new.txt");
+        fs::write(&edited, "before").unwrap();
+        let mut ledger = Ledger::open(&dir).unwrap();
+        ledger.begin(StepId::BluezConfig);
+        let backup = ledger.back_up(&edited).unwrap();
+        ledger
+            .push(Undo::Restore {
+                path: edited.clone(),
+                backup: backup.clone(),
+                root: false,
+            })
+            .unwrap();
+        ledger.push(delete(&created)).unwrap();
+        fs::write(&edited, "after").unwrap();
+        fs::write(&created, "new").unwrap();
+
+        let system = System::new(Mode::Live);
+        assert!(rollback(&system, &mut ledger, &RollbackOptions::default()));
+        assert_eq!(fs::read_to_string(&edited).unwrap(), "before");
+        assert!(!created.exists());
+        assert!(!backup.exists());
+        assert!(Ledger::open(&dir).unwrap().is_empty());
+    }
+}
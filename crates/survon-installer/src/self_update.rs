@@ -0,0 +1,103 @@
+//! Verified self-update of the installer binary.
+//!
+//! Replaces install.sh's `check_installer_updates`, which trusted whatever
+//! file at the download URL hashed differently from itself. Here the release
+//! manifest must carry a valid signature from the pinned release key, the
+//! binary must match the manifest's checksum, and an older release is never
+//! installed over a newer one.
+//!
+//! The `survon` command-line tool is released alongside the installer and
+//! kept next to it; a self-update installs it too, and brings it back if it
+//! is missing.
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use survon_update::{arch, Manifest, Version, VersionChange, RELEASE_PUBLIC_KEY};
+
+use crate::error::{Error, Result};
+
+pub const RELEASES_URL: &str = "https://github.com/survon/survon-os/releases/latest/download";
+
+/// What a self-update did.
+#[derive(Debug)]
+pub enum SelfUpdate {
+    UpToDate(Version),
+    /// A newer release exists; only reported with `check_only`.
+    Available {
+        from: Version,
+        to: Version,
+    },
+    Updated {
+        from: Version,
+        to: Version,
+    },
+}
+
+pub fn current_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("crate version is valid semver")
+}
+
+/// Released alongside the installer and installed into the same directory.
+pub const COMPANION_BINARY: &str = "survon";
+
+/// Release asset holding the installer for this architecture.
+pub fn asset_name() -> String {
+    format!("survon-installer-{}", arch::current())
+}
+
+fn companion_asset_name() -> String {
+    format!("{COMPANION_BINARY}-{}", arch::current())
+}
+
+/// Checks the release published at `base_url` (normally [`RELEASES_URL`])
+/// and, unless `check_only`, installs it over the running executable. Any
+/// URL curl understands works, so a mirror or a `file://` directory can
+/// stand in for GitHub; the signature is checked either way.
+pub fn self_update(base_url: &str, check_only: bool) -> Result<SelfUpdate> {
+    let scratch = env::temp_dir().join(format!("survon-installer-update-{}", std::process::id()));
+    let result = update_from(base_url, &scratch, check_only);
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+fn update_from(base_url: &str, scratch: &Path, check_only: bool) -> Result<SelfUpdate> {
+    let manifest = Manifest::fetch(base_url, scratch, RELEASE_PUBLIC_KEY)?;
+    if manifest.name != "survon-installer" {
+        return Err(survon_update::Error::Manifest(format!(
+            "release is for `{}`, not survon-installer",
+            manifest.name
+        ))
+        .into());
+    }
+    let exe = env::current_exe().map_err(|e| Error::io("current executable", e))?;
+    let companion = exe.with_file_name(COMPANION_BINARY);
+    let (from, to) = match manifest.change_from(&current_version())? {
+        VersionChange::UpToDate(version) => {
+            if !check_only && !companion.exists() {
+                install(&manifest, base_url, &companion_asset_name(), &companion)?;
+            }
+            return Ok(SelfUpdate::UpToDate(version));
+        }
+        VersionChange::Upgrade { from, to } => (from, to),
+    };
+    if check_only {
+        return Ok(SelfUpdate::Available { from, to });
+    }
+
+    install(&manifest, base_url, &companion_asset_name(), &companion)?;
+    install(&manifest, base_url, &asset_name(), &exe)?;
+    Ok(SelfUpdate::Updated { from, to })
+}
+
+/// Downloads `asset` next to `dest` so the final rename cannot cross
+/// filesystems and is atomic.
+fn install(manifest: &Manifest, base_url: &str, asset: &str, dest: &Path) -> Result<()> {
+    let staged = dest.with_extension("new");
+    manifest.download(base_url, asset, &staged)?;
+    fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))
+        .map_err(|e| Error::io(&staged, e))?;
+    fs::rename(&staged, dest).map_err(|e| Error::io(dest, e))
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Serialize, Serializer};
+
+use crate::context::Context;
+use crate::error::Result;
+
+/// Identifies an installer step. The kebab-case name doubles as the suffix
+/// of the step's `--skip-<name>` flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StepId {
+    Hostname,
+    AptUpdate,
+    InstallDeps,
+    TerminalColors,
+    BluezConfig,
+    DbusPerms,
+    BleTest,
+    InstallRustup,
+    DownloadRuntime,
+    ModelSelection,
+    DownloadJukeboxAudio,
+    Environment,
+    FetchBinary,
+    FetchSurvonSh,
+    BootFlow,
+    Cleanup,
+}
+
+impl StepId {
+    pub const ALL: [StepId; 16] = [
+        StepId::Hostname,
+        StepId::AptUpdate,
+        StepId::InstallDeps,
+        StepId::TerminalColors,
+        StepId::BluezConfig,
+        StepId::DbusPerms,
+        StepId::BleTest,
+        StepId::InstallRustup,
+        StepId::DownloadRuntime,
+        StepId::ModelSelection,
+        StepId::DownloadJukeboxAudio,
+        StepId::Environment,
+        StepId::FetchBinary,
+        StepId::FetchSurvonSh,
+        StepId::BootFlow,
+        StepId::Cleanup,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepId::Hostname => "hostname",
+            StepId::AptUpdate => "apt-update",
+            StepId::InstallDeps => "install-deps",
+            StepId::TerminalColors => "terminal-colors",
+            StepId::BluezConfig => "bluez-config",
+            StepId::DbusPerms => "dbus-perms",
+            StepId::BleTest => "ble-test",
+            StepId::InstallRustup => "install-rustup",
+            StepId::DownloadRuntime => "download-runtime",
+            StepId::ModelSelection => "model-selection",
+            StepId::DownloadJukeboxAudio => "download-jukebox-audio",
+            StepId::Environment => "environment",
+            StepId::FetchBinary => "fetch-binary",
+            StepId::FetchSurvonSh => "fetch-survon-sh",
+            StepId::BootFlow => "boot-flow",
+            StepId::Cleanup => "cleanup",
+        }
+    }
+
+    pub fn skip_flag(self) -> String {
+        format!("skip-{}", self.as_str())
+    }
+
+    /// Names the step went by before, still accepted wherever a step is
+    /// named.
+    pub fn aliases(self) -> &'static [&'static str] {
+        match self {
+            StepId::BootFlow => &["set-crontab"],
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for StepId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for StepId {
+    fn serialize<S: Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_str())
+    }
+}
+
+impl FromStr for StepId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StepId::ALL
+            .into_iter()
+            .find(|id| id.as_str() == s || id.aliases().contains(&s))
+            .ok_or_else(|| format!("unknown step `{s}`"))
+    }
+}
+
+/// Result of a step's `check` phase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Check {
+    /// The system is not yet in the state the step produces.
+    Needed,
+    /// Nothing to do; the reason is shown to the user.
+    Satisfied(String),
+}
+
+/// One unit of installation work.
+///
+/// The pipeline calls `check` first; if it reports [`Check::Needed`] it calls
+/// `apply` and then `verify`, so a step only counts as done once the system
+/// has been observed in the expected state. Files produced by `apply` are
+/// registered with [`Context::artifact`] so the journal can fingerprint them.
+pub trait Step {
+    fn id(&self) -> StepId;
+
+    fn title(&self) -> &'static str;
+
+    /// Steps that must have completed (or been skipped) before this one.
+    fn depends_on(&self) -> &'static [StepId] {
+        &[]
+    }
+
+    /// Values the step's result depends on. A completion recorded in the
+    /// install journal is only reused while these are unchanged.
+    fn inputs(&self, _ctx: &Context) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    /// Whether a completion recorded by an earlier run may stand in for
+    /// running the step again. Cheap diagnostics opt out.
+    fn resumable(&self) -> bool {
+        true
+    }
+
+    fn check(&self, _ctx: &Context) -> Result<Check> {
+        Ok(Check::Needed)
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()>;
+
+    fn verify(&self, _ctx: &Context) -> Result<()> {
+        Ok(())
+    }
+}
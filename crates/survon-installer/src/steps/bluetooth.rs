@@ -0,0 +1,217 @@
+use std::fs;
+use std::path::Path;
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::step::{Check, Step, StepId};
+
+const BLUETOOTH_SERVICE: &str = "/lib/systemd/system/bluetooth.service";
+const BLUETOOTH_SERVICE_BACKUP: &str = "/lib/systemd/system/bluetooth.service.backup";
+const DBUS_POLICY: &str = "/etc/dbus-1/system.d/bluetooth-user.conf";
+
+pub struct BluezConfig;
+
+impl Step for BluezConfig {
+    fn id(&self) -> StepId {
+        StepId::BluezConfig
+    }
+
+    fn title(&self) -> &'static str {
+        "Configure BlueZ for btleplug"
+    }
+
+    fn depends_on(&self) -> &'static [StepId] {
+        &[StepId::InstallDeps]
+    }
+
+    fn inputs(&self, ctx: &Context) -> Vec<(&'static str, String)> {
+        vec![("user", ctx.user.clone())]
+    }
+
+    fn check(&self, ctx: &Context) -> Result<Check> {
+        Ok(if experimental_enabled()? && in_bluetooth_group(ctx) {
+            Check::Satisfied("experimental features already enabled".into())
+        } else {
+            Check::Needed
+        })
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        let version = ctx
+            .system
+            .probe(ctx.system.command("bluetoothctl").arg("--version"))
+            .unwrap_or_default();
+        ctx.say(format!("Detected BlueZ version: {}", version.trim()));
+
+        // Keep the distro's original unit, not one we already edited.
+        if !Path::new(BLUETOOTH_SERVICE_BACKUP).exists() {
+            ctx.system.copy_root_file(
+                Path::new(BLUETOOTH_SERVICE),
+                Path::new(BLUETOOTH_SERVICE_BACKUP),
+            )?;
+        }
+
+        ctx.say("Enabling BlueZ experimental features...");
+        ctx.system
+            .edit_root_file(Path::new(BLUETOOTH_SERVICE), with_experimental)?;
+
+        ctx.system
+            .run(ctx.system.privileged("systemctl").arg("daemon-reload"))?;
+        ctx.system.service("restart", "bluetooth")?;
+        ctx.system.add_to_group(&ctx.user, "bluetooth")
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        if !experimental_enabled()? {
+            return Err(Error::Verify(format!(
+                "{BLUETOOTH_SERVICE} does not start bluetoothd with --experimental"
+            )));
+        }
+        if !ctx.system.service_active("bluetooth") {
+            return Err(Error::Verify("bluetooth service is not running".into()));
+        }
+        Ok(())
+    }
+}
+
+fn experimental_enabled() -> Result<bool> {
+    let unit =
+        fs::read_to_string(BLUETOOTH_SERVICE).map_err(|e| Error::io(BLUETOOTH_SERVICE, e))?;
+    Ok(unit
+        .lines()
+        .any(|l| l.starts_with("ExecStart=") && l.contains("--experimental")))
+}
+
+/// Adds `--experimental` to the bluetoothd `ExecStart=` line.
+fn with_experimental(unit: &str) -> String {
+    unit.lines()
+        .map(|line| {
+            if line.starts_with("ExecStart=")
+                && line.contains("bluetoothd")
+                && !line.contains("--experimental")
+            {
+                format!("{line} --experimental")
+            } else {
+                line.to_string()
+            }
+        })
+        .flat_map(|l| [l, "\n".to_string()])
+        .collect()
+}
+
+fn in_bluetooth_group(ctx: &Context) -> bool {
+    ctx.system
+        .probe(ctx.system.command("id").args(["-nG", &ctx.user]))
+        .is_some_and(|groups| groups.split_whitespace().any(|g| g == "bluetooth"))
+}
+
+pub struct DbusPerms;
+
+impl Step for DbusPerms {
+    fn id(&self) -> StepId {
+        StepId::DbusPerms
+    }
+
+    fn title(&self) -> &'static str {
+        "Configure DBus Permissions"
+    }
+
+    fn depends_on(&self) -> &'static [StepId] {
+        &[StepId::BluezConfig]
+    }
+
+    fn inputs(&self, ctx: &Context) -> Vec<(&'static str, String)> {
+        vec![("user", ctx.user.clone())]
+    }
+
+    fn check(&self, ctx: &Context) -> Result<Check> {
+        let current = fs::read_to_string(DBUS_POLICY).unwrap_or_default();
+        Ok(if current == dbus_policy(&ctx.user) {
+            Check::Satisfied("policy already installed".into())
+        } else {
+            Check::Needed
+        })
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        ctx.system
+            .write_root_file(Path::new(DBUS_POLICY), &dbus_policy(&ctx.user))?;
+        ctx.artifact(DBUS_POLICY);
+        ctx.system.service("reload", "dbus")?;
+        ctx.system.service("restart", "bluetooth")
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        let installed = fs::read_to_string(DBUS_POLICY).map_err(|e| Error::io(DBUS_POLICY, e))?;
+        if installed != dbus_policy(&ctx.user) {
+            return Err(Error::Verify(format!(
+                "{DBUS_POLICY} has unexpected contents"
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn dbus_policy(user: &str) -> String {
+    format!(
+        r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-BUS Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <policy user="{user}">
+    <allow send_destination="*"/>
+    <allow receive_sender="*"/>
+  </policy>
+</busconfig>
+"#
+    )
+}
+
+pub struct BleTest;
+
+impl Step for BleTest {
+    fn id(&self) -> StepId {
+        StepId::BleTest
+    }
+
+    fn title(&self) -> &'static str {
+        "Test BLE Setup"
+    }
+
+    fn depends_on(&self) -> &'static [StepId] {
+        &[StepId::BluezConfig, StepId::DbusPerms]
+    }
+
+    fn resumable(&self) -> bool {
+        false
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        if !hci0_up(ctx) {
+            ctx.say("hci0 not up, attempting to bring it up...");
+            ctx.system
+                .run(ctx.system.privileged("hciconfig").args(["hci0", "up"]))?;
+        }
+        if !ctx.system.service_active("bluetooth") {
+            ctx.say("Bluetooth service not running, starting it...");
+            ctx.system.service("start", "bluetooth")?;
+        }
+        Ok(())
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        if !hci0_up(ctx) {
+            return Err(Error::Verify("hci0 is not UP RUNNING".into()));
+        }
+        if !ctx.system.service_active("bluetooth") {
+            return Err(Error::Verify("bluetooth service is not running".into()));
+        }
+        ctx.say("hci0 is up and the bluetooth service is active");
+        Ok(())
+    }
+}
+
+fn hci0_up(ctx: &Context) -> bool {
+    ctx.system
+        .probe(ctx.system.command("hciconfig").arg("hci0"))
+        .is_some_and(|out| out.contains("UP RUNNING"))
+}
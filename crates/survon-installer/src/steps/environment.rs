@@ -0,0 +1,139 @@
+use survon_config::schema;
+
+use crate::bashrc;
+use crate::config;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::step::{Check, Step, StepId};
+
+const ENV_HOOK: &str = r#"# Load Survon settings (change them with `survon config set`)
+if [ -f /etc/survon/config/survon.env ]; then
+    set -a; . /etc/survon/config/survon.env; set +a
+fi
+"#;
+
+/// The hook earlier installs wrote, from before the settings moved into
+/// their own directory.
+const LEGACY_ENV_HOOK: &str = r#"# Load Survon settings (change them with `survon config set`)
+if [ -f /etc/survon/survon.env ]; then
+    set -a; . /etc/survon/survon.env; set +a
+fi
+"#;
+
+/// Stores the council strategy and extra variables from a provisioning file
+/// in the settings store, moves settings that earlier installs (and the old
+/// survon.sh environment menu) exported from `.bashrc` there too, and makes
+/// login shells load the settings.
+pub struct Environment;
+
+impl Environment {
+    fn exports(ctx: &Context) -> Vec<(String, String)> {
+        let Some(provision) = &ctx.provision else {
+            return Vec::new();
+        };
+        let mut exports: Vec<(String, String)> = provision
+            .council_strategy
+            .iter()
+            .map(|s| ("COUNCIL_STRATEGY".to_string(), s.clone()))
+            .collect();
+        exports.extend(provision.env.clone());
+        exports
+    }
+
+    /// Settings still exported from `.bashrc`: known keys, and whatever the
+    /// provisioning file sets. Values the store would reject stay where
+    /// they are.
+    fn legacy(ctx: &Context) -> Result<Vec<(String, String)>> {
+        let provisioned = Self::exports(ctx);
+        Ok(bashrc::exports(&ctx.bashrc())?
+            .into_iter()
+            .filter(|(key, value)| {
+                (schema::key(key).is_some() || provisioned.iter().any(|(k, _)| k == key))
+                    && schema::parse_custom(key, value).is_ok()
+            })
+            .collect())
+    }
+
+    fn missing(ctx: &Context) -> Result<Vec<(String, String)>> {
+        let mut missing = Vec::new();
+        for (key, value) in Self::exports(ctx) {
+            if !config::has(ctx, &key, &value)? {
+                missing.push((key, value));
+            }
+        }
+        Ok(missing)
+    }
+
+    fn hooked(ctx: &Context) -> Result<bool> {
+        bashrc::contains(&ctx.bashrc(), "/etc/survon/config/survon.env")
+    }
+}
+
+impl Step for Environment {
+    fn id(&self) -> StepId {
+        StepId::Environment
+    }
+
+    fn title(&self) -> &'static str {
+        "Configure Environment"
+    }
+
+    fn inputs(&self, ctx: &Context) -> Vec<(&'static str, String)> {
+        let exports: Vec<String> = Self::exports(ctx)
+            .into_iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        vec![("exports", exports.join(" "))]
+    }
+
+    fn check(&self, ctx: &Context) -> Result<Check> {
+        Ok(
+            if Self::hooked(ctx)? && Self::legacy(ctx)?.is_empty() && Self::missing(ctx)?.is_empty()
+            {
+                Check::Satisfied("settings already in place".into())
+            } else {
+                Check::Needed
+            },
+        )
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        let mut settings = config::load(ctx)?;
+        let mut moved = Vec::new();
+        for (key, value) in Self::legacy(ctx)? {
+            if settings.get(&key).is_none() {
+                settings.set_custom(&key, &value)?;
+                ctx.say(format!("Moving {key} from .bashrc to the settings store."));
+            }
+            moved.push(key);
+        }
+        for (key, value) in Self::exports(ctx) {
+            settings.set_custom(&key, &value)?;
+        }
+        config::save(ctx, &mut settings)?;
+
+        let moved: Vec<&str> = moved.iter().map(String::as_str).collect();
+        bashrc::remove_exports(&ctx.system, &ctx.bashrc(), &moved)?;
+        bashrc::replace(&ctx.system, &ctx.bashrc(), LEGACY_ENV_HOOK, ENV_HOOK)?;
+        bashrc::append_once(
+            &ctx.system,
+            &ctx.bashrc(),
+            "/etc/survon/config/survon.env",
+            ENV_HOOK,
+        )?;
+        Ok(())
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        if !Self::hooked(ctx)? {
+            return Err(Error::Verify("settings hook missing from .bashrc".into()));
+        }
+        match Self::missing(ctx)?.first() {
+            Some((key, _)) => Err(Error::Verify(format!(
+                "{key} missing from {}",
+                ctx.config_path().display()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
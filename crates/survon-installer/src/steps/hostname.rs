@@ -0,0 +1,118 @@
+use std::path::Path;
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::rollback::Undo;
+use crate::step::{Step, StepId};
+
+const HOSTS: &str = "/etc/hosts";
+
+pub struct SetHostname;
+
+impl Step for SetHostname {
+    fn id(&self) -> StepId {
+        StepId::Hostname
+    }
+
+    fn title(&self) -> &'static str {
+        "Set Hostname"
+    }
+
+    fn inputs(&self, ctx: &Context) -> Vec<(&'static str, String)> {
+        ctx.provision
+            .iter()
+            .map(|p| ("hostname", p.hostname.clone()))
+            .collect()
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        let name = match &ctx.provision {
+            Some(provision) => provision.hostname.clone(),
+            None => ctx.prompt.ask_default("Enter hostname", "survon")?,
+        };
+        validate(&name)?;
+
+        ctx.say(format!("Setting hostname to {name}..."));
+        if let Some(previous) = ctx.system.probe(&mut ctx.system.command("hostname")) {
+            ctx.system.on_rollback(Undo::command(
+                "hostname",
+                true,
+                &["hostnamectl", "set-hostname", previous.trim()],
+            ))?;
+        }
+        ctx.system.run(
+            ctx.system
+                .privileged("hostnamectl")
+                .args(["set-hostname", &name]),
+        )?;
+        ctx.system
+            .edit_root_file(Path::new(HOSTS), |hosts| with_loopback_name(hosts, &name))?;
+
+        // Restart avahi-daemon if available (for .local mDNS)
+        if ctx.system.service_active("avahi-daemon") {
+            ctx.system.service("restart", "avahi-daemon")?;
+            ctx.say(format!(
+                "Hostname set to {name} (accessible as {name}.local)"
+            ));
+        } else {
+            ctx.say(format!("Hostname set to {name}"));
+        }
+
+        ctx.hostname = Some(name);
+        Ok(())
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        let expected = ctx.hostname.as_deref().unwrap_or_default();
+        let actual = ctx
+            .system
+            .probe(&mut ctx.system.command("hostname"))
+            .unwrap_or_default();
+        if actual.trim() != expected {
+            return Err(Error::Verify(format!(
+                "hostname is `{}`, expected `{expected}`",
+                actual.trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// RFC 1123 label: letters, digits and inner hyphens, at most 63 characters.
+pub(crate) fn validate(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 63
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Input(format!(
+            "`{name}` is not a valid hostname (letters, digits and '-' only)"
+        )))
+    }
+}
+
+/// Points the Debian `127.0.1.1` loopback entry at `name`, adding it if absent.
+fn with_loopback_name(hosts: &str, name: &str) -> String {
+    let entry = format!("127.0.1.1\t{name}");
+    let mut found = false;
+    let mut out: String = hosts
+        .lines()
+        .map(|line| {
+            if line.starts_with("127.0.1.1") {
+                found = true;
+                entry.as_str()
+            } else {
+                line
+            }
+        })
+        .flat_map(|l| [l, "\n"])
+        .collect();
+    if !found {
+        out.push_str(&entry);
+        out.push('\n');
+    }
+    out
+}
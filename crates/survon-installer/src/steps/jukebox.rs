@@ -0,0 +1,125 @@
+use std::fs;
+use std::path::Path;
+
+use crate::context::Context;
+use crate::error::Result;
+use crate::step::{Step, StepId};
+
+pub(crate) const ARCHIVE_URL: &str = "https://archive.org/compress/BigBandMixRecordings1935-1945/formats=VBR%20MP3&file=/BigBandMixRecordings1935-1945.zip";
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "wav", "flac", "ogg"];
+
+pub struct DownloadJukeboxAudio;
+
+impl Step for DownloadJukeboxAudio {
+    fn id(&self) -> StepId {
+        StepId::DownloadJukeboxAudio
+    }
+
+    fn title(&self) -> &'static str {
+        "Optional Jukebox Audio Download"
+    }
+
+    fn depends_on(&self) -> &'static [StepId] {
+        &[StepId::DownloadRuntime]
+    }
+
+    fn inputs(&self, ctx: &Context) -> Vec<(&'static str, String)> {
+        ctx.provision
+            .iter()
+            .map(|p| ("download_audio", p.download_audio.to_string()))
+            .collect()
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        let audio_dir = ctx.modules_dir().join("core/big_band_mix/audio");
+        let choice = match &ctx.provision {
+            Some(provision) if provision.download_audio => "1".to_string(),
+            Some(_) => "2".to_string(),
+            None => ask(ctx)?,
+        };
+
+        if choice != "1" {
+            if choice != "2" {
+                ctx.say("Invalid choice. Skipping audio download.");
+            }
+            print_manual_instructions(ctx, &audio_dir);
+            return Ok(());
+        }
+
+        ctx.say("Downloading Big Band Mix audio files...");
+        ctx.say("This will take 5-10 minutes depending on your connection.");
+        ctx.system.create_dir_all(&audio_dir)?;
+        let zip = audio_dir.join("BigBandMix.zip");
+
+        let result = ctx.system.download(ARCHIVE_URL, &zip).and_then(|()| {
+            ctx.system.run(
+                ctx.system
+                    .command("unzip")
+                    .args(["-q", "-o"])
+                    .arg(&zip)
+                    .arg("-d")
+                    .arg(&audio_dir),
+            )
+        });
+        ctx.system.remove(&zip)?;
+        if let Err(e) = result {
+            print_manual_instructions(ctx, &audio_dir);
+            return Err(e);
+        }
+
+        ctx.say(format!(
+            "Successfully extracted {} audio files",
+            count_audio(&audio_dir)
+        ));
+        ctx.say(format!("   Location: {}", audio_dir.display()));
+        ctx.artifact(audio_dir);
+        Ok(())
+    }
+}
+
+/// Describes the album and asks whether to download it now.
+fn ask(ctx: &Context) -> Result<String> {
+    println!("========================================");
+    println!("   Optional: Jukebox Audio Download    ");
+    println!("========================================");
+    println!();
+    println!("The Jukebox module includes a Big Band Mix album");
+    println!("but the audio files need to be downloaded separately.");
+    println!();
+    println!("Collection: Big Band Mix (Recordings 1935-1945)");
+    println!("Source: Internet Archive (Public Domain)");
+    println!("Size: ~117MB (25 tracks)");
+    println!();
+    println!("Download options:");
+    println!("1. Download now (~5-10 minutes)");
+    println!("2. Skip (you can download manually later)");
+    println!();
+    ctx.prompt.ask("Choice: ")
+}
+
+fn count_audio(dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .map(|p| {
+            if p.is_dir() {
+                count_audio(&p)
+            } else {
+                let ext = p.extension().and_then(|e| e.to_str()).unwrap_or_default();
+                usize::from(AUDIO_EXTENSIONS.contains(&ext))
+            }
+        })
+        .sum()
+}
+
+fn print_manual_instructions(ctx: &Context, audio_dir: &Path) {
+    ctx.say("");
+    ctx.say("To download manually later, run these commands:");
+    ctx.say(format!("  cd {}", audio_dir.display()));
+    ctx.say(format!("  curl -L '{ARCHIVE_URL}' -o BigBandMix.zip"));
+    ctx.say("  unzip BigBandMix.zip");
+    ctx.say("  rm BigBandMix.zip");
+}
@@ -0,0 +1,231 @@
+use crate::bashrc;
+use crate::config;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::rollback::Undo;
+use crate::step::{Step, StepId};
+use crate::units::{self, LaunchMode};
+
+pub(crate) const SURVON_SH_URL: &str =
+    "https://raw.githubusercontent.com/survon/survon-os/master/scripts/survon.sh";
+const BOOT_SELECTOR_SH: &str = include_str!("../../assets/boot_selector.sh");
+
+const BOOT_SELECTOR_HOOK: &str = r#"# Auto-run boot selector only on console login (not SSH, and not in the
+# shell the boot selector opens)
+if [ -z "$SSH_CLIENT" ] && [ -z "$SSH_TTY" ] && [ -z "$SURVON_BOOT_SHELL" ]; then
+    exec /home/survon/boot_selector.sh
+fi
+"#;
+/// The hook before the boot selector could open a shell, which would have
+/// started the boot selector again.
+const LEGACY_BOOT_SELECTOR_HOOK: &str = r#"# Auto-run boot selector only on console login (not SSH)
+if [ -z "$SSH_CLIENT" ] && [ -z "$SSH_TTY" ]; then
+    exec /home/survon/boot_selector.sh
+fi
+"#;
+
+/// Installs the menu (survon.sh) and the boot selector into the user's home
+/// directory, and removes the module manager script the menu replaced.
+pub struct FetchSurvonSh;
+
+impl FetchSurvonSh {
+    const SCRIPTS: [&'static str; 2] = ["survon.sh", "boot_selector.sh"];
+}
+
+impl Step for FetchSurvonSh {
+    fn id(&self) -> StepId {
+        StepId::FetchSurvonSh
+    }
+
+    fn title(&self) -> &'static str {
+        "Fetch Latest Survon Launcher"
+    }
+
+    fn inputs(&self, _ctx: &Context) -> Vec<(&'static str, String)> {
+        vec![("boot_selector.sh", fsutil::sha256_str(BOOT_SELECTOR_SH))]
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        let survon_sh = ctx.home.join("survon.sh");
+        ctx.system.download(SURVON_SH_URL, &survon_sh)?;
+        ctx.system.remove(&ctx.home.join("module_manager.sh"))?;
+        let boot_selector = ctx.home.join("boot_selector.sh");
+        ctx.system.write_file(&boot_selector, BOOT_SELECTOR_SH)?;
+
+        for script in Self::SCRIPTS {
+            let path = ctx.home.join(script);
+            ctx.system.set_executable(&path)?;
+            ctx.artifact(path);
+        }
+        Ok(())
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        for script in Self::SCRIPTS {
+            let path = ctx.home.join(script);
+            if !fsutil::is_executable(&path) {
+                return Err(Error::Verify(format!(
+                    "{} is not executable",
+                    path.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sets up how the unit starts the runtime and the council seat, in the
+/// configured `LAUNCH_MODE` (see [`crate::units`]). Also removes the
+/// crontab entry that started survon.sh before there was a boot selector.
+pub struct BootFlow;
+
+impl Step for BootFlow {
+    fn id(&self) -> StepId {
+        StepId::BootFlow
+    }
+
+    fn title(&self) -> &'static str {
+        "Set Up Boot Flow"
+    }
+
+    fn depends_on(&self) -> &'static [StepId] {
+        &[StepId::FetchSurvonSh, StepId::Environment]
+    }
+
+    fn inputs(&self, ctx: &Context) -> Vec<(&'static str, String)> {
+        let mode = config::load(ctx).map_or(LaunchMode::Console, |c| LaunchMode::configured(&c));
+        let units: String = units::UNITS
+            .iter()
+            .map(|unit| unit.render(&ctx.user, &ctx.home))
+            .collect();
+        vec![
+            ("launch mode", mode.to_string()),
+            ("bashrc hook", fsutil::sha256_str(BOOT_SELECTOR_HOOK)),
+            ("units", fsutil::sha256_str(&units)),
+        ]
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        remove_legacy_crontab(ctx)?;
+        let mode = LaunchMode::configured(&config::load(ctx)?);
+        set_launch_mode(ctx, mode)
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        let mode = LaunchMode::configured(&config::load(ctx)?);
+        let hooked = bashrc::contains(&ctx.bashrc(), "SURVON_BOOT_SHELL")?;
+        match mode {
+            LaunchMode::Console if !hooked => Err(Error::Verify(
+                "boot selector hook missing from .bashrc".into(),
+            )),
+            LaunchMode::Systemd if !units::RUNTIME.is_installed() => Err(Error::Verify(format!(
+                "{} is missing",
+                units::RUNTIME.path().display()
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Switches the unit to `mode`. The console mode logs the console in and
+/// starts the boot selector from `.bashrc`; the systemd mode installs the
+/// services instead, and leaves the console at a login prompt (tty1 goes to
+/// the runtime). Either takes effect at the next boot.
+pub fn set_launch_mode(ctx: &Context, mode: LaunchMode) -> Result<()> {
+    match mode {
+        LaunchMode::Console => {
+            units::remove(ctx)?;
+            ctx.say("Setting up auto-login for boot loader...");
+            set_boot_behaviour(ctx, "B2")?;
+            bashrc::replace(
+                &ctx.system,
+                &ctx.bashrc(),
+                LEGACY_BOOT_SELECTOR_HOOK,
+                BOOT_SELECTOR_HOOK,
+            )?;
+            bashrc::append_once(
+                &ctx.system,
+                &ctx.bashrc(),
+                "boot_selector.sh",
+                BOOT_SELECTOR_HOOK,
+            )?;
+        }
+        LaunchMode::Systemd => {
+            ctx.say("Installing systemd services for the runtime and council seat...");
+            units::install(ctx)?;
+            // A console login would start a second runtime from the boot
+            // selector.
+            set_boot_behaviour(ctx, "B1")?;
+            for hook in [LEGACY_BOOT_SELECTOR_HOOK, BOOT_SELECTOR_HOOK] {
+                bashrc::replace(&ctx.system, &ctx.bashrc(), &format!("\n{hook}"), "")?;
+                bashrc::replace(&ctx.system, &ctx.bashrc(), hook, "")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Removes the crontab line that started survon.sh in earlier installs.
+/// The crontab is not recorded for rollback: the only line removed from it
+/// was added by an earlier Survon install.
+fn remove_legacy_crontab(ctx: &Context) -> Result<()> {
+    let Some(crontab) = ctx.system.probe(ctx.system.command("crontab").arg("-l")) else {
+        return Ok(());
+    };
+    if crontab.contains("survon.sh") {
+        let kept: String = crontab
+            .lines()
+            .filter(|l| !l.contains("survon.sh"))
+            .flat_map(|l| [l, "\n"])
+            .collect();
+        ctx.system.run_with_input(
+            ctx.system.command("crontab").arg("-"),
+            Some(kept.as_bytes()),
+        )?;
+    }
+    Ok(())
+}
+
+/// Sets raspi-config's boot behaviour to `code`, recording the one it had
+/// before for rollback.
+fn set_boot_behaviour(ctx: &Context, code: &str) -> Result<()> {
+    let previous = boot_behaviour(ctx);
+    if previous == Some(code) {
+        return Ok(());
+    }
+    if let Some(previous) = previous {
+        ctx.system.on_rollback(Undo::command(
+            "boot-behaviour",
+            true,
+            &["raspi-config", "nonint", "do_boot_behaviour", previous],
+        ))?;
+    }
+    ctx.system.run(ctx.system.privileged("raspi-config").args([
+        "nonint",
+        "do_boot_behaviour",
+        code,
+    ]))?;
+    Ok(())
+}
+
+/// The current `do_boot_behaviour` code (B1 console, B2 console autologin,
+/// B3 desktop, B4 desktop autologin). raspi-config's getters print 0 for
+/// "yes".
+fn boot_behaviour(ctx: &Context) -> Option<&'static str> {
+    let get = |setting: &str| {
+        ctx.system
+            .probe(
+                ctx.system
+                    .privileged("raspi-config")
+                    .args(["nonint", setting]),
+            )
+            .map(|out| out.trim() == "0")
+    };
+    Some(match (get("get_boot_cli")?, get("get_autologin")?) {
+        (true, false) => "B1",
+        (true, true) => "B2",
+        (false, false) => "B3",
+        (false, true) => "B4",
+    })
+}
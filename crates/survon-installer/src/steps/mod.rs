@@ -0,0 +1,44 @@
+//! The built-in installer steps, one module per area of the system.
+
+mod bluetooth;
+mod environment;
+mod hostname;
+mod jukebox;
+mod launcher;
+mod model;
+mod packages;
+mod runtime;
+mod rustup;
+mod terminal;
+
+use crate::step::Step;
+
+pub(crate) use hostname::validate as validate_hostname;
+pub(crate) use jukebox::ARCHIVE_URL as AUDIO_ARCHIVE_URL;
+pub use launcher::set_launch_mode;
+pub(crate) use launcher::SURVON_SH_URL;
+pub use runtime::update_runtime;
+pub use runtime::MODULE_BACKUP_DIR;
+pub(crate) use runtime::RUNTIME_TARBALL_URL;
+
+/// Every step in the order install.sh historically ran them.
+pub fn all() -> Vec<Box<dyn Step>> {
+    vec![
+        Box::new(hostname::SetHostname),
+        Box::new(packages::AptUpdate),
+        Box::new(packages::InstallDeps),
+        Box::new(terminal::TerminalColors),
+        Box::new(bluetooth::BluezConfig),
+        Box::new(bluetooth::DbusPerms),
+        Box::new(bluetooth::BleTest),
+        Box::new(rustup::InstallRustup),
+        Box::new(runtime::DownloadRuntime),
+        Box::new(model::ModelSelection),
+        Box::new(jukebox::DownloadJukeboxAudio),
+        Box::new(environment::Environment),
+        Box::new(runtime::FetchBinary),
+        Box::new(launcher::FetchSurvonSh),
+        Box::new(launcher::BootFlow),
+        Box::new(runtime::Cleanup),
+    ]
+}
@@ -0,0 +1,167 @@
+use std::fs;
+
+use crate::config;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::models::{self, LlmMode};
+use crate::provision::ModelSource;
+use crate::step::{Step, StepId};
+
+/// Pis below this much RAM are steered towards search-only mode.
+const SUMMARIZER_MIN_RAM_MB: u64 = 2000;
+
+pub struct ModelSelection;
+
+impl Step for ModelSelection {
+    fn id(&self) -> StepId {
+        StepId::ModelSelection
+    }
+
+    fn title(&self) -> &'static str {
+        "Select Survon AI Model"
+    }
+
+    fn depends_on(&self) -> &'static [StepId] {
+        // Switching to summarizer mode edits the survon_llm module's config.
+        &[StepId::DownloadRuntime]
+    }
+
+    fn inputs(&self, ctx: &Context) -> Vec<(&'static str, String)> {
+        ctx.provision
+            .iter()
+            .map(|p| ("model", p.model.to_string()))
+            .collect()
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        let source = match &ctx.provision {
+            Some(provision) => provision.model.clone(),
+            None => ask(ctx)?,
+        };
+        let (file, download) = match source {
+            ModelSource::Default => {
+                let known = models::default_model();
+                (
+                    known.file.to_string(),
+                    Some((known.url.to_string(), known.sha256.map(str::to_string))),
+                )
+            }
+            ModelSource::Catalog(known) => (
+                known.file.to_string(),
+                Some((known.url.to_string(), known.sha256.map(str::to_string))),
+            ),
+            ModelSource::None => (models::default_model().file.to_string(), None),
+            ModelSource::Url { url, name, sha256 } => (name, Some((url, sha256))),
+        };
+
+        let model_path = models::dir(&ctx.home).join(&file);
+        config::set(ctx, models::settings(&model_path))?;
+
+        if let Some((url, sha256)) = download {
+            ctx.say(format!(
+                "Downloading {file} to {}...",
+                models::dir(&ctx.home).display()
+            ));
+            ctx.say("This will take 5-10 minutes depending on your connection.");
+            if let Err(e) = models::download(ctx, &url, &file, sha256.as_deref()) {
+                ctx.say("The system will fall back to search-only mode.");
+                ctx.say("Re-run the installer to resume the download.");
+                return Err(e);
+            }
+            ctx.artifact(&model_path);
+            if models::set_llm_mode(ctx, LlmMode::Summarizer)? {
+                ctx.say("Updated LLM config to use summarizer mode.");
+            }
+            ctx.say(format!("Model ready at: {}", model_path.display()));
+        } else {
+            ctx.say("No model installed - using search-only mode");
+        }
+        Ok(())
+    }
+}
+
+/// Shows the RAM-based recommendation and asks where the model comes from.
+fn ask(ctx: &Context) -> Result<ModelSource> {
+    print_recommendation();
+    let ram_mb = models::total_ram_mb();
+    println!("Select model:");
+    for (i, known) in models::CATALOG.iter().enumerate() {
+        let fit = if known.name == models::DEFAULT_MODEL && ram_mb >= SUMMARIZER_MIN_RAM_MB {
+            " - Recommended"
+        } else if known.min_ram_mb > ram_mb {
+            " - Too big for this Pi"
+        } else {
+            ""
+        };
+        println!(
+            "{}. {} ({}, ~{}MB, needs {}MB RAM){fit}",
+            i + 1,
+            known.file,
+            known.quantization,
+            known.size_mb,
+            known.min_ram_mb
+        );
+    }
+    let skip = models::CATALOG.len() + 1;
+    let custom = skip + 1;
+    let recommended = if ram_mb < SUMMARIZER_MIN_RAM_MB {
+        " - Recommended for Pi 3B"
+    } else {
+        ""
+    };
+    println!("{skip}. Skip download (search-only mode){recommended}");
+    println!("{custom}. Custom URL");
+    let choice = ctx.prompt.ask("Choice: ")?;
+
+    Ok(match choice.parse::<usize>() {
+        Ok(n) if (1..skip).contains(&n) => ModelSource::Catalog(&models::CATALOG[n - 1]),
+        Ok(n) if n == skip => {
+            ctx.say("Skipping model download - using search-only mode.");
+            ModelSource::None
+        }
+        Ok(n) if n == custom => {
+            let url = ctx.prompt.ask("Enter URL to your model: ")?;
+            let name = models::url_file_name(&url).to_string();
+            if name.is_empty() || name.starts_with('.') {
+                return Err(Error::Input(format!("no file name in `{url}`")));
+            }
+            models::check_file_name(&name)?;
+            ModelSource::Url {
+                url,
+                name,
+                sha256: None,
+            }
+        }
+        _ => {
+            ctx.say("Invalid choice. Defaulting to search-only mode.");
+            ModelSource::None
+        }
+    })
+}
+
+fn print_recommendation() {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+    let model = cpuinfo
+        .lines()
+        .find(|l| l.starts_with("Model"))
+        .and_then(|l| l.split_once(':'))
+        .map(|(_, v)| v.trim())
+        .unwrap_or("unknown");
+    let ram_mb = models::total_ram_mb();
+
+    println!("========================================");
+    println!("Detected: {model}");
+    println!("Available RAM: {ram_mb}MB");
+    println!("========================================");
+    println!();
+    if ram_mb < SUMMARIZER_MIN_RAM_MB {
+        println!("RECOMMENDATION: Skip model download for Pi 3B");
+        println!("   Your system will use fast search-only mode (no LLM)");
+        println!("   This is perfect for 1GB RAM and gives instant responses");
+    } else {
+        println!("RECOMMENDATION: Download model for Pi 4/5");
+        println!("   Your system has enough RAM for the summarizer mode");
+        println!("   This gives humanized, natural language responses");
+    }
+    println!();
+}
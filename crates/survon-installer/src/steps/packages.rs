@@ -0,0 +1,87 @@
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::step::{Step, StepId};
+
+/// Minimal set of packages needed to run the prebuilt runtime and talk BLE.
+pub const PACKAGES: &[&str] = &[
+    "curl",
+    "bc",
+    "unzip",
+    "zstd",
+    "libasound2-dev",
+    "pkg-config",
+    "git",
+    "bluez",
+    "bluez-tools",
+    "libbluetooth-dev",
+    "libdbus-1-dev",
+    "libglib2.0-dev",
+    "libical-dev",
+    "libreadline-dev",
+];
+
+pub struct AptUpdate;
+
+impl Step for AptUpdate {
+    fn id(&self) -> StepId {
+        StepId::AptUpdate
+    }
+
+    fn title(&self) -> &'static str {
+        "Update Unix System"
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        ctx.system.upgrade_packages()
+    }
+}
+
+pub struct InstallDeps;
+
+impl Step for InstallDeps {
+    fn id(&self) -> StepId {
+        StepId::InstallDeps
+    }
+
+    fn title(&self) -> &'static str {
+        "Install System Libraries"
+    }
+
+    fn depends_on(&self) -> &'static [StepId] {
+        &[StepId::AptUpdate]
+    }
+
+    fn inputs(&self, _ctx: &Context) -> Vec<(&'static str, String)> {
+        vec![("packages", PACKAGES.join(" "))]
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        ctx.system.install_packages(PACKAGES)
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        let missing: Vec<&str> = PACKAGES
+            .iter()
+            .copied()
+            .filter(|pkg| !installed(ctx, pkg))
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Verify(format!(
+                "packages not installed: {}",
+                missing.join(", ")
+            )))
+        }
+    }
+}
+
+fn installed(ctx: &Context, package: &str) -> bool {
+    ctx.system
+        .probe(
+            ctx.system
+                .command("dpkg-query")
+                .args(["-W", "-f=${Status}", package]),
+        )
+        .is_some_and(|status| status.contains("install ok installed"))
+}
@@ -0,0 +1,402 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use survon_update::arch;
+use survon_update::runtime::{
+    RuntimeUpdate, Store, DEFAULT_ROOT, RUNTIME_NAME, RUNTIME_RELEASES_URL,
+};
+
+use crate::change::Change;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::journal;
+use crate::merge;
+use crate::step::{Step, StepId};
+use crate::system::System;
+
+pub(crate) const RUNTIME_TARBALL_URL: &str =
+    "https://github.com/survon/runtime-base-rust/archive/master.tar.gz";
+const RUNTIME_REPO_URL: &str = "https://github.com/survon/runtime-base-rust";
+pub use survon_update::runtime::RUNTIME_LINK as RUNTIME_BINARY;
+/// The SHA-256 of each bundled module file as last copied, relative to the
+/// modules directory, so edits made on the unit since can be told apart
+/// from upstream changes.
+pub const BUNDLED_RECORD: &str = ".bundled.json";
+/// Where copies of locally modified module files are kept, under the
+/// modules directory: bundled files before the installer replaces them,
+/// and Wasteland modules before `survon module` upgrades them.
+pub const MODULE_BACKUP_DIR: &str = ".backup";
+/// The bundled modules' config.yml files as last shipped, under the modules
+/// directory: the common ancestor when merging upstream changes into edited
+/// ones.
+pub const SHIPPED_CONFIGS_DIR: &str = ".shipped";
+/// Appended to a config's name in [`SHIPPED_CONFIGS_DIR`] for the record of
+/// a merge that left conflicts. The record holds the ancestor of that
+/// merge, which stays the ancestor until the user deletes the record.
+pub const CONFLICT_RECORD_EXTENSION: &str = "conflict";
+const MODULE_CONFIG: &str = "config.yml";
+
+/// Fetches the runtime source tree, which is where the bundled modules come
+/// from.
+pub struct DownloadRuntime;
+
+impl Step for DownloadRuntime {
+    fn id(&self) -> StepId {
+        StepId::DownloadRuntime
+    }
+
+    fn title(&self) -> &'static str {
+        "Download Survon Runtime"
+    }
+
+    fn depends_on(&self) -> &'static [StepId] {
+        &[StepId::InstallDeps]
+    }
+
+    fn inputs(&self, ctx: &Context) -> Vec<(&'static str, String)> {
+        // The tarball follows master, so the commit master is at tells a
+        // new upstream from the one already copied. Offline it is unknown
+        // and the step runs again, from a bundle if one is in use.
+        let commit = ctx
+            .system
+            .probe(ctx.system.command("git").args([
+                "ls-remote",
+                RUNTIME_REPO_URL,
+                "refs/heads/master",
+            ]))
+            .and_then(|out| master_commit(&out).map(str::to_string))
+            .unwrap_or_else(|| "unknown".to_string());
+        vec![("url", RUNTIME_TARBALL_URL.to_string()), ("commit", commit)]
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        let tarball = ctx.home.join("runtime-base-rust.tar.gz");
+        let extracted = ctx.home.join("runtime-base-rust-master");
+        let source = ctx.home.join("runtime-base-rust");
+
+        ctx.system.download(RUNTIME_TARBALL_URL, &tarball)?;
+        ctx.system.remove(&extracted)?;
+        ctx.system.run(
+            ctx.system
+                .command("tar")
+                .arg("-xzf")
+                .arg(&tarball)
+                .arg("-C")
+                .arg(&ctx.home),
+        )?;
+        ctx.system.remove(&source)?;
+        ctx.system.rename(&extracted, &source)?;
+        ctx.system.remove(&tarball)?;
+
+        let bundled = source.join("modules");
+        if bundled.is_dir() || ctx.system.is_planning() {
+            // Overwrites bundled modules but leaves user additions alone,
+            // and edits to their configs where upstream allows.
+            back_up_modified(ctx, &bundled)?;
+            let merges = merge_configs(ctx, &bundled)?;
+            ctx.system.copy_tree(&bundled, &ctx.modules_dir())?;
+            for (path, text) in &merges.configs {
+                ctx.system.write_file(path, text)?;
+            }
+            if bundled.is_dir() {
+                let hashes = fsutil::hash_tree(&bundled)?;
+                let record = serde_json::to_string_pretty(&hashes).expect("hashes serialize");
+                ctx.system
+                    .write_file(&ctx.modules_dir().join(BUNDLED_RECORD), &record)?;
+                keep_shipped_configs(ctx, &bundled, &merges.conflicts)?;
+            }
+            ctx.say(format!("Copied modules to {}", ctx.modules_dir().display()));
+            ctx.artifact(ctx.modules_dir());
+        }
+        Ok(())
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        let source = ctx.home.join("runtime-base-rust");
+        if !source.is_dir() {
+            return Err(Error::Verify(format!("{} missing", source.display())));
+        }
+        Ok(())
+    }
+}
+
+/// The commit in `git ls-remote` output for a single ref.
+fn master_commit(ls_remote: &str) -> Option<&str> {
+    let commit = ls_remote.split_whitespace().next()?;
+    (commit.len() == 40 && commit.bytes().all(|b| b.is_ascii_hexdigit())).then_some(commit)
+}
+
+/// The [`BUNDLED_RECORD`] of the last copy, if there is one.
+fn bundled_record(modules: &Path) -> Option<BTreeMap<String, String>> {
+    fs::read_to_string(modules.join(BUNDLED_RECORD))
+        .ok()
+        .and_then(|record| serde_json::from_str(&record).ok())
+}
+
+/// Copies the installed files that `bundled` would overwrite aside if they
+/// were edited since the last copy. Without a record of that copy, any
+/// difference may be an edit.
+fn back_up_modified(ctx: &Context, bundled: &Path) -> Result<()> {
+    if !bundled.is_dir() {
+        return Ok(());
+    }
+    let modules = ctx.modules_dir();
+    let copied = bundled_record(&modules);
+    let mut modified = Vec::new();
+    for (file, hash) in fsutil::hash_tree(bundled)? {
+        let Ok(installed) = fsutil::sha256_file(&modules.join(&file)) else {
+            continue;
+        };
+        let edited = copied
+            .as_ref()
+            .and_then(|copied| copied.get(&file))
+            .is_none_or(|was| *was != installed);
+        if installed != hash && edited {
+            modified.push(file);
+        }
+    }
+    if modified.is_empty() {
+        return Ok(());
+    }
+    let backup = modules
+        .join(MODULE_BACKUP_DIR)
+        .join(format!("bundled-{}", journal::now()));
+    for file in &modified {
+        let to = backup.join(file);
+        ctx.system
+            .create_dir_all(to.parent().expect("backup files have a parent"))?;
+        ctx.system.copy_file(&modules.join(file), &to)?;
+    }
+    ctx.say(format!(
+        "Kept copies of locally modified module files in {}: {}",
+        backup.display(),
+        modified.join(", ")
+    ));
+    Ok(())
+}
+
+/// What [`merge_configs`] makes of the installed configs.
+struct Merges {
+    /// What to write over the freshly copied configs.
+    configs: Vec<(PathBuf, String)>,
+    /// The ancestor to keep in a conflict record, by config path relative
+    /// to the modules directory.
+    conflicts: BTreeMap<PathBuf, String>,
+}
+
+/// Where the shipped copy of `file` (relative to the modules directory)
+/// is kept, and the record of its unresolved conflicts.
+fn shipped_paths(modules: &Path, file: &Path) -> (PathBuf, PathBuf) {
+    let kept = modules.join(SHIPPED_CONFIGS_DIR).join(file);
+    let mut record = kept.clone().into_os_string();
+    record.push(".");
+    record.push(CONFLICT_RECORD_EXTENSION);
+    (kept, record.into())
+}
+
+/// Merges upstream changes to each bundled config.yml into the installed
+/// one (see [`merge`]), using the copy kept in [`SHIPPED_CONFIGS_DIR`] as
+/// the common ancestor, or the one in a conflict record while there is
+/// one. Changes made both here and upstream keep the local value and are
+/// reported for resolving by hand; they are reported again on every
+/// install until the user deletes the conflict record.
+fn merge_configs(ctx: &Context, bundled: &Path) -> Result<Merges> {
+    let mut merges = Merges {
+        configs: Vec::new(),
+        conflicts: BTreeMap::new(),
+    };
+    if !bundled.is_dir() {
+        return Ok(merges);
+    }
+    let modules = ctx.modules_dir();
+    let copied = bundled_record(&modules);
+    for file in fsutil::files_under(bundled)? {
+        if file.file_name().is_none_or(|name| name != MODULE_CONFIG) {
+            continue;
+        }
+        let installed = modules.join(&file);
+        let Ok(local) = fs::read_to_string(&installed) else {
+            continue;
+        };
+        let shipped_path = bundled.join(&file);
+        let shipped = fs::read_to_string(&shipped_path).map_err(|e| Error::io(&shipped_path, e))?;
+        let name = file.to_string_lossy();
+        let (kept, record) = shipped_paths(&modules, &file);
+        // Configs copied before the shipped ones were kept are the
+        // ancestor themselves if the record says they were not edited.
+        let base = fs::read_to_string(&record)
+            .ok()
+            .or_else(|| fs::read_to_string(&kept).ok())
+            .or_else(|| {
+                let hash = copied.as_ref()?.get(name.as_ref())?;
+                (*hash == fsutil::sha256_file(&installed).ok()?).then(|| local.clone())
+            });
+        let result = merge::merge(base.as_deref(), &local, &shipped);
+        if !result.conflicts.is_empty() {
+            ctx.say(format!(
+                "{} was changed here and upstream. Kept the local version of:",
+                installed.display()
+            ));
+            for conflict in &result.conflicts {
+                ctx.say(format!("  - {conflict}"));
+            }
+            ctx.say(format!(
+                "Compare it with {} and resolve these by hand, then delete {}.",
+                kept.display(),
+                record.display()
+            ));
+            merges
+                .conflicts
+                .insert(file.clone(), base.unwrap_or_default());
+        } else if result.text != local && result.text != shipped {
+            ctx.say(format!(
+                "Merged upstream changes into the edited {}",
+                installed.display()
+            ));
+        }
+        if result.rewritten {
+            ctx.say(format!(
+                "{} had to be written out anew: its comments and formatting were dropped.",
+                installed.display()
+            ));
+        }
+        if result.text != shipped {
+            merges.configs.push((installed, result.text));
+        }
+    }
+    Ok(merges)
+}
+
+/// Keeps a copy of each bundled config.yml in [`SHIPPED_CONFIGS_DIR`] for
+/// the next merge, with a conflict record holding the ancestor of a merge
+/// that left `conflicts`. Records of merges that no longer conflict are
+/// removed.
+fn keep_shipped_configs(
+    ctx: &Context,
+    bundled: &Path,
+    conflicts: &BTreeMap<PathBuf, String>,
+) -> Result<()> {
+    let modules = ctx.modules_dir();
+    for file in fsutil::files_under(bundled)? {
+        if file.file_name().is_none_or(|name| name != MODULE_CONFIG) {
+            continue;
+        }
+        let (kept, record) = shipped_paths(&modules, &file);
+        ctx.system
+            .create_dir_all(kept.parent().expect("shipped configs have a parent"))?;
+        ctx.system.copy_file(&bundled.join(&file), &kept)?;
+        match conflicts.get(&file) {
+            Some(base) => ctx.system.write_file(&record, base)?,
+            None => ctx.system.remove(&record)?,
+        }
+    }
+    Ok(())
+}
+
+/// Installs the prebuilt runtime binary from the latest signed release into
+/// the versioned runtime store, and links [`RUNTIME_BINARY`] to it.
+pub struct FetchBinary;
+
+impl Step for FetchBinary {
+    fn id(&self) -> StepId {
+        StepId::FetchBinary
+    }
+
+    fn title(&self) -> &'static str {
+        "Fetch Pre-built Binary"
+    }
+
+    fn inputs(&self, _ctx: &Context) -> Vec<(&'static str, String)> {
+        vec![("url", RUNTIME_RELEASES_URL.to_string())]
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        match update_runtime(&ctx.system, &ctx.user, RUNTIME_RELEASES_URL)? {
+            Some(RuntimeUpdate::UpToDate(version)) => {
+                ctx.say(format!("Runtime {version} is already installed"));
+            }
+            Some(RuntimeUpdate::Updated { to, .. }) => {
+                ctx.say(format!("Runtime {to} installed to {RUNTIME_BINARY}"));
+            }
+            Some(RuntimeUpdate::Available { .. }) | None => {}
+        }
+        ctx.artifact(RUNTIME_BINARY);
+        Ok(())
+    }
+
+    fn verify(&self, _ctx: &Context) -> Result<()> {
+        if !fsutil::is_executable(Path::new(RUNTIME_BINARY)) {
+            return Err(Error::Verify(format!("{RUNTIME_BINARY} is not executable")));
+        }
+        if Store::default().current().is_none() {
+            return Err(Error::Verify(format!(
+                "no current runtime under {DEFAULT_ROOT}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Updates the runtime store from the release at `base_url` (see
+/// [`Store::update`]) and makes sure [`RUNTIME_BINARY`] runs its current
+/// version. Shared by the install step and `survon-installer runtime
+/// update`. Returns `None` in plan mode.
+pub fn update_runtime(
+    system: &System,
+    user: &str,
+    base_url: &str,
+) -> Result<Option<RuntimeUpdate>> {
+    let store = Store::default();
+    let arch = arch::system();
+    system.create_user_dir(store.root(), user)?;
+    let change = Change::Download {
+        url: format!("{base_url}/{RUNTIME_NAME}-{arch}"),
+        dest: store.root().to_path_buf(),
+    };
+    let source = system.source_url(base_url);
+    let update = system.perform(change, || Ok(store.update(&source, arch, false)?))?;
+    system.symlink_root(&store.current_binary(), Path::new(RUNTIME_BINARY))?;
+    Ok(update)
+}
+
+/// Removes build trees left behind by earlier steps.
+pub struct Cleanup;
+
+impl Step for Cleanup {
+    fn id(&self) -> StepId {
+        StepId::Cleanup
+    }
+
+    fn title(&self) -> &'static str {
+        "Cleanup"
+    }
+
+    fn resumable(&self) -> bool {
+        false
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        for dir in ["runtime-base-rust", "llama.cpp"] {
+            ctx.system.remove(&ctx.home.join(dir))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_master_commit_is_read_from_ls_remote() {
+        let sha = "3f786850e387550fdab836ed7e6dc881de23001b";
+        assert_eq!(
+            master_commit(&format!("{sha}\trefs/heads/master\n")),
+            Some(sha)
+        );
+        assert_eq!(master_commit(""), None);
+        assert_eq!(master_commit("fatal: unable to access\n"), None);
+    }
+}
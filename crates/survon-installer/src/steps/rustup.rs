@@ -0,0 +1,57 @@
+use std::env;
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::rollback::Undo;
+use crate::step::{Step, StepId};
+
+const RUSTUP_INIT_URL: &str = "https://sh.rustup.rs";
+
+pub struct InstallRustup;
+
+impl Step for InstallRustup {
+    fn id(&self) -> StepId {
+        StepId::InstallRustup
+    }
+
+    fn title(&self) -> &'static str {
+        "Update Rust"
+    }
+
+    fn depends_on(&self) -> &'static [StepId] {
+        &[StepId::InstallDeps]
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        let rustup = ctx.home.join(".cargo/bin/rustup");
+        if rustup.exists() {
+            ctx.system
+                .run(ctx.system.command(&rustup).args(["update", "stable"]))?;
+            ctx.artifact(rustup);
+            return Ok(());
+        }
+
+        ctx.system.on_rollback(Undo::command(
+            "rustup",
+            false,
+            &[&rustup.to_string_lossy(), "self", "uninstall", "-y"],
+        ))?;
+        let script = env::temp_dir().join("rustup-init.sh");
+        ctx.system.download(RUSTUP_INIT_URL, &script)?;
+        let result = ctx
+            .system
+            .run(ctx.system.command("sh").arg(&script).arg("-y"));
+        ctx.system.remove(&script)?;
+        result?;
+        ctx.artifact(rustup);
+        Ok(())
+    }
+
+    fn verify(&self, ctx: &Context) -> Result<()> {
+        let rustup = ctx.home.join(".cargo/bin/rustup");
+        if !rustup.exists() {
+            return Err(Error::Verify(format!("{} not found", rustup.display())));
+        }
+        Ok(())
+    }
+}
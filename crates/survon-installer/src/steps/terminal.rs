@@ -0,0 +1,30 @@
+use crate::config;
+use crate::context::Context;
+use crate::error::Result;
+use crate::step::{Check, Step, StepId};
+
+const TERM: &str = "xterm-256color";
+
+pub struct TerminalColors;
+
+impl Step for TerminalColors {
+    fn id(&self) -> StepId {
+        StepId::TerminalColors
+    }
+
+    fn title(&self) -> &'static str {
+        "Configure Terminal Colors"
+    }
+
+    fn check(&self, ctx: &Context) -> Result<Check> {
+        Ok(if config::has(ctx, "TERM", TERM)? {
+            Check::Satisfied("256 colors already enabled".into())
+        } else {
+            Check::Needed
+        })
+    }
+
+    fn apply(&self, ctx: &mut Context) -> Result<()> {
+        config::set(ctx, [("TERM", TERM)])
+    }
+}
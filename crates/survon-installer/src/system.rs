@@ -0,0 +1,778 @@
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+
+use survon_update::bundle::Bundle;
+
+use crate::change::Change;
+use crate::error::{Error, Result};
+use crate::fsutil;
+use crate::log::Log;
+use crate::rollback::{Ledger, Undo};
+use crate::step::StepId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Mutations are carried out.
+    Live,
+    /// Mutations are only recorded; read-only probes still run.
+    Plan,
+}
+
+/// The installer's only way of touching the machine.
+///
+/// Subprocess failures become errors carrying the command line and stderr.
+/// Every mutation is recorded as a [`Change`], and in [`Mode::Plan`] that is
+/// all that happens, so steps never need to know whether they are being
+/// previewed.
+///
+/// With a [`Ledger`] attached, a live mutation first records how to undo
+/// it: files are backed up before they are overwritten and new paths are
+/// noted for deletion.
+///
+/// Every subprocess, probes included, is written to the current step's
+/// [`Log`] with its complete output.
+#[derive(Debug)]
+pub struct System {
+    root: bool,
+    mode: Mode,
+    changes: RefCell<Vec<Change>>,
+    ledger: RefCell<Option<Ledger>>,
+    log: Log,
+    bundle: Option<Bundle>,
+}
+
+impl System {
+    pub fn new(mode: Mode) -> Self {
+        let root = fs::metadata("/proc/self")
+            .map(|m| m.uid() == 0)
+            .unwrap_or(false);
+        System {
+            root,
+            mode,
+            changes: RefCell::new(Vec::new()),
+            ledger: RefCell::new(None),
+            log: Log::default(),
+            bundle: None,
+        }
+    }
+
+    pub fn log(&self) -> &Log {
+        &self.log
+    }
+
+    pub fn log_mut(&mut self) -> &mut Log {
+        &mut self.log
+    }
+
+    /// Serves downloads from `bundle` wherever it carries the URL.
+    pub fn use_bundle(&mut self, bundle: Bundle) {
+        self.bundle = Some(bundle);
+    }
+
+    /// Where to fetch `url` from: the bundle's copy if there is one,
+    /// otherwise `url` itself.
+    pub fn source_url(&self, url: &str) -> String {
+        self.bundle
+            .as_ref()
+            .and_then(|bundle| bundle.local_url(url))
+            .unwrap_or_else(|| url.to_string())
+    }
+
+    pub fn is_planning(&self) -> bool {
+        self.mode == Mode::Plan
+    }
+
+    /// Changes recorded since the last call.
+    pub fn take_changes(&self) -> Vec<Change> {
+        self.changes.take()
+    }
+
+    /// Starts recording undo actions into `ledger`.
+    pub fn attach_ledger(&self, ledger: Ledger) {
+        self.ledger.replace(Some(ledger));
+    }
+
+    /// Undo actions recorded from now on belong to `step`.
+    pub fn begin_transaction(&self, step: StepId) {
+        if let Some(ledger) = self.ledger.borrow_mut().as_mut() {
+            ledger.begin(step);
+        }
+    }
+
+    /// Records how to undo a change the caller is about to make by other
+    /// means, such as a command whose effect only the step understands.
+    pub fn on_rollback(&self, undo: Undo) -> Result<()> {
+        if self.is_planning() {
+            return Ok(());
+        }
+        match self.ledger.borrow_mut().as_mut() {
+            Some(ledger) => ledger.push(undo),
+            None => Ok(()),
+        }
+    }
+
+    /// A command run as the invoking user.
+    pub fn command(&self, program: impl AsRef<OsStr>) -> Command {
+        Command::new(program)
+    }
+
+    /// A command run as root, through `sudo` unless we already are root.
+    pub fn privileged(&self, program: impl AsRef<OsStr>) -> Command {
+        if self.root {
+            Command::new(program)
+        } else {
+            let mut cmd = Command::new("sudo");
+            cmd.arg(program);
+            cmd
+        }
+    }
+
+    /// Runs a command with side effects and returns its stdout (empty when
+    /// planning).
+    pub fn run(&self, cmd: &mut Command) -> Result<String> {
+        self.run_with_input(cmd, None)
+    }
+
+    pub fn run_with_input(&self, cmd: &mut Command, input: Option<&[u8]>) -> Result<String> {
+        self.record(Change::Command(describe(cmd)));
+        if self.is_planning() {
+            return Ok(String::new());
+        }
+        self.exec(cmd, input)
+    }
+
+    /// Runs a read-only query, returning stdout on success and `None` on any
+    /// failure. For probes where absence is an answer, not an error. Probes
+    /// run in plan mode too.
+    pub fn probe(&self, cmd: &mut Command) -> Option<String> {
+        self.exec(cmd, None).ok()
+    }
+
+    pub fn service_active(&self, unit: &str) -> bool {
+        self.probe(
+            self.command("systemctl")
+                .args(["is-active", "--quiet", unit]),
+        )
+        .is_some()
+    }
+
+    /// Downloads `url` to `dest`, failing on HTTP errors rather than saving
+    /// the error page. Data goes to `<dest>.part` first and an interrupted
+    /// download continues from there on the next attempt.
+    pub fn download(&self, url: &str, dest: &Path) -> Result<()> {
+        self.record(Change::Download {
+            url: url.to_string(),
+            dest: dest.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.preserve_new(dest, false)?;
+        let mut partial = dest.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        self.spawn(
+            self.command("curl")
+                .args(["-fSL", "--progress-bar", "--retry", "3", "-C", "-", "-o"])
+                .arg(&partial)
+                .arg(self.source_url(url)),
+            None,
+            true,
+        )?;
+        fs::rename(&partial, dest).map_err(|e| Error::io(dest, e))
+    }
+
+    /// Marks `path` for deletion on rollback if it does not exist yet.
+    fn preserve_new(&self, path: &Path, root: bool) -> Result<()> {
+        if fs::symlink_metadata(path).is_ok() {
+            return Ok(());
+        }
+        self.on_rollback(Undo::Delete {
+            path: path.to_path_buf(),
+            root,
+        })
+    }
+
+    /// Backs up `path` before it is overwritten, or marks it for deletion if
+    /// it does not exist yet. Only the first call per path has any effect.
+    fn preserve(&self, path: &Path, root: bool) -> Result<()> {
+        if self.is_planning() {
+            return Ok(());
+        }
+        let mut ledger = self.ledger.borrow_mut();
+        let Some(ledger) = ledger.as_mut() else {
+            return Ok(());
+        };
+        let probe = Undo::Delete {
+            path: path.to_path_buf(),
+            root,
+        };
+        if ledger.covers(&probe) {
+            return Ok(());
+        }
+        match fs::symlink_metadata(path) {
+            Ok(meta) if meta.is_dir() => Ok(()),
+            Ok(_) => {
+                let backup = ledger.back_up(path)?;
+                ledger.push(Undo::Restore {
+                    path: path.to_path_buf(),
+                    backup,
+                    root,
+                })
+            }
+            Err(_) => ledger.push(probe),
+        }
+    }
+
+    pub fn upgrade_packages(&self) -> Result<()> {
+        self.record(Change::UpgradePackages);
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.exec(self.privileged("apt-get").arg("update"), None)?;
+        self.exec(
+            self.privileged("apt-get")
+                .args(["upgrade", "-y"])
+                .env("DEBIAN_FRONTEND", "noninteractive"),
+            None,
+        )?;
+        Ok(())
+    }
+
+    pub fn install_packages(&self, packages: &[&str]) -> Result<()> {
+        self.record(Change::InstallPackages(
+            packages.iter().map(|p| p.to_string()).collect(),
+        ));
+        if self.is_planning() {
+            return Ok(());
+        }
+        let missing = self.missing_packages(packages);
+        if !missing.is_empty() {
+            self.on_rollback(Undo::RemovePackages { packages: missing })?;
+        }
+        self.exec(
+            self.privileged("apt-get")
+                .args(["install", "-y"])
+                .args(packages)
+                .env("DEBIAN_FRONTEND", "noninteractive"),
+            None,
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_packages(&self, packages: &[&str]) -> Result<()> {
+        self.record(Change::RemovePackages(
+            packages.iter().map(|p| p.to_string()).collect(),
+        ));
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.exec(
+            self.privileged("apt-get")
+                .args(["remove", "-y"])
+                .args(packages)
+                .env("DEBIAN_FRONTEND", "noninteractive"),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Packages from `packages` that dpkg does not report as installed.
+    /// dpkg-query fails when any package is unknown, so its output is read
+    /// regardless of the exit status.
+    fn missing_packages(&self, packages: &[&str]) -> Vec<String> {
+        let listed = self
+            .command("dpkg-query")
+            .args(["-W", "-f=${Package} ${Status}\n"])
+            .args(packages)
+            .stderr(Stdio::null())
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+            .unwrap_or_default();
+        let installed: Vec<&str> = listed
+            .lines()
+            .filter(|l| l.ends_with(" installed"))
+            .filter_map(|l| l.split_whitespace().next())
+            .map(|p| p.split(':').next().unwrap_or(p))
+            .collect();
+        packages
+            .iter()
+            .filter(|p| !installed.contains(p))
+            .map(|p| p.to_string())
+            .collect()
+    }
+
+    /// `systemctl <action> <unit>`, e.g. restart or reload.
+    pub fn service(&self, action: &str, unit: &str) -> Result<()> {
+        self.record(Change::Service {
+            action: action.to_string(),
+            unit: unit.to_string(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        if matches!(action, "restart" | "reload") {
+            self.on_rollback(Undo::Service {
+                action: action.to_string(),
+                unit: unit.to_string(),
+            })?;
+        }
+        self.exec(self.privileged("systemctl").args([action, unit]), None)?;
+        Ok(())
+    }
+
+    pub fn add_to_group(&self, user: &str, group: &str) -> Result<()> {
+        self.record(Change::AddToGroup {
+            user: user.to_string(),
+            group: group.to_string(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        let member = self
+            .probe(self.command("id").args(["-nG", user]))
+            .is_some_and(|groups| groups.split_whitespace().any(|g| g == group));
+        if !member {
+            self.on_rollback(Undo::LeaveGroup {
+                user: user.to_string(),
+                group: group.to_string(),
+            })?;
+        }
+        self.exec(
+            self.privileged("usermod").args(["-a", "-G", group, user]),
+            None,
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_from_group(&self, user: &str, group: &str) -> Result<()> {
+        self.record(Change::RemoveFromGroup {
+            user: user.to_string(),
+            group: group.to_string(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.exec(self.privileged("gpasswd").args(["-d", user, group]), None)?;
+        Ok(())
+    }
+
+    /// Replaces a file owned by the invoking user.
+    pub fn write_file(&self, path: &Path, contents: &str) -> Result<()> {
+        self.write(path, contents, false)
+    }
+
+    /// Replaces a root-owned file's contents.
+    pub fn write_root_file(&self, path: &Path, contents: &str) -> Result<()> {
+        self.write(path, contents, true)
+    }
+
+    /// Rewrites a user-owned file through `edit`, treating a missing file as
+    /// empty. Returns whether the contents changed.
+    pub fn edit_file(&self, path: &Path, edit: impl FnOnce(&str) -> String) -> Result<bool> {
+        let current = fsutil::read_or_empty(path)?;
+        let updated = edit(&current);
+        if updated == current {
+            return Ok(false);
+        }
+        self.write(path, &updated, false)?;
+        Ok(true)
+    }
+
+    /// Rewrites a root-owned file through `edit`, touching it only if the
+    /// contents actually change. Returns whether it was written.
+    pub fn edit_root_file(&self, path: &Path, edit: impl FnOnce(&str) -> String) -> Result<bool> {
+        let current = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let updated = edit(&current);
+        if updated == current {
+            return Ok(false);
+        }
+        self.write(path, &updated, true)?;
+        Ok(true)
+    }
+
+    fn write(&self, path: &Path, contents: &str, privileged: bool) -> Result<()> {
+        self.record(Change::WriteFile {
+            path: path.to_path_buf(),
+            before: fs::read_to_string(path).ok(),
+            after: contents.to_string(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.preserve(path, privileged)?;
+        if privileged && !self.root {
+            self.exec(self.privileged("tee").arg(path), Some(contents.as_bytes()))?;
+            return Ok(());
+        }
+        fs::write(path, contents).map_err(|e| Error::io(path, e))
+    }
+
+    pub fn copy_file(&self, from: &Path, to: &Path) -> Result<()> {
+        self.record(Change::CopyFile {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.preserve(to, false)?;
+        fs::copy(from, to).map_err(|e| Error::io(from, e))?;
+        Ok(())
+    }
+
+    pub fn copy_root_file(&self, from: &Path, to: &Path) -> Result<()> {
+        self.record(Change::CopyFile {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.preserve(to, true)?;
+        self.exec(self.privileged("cp").arg(from).arg(to), None)?;
+        Ok(())
+    }
+
+    /// Installs `from` as root-owned `to` with `mode`, creating parent
+    /// directories as needed.
+    pub fn install_root_file(&self, from: &Path, to: &Path, mode: u32) -> Result<()> {
+        self.record(Change::CopyFile {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        self.record(Change::SetMode {
+            path: to.to_path_buf(),
+            mode,
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.preserve(to, true)?;
+        self.exec(
+            self.privileged("install")
+                .args(["-D", "-m", &format!("{mode:o}")])
+                .arg(from)
+                .arg(to),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Recursively copies `from` into `to`, overwriting files present in both.
+    pub fn copy_tree(&self, from: &Path, to: &Path) -> Result<()> {
+        self.record(Change::CopyTree {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        if to.exists() {
+            for file in fsutil::files_under(from)? {
+                self.preserve(&to.join(file), false)?;
+            }
+        } else {
+            self.preserve_new(to, false)?;
+        }
+        fsutil::copy_dir_all(from, to)
+    }
+
+    pub fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.record(Change::Move {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.preserve_new(to, false)?;
+        fs::rename(from, to).map_err(|e| Error::io(from, e))
+    }
+
+    /// Removes a file or directory tree. Missing paths are not an error and
+    /// are not recorded.
+    pub fn remove(&self, path: &Path) -> Result<()> {
+        let Ok(meta) = fs::symlink_metadata(path) else {
+            return Ok(());
+        };
+        self.record(Change::Remove {
+            path: path.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        if meta.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+        .map_err(|e| Error::io(path, e))
+    }
+
+    /// Removes a root-owned file or directory tree. Missing paths are not an
+    /// error and are not recorded.
+    pub fn remove_root(&self, path: &Path) -> Result<()> {
+        if fs::symlink_metadata(path).is_err() {
+            return Ok(());
+        }
+        self.record(Change::Remove {
+            path: path.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.exec(self.privileged("rm").arg("-rf").arg(path), None)?;
+        Ok(())
+    }
+
+    pub fn create_dir_all(&self, path: &Path) -> Result<()> {
+        if path.is_dir() {
+            return Ok(());
+        }
+        self.record(Change::CreateDir {
+            path: path.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        let outermost = path
+            .ancestors()
+            .take_while(|p| !p.exists())
+            .last()
+            .unwrap_or(path);
+        self.preserve_new(outermost, false)?;
+        fs::create_dir_all(path).map_err(|e| Error::io(path, e))
+    }
+
+    /// Creates `path` as a root-level directory owned by `user`, so later
+    /// runs can write to it without root.
+    pub fn create_user_dir(&self, path: &Path, user: &str) -> Result<()> {
+        if path.is_dir() {
+            return Ok(());
+        }
+        self.record(Change::CreateDir {
+            path: path.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        let outermost = path
+            .ancestors()
+            .take_while(|p| !p.exists())
+            .last()
+            .unwrap_or(path);
+        self.preserve_new(outermost, true)?;
+        self.exec(
+            self.privileged("install")
+                .args(["-d", "-m", "0755", "-o", user])
+                .arg(path),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Points root-owned symlink `link` at `target`, replacing whatever
+    /// `link` was.
+    pub fn symlink_root(&self, target: &Path, link: &Path) -> Result<()> {
+        if fs::read_link(link).is_ok_and(|current| current == target) {
+            return Ok(());
+        }
+        self.record(Change::Symlink {
+            target: target.to_path_buf(),
+            link: link.to_path_buf(),
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        self.preserve(link, true)?;
+        self.exec(
+            self.privileged("ln").arg("-sfn").arg(target).arg(link),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Records `change` and, in a live run, carries it out with `apply`.
+    /// For work other crates do on the machine directly, which the log
+    /// should still show.
+    pub fn perform<T>(
+        &self,
+        change: Change,
+        apply: impl FnOnce() -> Result<T>,
+    ) -> Result<Option<T>> {
+        self.log.write(&format!("> {change}"));
+        self.record(change);
+        if self.is_planning() {
+            return Ok(None);
+        }
+        apply().map(Some)
+    }
+
+    pub fn set_executable(&self, path: &Path) -> Result<()> {
+        self.set_mode(path, 0o755)
+    }
+
+    pub fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.record(Change::SetMode {
+            path: path.to_path_buf(),
+            mode,
+        });
+        if self.is_planning() {
+            return Ok(());
+        }
+        let meta = fs::metadata(path).map_err(|e| Error::io(path, e))?;
+        self.on_rollback(Undo::SetMode {
+            path: path.to_path_buf(),
+            mode: meta.permissions().mode() & 0o7777,
+        })?;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|e| Error::io(path, e))
+    }
+
+    fn exec(&self, cmd: &mut Command, input: Option<&[u8]>) -> Result<String> {
+        self.spawn(cmd, input, false)
+    }
+
+    /// Runs `cmd` to completion, logging its output as it arrives. With
+    /// `progress`, stderr lines ending in a percentage are reported to the
+    /// log as progress instead of being logged.
+    fn spawn(&self, cmd: &mut Command, input: Option<&[u8]>, progress: bool) -> Result<String> {
+        let command = describe(cmd);
+        self.log.write(&format!("$ {command}"));
+        cmd.stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+        let spawn_error = |source| Error::Spawn {
+            command: command.clone(),
+            source,
+        };
+        let mut child = cmd.spawn().map_err(spawn_error)?;
+        let (tx, rx) = mpsc::channel();
+        let readers = [
+            child.stdout.take().map(|out| {
+                let tx = tx.clone();
+                thread::spawn(move || forward_lines(out, false, tx))
+            }),
+            child.stderr.take().map(|err| {
+                let tx = tx.clone();
+                thread::spawn(move || forward_lines(err, true, tx))
+            }),
+        ];
+        drop(tx);
+        // Readers are already draining the pipes, so a child that echoes its
+        // input (tee) cannot block us here.
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(input).map_err(spawn_error)?;
+        }
+
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        for line in rx {
+            match line {
+                Line::Stdout(text) => {
+                    self.log.write(&format!("  | {text}"));
+                    stdout.push_str(&text);
+                    stdout.push('\n');
+                }
+                Line::Stderr(text) if text.trim().is_empty() => {}
+                Line::Stderr(text) => match parse_progress(&text).filter(|_| progress) {
+                    Some(percent) => self.log.progress(percent),
+                    None => {
+                        self.log.write(&format!("  ! {text}"));
+                        stderr.push_str(&text);
+                        stderr.push('\n');
+                    }
+                },
+            }
+        }
+        for reader in readers.into_iter().flatten() {
+            let _ = reader.join();
+        }
+        let status = child.wait().map_err(spawn_error)?;
+        self.log.write(&format!("  exit: {status}"));
+
+        if status.success() {
+            Ok(stdout)
+        } else {
+            Err(Error::Command {
+                command,
+                status: status.to_string(),
+                stderr,
+            })
+        }
+    }
+
+    fn record(&self, change: Change) {
+        self.changes.borrow_mut().push(change);
+    }
+}
+
+impl Default for System {
+    fn default() -> Self {
+        System::new(Mode::Live)
+    }
+}
+
+/// A line of subprocess output.
+enum Line {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// Sends `reader`'s output to `tx` line by line. Lines end at `\n`, and for
+/// stderr also at `\r`, which is how progress meters redraw themselves.
+fn forward_lines(mut reader: impl Read, stderr: bool, tx: mpsc::Sender<Line>) {
+    let mut pending = Vec::new();
+    let mut buf = [0u8; 4096];
+    let send = |bytes: &[u8]| {
+        let text = String::from_utf8_lossy(bytes).into_owned();
+        let _ = tx.send(if stderr {
+            Line::Stderr(text)
+        } else {
+            Line::Stdout(text)
+        });
+    };
+    while let Ok(n) = reader.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        for &byte in &buf[..n] {
+            if byte == b'\n' || (stderr && byte == b'\r') {
+                send(&pending);
+                pending.clear();
+            } else {
+                pending.push(byte);
+            }
+        }
+    }
+    if !pending.is_empty() {
+        send(&pending);
+    }
+}
+
+/// The percentage at the end of a curl `--progress-bar` line.
+fn parse_progress(line: &str) -> Option<f32> {
+    line.split_whitespace()
+        .last()?
+        .strip_suffix('%')?
+        .parse()
+        .ok()
+}
+
+fn describe(cmd: &Command) -> String {
+    let mut parts = vec![cmd.get_program().to_string_lossy().into_owned()];
+    parts.extend(cmd.get_args().map(|a| a.to_string_lossy().into_owned()));
+    parts.join(" ")
+}
@@ -0,0 +1,319 @@
+//! How the unit starts the runtime and the council seat.
+//!
+//! In the `console` launch mode (the default) the console logs in by itself
+//! and `.bashrc` hands it to the boot selector, which starts whatever
+//! `BOOT_TARGET` says on the TTY. In the `systemd` mode the runtime and the
+//! council seat are services instead: systemd starts them at boot, restarts
+//! them when they fail and gives them the settings from the environment
+//! file. The runtime keeps the console (tty1), the council seat runs in the
+//! background and logs to the journal.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use survon_config::{schema, Config, ENV_PATH};
+
+use crate::context::Context;
+use crate::error::Result;
+use crate::fsutil;
+use crate::rollback::Undo;
+
+pub const UNIT_DIR: &str = "/etc/systemd/system";
+pub const RUNTIME_BINARY: &str = "/usr/local/bin/runtime-base-rust";
+pub const COUNCIL_SEAT_BINARY: &str = "/usr/local/bin/survon-council-seat";
+
+/// Seconds systemd waits before restarting a service that failed.
+const RESTART_SEC: u32 = 5;
+/// Starts within `START_LIMIT_INTERVAL` seconds after which systemd stops
+/// restarting a service, so a crash loop does not spin forever.
+const START_LIMIT_BURST: u32 = 5;
+const START_LIMIT_INTERVAL: u32 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchMode {
+    /// Console autologin and the boot selector, on tty1.
+    Console,
+    /// systemd services for the runtime and the council seat.
+    Systemd,
+}
+
+impl LaunchMode {
+    pub const ALL: [LaunchMode; 2] = [LaunchMode::Console, LaunchMode::Systemd];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LaunchMode::Console => "console",
+            LaunchMode::Systemd => "systemd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LaunchMode> {
+        LaunchMode::ALL.into_iter().find(|m| m.name() == name)
+    }
+
+    /// `LAUNCH_MODE`, or the console flow if it is not set.
+    pub fn configured(config: &Config) -> LaunchMode {
+        config
+            .effective("LAUNCH_MODE")
+            .and_then(|mode| LaunchMode::from_name(&mode.to_string()))
+            .unwrap_or(LaunchMode::Console)
+    }
+}
+
+impl fmt::Display for LaunchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A service the `systemd` launch mode installs.
+#[derive(Debug, Clone, Copy)]
+pub struct Unit {
+    /// The unit file's name.
+    pub name: &'static str,
+    pub description: &'static str,
+    pub binary: &'static str,
+    /// Whether the program is a TUI that needs the console.
+    pub console: bool,
+}
+
+pub const RUNTIME: Unit = Unit {
+    name: "survon-runtime.service",
+    description: "Survon runtime (runtime-base-rust)",
+    binary: RUNTIME_BINARY,
+    console: true,
+};
+
+pub const COUNCIL_SEAT: Unit = Unit {
+    name: "survon-council-seat.service",
+    description: "Survon council seat",
+    binary: COUNCIL_SEAT_BINARY,
+    console: false,
+};
+
+pub const UNITS: [Unit; 2] = [RUNTIME, COUNCIL_SEAT];
+
+impl Unit {
+    pub fn path(&self) -> PathBuf {
+        Path::new(UNIT_DIR).join(self.name)
+    }
+
+    /// Whether the unit file is installed.
+    pub fn is_installed(&self) -> bool {
+        self.path().is_file()
+    }
+
+    /// The unit file, running as `user` from `home`.
+    pub fn render(&self, user: &str, home: &Path) -> String {
+        let term = schema::key("TERM")
+            .and_then(|key| key.default)
+            .unwrap_or("xterm-256color");
+        let (after, io) = if self.console {
+            (
+                "bluetooth.target systemd-user-sessions.service\n\
+                 # The runtime takes tty1 over from the login prompt.\n\
+                 Conflicts=getty@tty1.service",
+                "StandardInput=tty\n\
+                 StandardOutput=tty\n\
+                 StandardError=journal\n\
+                 TTYPath=/dev/tty1\n\
+                 TTYReset=yes\n\
+                 TTYVHangup=yes",
+            )
+        } else {
+            (
+                "network.target bluetooth.target",
+                "StandardOutput=journal\n\
+                 StandardError=journal",
+            )
+        };
+        format!(
+            "# Generated by survon-installer; change the launch mode with\n\
+             # `survon launch-mode` rather than editing this file.\n\
+             [Unit]\n\
+             Description={description}\n\
+             After={after}\n\
+             StartLimitIntervalSec={START_LIMIT_INTERVAL}\n\
+             StartLimitBurst={START_LIMIT_BURST}\n\
+             \n\
+             [Service]\n\
+             Type=simple\n\
+             User={user}\n\
+             WorkingDirectory={home}\n\
+             Environment=TERM={term}\n\
+             EnvironmentFile=-{ENV_PATH}\n\
+             ExecStart={binary}\n\
+             Restart=on-failure\n\
+             RestartSec={RESTART_SEC}\n\
+             {io}\n\
+             \n\
+             [Install]\n\
+             WantedBy=multi-user.target\n",
+            description = self.description,
+            home = home.display(),
+            binary = self.binary,
+        )
+    }
+
+    /// The unit's state as `systemctl is-enabled` and `is-active` report
+    /// it, e.g. "enabled, active".
+    pub fn state(&self, ctx: &Context) -> String {
+        let query = |what: &str| {
+            let out = ctx
+                .system
+                .probe(ctx.system.command("systemctl").args([what, self.name]));
+            out.map_or_else(
+                || format!("not {}", what.trim_start_matches("is-")),
+                |s| s.trim().to_string(),
+            )
+        };
+        if !self.is_installed() {
+            return "not installed".to_string();
+        }
+        format!("{}, {}", query("is-enabled"), query("is-active"))
+    }
+}
+
+/// Writes the unit files and enables the runtime's, and the council seat's
+/// if it is installed. They start at the next boot.
+pub fn install(ctx: &Context) -> Result<()> {
+    let mut changed = false;
+    for unit in UNITS {
+        let contents = unit.render(&ctx.user, &ctx.home);
+        if fsutil::read_or_empty(&unit.path())? != contents {
+            ctx.system.write_root_file(&unit.path(), &contents)?;
+            changed = true;
+        }
+    }
+    if changed {
+        daemon_reload(ctx)?;
+    }
+    for unit in UNITS {
+        let wanted = unit.console || Path::new(unit.binary).exists();
+        set_enabled(ctx, unit, wanted)?;
+    }
+    Ok(())
+}
+
+/// Disables and deletes whatever unit files [`install`] wrote.
+pub fn remove(ctx: &Context) -> Result<()> {
+    let installed: Vec<Unit> = UNITS.into_iter().filter(Unit::is_installed).collect();
+    if installed.is_empty() {
+        return Ok(());
+    }
+    for unit in &installed {
+        set_enabled(ctx, *unit, false)?;
+        // remove_root keeps no backup; put the file back by hand.
+        let path = unit.path();
+        ctx.system.on_rollback(Undo::Command {
+            key: format!("unit-file:{}", unit.name),
+            argv: vec!["tee".to_string(), path.display().to_string()],
+            privileged: true,
+            input: Some(fsutil::read_or_empty(&path)?),
+        })?;
+        ctx.system.remove_root(&path)?;
+    }
+    daemon_reload(ctx)
+}
+
+fn daemon_reload(ctx: &Context) -> Result<()> {
+    ctx.system
+        .run(ctx.system.privileged("systemctl").arg("daemon-reload"))?;
+    Ok(())
+}
+
+fn set_enabled(ctx: &Context, unit: Unit, enabled: bool) -> Result<()> {
+    let was = ctx
+        .system
+        .probe(
+            ctx.system
+                .command("systemctl")
+                .args(["is-enabled", "--quiet", unit.name]),
+        )
+        .is_some();
+    if was == enabled {
+        return Ok(());
+    }
+    let (action, undo) = if enabled {
+        ("enable", "disable")
+    } else {
+        ("disable", "enable")
+    };
+    ctx.system.on_rollback(Undo::command(
+        &format!("unit:{}", unit.name),
+        true,
+        &["systemctl", undo, unit.name],
+    ))?;
+    ctx.system
+        .run(ctx.system.privileged("systemctl").args([action, unit.name]))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(unit: Unit) -> Vec<String> {
+        unit.render("survon", Path::new("/home/survon"))
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn has(lines: &[String], line: &str) -> bool {
+        lines.iter().any(|l| l == line)
+    }
+
+    #[test]
+    fn units_start_their_binary_and_restart_on_failure() {
+        for (unit, exec) in [
+            (RUNTIME, "ExecStart=/usr/local/bin/runtime-base-rust"),
+            (COUNCIL_SEAT, "ExecStart=/usr/local/bin/survon-council-seat"),
+        ] {
+            let lines = lines(unit);
+            for line in [
+                exec,
+                "Restart=on-failure",
+                "RestartSec=5",
+                "StartLimitBurst=5",
+                "StartLimitIntervalSec=300",
+                "WantedBy=multi-user.target",
+                "User=survon",
+                "WorkingDirectory=/home/survon",
+                &format!("EnvironmentFile=-{ENV_PATH}"),
+            ] {
+                assert!(has(&lines, line), "{}: no `{line}`", unit.name);
+            }
+            assert_eq!(
+                lines.iter().filter(|l| l.starts_with("ExecStart=")).count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn only_the_runtime_takes_the_console() {
+        let runtime = lines(RUNTIME);
+        assert!(has(&runtime, "TTYPath=/dev/tty1"));
+        assert!(has(&runtime, "StandardInput=tty"));
+        assert!(has(&runtime, "Conflicts=getty@tty1.service"));
+
+        let council = lines(COUNCIL_SEAT);
+        assert!(has(&council, "StandardOutput=journal"));
+        assert!(!council.iter().any(|l| l.starts_with("TTY")));
+        assert!(!council.iter().any(|l| l.starts_with("Conflicts=")));
+    }
+
+    #[test]
+    fn launch_modes_default_to_the_console() {
+        let config = |text: &str| Config::parse("config.toml", text).unwrap();
+        assert_eq!(LaunchMode::configured(&config("")), LaunchMode::Console);
+        assert_eq!(
+            LaunchMode::configured(&config("LAUNCH_MODE = \"systemd\"\n")),
+            LaunchMode::Systemd
+        );
+        for mode in LaunchMode::ALL {
+            assert_eq!(LaunchMode::from_name(mode.name()), Some(mode));
+        }
+    }
+}
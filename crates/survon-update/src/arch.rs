@@ -0,0 +1,192 @@
+//! Names for the CPU architectures Survon ships binaries for, and checks
+//! that a downloaded binary is built for one of them.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::{Error, Result};
+
+const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const EM_X86_64: u16 = 62;
+const EM_ARM: u16 = 40;
+const EM_AARCH64: u16 = 183;
+/// `e_flags` bit marking the ARM hard-float (armhf) calling convention.
+const EF_ARM_ABI_FLOAT_HARD: u32 = 0x400;
+
+/// Release asset suffix for the architecture this binary was built for:
+/// `armv7` for 32-bit Pi OS (armhf), `aarch64` for 64-bit.
+pub fn current() -> &'static str {
+    match std::env::consts::ARCH {
+        "arm" => "armv7",
+        other => other,
+    }
+}
+
+/// Release asset suffix for the userland the system runs, which is what
+/// binaries installed for it must match. A 32-bit Pi OS on a 64-bit kernel
+/// reports `aarch64` from `uname -m` but can only run armhf binaries, so
+/// this asks dpkg rather than the kernel. Falls back to [`current`].
+pub fn system() -> &'static str {
+    let dpkg = Command::new("dpkg").arg("--print-architecture").output();
+    match dpkg.as_ref().map(|out| out.stdout.trim_ascii()) {
+        Ok(b"armhf") => "armv7",
+        Ok(b"arm64") => "aarch64",
+        Ok(b"amd64") => "x86_64",
+        _ => current(),
+    }
+}
+
+/// Checks that `path` is an ELF executable for `arch` (as named by
+/// [`current`]).
+pub fn check_elf(path: &Path, arch: &str) -> Result<()> {
+    let mut header = [0u8; 52];
+    let read = File::open(path)
+        .and_then(|mut f| f.read(&mut header))
+        .map_err(|e| Error::io(path, e))?;
+    let header = &header[..read];
+    if Elf::parse(header).is_some_and(|elf| elf.is(arch)) {
+        Ok(())
+    } else {
+        Err(Error::WrongArch {
+            path: path.to_path_buf(),
+            expected: arch.to_string(),
+            found: describe(header),
+        })
+    }
+}
+
+/// The fields of an ELF header that decide where it runs.
+struct Elf {
+    class: u8,
+    machine: u16,
+    flags: u32,
+}
+
+impl Elf {
+    fn parse(header: &[u8]) -> Option<Elf> {
+        if header.len() < 20 || header[..4] != ELF_MAGIC {
+            return None;
+        }
+        Some(Elf {
+            class: header[4],
+            machine: u16::from_le_bytes([header[18], header[19]]),
+            flags: header
+                .get(36..40)
+                .map_or(0, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        })
+    }
+
+    fn hard_float(&self) -> bool {
+        self.flags & EF_ARM_ABI_FLOAT_HARD != 0
+    }
+
+    /// Whether this is a binary for `arch` (as named by [`current`]).
+    fn is(&self, arch: &str) -> bool {
+        match (arch, self.class, self.machine) {
+            ("armv7", ELFCLASS32, EM_ARM) => self.hard_float(),
+            ("aarch64", ELFCLASS64, EM_AARCH64) => true,
+            ("x86_64", ELFCLASS64, EM_X86_64) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Human-readable architecture of the ELF file starting with `header`.
+fn describe(header: &[u8]) -> String {
+    let Some(elf) = Elf::parse(header) else {
+        return "not an ELF executable".into();
+    };
+    match (elf.class, elf.machine) {
+        (ELFCLASS32, EM_ARM) if elf.hard_float() => "a 32-bit ARM (armhf) binary".into(),
+        (ELFCLASS32, EM_ARM) => "a 32-bit ARM (soft-float) binary".into(),
+        (ELFCLASS64, EM_AARCH64) => "a 64-bit ARM (aarch64) binary".into(),
+        (ELFCLASS64, EM_X86_64) => "an x86-64 binary".into(),
+        (class, machine) => {
+            let bits = if class == ELFCLASS64 { 64 } else { 32 };
+            format!("a {bits}-bit binary for ELF machine {machine}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use survon_test_support::Scratch;
+
+    use super::*;
+
+    /// The first 40 bytes of an ELF header with the given class, machine
+    /// and flags; the rest does not matter here.
+    fn header(class: u8, machine: u16, flags: u32) -> Vec<u8> {
+        let mut header = vec![0u8; 40];
+        header[..4].copy_from_slice(&ELF_MAGIC);
+        header[4] = class;
+        header[18..20].copy_from_slice(&machine.to_le_bytes());
+        header[36..40].copy_from_slice(&flags.to_le_bytes());
+        header
+    }
+
+    #[test]
+    fn describes_the_shipped_architectures() {
+        assert_eq!(
+            describe(&header(
+                ELFCLASS32,
+                EM_ARM,
+                EF_ARM_ABI_FLOAT_HARD | 0x0500_0000
+            )),
+            "a 32-bit ARM (armhf) binary"
+        );
+        assert_eq!(
+            describe(&header(ELFCLASS64, EM_AARCH64, 0)),
+            "a 64-bit ARM (aarch64) binary"
+        );
+        assert_eq!(
+            describe(&header(ELFCLASS64, EM_X86_64, 0)),
+            "an x86-64 binary"
+        );
+    }
+
+    #[test]
+    fn soft_float_arm_is_not_armhf() {
+        assert_eq!(
+            describe(&header(ELFCLASS32, EM_ARM, 0x0500_0000)),
+            "a 32-bit ARM (soft-float) binary"
+        );
+    }
+
+    #[test]
+    fn describes_anything_else() {
+        assert_eq!(
+            describe(&header(ELFCLASS32, 3, 0)),
+            "a 32-bit binary for ELF machine 3"
+        );
+        assert_eq!(describe(b"#!/bin/sh\necho hi\n"), "not an ELF executable");
+        assert_eq!(describe(&ELF_MAGIC), "not an ELF executable");
+        assert_eq!(describe(&[]), "not an ELF executable");
+    }
+
+    #[test]
+    fn checks_binaries_against_the_expected_architecture() {
+        let dir = Scratch::new("arch");
+        let binary = dir.join("runtime-base-rust");
+        fs::write(&binary, header(ELFCLASS32, EM_ARM, EF_ARM_ABI_FLOAT_HARD)).unwrap();
+        check_elf(&binary, "armv7").unwrap();
+        let err = check_elf(&binary, "aarch64").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::WrongArch { ref found, .. } if found == "a 32-bit ARM (armhf) binary"
+        ));
+
+        fs::write(&binary, header(ELFCLASS32, EM_ARM, 0)).unwrap();
+        assert!(check_elf(&binary, "armv7").is_err());
+        fs::write(&binary, header(ELFCLASS64, EM_AARCH64, 0)).unwrap();
+        check_elf(&binary, "aarch64").unwrap();
+        assert!(check_elf(&binary, "x86_64").is_err());
+        assert!(check_elf(&binary, "riscv64").is_err());
+    }
+}
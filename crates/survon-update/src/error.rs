@@ -0,0 +1,68 @@
+use std::io;
+use std::path::PathBuf;
+
+use semver::Version;
+use thiserror::Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{}: {source}", .path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("could not download {url}: {reason}")]
+    Download { url: String, reason: String },
+
+    #[error("invalid release manifest: {0}")]
+    Manifest(String),
+
+    #[error("signature check failed: {0}")]
+    Signature(String),
+
+    #[error("release manifest has no asset `{0}`")]
+    MissingArtifact(String),
+
+    #[error("{}: expected {expected}, got {actual}", .path.display())]
+    Checksum {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("refusing to downgrade from {current} to {offered}")]
+    Downgrade { current: Version, offered: Version },
+
+    #[error("invalid bundle: {0}")]
+    Bundle(String),
+
+    #[error("{} is {found}, but this system needs {expected}", .path.display())]
+    WrongArch {
+        path: PathBuf,
+        expected: String,
+        found: String,
+    },
+
+    #[error("runtime {version} failed to start: {reason}; {}", restored_to(.restored))]
+    StartupFailed {
+        version: Version,
+        reason: String,
+        restored: Option<Version>,
+    },
+}
+
+fn restored_to(restored: &Option<Version>) -> String {
+    match restored {
+        Some(version) => format!("switched back to {version}"),
+        None => "no earlier version to switch back to".into(),
+    }
+}
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Downloads through curl, like the rest of the Survon tooling.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::error::{Error, Result};
+
+/// Downloads `url` to `dest`, failing on HTTP errors.
+pub fn fetch(url: &str, dest: &Path) -> Result<()> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "--retry", "3", "-o"])
+        .arg(dest)
+        .arg(url)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| Error::Download {
+            url: url.to_string(),
+            reason: e.to_string(),
+        })?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::Download {
+            url: url.to_string(),
+            reason: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
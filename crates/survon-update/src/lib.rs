@@ -0,0 +1,27 @@
+//! Signed releases for Survon OS components.
+//!
+//! Every release publishes a `manifest.json` next to its assets, listing the
+//! release version and the size and SHA-256 of each asset, plus a detached
+//! minisign signature `manifest.json.minisig`. The signature is checked
+//! against the public key pinned into this crate, so an asset is only
+//! trusted if its checksum matches a manifest the release key signed.
+//!
+//! [`runtime`] builds on this to keep verified runtime versions side by
+//! side and switch between them, and [`bundle`] to carry releases and
+//! downloads to units without internet access.
+
+pub mod arch;
+pub mod bundle;
+pub mod error;
+pub mod fetch;
+pub mod manifest;
+pub mod runtime;
+pub mod signature;
+
+pub use error::{Error, Result};
+pub use manifest::{Artifact, Manifest, VersionChange};
+pub use semver::Version;
+
+/// The Survon release signing key (minisign, ed25519). The matching secret
+/// key only lives in the release workflow's secrets.
+pub const RELEASE_PUBLIC_KEY: &str = include_str!("../keys/release.pub");
@@ -0,0 +1,248 @@
+//! Release manifests and their signatures.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::fetch::fetch;
+use crate::signature;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const SIGNATURE_FILE: &str = "manifest.json.minisig";
+
+/// One downloadable file of a release.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl Artifact {
+    /// Checks that the file at `path` is this artifact.
+    pub fn verify_file(&self, path: &Path) -> Result<()> {
+        let size = fs::metadata(path).map_err(|e| Error::io(path, e))?.len();
+        if size != self.size {
+            return Err(Error::Checksum {
+                path: path.to_path_buf(),
+                expected: format!("{} bytes", self.size),
+                actual: format!("{size} bytes"),
+            });
+        }
+        let actual = sha256_file(path)?;
+        if !actual.eq_ignore_ascii_case(&self.sha256) {
+            return Err(Error::Checksum {
+                path: path.to_path_buf(),
+                expected: format!("sha256 {}", self.sha256),
+                actual: format!("sha256 {actual}"),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// What a release contains. Asset files sit next to the manifest and are
+/// named by their key in `artifacts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The component released, e.g. `survon-installer`.
+    pub name: String,
+    pub version: Version,
+    pub artifacts: BTreeMap<String, Artifact>,
+    /// Bundles only: the URL each bundled file or release directory stands
+    /// in for, mapped to its path in the bundle.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<String, String>,
+}
+
+/// How an offered release relates to the installed one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionChange {
+    UpToDate(Version),
+    Upgrade { from: Version, to: Version },
+}
+
+impl Manifest {
+    /// Parses `json` after checking `signature` (the contents of a
+    /// `.minisig` file) against `public_key`.
+    pub fn verify(json: &[u8], signature: &str, public_key: &str) -> Result<Self> {
+        Manifest::verify_any(json, signature, &[public_key])
+    }
+
+    /// Like [`Manifest::verify`], accepting a signature from any of
+    /// `public_keys`. Keys that do not parse are skipped.
+    pub fn verify_any(json: &[u8], signature: &str, public_keys: &[&str]) -> Result<Self> {
+        signature::verify(json, signature, public_keys)?;
+        serde_json::from_slice(json).map_err(|e| Error::Manifest(e.to_string()))
+    }
+
+    /// Reads and verifies `manifest.json` and its signature from `dir`.
+    pub fn load(dir: &Path, public_key: &str) -> Result<Self> {
+        Manifest::load_any(dir, &[public_key])
+    }
+
+    /// Like [`Manifest::load`], accepting a signature from any of
+    /// `public_keys`.
+    pub fn load_any(dir: &Path, public_keys: &[&str]) -> Result<Self> {
+        let json_path = dir.join(MANIFEST_FILE);
+        let sig_path = dir.join(SIGNATURE_FILE);
+        let json = fs::read(&json_path).map_err(|e| Error::io(&json_path, e))?;
+        let signature = fs::read_to_string(&sig_path).map_err(|e| Error::io(&sig_path, e))?;
+        Manifest::verify_any(&json, &signature, public_keys)
+    }
+
+    /// Downloads and verifies the manifest published at `base_url`, using
+    /// `scratch` for the downloaded files.
+    pub fn fetch(base_url: &str, scratch: &Path, public_key: &str) -> Result<Self> {
+        fs::create_dir_all(scratch).map_err(|e| Error::io(scratch, e))?;
+        for file in [MANIFEST_FILE, SIGNATURE_FILE] {
+            fetch(&format!("{base_url}/{file}"), &scratch.join(file))?;
+        }
+        Manifest::load(scratch, public_key)
+    }
+
+    pub fn artifact(&self, name: &str) -> Result<&Artifact> {
+        self.artifacts
+            .get(name)
+            .ok_or_else(|| Error::MissingArtifact(name.to_string()))
+    }
+
+    /// Compares this release against the `current` version, refusing
+    /// anything older.
+    pub fn change_from(&self, current: &Version) -> Result<VersionChange> {
+        if self.version < *current {
+            return Err(Error::Downgrade {
+                current: current.clone(),
+                offered: self.version.clone(),
+            });
+        }
+        Ok(if self.version == *current {
+            VersionChange::UpToDate(current.clone())
+        } else {
+            VersionChange::Upgrade {
+                from: current.clone(),
+                to: self.version.clone(),
+            }
+        })
+    }
+
+    /// Downloads artifact `name` from `base_url` to `dest` and verifies it.
+    /// A file that fails verification is removed.
+    pub fn download(&self, base_url: &str, name: &str, dest: &Path) -> Result<()> {
+        let artifact = self.artifact(name)?;
+        fetch(&format!("{base_url}/{name}"), dest)?;
+        artifact.verify_file(dest).inspect_err(|_| {
+            let _ = fs::remove_file(dest);
+        })
+    }
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| Error::io(path, e))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const JSON: &str = r#"{"name":"survon-installer","version":"1.2.0","artifacts":{}}"#;
+
+    /// A fresh key pair's public key (`.pub` contents) and the signature
+    /// over `data` it checks.
+    fn signed(data: &[u8]) -> (String, String) {
+        let pair = minisign::KeyPair::generate_unencrypted_keypair().unwrap();
+        let public = pair.pk.to_box().unwrap().to_string();
+        let signature = minisign::sign(None, &pair.sk, Cursor::new(data), None, None)
+            .unwrap()
+            .to_string();
+        (public, signature)
+    }
+
+    fn manifest(version: &str) -> Manifest {
+        Manifest {
+            name: "survon-installer".into(),
+            version: version.parse().unwrap(),
+            artifacts: BTreeMap::new(),
+            sources: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn signed_manifests_verify() {
+        let (public, signature) = signed(JSON.as_bytes());
+        let manifest = Manifest::verify(JSON.as_bytes(), &signature, &public).unwrap();
+        assert_eq!(manifest.version, Version::new(1, 2, 0));
+    }
+
+    #[test]
+    fn tampered_manifests_are_rejected() {
+        let (public, signature) = signed(JSON.as_bytes());
+        let tampered = JSON.replace("1.2.0", "9.9.9");
+        assert!(matches!(
+            Manifest::verify(tampered.as_bytes(), &signature, &public),
+            Err(Error::Signature(_))
+        ));
+    }
+
+    #[test]
+    fn other_keys_are_rejected() {
+        let (_, signature) = signed(JSON.as_bytes());
+        let (other, _) = signed(JSON.as_bytes());
+        assert!(Manifest::verify(JSON.as_bytes(), &signature, &other).is_err());
+        assert!(Manifest::verify_any(JSON.as_bytes(), &signature, &["not a key", &other]).is_err());
+    }
+
+    #[test]
+    fn any_listed_key_will_do() {
+        let (public, signature) = signed(JSON.as_bytes());
+        let (other, _) = signed(JSON.as_bytes());
+        assert!(Manifest::verify_any(JSON.as_bytes(), &signature, &[&other, &public]).is_ok());
+    }
+
+    #[test]
+    fn downgrades_are_refused() {
+        let current = Version::new(1, 2, 0);
+        assert!(matches!(
+            manifest("1.1.9").change_from(&current),
+            Err(Error::Downgrade { .. })
+        ));
+        assert_eq!(
+            manifest("1.2.0").change_from(&current).unwrap(),
+            VersionChange::UpToDate(current.clone())
+        );
+        assert_eq!(
+            manifest("1.3.0-rc.1").change_from(&current).unwrap(),
+            VersionChange::Upgrade {
+                from: current,
+                to: "1.3.0-rc.1".parse().unwrap()
+            }
+        );
+    }
+
+    #[test]
+    fn artifacts_are_checked_by_size_and_hash() {
+        let path = std::env::temp_dir().join(format!("survon-artifact-{}", std::process::id()));
+        fs::write(&path, "release").unwrap();
+        let artifact = Artifact {
+            sha256: sha256_file(&path).unwrap(),
+            size: 7,
+        };
+        artifact.verify_file(&path).unwrap();
+        fs::write(&path, "relaese").unwrap();
+        assert!(matches!(
+            artifact.verify_file(&path),
+            Err(Error::Checksum { .. })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
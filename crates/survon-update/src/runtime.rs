@@ -0,0 +1,278 @@
+//! Side-by-side installs of the Survon runtime binary.
+//!
+//! Every runtime version lives in its own directory and a `current` symlink
+//! selects the one that runs:
+//!
+//! ```text
+//! /opt/survon/runtime/
+//!   versions/2.0.0/runtime-base-rust
+//!   versions/2.1.0/runtime-base-rust
+//!   current  -> versions/2.1.0
+//!   previous -> versions/2.0.0
+//! ```
+//!
+//! `/usr/local/bin/runtime-base-rust` links to `current/runtime-base-rust`.
+//! An update is downloaded and verified (manifest signature, checksum and
+//! ELF architecture) next to the running version, `current` is switched in a
+//! single rename, and the new binary must then pass a start-up check or
+//! `current` is switched straight back.
+
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use semver::Version;
+
+use crate::arch;
+use crate::error::{Error, Result};
+use crate::manifest::{Manifest, VersionChange};
+use crate::RELEASE_PUBLIC_KEY;
+
+/// Name of the runtime binary, its release manifest and its assets.
+pub const RUNTIME_NAME: &str = "runtime-base-rust";
+pub const RUNTIME_RELEASES_URL: &str =
+    "https://github.com/survon/runtime-base-rust/releases/latest/download";
+pub const DEFAULT_ROOT: &str = "/opt/survon/runtime";
+/// Where the runtime is started from; a symlink into the store.
+pub const RUNTIME_LINK: &str = "/usr/local/bin/runtime-base-rust";
+
+const VERSIONS_DIR: &str = "versions";
+const CURRENT: &str = "current";
+const PREVIOUS: &str = "previous";
+const SCRATCH_DIR: &str = ".download";
+/// How long the start-up check waits for `--version` to answer. A binary
+/// still running by then has at least started.
+const SMOKE_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a runtime update did.
+#[derive(Debug)]
+pub enum RuntimeUpdate {
+    UpToDate(Version),
+    /// A newer release exists; only reported with `check_only`.
+    Available {
+        from: Option<Version>,
+        to: Version,
+    },
+    Updated {
+        from: Option<Version>,
+        to: Version,
+    },
+}
+
+/// The directory holding every installed runtime version.
+#[derive(Debug, Clone)]
+pub struct Store {
+    root: PathBuf,
+}
+
+impl Store {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Store { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The binary `RUNTIME_LINK` should point at.
+    pub fn current_binary(&self) -> PathBuf {
+        self.root.join(CURRENT).join(RUNTIME_NAME)
+    }
+
+    pub fn binary(&self, version: &Version) -> PathBuf {
+        self.version_dir(version).join(RUNTIME_NAME)
+    }
+
+    fn version_dir(&self, version: &Version) -> PathBuf {
+        self.root.join(VERSIONS_DIR).join(version.to_string())
+    }
+
+    /// The version `current` selects, if any.
+    pub fn current(&self) -> Option<Version> {
+        self.link_target(CURRENT)
+    }
+
+    /// The version that was current before the last switch, if still
+    /// installed.
+    pub fn previous(&self) -> Option<Version> {
+        self.link_target(PREVIOUS)
+    }
+
+    fn link_target(&self, link: &str) -> Option<Version> {
+        let target = fs::read_link(self.root.join(link)).ok()?;
+        let version = Version::parse(target.file_name()?.to_str()?).ok()?;
+        self.binary(&version).is_file().then_some(version)
+    }
+
+    /// Every installed version, oldest first.
+    pub fn installed(&self) -> Result<Vec<Version>> {
+        let dir = self.root.join(VERSIONS_DIR);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::io(&dir, e)),
+        };
+        let mut versions: Vec<Version> = entries
+            .filter_map(|entry| Version::parse(entry.ok()?.file_name().to_str()?).ok())
+            .filter(|version| self.binary(version).is_file())
+            .collect();
+        versions.sort();
+        Ok(versions)
+    }
+
+    /// Checks the release published at `base_url` and, unless `check_only`,
+    /// installs and switches to it. `arch` names the asset to install, as
+    /// returned by [`arch::system`].
+    pub fn update(&self, base_url: &str, arch: &str, check_only: bool) -> Result<RuntimeUpdate> {
+        let scratch = self.root.join(SCRATCH_DIR);
+        let result = self.update_from(base_url, arch, &scratch, check_only);
+        let _ = fs::remove_dir_all(&scratch);
+        result
+    }
+
+    fn update_from(
+        &self,
+        base_url: &str,
+        arch: &str,
+        scratch: &Path,
+        check_only: bool,
+    ) -> Result<RuntimeUpdate> {
+        let manifest = Manifest::fetch(base_url, scratch, RELEASE_PUBLIC_KEY)?;
+        if manifest.name != RUNTIME_NAME {
+            return Err(Error::Manifest(format!(
+                "release is for `{}`, not {RUNTIME_NAME}",
+                manifest.name
+            )));
+        }
+        let from = self.current();
+        if let Some(current) = &from {
+            if let VersionChange::UpToDate(version) = manifest.change_from(current)? {
+                return Ok(RuntimeUpdate::UpToDate(version));
+            }
+        }
+        let to = manifest.version.clone();
+        if check_only {
+            return Ok(RuntimeUpdate::Available { from, to });
+        }
+
+        // A version already installed (say, the one `previous` points at)
+        // stays if it fails to start; only one this update added goes.
+        let installed = self.version_dir(&to).exists();
+        self.stage(&manifest, base_url, arch, scratch)?;
+        self.switch_to(&to).inspect_err(|_| {
+            if !installed {
+                let _ = fs::remove_dir_all(self.version_dir(&to));
+            }
+        })?;
+        self.prune();
+        Ok(RuntimeUpdate::Updated { from, to })
+    }
+
+    /// Downloads and verifies the release's binary into its version
+    /// directory. Nothing lands there unless every check passed.
+    fn stage(&self, manifest: &Manifest, base_url: &str, arch: &str, scratch: &Path) -> Result<()> {
+        let download = scratch.join(RUNTIME_NAME);
+        manifest.download(base_url, &format!("{RUNTIME_NAME}-{arch}"), &download)?;
+        arch::check_elf(&download, arch)?;
+        fs::set_permissions(&download, fs::Permissions::from_mode(0o755))
+            .map_err(|e| Error::io(&download, e))?;
+
+        let dir = self.version_dir(&manifest.version);
+        fs::create_dir_all(&dir).map_err(|e| Error::io(&dir, e))?;
+        let binary = self.binary(&manifest.version);
+        fs::rename(&download, &binary).map_err(|e| Error::io(&binary, e))
+    }
+
+    /// Makes installed `version` current and checks that it starts. If it
+    /// does not, whatever was current before is made current again.
+    pub fn switch_to(&self, version: &Version) -> Result<()> {
+        let before = self.current();
+        if before.as_ref() == Some(version) {
+            return Ok(());
+        }
+        self.point(CURRENT, version)?;
+        if let Err(reason) = smoke_test(&self.current_binary()) {
+            let restored = match &before {
+                Some(before) => self.point(CURRENT, before).ok().map(|()| before.clone()),
+                None => {
+                    let _ = fs::remove_file(self.root.join(CURRENT));
+                    None
+                }
+            };
+            return Err(Error::StartupFailed {
+                version: version.clone(),
+                reason,
+                restored,
+            });
+        }
+        if let Some(before) = before {
+            self.point(PREVIOUS, &before)?;
+        }
+        Ok(())
+    }
+
+    /// Atomically points `link` at `version`'s directory.
+    fn point(&self, link: &str, version: &Version) -> Result<()> {
+        let path = self.root.join(link);
+        let staged = self.root.join(format!(".{link}.new"));
+        let _ = fs::remove_file(&staged);
+        let target = Path::new(VERSIONS_DIR).join(version.to_string());
+        symlink(&target, &staged).map_err(|e| Error::io(&staged, e))?;
+        fs::rename(&staged, &path).map_err(|e| Error::io(&path, e))
+    }
+
+    /// Removes every version but the current and previous ones.
+    fn prune(&self) {
+        let keep = [self.current(), self.previous()];
+        for version in self.installed().unwrap_or_default() {
+            if !keep.contains(&Some(version.clone())) {
+                let _ = fs::remove_dir_all(self.version_dir(&version));
+            }
+        }
+    }
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Store::new(DEFAULT_ROOT)
+    }
+}
+
+/// Runs `binary --version` and fails if it cannot be started or exits
+/// unsuccessfully (a missing loader, wrong libc, a crash on start-up).
+fn smoke_test(binary: &Path) -> Result<(), String> {
+    let mut child = Command::new(binary)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let deadline = Instant::now() + SMOKE_TEST_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(status)) => {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = child.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr);
+                }
+                return Err(match stderr.lines().rfind(|l| !l.trim().is_empty()) {
+                    Some(line) => format!("{status}: {}", line.trim()),
+                    None => status.to_string(),
+                });
+            }
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(100)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(());
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
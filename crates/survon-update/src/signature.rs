@@ -0,0 +1,47 @@
+//! minisign signatures over arbitrary files, shared by release manifests,
+//! bundles and module indexes.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use minisign_verify::{PublicKey, Signature};
+
+use crate::error::{Error, Result};
+
+/// Checks `signature` (the contents of a `.minisig` file) over `data`
+/// against each of `public_keys` until one matches. Keys that do not parse
+/// are skipped.
+pub fn verify(data: &[u8], signature: &str, public_keys: &[&str]) -> Result<()> {
+    let signature = Signature::decode(signature).map_err(|e| Error::Signature(e.to_string()))?;
+    let mut reason = "no public key to check against".to_string();
+    for public_key in public_keys {
+        let key = match PublicKey::decode(public_key.trim()) {
+            Ok(key) => key,
+            Err(e) => {
+                reason = format!("bad public key: {e}");
+                continue;
+            }
+        };
+        match key.verify(data, &signature, false) {
+            Ok(()) => return Ok(()),
+            Err(e) => reason = e.to_string(),
+        }
+    }
+    Err(Error::Signature(reason))
+}
+
+/// Signs `data` with the minisign secret key at `secret_key`, returning the
+/// `.minisig` contents. Without a `password` the key's password is asked
+/// for on the terminal.
+pub fn sign(
+    data: &[u8],
+    secret_key: &Path,
+    password: Option<String>,
+    comment: &str,
+) -> Result<String> {
+    let key = minisign::SecretKey::from_file(secret_key, password)
+        .map_err(|e| Error::Signature(format!("{}: {e}", secret_key.display())))?;
+    let signature = minisign::sign(None, &key, Cursor::new(data), Some(comment), None)
+        .map_err(|e| Error::Signature(e.to_string()))?;
+    Ok(signature.to_string())
+}
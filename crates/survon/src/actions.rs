@@ -0,0 +1,56 @@
+//! Running the installer and other programs from the plain terminal.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{Error, Result};
+
+pub fn home() -> PathBuf {
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/home/survon"))
+}
+
+/// survon-installer, which is installed next to this binary.
+pub fn installer() -> PathBuf {
+    env::current_exe()
+        .map(|exe| exe.with_file_name("survon-installer"))
+        .unwrap_or_else(|_| home().join(".local/bin/survon-installer"))
+}
+
+/// Re-runs the installer, through `~/install.sh` when it is there so the
+/// installer updates itself first. `args` go to survon-installer, e.g.
+/// `--provision FILE` for an unattended run.
+pub fn install(args: &[String]) -> Result<()> {
+    let script = home().join("install.sh");
+    let mut cmd = if script.is_file() {
+        let mut cmd = Command::new("bash");
+        cmd.arg(script);
+        cmd
+    } else {
+        Command::new(installer())
+    };
+    run("survon-installer", cmd.args(args).current_dir(home()))
+}
+
+/// Imports an update bundle from `path`, or from the first USB stick that
+/// holds one.
+pub fn import_bundle(path: Option<&Path>) -> Result<()> {
+    run(
+        "survon-installer",
+        Command::new(installer())
+            .args(["bundle", "import"])
+            .args(path),
+    )
+}
+
+/// Runs `cmd` to completion, with `program` naming it in errors.
+pub(crate) fn run(program: &'static str, cmd: &mut Command) -> Result<()> {
+    let status = cmd.status().map_err(|e| Error::io(cmd.get_program(), e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::Failed { program, status })
+    }
+}
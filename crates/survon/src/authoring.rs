@@ -0,0 +1,616 @@
+//! Writing Wasteland modules: templates to start one from, and a linter
+//! that checks a module's `config.yml` against what the runtime and the
+//! module manager read from it (see [`crate::modules::Manifest`] and
+//! [`crate::requirements`]).
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use semver::VersionReq;
+use serde_yaml::{Mapping, Value};
+use survon_update::runtime::Store;
+use survon_update::Version;
+
+use crate::error::{Error, Result};
+use crate::modules::{self, CONFIG_FILE};
+use crate::requirements::{self, Capability};
+
+/// What a new module starts out as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    /// A name, a version and nothing else.
+    Basic,
+    /// Reads a BLE sensor.
+    Sensor,
+    /// Asks the survon_llm module and its model.
+    Llm,
+    /// Plays sounds it ships.
+    Audio,
+}
+
+impl Template {
+    pub const ALL: [Template; 4] = [
+        Template::Basic,
+        Template::Sensor,
+        Template::Llm,
+        Template::Audio,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Template::Basic => "basic",
+            Template::Sensor => "sensor",
+            Template::Llm => "llm",
+            Template::Audio => "audio",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Template> {
+        Template::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    /// The template's lines of config.yml after the name and version.
+    fn requirements(self) -> &'static str {
+        match self {
+            Template::Basic => "tags: []\n",
+            Template::Sensor => {
+                "tags: [sensors]\n\
+                 # The unit needs a Bluetooth adapter to talk to the sensor.\n\
+                 capabilities: [ble]\n"
+            }
+            Template::Llm => {
+                "tags: [llm]\n\
+                 dependencies:\n  \
+                   - survon_llm\n\
+                 assets:\n  \
+                   - path: ~/bundled/models/phi3-mini.gguf\n    \
+                     description: The model survon_llm loads\n"
+            }
+            Template::Audio => {
+                "tags: [audio]\n\
+                 capabilities: [audio]\n\
+                 assets:\n  \
+                   - path: sounds/\n    \
+                     description: The sounds the module plays\n"
+            }
+        }
+    }
+
+    /// Placeholder files besides config.yml, by path in the module.
+    fn files(self, name: &str) -> Vec<(&'static str, String)> {
+        let mut files = vec![(
+            "README.md",
+            format!(
+                "# {name}\n\n\
+                 What {name} does, and how to use it.\n\n\
+                 `survon module lint` checks config.yml, `survon module pack` \
+                 turns this directory into a .survonmod package.\n"
+            ),
+        )];
+        if self == Template::Audio {
+            files.push((
+                "sounds/README.md",
+                "Put the module's sound files here.\n".to_string(),
+            ));
+        }
+        files
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Creates the module `name` from `template` in a new directory under
+/// `parent`, returning the directory.
+pub fn new(parent: &Path, name: &str, template: Template) -> Result<PathBuf> {
+    modules::check_name(name)?;
+    let dir = parent.join(name);
+    if dir.exists() {
+        return Err(Error::InvalidModule(format!(
+            "{} already exists",
+            dir.display()
+        )));
+    }
+    // Works with the installed runtime and what follows it.
+    let runtime = match Store::default().current() {
+        Some(v) => format!("runtime: \">={}.{}\"\n", v.major, v.minor),
+        None => "# runtime: \">=2.0\"\n".to_string(),
+    };
+    let config = format!(
+        "# Read by the runtime and by `survon module`; see `survon module lint`.\n\
+         name: {}\n\
+         version: 0.1.0\n\
+         description: {}\n\
+         {runtime}\
+         {}",
+        yaml_string(name),
+        yaml_string(&format!("TODO: what {name} does")),
+        template.requirements()
+    );
+    let mut files = vec![(CONFIG_FILE, config)];
+    files.extend(template.files(name));
+    for (file, contents) in files {
+        let path = dir.join(file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io(parent, e))?;
+        }
+        fs::write(&path, contents).map_err(|e| Error::io(&path, e))?;
+    }
+    Ok(dir)
+}
+
+/// `text` as a YAML string scalar, quoted where it would otherwise read
+/// as a number, a boolean or something else.
+fn yaml_string(text: &str) -> String {
+    serde_yaml::to_string(text)
+        .expect("strings serialize")
+        .trim_end()
+        .to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    /// Works, but packing or indexing the module will not go well.
+    Warning,
+}
+
+/// Something wrong with a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// The top-level field of config.yml it is about, if any.
+    pub field: Option<String>,
+    /// The line of config.yml the field starts on.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(CONFIG_FILE)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, ": {severity}: ")?;
+        if let Some(field) = &self.field {
+            write!(f, "{field}: ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Checks the module in `dir`. Findings come in the order of config.yml's
+/// fields, errors first.
+pub fn lint(dir: &Path) -> Result<Vec<Finding>> {
+    let path = dir.join(CONFIG_FILE);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(vec![Finding {
+                severity: Severity::Error,
+                field: None,
+                line: None,
+                message: format!("{} has no {CONFIG_FILE}", dir.display()),
+            }]);
+        }
+        Err(e) => return Err(Error::io(path, e)),
+    };
+    let mut lint = Lint {
+        text: &text,
+        findings: Vec::new(),
+    };
+    let config: Value = match serde_yaml::from_str(&text) {
+        Ok(config) => config,
+        Err(e) => {
+            lint.findings.push(Finding {
+                severity: Severity::Error,
+                field: None,
+                line: e.location().map(|l| l.line()),
+                message: format!("not valid YAML: {e}"),
+            });
+            return Ok(lint.findings);
+        }
+    };
+    let empty = Mapping::new();
+    let config = match &config {
+        Value::Mapping(map) => map,
+        Value::Null => &empty,
+        _ => {
+            lint.error(None, "must be a mapping of fields".to_string());
+            return Ok(lint.findings);
+        }
+    };
+    lint.check(dir, config);
+    lint.findings.sort_by_key(|f| (f.severity, f.line));
+    Ok(lint.findings)
+}
+
+struct Lint<'a> {
+    text: &'a str,
+    findings: Vec<Finding>,
+}
+
+impl Lint<'_> {
+    fn check(&mut self, dir: &Path, config: &Mapping) {
+        let field = |name: &str| config.get(name).filter(|v| !v.is_null());
+
+        let dir_name = dir
+            .canonicalize()
+            .ok()
+            .and_then(|d| Some(d.file_name()?.to_string_lossy().into_owned()));
+        match field("name") {
+            None => self.warning(
+                "name",
+                "missing; the module is named after its directory".to_string(),
+            ),
+            Some(Value::String(name)) => {
+                if let Err(e) = modules::check_name(name) {
+                    self.error("name", e.to_string());
+                } else if dir_name.as_ref().is_some_and(|d| d != name) {
+                    self.warning(
+                        "name",
+                        format!(
+                            "`{name}` differs from the directory name `{}`",
+                            dir_name.as_deref().unwrap_or_default()
+                        ),
+                    );
+                }
+            }
+            Some(_) => self.error("name", "must be a string".to_string()),
+        }
+
+        match field("version").map(scalar) {
+            None => self.warning(
+                "version",
+                "missing; the module cannot be packed or depended on by version".to_string(),
+            ),
+            Some(None) => self.error("version", "must be a version such as 1.0.0".to_string()),
+            Some(Some(version)) => {
+                if requirements::version(&version).is_none() {
+                    self.error("version", format!("`{version}` is not a version"));
+                } else if Version::parse(&version).is_err() {
+                    self.warning(
+                        "version",
+                        format!(
+                            "`{version}` is not full semver (MAJOR.MINOR.PATCH); packing needs it"
+                        ),
+                    );
+                }
+            }
+        }
+
+        match field("description") {
+            None => self.warning(
+                "description",
+                "missing; search results will not say what the module does".to_string(),
+            ),
+            Some(Value::String(text)) if text.trim().is_empty() || text.contains("TODO") => {
+                self.warning("description", "still a placeholder".to_string());
+            }
+            Some(Value::String(_)) => {}
+            Some(_) => self.error("description", "must be a string".to_string()),
+        }
+
+        if let Some(tags) = field("tags") {
+            if !is_string_list(tags) {
+                self.error("tags", "must be a list of strings".to_string());
+            }
+        }
+
+        match field("runtime").map(scalar) {
+            None => {}
+            Some(None) => self.error("runtime", "must be a version requirement".to_string()),
+            Some(Some(req)) => {
+                if let Err(e) = VersionReq::parse(&req) {
+                    self.error(
+                        "runtime",
+                        format!("`{req}` is not a version requirement: {e}"),
+                    );
+                }
+            }
+        }
+
+        if let Some(dependencies) = field("dependencies") {
+            self.check_dependencies(config, dependencies);
+        }
+        if let Some(assets) = field("assets") {
+            self.check_assets(dir, assets);
+        }
+
+        if let Some(capabilities) = field("capabilities") {
+            match capabilities.as_sequence() {
+                Some(list) if is_string_list(capabilities) => {
+                    for name in list.iter().filter_map(Value::as_str) {
+                        if Capability::from_name(name).is_none() {
+                            let known: Vec<&str> =
+                                Capability::ALL.iter().map(|c| c.name()).collect();
+                            self.error(
+                                "capabilities",
+                                format!(
+                                    "unknown capability `{name}` (known: {})",
+                                    known.join(", ")
+                                ),
+                            );
+                        }
+                    }
+                }
+                _ => self.error("capabilities", "must be a list of strings".to_string()),
+            }
+        }
+    }
+
+    fn check_dependencies(&mut self, config: &Mapping, dependencies: &Value) {
+        let own = config.get("name").and_then(Value::as_str);
+        let mut entries: Vec<(String, Option<Value>)> = Vec::new();
+        match dependencies {
+            Value::Sequence(items) => {
+                for item in items {
+                    match item {
+                        Value::String(name) => entries.push((name.clone(), None)),
+                        Value::Mapping(map) => match map.get("name").and_then(Value::as_str) {
+                            Some(name) => {
+                                entries.push((name.to_string(), map.get("version").cloned()))
+                            }
+                            None => {
+                                self.error("dependencies", "each entry needs a `name`".to_string())
+                            }
+                        },
+                        _ => self.error(
+                            "dependencies",
+                            "entries must be names or {name, version}".to_string(),
+                        ),
+                    }
+                }
+            }
+            Value::Mapping(map) => {
+                for (name, version) in map {
+                    match name.as_str() {
+                        Some(name) => entries.push((name.to_string(), Some(version.clone()))),
+                        None => {
+                            self.error("dependencies", "module names must be strings".to_string())
+                        }
+                    }
+                }
+            }
+            _ => {
+                self.error(
+                    "dependencies",
+                    "must be a list of names or a map of name to version requirement".to_string(),
+                );
+                return;
+            }
+        }
+        for (name, version) in entries {
+            if Some(name.as_str()) == own {
+                self.error("dependencies", format!("`{name}` depends on itself"));
+            }
+            match version.filter(|v| !v.is_null()).map(|v| scalar(&v)) {
+                None => {}
+                Some(None) => self.error(
+                    "dependencies",
+                    format!("{name}: the version must be a requirement such as \">=1.0\""),
+                ),
+                Some(Some(req)) => {
+                    if let Err(e) = VersionReq::parse(&req) {
+                        self.error(
+                            "dependencies",
+                            format!("{name}: `{req}` is not a version requirement: {e}"),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_assets(&mut self, dir: &Path, assets: &Value) {
+        let Some(items) = assets.as_sequence() else {
+            self.error(
+                "assets",
+                "must be a list of paths or {path, description}".to_string(),
+            );
+            return;
+        };
+        for item in items {
+            let path = match item {
+                Value::String(path) => path.as_str(),
+                Value::Mapping(map) => match map.get("path").and_then(Value::as_str) {
+                    Some(path) => path,
+                    None => {
+                        self.error("assets", "each entry needs a `path`".to_string());
+                        continue;
+                    }
+                },
+                _ => {
+                    self.error(
+                        "assets",
+                        "entries must be paths or {path, description}".to_string(),
+                    );
+                    continue;
+                }
+            };
+            if path.starts_with('/') {
+                self.warning(
+                    "assets",
+                    format!("{path} is absolute; use ~/... so it works for any user"),
+                );
+            } else if !path.starts_with("~/") && !dir.join(path).exists() {
+                self.error(
+                    "assets",
+                    format!("{path} is not in the module; ship it or use a ~/... path"),
+                );
+            }
+        }
+    }
+
+    fn error(&mut self, field: impl Into<Option<&'static str>>, message: String) {
+        self.push(Severity::Error, field.into(), message);
+    }
+
+    fn warning(&mut self, field: &'static str, message: String) {
+        self.push(Severity::Warning, Some(field), message);
+    }
+
+    fn push(&mut self, severity: Severity, field: Option<&str>, message: String) {
+        self.findings.push(Finding {
+            severity,
+            field: field.map(str::to_string),
+            line: field.and_then(|f| self.line_of(f)),
+            message,
+        });
+    }
+
+    /// The line a top-level `field:` starts on.
+    fn line_of(&self, field: &str) -> Option<usize> {
+        self.text
+            .lines()
+            .position(|line| {
+                line.strip_prefix(field)
+                    .is_some_and(|rest| rest.trim_start().starts_with(':'))
+            })
+            .map(|i| i + 1)
+    }
+}
+
+/// A string or number, as text.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn is_string_list(value: &Value) -> bool {
+    value
+        .as_sequence()
+        .is_some_and(|items| items.iter().all(Value::is_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::Manifest;
+    use survon_test_support::Scratch;
+
+    fn lint_config(config: &str) -> Vec<String> {
+        let dir = Scratch::new("lint");
+        let module = dir.join("weather");
+        fs::create_dir_all(&module).unwrap();
+        fs::write(module.join(CONFIG_FILE), config).unwrap();
+        lint(&module)
+            .unwrap()
+            .iter()
+            .map(Finding::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn new_modules_pass_their_own_lint() {
+        let dir = Scratch::new("new");
+        for template in Template::ALL {
+            let name = format!("{template}-module");
+            let module = new(&dir, &name, template).unwrap();
+            let findings = lint(&module).unwrap();
+            assert!(
+                findings.iter().all(|f| f.severity == Severity::Warning
+                    && f.field.as_deref() == Some("description")),
+                "{template}: {findings:?}"
+            );
+            let config = Manifest::load(&module).unwrap();
+            assert_eq!(config.name.as_deref(), Some(name.as_str()));
+        }
+        assert!(new(&dir, "basic-module", Template::Basic).is_err());
+        assert!(new(&dir, "../escape", Template::Basic).is_err());
+    }
+
+    #[test]
+    fn names_that_read_as_other_types_stay_strings() {
+        let dir = Scratch::new("new-quoted");
+        for name in ["1.0", "true", "null", "yes"] {
+            let module = new(&dir, name, Template::Basic).unwrap();
+            assert_eq!(Manifest::load(&module).unwrap().name.as_deref(), Some(name));
+        }
+    }
+
+    #[test]
+    fn missing_fields_are_warnings() {
+        assert_eq!(
+            lint_config("tags: [sensors]\n"),
+            [
+                "config.yml: warning: name: missing; the module is named after its directory",
+                "config.yml: warning: version: missing; the module cannot be packed or depended on by version",
+                "config.yml: warning: description: missing; search results will not say what the module does",
+            ]
+        );
+        assert_eq!(
+            lint_config("version: 1.0.0\ndescription: Weather\n"),
+            ["config.yml: warning: name: missing; the module is named after its directory"]
+        );
+    }
+
+    #[test]
+    fn bad_versions_are_errors() {
+        let findings = |version: &str| {
+            lint_config(&format!(
+                "name: weather\nversion: {version}\ndescription: Weather\n"
+            ))
+        };
+        assert_eq!(
+            findings("banana"),
+            ["config.yml:2: error: version: `banana` is not a version"]
+        );
+        assert_eq!(
+            findings("[1, 0]"),
+            ["config.yml:2: error: version: must be a version such as 1.0.0"]
+        );
+        assert_eq!(
+            findings("1.2"),
+            ["config.yml:2: warning: version: `1.2` is not full semver (MAJOR.MINOR.PATCH); packing needs it"]
+        );
+        assert!(findings("1.2.3-beta.1").is_empty());
+    }
+
+    #[test]
+    fn unknown_fields_are_the_modules_own() {
+        // Modules keep their own settings next to the fields read here.
+        assert!(lint_config(
+            "name: weather\nversion: 1.0.0\ndescription: Weather\nposition: [0, 1]\npoll_seconds: 30\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn requirements_are_checked() {
+        let findings = lint_config(
+            "name: weather\nversion: 1.0.0\ndescription: Weather\nruntime: soon\n\
+             dependencies: {weather: \">=1\", maps: nope}\ncapabilities: [ble, lasers]\n\
+             assets: [/opt/model.gguf, sounds/]\n",
+        );
+        assert_eq!(
+            findings,
+            [
+                "config.yml:4: error: runtime: `soon` is not a version requirement: unexpected character 's' while parsing major version number",
+                "config.yml:5: error: dependencies: `weather` depends on itself",
+                "config.yml:5: error: dependencies: maps: `nope` is not a version requirement: unexpected character 'n' while parsing major version number",
+                "config.yml:6: error: capabilities: unknown capability `lasers` (known: ble, audio)",
+                "config.yml:7: error: assets: sounds/ is not in the module; ship it or use a ~/... path",
+                "config.yml:7: warning: assets: /opt/model.gguf is absolute; use ~/... so it works for any user",
+            ]
+        );
+        assert_eq!(
+            lint_config("- name\n"),
+            ["config.yml: error: must be a mapping of fields"]
+        );
+        let broken = lint_config("name: [unclosed\n");
+        assert!(broken[0].contains(": error: not valid YAML"), "{broken:?}");
+    }
+}
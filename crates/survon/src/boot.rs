@@ -0,0 +1,304 @@
+//! What the console can boot into, and what it booted into last.
+//!
+//! The boot selector (`survon boot`) offers every [`Target`], counts down
+//! `BOOT_TIMEOUT` seconds and then starts `BOOT_TARGET`. The choice is
+//! remembered in `~/.local/state/survon/last-boot`, both to preselect it
+//! next time and for `BOOT_TARGET=last`. How the runtime's starts ended is
+//! kept by [`crate::crash_loop`], which turns repeated failures into a
+//! safe-mode boot.
+//!
+//! All of this is the `console` launch mode. In the `systemd` mode the
+//! runtime and council seat are services instead; [`set_launch_mode`]
+//! switches between the two.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use survon_config::{Config, Source};
+use survon_installer::steps;
+use survon_installer::system::Mode;
+use survon_installer::units::LaunchMode;
+use survon_installer::Context;
+use survon_update::runtime::{Store, RUNTIME_LINK};
+use survon_update::Version;
+
+use crate::actions;
+use crate::council;
+use crate::crash_loop::Record;
+use crate::error::{Error, Result};
+use crate::modules;
+
+const LAST_BOOT_FILE: &str = "last-boot";
+/// Set in the shell the boot selector opens, so `.bashrc` does not start
+/// the boot selector again.
+pub const SHELL_ENV: &str = "SURVON_BOOT_SHELL";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Runtime,
+    CouncilSeat,
+    Menu,
+    Shell,
+    /// The last known-good runtime, with debug logging and without
+    /// Wasteland modules; see [`crate::crash_loop`].
+    SafeMode,
+    /// The runtime version installed before the current one.
+    PreviousRuntime,
+}
+
+impl Target {
+    pub const ALL: [Target; 6] = [
+        Target::Runtime,
+        Target::CouncilSeat,
+        Target::Menu,
+        Target::Shell,
+        Target::SafeMode,
+        Target::PreviousRuntime,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Runtime => "runtime",
+            Target::CouncilSeat => "council-seat",
+            Target::Menu => "menu",
+            Target::Shell => "shell",
+            Target::SafeMode => "safe-mode",
+            Target::PreviousRuntime => "previous-runtime",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Target> {
+        Target::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Target::Runtime => "Survon runtime",
+            Target::CouncilSeat => "Council seat",
+            Target::Menu => "Survon OS menu",
+            Target::Shell => "Shell",
+            Target::SafeMode => "Safe mode",
+            Target::PreviousRuntime => "Previous runtime",
+        }
+    }
+
+    /// Why the target cannot be started on this unit, if it cannot.
+    pub fn unavailable(self) -> Option<&'static str> {
+        let runtime = || Path::new(RUNTIME_LINK).exists();
+        match self {
+            Target::Runtime | Target::SafeMode if !runtime() => {
+                Some("the runtime is not installed")
+            }
+            Target::CouncilSeat if !council::is_installed() => {
+                Some("the council seat is not installed")
+            }
+            Target::PreviousRuntime if Store::default().previous().is_none() => {
+                Some("no earlier runtime version is installed")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The runtime version `target` starts, for the targets that start the
+/// runtime as installed.
+pub fn version(target: Target) -> Option<Version> {
+    match target {
+        Target::Runtime => Store::default().current(),
+        Target::PreviousRuntime => Store::default().previous(),
+        _ => None,
+    }
+}
+
+/// `~/.local/state/survon`, where the boot selector keeps its state.
+pub fn state_dir() -> PathBuf {
+    actions::home().join(".local/state/survon")
+}
+
+/// The target started at the previous boot.
+pub fn last() -> Option<Target> {
+    let text = fs::read_to_string(state_dir().join(LAST_BOOT_FILE)).ok()?;
+    Target::from_name(text.trim())
+}
+
+pub fn remember(target: Target) -> Result<()> {
+    let dir = state_dir();
+    fs::create_dir_all(&dir).map_err(|e| Error::io(&dir, e))?;
+    let path = dir.join(LAST_BOOT_FILE);
+    fs::write(&path, format!("{target}\n")).map_err(|e| Error::io(&path, e))
+}
+
+/// `BOOT_TARGET`, with `last` resolved. A target that cannot start falls
+/// back to the runtime, and without a runtime to the menu.
+pub fn default_target(config: &Config) -> Target {
+    let configured = config
+        .effective("BOOT_TARGET")
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    choose_default(&configured, last, |t| t.unavailable().is_none())
+}
+
+/// [`default_target`] for a `BOOT_TARGET` of `configured`, given the
+/// previous boot's target and which targets can start.
+fn choose_default(
+    configured: &str,
+    last: impl FnOnce() -> Option<Target>,
+    available: impl Fn(Target) -> bool,
+) -> Target {
+    let target = match configured {
+        "last" => last().unwrap_or(Target::Runtime),
+        name => Target::from_name(name).unwrap_or(Target::Runtime),
+    };
+    [target, Target::Runtime]
+        .into_iter()
+        .find(|t| available(*t))
+        .unwrap_or(Target::Menu)
+}
+
+/// `BOOT_TIMEOUT`.
+pub fn timeout(config: &Config) -> Duration {
+    Duration::from_secs(number(config, "BOOT_TIMEOUT", 5))
+}
+
+/// The number setting `key`, or `default` if it does not hold one.
+pub fn number(config: &Config, key: &str, default: u64) -> u64 {
+    config
+        .effective(key)
+        .and_then(|v| v.to_string().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Sets the unit up to start the runtime and council seat in `mode` from
+/// the next boot, and records it as `LAUNCH_MODE`.
+pub fn set_launch_mode(config: &mut Config, mode: LaunchMode, source: Source) -> Result<()> {
+    let ctx = Context::from_env(Mode::Live);
+    steps::set_launch_mode(&ctx, mode)?;
+    config.set("LAUNCH_MODE", mode.name())?;
+    config.save(source)?;
+    Ok(())
+}
+
+/// The command that starts `target`, with the settings in its
+/// environment. For the boot selector to `exec`, except the menu, which
+/// it waits for.
+pub fn command(target: Target, config: &Config) -> Result<Command> {
+    if let Some(reason) = target.unavailable() {
+        return Err(Error::Unavailable {
+            target: target.title(),
+            reason,
+        });
+    }
+    let home = actions::home();
+    let mut cmd = match target {
+        Target::Runtime => Command::new(RUNTIME_LINK),
+        Target::CouncilSeat => Command::new(council::BINARY),
+        Target::Menu => {
+            let exe = env::current_exe().map_err(|e| Error::io("survon", e))?;
+            let mut cmd = Command::new(exe);
+            cmd.arg("--config").arg(config.path()).arg("menu");
+            cmd
+        }
+        Target::Shell => {
+            let shell = env::var_os("SHELL").unwrap_or_else(|| "/bin/bash".into());
+            let mut cmd = Command::new(shell);
+            cmd.arg("-l").env(SHELL_ENV, "1");
+            cmd
+        }
+        Target::SafeMode => Command::new(
+            Record::load()
+                .safe_binary()
+                .unwrap_or_else(|| RUNTIME_LINK.into()),
+        ),
+        Target::PreviousRuntime => {
+            let store = Store::default();
+            let previous = store.previous().expect("checked by unavailable()");
+            Command::new(store.binary(&previous))
+        }
+    };
+    cmd.current_dir(&home)
+        .envs(config.iter().map(|(key, value)| (key, value.to_string())));
+    if target == Target::SafeMode {
+        cmd.current_dir(safe_home(&home)?).env("DEBUG", "true");
+    }
+    Ok(cmd)
+}
+
+/// A working directory for safe mode: the runtime finds its models and
+/// bundled modules through symlinks into `home`, but no Wasteland modules.
+/// Its debug log ends up in `logs/` here.
+fn safe_home(home: &Path) -> Result<PathBuf> {
+    let dir = state_dir().join("safe-mode");
+    let modules = dir.join("modules");
+    fs::create_dir_all(&modules).map_err(|e| Error::io(&modules, e))?;
+    let links = [
+        (home.join("bundled"), dir.join("bundled")),
+        (
+            modules::dir().join(modules::CORE),
+            modules.join(modules::CORE),
+        ),
+    ];
+    for (target, link) in links {
+        match symlink(&target, &link) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(Error::io(link, e)),
+        }
+    }
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default(configured: &str, last: Option<Target>, unavailable: &[Target]) -> Target {
+        choose_default(configured, || last, |t| !unavailable.contains(&t))
+    }
+
+    #[test]
+    fn targets_round_trip_through_their_names() {
+        for target in Target::ALL {
+            assert_eq!(Target::from_name(target.name()), Some(target));
+        }
+        assert_eq!(Target::from_name("council"), None);
+    }
+
+    #[test]
+    fn the_configured_target_is_the_default() {
+        assert_eq!(default("council-seat", None, &[]), Target::CouncilSeat);
+        assert_eq!(default("shell", Some(Target::Menu), &[]), Target::Shell);
+        assert_eq!(default("last", Some(Target::Menu), &[]), Target::Menu);
+    }
+
+    #[test]
+    fn unknown_or_missing_targets_fall_back_to_the_runtime() {
+        assert_eq!(default("", None, &[]), Target::Runtime);
+        assert_eq!(default("desktop", None, &[]), Target::Runtime);
+        assert_eq!(default("last", None, &[]), Target::Runtime);
+    }
+
+    #[test]
+    fn targets_that_cannot_start_fall_back() {
+        let no_council = [Target::CouncilSeat];
+        assert_eq!(default("council-seat", None, &no_council), Target::Runtime);
+        assert_eq!(
+            default("last", Some(Target::CouncilSeat), &no_council),
+            Target::Runtime
+        );
+        let nothing = [Target::Runtime, Target::CouncilSeat, Target::SafeMode];
+        assert_eq!(default("council-seat", None, &nothing), Target::Menu);
+        assert_eq!(default("shell", None, &nothing), Target::Shell);
+    }
+}
@@ -0,0 +1,67 @@
+//! The council seat (survon-runtime-council-seat): installing it, choosing
+//! its strategy and launching it.
+
+use std::path::Path;
+use std::process::Command;
+
+use survon_config::{schema, Config, Source};
+use survon_installer::system::Mode;
+use survon_installer::units::{self, LaunchMode};
+use survon_installer::Context;
+
+use crate::actions;
+use crate::error::{Error, Result};
+use crate::supervisor::{self, Program};
+
+pub const BINARY: &str = units::COUNCIL_SEAT_BINARY;
+pub const INSTALL_URL: &str =
+    "https://raw.githubusercontent.com/survon/survon-runtime-council-seat/master/scripts/install.sh";
+
+pub fn is_installed() -> bool {
+    Path::new(BINARY).exists()
+}
+
+/// The configured strategy, or the default one.
+pub fn strategy(config: &Config) -> String {
+    config
+        .effective("COUNCIL_STRATEGY")
+        .map_or_else(String::new, |s| s.to_string())
+}
+
+pub fn set_strategy(config: &mut Config, strategy: &str, source: Source) -> Result<()> {
+    config.set("COUNCIL_STRATEGY", strategy)?;
+    config.save(source)?;
+    Ok(())
+}
+
+/// Installs the council seat with its install script, set up for
+/// `strategy`, and makes that the configured strategy. In the systemd
+/// launch mode its service is enabled too.
+pub fn install(config: &mut Config, strategy: &str, source: Source) -> Result<()> {
+    // A misspelt strategy should fail before anything is downloaded.
+    schema::parse("COUNCIL_STRATEGY", strategy)?;
+    actions::run(
+        "the council seat installer",
+        Command::new("bash")
+            .arg("-c")
+            .arg(format!(
+                "curl -fsSL {INSTALL_URL} | bash -s -- --strategy \"$1\""
+            ))
+            .arg("install-council-seat")
+            .arg(strategy),
+    )?;
+    set_strategy(config, strategy, source)?;
+    if LaunchMode::configured(config) == LaunchMode::Systemd {
+        units::install(&Context::from_env(Mode::Live))?;
+    }
+    Ok(())
+}
+
+/// Runs the council seat under the [`supervisor`], which restarts it when
+/// it fails.
+pub fn launch(config: &Config) -> Result<()> {
+    if !is_installed() {
+        return Err(Error::NotInstalled("the council seat"));
+    }
+    supervisor::run(Program::CouncilSeat, config)
+}
@@ -0,0 +1,228 @@
+//! Crash-loop detection for the runtime started at boot.
+//!
+//! The boot selector waits for the runtime instead of handing the console
+//! over to it, and records how each start ended in
+//! `~/.local/state/survon/runtime-starts.json`. A start fails if the
+//! runtime exits with an error or crashes, or exits at all within
+//! `CRASH_LOOP_SECONDS`. Being asked to stop (SIGTERM, SIGHUP or SIGINT,
+//! as at shutdown) is not an error.
+//! Once `CRASH_LOOP_LIMIT` starts in a row have failed, the boot selector
+//! defaults to safe mode, on the last version that started fine.
+
+use std::fs;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use survon_config::Config;
+use survon_update::runtime::Store;
+use survon_update::Version;
+
+use crate::boot;
+use crate::error::{Error, Result};
+
+const STARTS_FILE: &str = "runtime-starts.json";
+/// Failed starts kept in the record; `CRASH_LOOP_LIMIT` goes no higher.
+const KEPT_FAILURES: usize = 20;
+
+/// How one start of the runtime ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Start {
+    /// Seconds since the epoch.
+    pub time: u64,
+    pub version: Option<Version>,
+    pub exit_code: Option<i32>,
+    /// The signal that killed it, if one did.
+    pub signal: Option<i32>,
+    /// How long it ran, in seconds.
+    pub seconds: u64,
+}
+
+impl Start {
+    fn new(time: u64, version: Option<Version>, status: ExitStatus, seconds: u64) -> Self {
+        Start {
+            time,
+            version,
+            exit_code: status.code(),
+            signal: status.signal(),
+            seconds,
+        }
+    }
+
+    /// What ended it, for the diagnostic screen.
+    pub fn describe(&self) -> String {
+        let how = match (self.exit_code, self.signal) {
+            (_, Some(signal)) => format!("killed by signal {signal}"),
+            (Some(code), _) => format!("exited with code {code}"),
+            (None, None) => "ended".to_string(),
+        };
+        format!("{how} after {}s", self.seconds)
+    }
+}
+
+/// The runtime's recent starts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Record {
+    /// The failed starts since the last good one, oldest first.
+    #[serde(default)]
+    pub failures: Vec<Start>,
+    /// The version of the last start that did not fail.
+    #[serde(default)]
+    pub known_good: Option<Version>,
+}
+
+impl Record {
+    fn path() -> PathBuf {
+        boot::state_dir().join(STARTS_FILE)
+    }
+
+    /// The record so far. A missing or unreadable file is an empty one: it
+    /// must never keep the unit from booting.
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+        }
+        let json = serde_json::to_string_pretty(self).expect("start records serialize");
+        fs::write(&path, json).map_err(|e| Error::io(&path, e))
+    }
+
+    /// Whether enough starts in a row have failed to give up on the
+    /// runtime for now.
+    pub fn in_crash_loop(&self, config: &Config) -> bool {
+        let limit = boot::number(config, "CRASH_LOOP_LIMIT", 3);
+        limit > 0 && self.failures.len() as u64 >= limit
+    }
+
+    /// Records how a start ended. One that exited cleanly or was asked to
+    /// stop after at least `min_seconds` ends the failures; any other is one
+    /// more, and only the last [`KEPT_FAILURES`] are kept.
+    fn add(&mut self, start: Start, min_seconds: u64) {
+        let stopped = matches!(
+            start.signal,
+            Some(libc::SIGTERM | libc::SIGHUP | libc::SIGINT)
+        );
+        let clean = start.exit_code == Some(0) || stopped;
+        if clean && start.seconds >= min_seconds {
+            self.failures.clear();
+            self.known_good = start.version.or(self.known_good.take());
+        } else {
+            self.failures.push(start);
+            let excess = self.failures.len().saturating_sub(KEPT_FAILURES);
+            self.failures.drain(..excess);
+        }
+    }
+
+    /// The runtime binary safe mode runs: the last known-good version,
+    /// else the previous one, else the current one.
+    pub fn safe_binary(&self) -> Option<PathBuf> {
+        let store = Store::default();
+        self.known_good
+            .iter()
+            .chain(store.previous().iter())
+            .map(|version| store.binary(version))
+            .find(|binary| binary.is_file())
+    }
+}
+
+/// Runs `cmd`, a runtime of `version`, until it exits, and records whether
+/// the start failed.
+pub fn run(cmd: &mut Command, version: Option<Version>, config: &Config) -> Result<ExitStatus> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let started = Instant::now();
+    let status = cmd.status().map_err(|e| Error::io(cmd.get_program(), e))?;
+    let start = Start::new(time, version, status, started.elapsed().as_secs());
+
+    let mut record = Record::load();
+    record.add(start, boot::number(config, "CRASH_LOOP_SECONDS", 30));
+    record.save()?;
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(time: u64, exit_code: Option<i32>, signal: Option<i32>, seconds: u64) -> Start {
+        Start {
+            time,
+            version: Some(Version::new(1, 4, 0)),
+            exit_code,
+            signal,
+            seconds,
+        }
+    }
+
+    fn config(text: &str) -> Config {
+        Config::parse("config.toml", text).unwrap()
+    }
+
+    #[test]
+    fn quick_or_failed_exits_are_failures() {
+        let mut record = Record::default();
+        record.add(start(1, Some(1), None, 600), 30);
+        record.add(start(2, None, Some(libc::SIGSEGV), 600), 30);
+        record.add(start(3, Some(0), None, 4), 30);
+        record.add(start(4, None, Some(libc::SIGTERM), 4), 30);
+        assert_eq!(
+            record.failures.iter().map(|s| s.time).collect::<Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+        assert_eq!(record.known_good, None);
+        assert_eq!(
+            record.failures[1].describe(),
+            "killed by signal 11 after 600s"
+        );
+        assert_eq!(record.failures[2].describe(), "exited with code 0 after 4s");
+    }
+
+    #[test]
+    fn a_good_start_ends_the_failures() {
+        let mut record = Record::default();
+        record.add(start(1, Some(1), None, 2), 30);
+        record.add(start(2, Some(0), None, 30), 30);
+        assert!(record.failures.is_empty());
+        assert_eq!(record.known_good, Some(Version::new(1, 4, 0)));
+
+        record.add(start(3, Some(1), None, 2), 30);
+        let mut stopped = start(4, None, Some(libc::SIGTERM), 3_600);
+        stopped.version = None;
+        record.add(stopped, 30);
+        assert!(record.failures.is_empty());
+        assert_eq!(record.known_good, Some(Version::new(1, 4, 0)));
+    }
+
+    #[test]
+    fn only_the_latest_failures_are_kept() {
+        let mut record = Record::default();
+        for time in 0..KEPT_FAILURES as u64 + 5 {
+            record.add(start(time, Some(1), None, 1), 30);
+        }
+        assert_eq!(record.failures.len(), KEPT_FAILURES);
+        assert_eq!(record.failures[0].time, 5);
+    }
+
+    #[test]
+    fn crash_loops_start_at_the_limit() {
+        let mut record = Record::default();
+        for time in 0..2 {
+            record.add(start(time, Some(1), None, 1), 30);
+        }
+        assert!(!record.in_crash_loop(&config("")));
+        assert!(record.in_crash_loop(&config("CRASH_LOOP_LIMIT = 2\n")));
+        record.add(start(2, Some(1), None, 1), 30);
+        assert!(record.in_crash_loop(&config("")));
+        assert!(!record.in_crash_loop(&config("CRASH_LOOP_LIMIT = 0\n")));
+    }
+}
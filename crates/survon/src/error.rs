@@ -0,0 +1,135 @@
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use thiserror::Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Exit codes of the `survon` command, so scripts can tell outcomes apart
+/// without parsing messages. A launched program's own exit code is passed
+/// through unchanged.
+pub mod exit {
+    pub const SUCCESS: u8 = 0;
+    /// Anything else went wrong.
+    pub const FAILURE: u8 = 1;
+    /// Bad arguments, including a value a setting does not accept. clap
+    /// exits with the same code for the arguments it rejects.
+    pub const USAGE: u8 = 2;
+    /// The runtime, council seat, module, model or setting asked for is not
+    /// there.
+    pub const NOT_FOUND: u8 = 3;
+    /// `--check` found a newer release or module; nothing was changed.
+    pub const UPDATE_AVAILABLE: u8 = 4;
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{}: {source}", .path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error(transparent)]
+    Config(#[from] survon_config::Error),
+
+    #[error(transparent)]
+    Installer(#[from] survon_installer::Error),
+
+    #[error(transparent)]
+    Update(#[from] survon_update::Error),
+
+    #[error("{0} is not installed")]
+    NotInstalled(&'static str),
+
+    #[error("cannot start {target}: {reason}")]
+    Unavailable {
+        target: &'static str,
+        reason: &'static str,
+    },
+
+    #[error("no module named `{0}`")]
+    NoSuchModule(String),
+
+    #[error("{0}")]
+    InvalidModule(String),
+
+    #[error("no model named `{0}`")]
+    NoSuchModel(String),
+
+    #[error("{0}")]
+    InvalidModel(String),
+
+    /// A program we ran exited unsuccessfully.
+    #[error("{program} failed ({status})")]
+    Failed {
+        program: &'static str,
+        status: ExitStatus,
+    },
+}
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    /// What `survon` exits with when a command fails with this error.
+    pub fn exit_code(&self) -> u8 {
+        use survon_config::Error as ConfigError;
+        match self {
+            Error::Config(ConfigError::Invalid { .. } | ConfigError::UnknownKey { .. })
+            | Error::InvalidModule(_)
+            | Error::InvalidModel(_) => exit::USAGE,
+            Error::Config(ConfigError::NoSuchChange(_))
+            | Error::NotInstalled(_)
+            | Error::Unavailable { .. }
+            | Error::NoSuchModule(_)
+            | Error::NoSuchModel(_) => exit::NOT_FOUND,
+            Error::Failed { status, .. } => status_code(*status),
+            _ => exit::FAILURE,
+        }
+    }
+}
+
+/// `status` as an exit code of ours: the program's own code, or
+/// [`exit::FAILURE`] if a signal ended it.
+pub fn status_code(status: ExitStatus) -> u8 {
+    status
+        .code()
+        .and_then(|code| u8::try_from(code).ok())
+        .unwrap_or(exit::FAILURE)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+
+    #[test]
+    fn errors_map_to_documented_exit_codes() {
+        let unknown = survon_config::Error::NoSuchChange(7);
+        assert_eq!(Error::Config(unknown).exit_code(), exit::NOT_FOUND);
+        assert_eq!(
+            Error::NotInstalled("the runtime").exit_code(),
+            exit::NOT_FOUND
+        );
+        assert_eq!(Error::NoSuchModule("x".into()).exit_code(), exit::NOT_FOUND);
+        assert_eq!(Error::InvalidModule("x".into()).exit_code(), exit::USAGE);
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(Error::io("/x", missing).exit_code(), exit::FAILURE);
+    }
+
+    #[test]
+    fn failed_programs_pass_their_code_through() {
+        let failed = |raw| Error::Failed {
+            program: "runtime-base-rust",
+            status: ExitStatus::from_raw(raw),
+        };
+        assert_eq!(failed(5 << 8).exit_code(), 5);
+        assert_eq!(status_code(ExitStatus::from_raw(0)), exit::SUCCESS);
+        // Killed by SIGKILL: no code of its own.
+        assert_eq!(failed(9).exit_code(), exit::FAILURE);
+    }
+}
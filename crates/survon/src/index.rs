@@ -0,0 +1,530 @@
+//! Module indexes: signed catalogs of module packages.
+//!
+//! An index is an `index.json` listing packages, with a detached minisign
+//! signature `index.json.minisig` by the release key or an operator key in
+//! `/etc/survon/trusted-keys`. It can sit in a local directory, on a USB drive
+//! (any `survon-modules/` directory under `/media` or `/mnt`) or on an
+//! HTTP mirror; the `MODULE_INDEXES` setting lists the directories and
+//! mirrors to use. An index that cannot be read or whose signature does not
+//! check is skipped, and said so.
+//!
+//! ```json
+//! {
+//!   "name": "Wasteland mirror",
+//!   "modules": [
+//!     {
+//!       "name": "weather",
+//!       "version": "1.3.0",
+//!       "description": "Weather station readings",
+//!       "tags": ["sensors", "ble"],
+//!       "url": "weather-1.3.0.survonmod",
+//!       "sha256": "…",
+//!       "size": 18232
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! A relative `url` is relative to the index. Every package is checked
+//! against its size and SHA-256 before it is installed, and then against
+//! its own manifest by [`crate::package`].
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use survon_config::Config;
+use survon_update::bundle::{trusted_keys, MEDIA_DIRS};
+use survon_update::fetch::fetch;
+use survon_update::{signature, Artifact, Version};
+
+use crate::error::{Error, Result};
+use crate::modules::{self, InstallOptions, Module};
+use crate::package::{self, Installed, EXTENSION};
+use crate::requirements;
+
+pub const INDEX_FILE: &str = "index.json";
+pub const SIGNATURE_FILE: &str = "index.json.minisig";
+/// The directory holding an index on a USB drive.
+pub const USB_DIR: &str = "survon-modules";
+
+/// The contents of an `index.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Catalog {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub modules: Vec<Entry>,
+}
+
+/// One package in an index. A module may be listed in several versions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub version: Version,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Where the package is: a URL, an absolute path, or a path relative to
+    /// the index.
+    pub url: String,
+    #[serde(flatten)]
+    pub artifact: Artifact,
+}
+
+impl Entry {
+    /// Whether `term` appears in the name, description or a tag, ignoring
+    /// case.
+    pub fn matches(&self, term: &str) -> bool {
+        let term = term.to_lowercase();
+        self.name.to_lowercase().contains(&term)
+            || self
+                .description
+                .as_ref()
+                .is_some_and(|d| d.to_lowercase().contains(&term))
+            || self.tags.iter().any(|t| t.to_lowercase().contains(&term))
+    }
+}
+
+/// An index and where it was read from.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub location: String,
+    pub catalog: Catalog,
+}
+
+impl Source {
+    /// Reads and verifies the index at `location`, a directory or a URL,
+    /// using `scratch` for downloads.
+    fn load(location: &str, scratch: &Path) -> Result<Source> {
+        let dir = if is_url(location) {
+            fs::create_dir_all(scratch).map_err(|e| Error::io(scratch, e))?;
+            for file in [INDEX_FILE, SIGNATURE_FILE] {
+                fetch(&join(location, file), &scratch.join(file))?;
+            }
+            scratch.to_path_buf()
+        } else {
+            PathBuf::from(location)
+        };
+        let json_path = dir.join(INDEX_FILE);
+        let sig_path = dir.join(SIGNATURE_FILE);
+        let json = fs::read(&json_path).map_err(|e| Error::io(&json_path, e))?;
+        let sig = fs::read_to_string(&sig_path).map_err(|e| Error::io(&sig_path, e))?;
+        let keys = trusted_keys();
+        let key_refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+        signature::verify(&json, &sig, &key_refs)?;
+        Ok(Source {
+            location: location.to_string(),
+            catalog: parse(location, &json)?,
+        })
+    }
+
+    /// Where the package of `entry` is.
+    pub fn package_location(&self, entry: &Entry) -> String {
+        if is_url(&entry.url) || entry.url.starts_with('/') {
+            entry.url.clone()
+        } else {
+            join(&self.location, &entry.url)
+        }
+    }
+
+    /// Fetches the package of `entry` into `dir` and checks it against the
+    /// index.
+    pub fn download(&self, entry: &Entry, dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+        let dest = dir.join(format!("{}-{}.{EXTENSION}", entry.name, entry.version));
+        let location = self.package_location(entry);
+        if is_url(&location) {
+            fetch(&location, &dest)?;
+        } else {
+            fs::copy(&location, &dest).map_err(|e| Error::io(&location, e))?;
+        }
+        entry.artifact.verify_file(&dest)?;
+        Ok(dest)
+    }
+}
+
+/// Every index there is to read, and why the others could not be.
+#[derive(Debug, Default)]
+pub struct Index {
+    pub sources: Vec<Source>,
+    pub failed: Vec<(String, Error)>,
+}
+
+impl Index {
+    /// Reads the indexes `MODULE_INDEXES` lists and those on USB drives.
+    pub fn load(config: &Config) -> Index {
+        let scratch = env::temp_dir().join(format!("survon-index-{}", std::process::id()));
+        let mut index = Index::default();
+        for (i, location) in locations(config).into_iter().enumerate() {
+            match Source::load(&location, &scratch.join(i.to_string())) {
+                Ok(source) => index.sources.push(source),
+                Err(e) => index.failed.push((location, e)),
+            }
+        }
+        let _ = fs::remove_dir_all(&scratch);
+        index
+    }
+
+    /// Every entry, with the index it is in.
+    pub fn entries(&self) -> impl Iterator<Item = (&Source, &Entry)> {
+        self.sources
+            .iter()
+            .flat_map(|source| source.catalog.modules.iter().map(move |e| (source, e)))
+    }
+
+    /// The newest version of the module `name` in any index.
+    pub fn newest(&self, name: &str) -> Option<(&Source, &Entry)> {
+        self.entries()
+            .filter(|(_, entry)| entry.name == name)
+            .max_by(|(_, a), (_, b)| a.version.cmp(&b.version))
+    }
+
+    /// The newest version of each module matching `term`, by name.
+    pub fn search(&self, term: &str) -> Vec<(&Source, &Entry)> {
+        let mut names: Vec<&str> = self
+            .entries()
+            .filter(|(_, entry)| entry.matches(term))
+            .map(|(_, entry)| entry.name.as_str())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+            .into_iter()
+            .filter_map(|name| self.newest(name))
+            .collect()
+    }
+
+    /// The installed Wasteland modules with a newer version in an index.
+    pub fn upgrades<'a>(&'a self, installed: &[Module]) -> Vec<(Module, &'a Source, &'a Entry)> {
+        installed
+            .iter()
+            .filter(|module| module.group != crate::modules::CORE)
+            .filter_map(|module| {
+                let (source, entry) = self.newest(&module.name)?;
+                let current = module.version.as_deref().and_then(requirements::version);
+                current
+                    .is_none_or(|current| entry.version > current)
+                    .then(|| (module.clone(), source, entry))
+            })
+            .collect()
+    }
+}
+
+/// Downloads the package of `entry` and installs it into `root`. A package
+/// that is not the module and version `entry` lists is rejected.
+pub fn install(
+    root: &Path,
+    source: &Source,
+    entry: &Entry,
+    options: &InstallOptions,
+) -> Result<Installed> {
+    let scratch = env::temp_dir().join(format!("survon-download-{}", std::process::id()));
+    let result = source.download(entry, &scratch).and_then(|archive| {
+        package::install_listed(root, &archive, &entry.name, &entry.version, options)
+    });
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+/// The directories and mirrors in `MODULE_INDEXES`, then every
+/// `survon-modules/` directory with an index up to three levels below the
+/// USB mount points.
+pub fn locations(config: &Config) -> Vec<String> {
+    let mut locations: Vec<String> = config
+        .effective("MODULE_INDEXES")
+        .map(|value| {
+            value
+                .to_string()
+                .split_whitespace()
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut level: Vec<PathBuf> = MEDIA_DIRS.iter().map(PathBuf::from).collect();
+    for _ in 0..3 {
+        let mut next = Vec::new();
+        for dir in &level {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for path in entries.filter_map(|e| Some(e.ok()?.path())) {
+                if !path.is_dir() {
+                    continue;
+                }
+                if path.file_name().is_some_and(|n| n == USB_DIR) && path.join(INDEX_FILE).is_file()
+                {
+                    locations.push(path.display().to_string());
+                } else {
+                    next.push(path);
+                }
+            }
+        }
+        level = next;
+    }
+    let mut seen = HashSet::new();
+    locations.retain(|location| seen.insert(location.clone()));
+    locations
+}
+
+/// Writes an index of every package in `dir` there and signs it with the
+/// minisign secret key at `secret_key`. Without a `password` the key's
+/// password is asked for on the terminal. Returns the number of packages.
+pub fn build(
+    dir: &Path,
+    name: Option<&str>,
+    secret_key: &Path,
+    password: Option<String>,
+) -> Result<usize> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| Error::io(dir, e))?
+        .filter_map(|e| Some(e.ok()?.path()))
+        .filter(|path| package::is_package(path))
+        .collect();
+    paths.sort();
+    let scratch = env::temp_dir().join(format!("survon-index-build-{}", std::process::id()));
+    let mut catalog = Catalog {
+        name: name.map(str::to_string),
+        modules: Vec::new(),
+    };
+    for path in &paths {
+        let _ = fs::remove_dir_all(&scratch);
+        fs::create_dir_all(&scratch).map_err(|e| Error::io(&scratch, e))?;
+        let opened = package::open(path, &scratch);
+        let _ = fs::remove_dir_all(&scratch);
+        let (package, _) = opened?;
+        let size = fs::metadata(path).map_err(|e| Error::io(path, e))?.len();
+        catalog.modules.push(Entry {
+            name: package.name,
+            version: package.version,
+            description: package.description,
+            tags: package.tags,
+            url: path
+                .file_name()
+                .expect("packages have file names")
+                .to_string_lossy()
+                .into_owned(),
+            artifact: Artifact {
+                sha256: survon_installer::fsutil::sha256_file(path)?,
+                size,
+            },
+        });
+    }
+    let json = serde_json::to_vec_pretty(&catalog).expect("indexes serialize");
+    let comment = format!("survon module index ({} packages)", catalog.modules.len());
+    let sig = signature::sign(&json, secret_key, password, &comment)?;
+    for (file, contents) in [(INDEX_FILE, json), (SIGNATURE_FILE, sig.into_bytes())] {
+        let path = dir.join(file);
+        fs::write(&path, contents).map_err(|e| Error::io(&path, e))?;
+    }
+    Ok(catalog.modules.len())
+}
+
+fn is_url(location: &str) -> bool {
+    location.contains("://")
+}
+
+/// Reads the `index.json` from `location`. Module names become file names
+/// in the download cache, so one that is not a valid module name fails the
+/// whole index.
+fn parse(location: &str, json: &[u8]) -> Result<Catalog> {
+    let catalog: Catalog = serde_json::from_slice(json)
+        .map_err(|e| Error::InvalidModule(format!("{location}: invalid index: {e}")))?;
+    let unsafe_name = catalog
+        .modules
+        .iter()
+        .find(|entry| modules::check_name(&entry.name).is_err());
+    if let Some(entry) = unsafe_name {
+        return Err(Error::InvalidModule(format!(
+            "{location}: invalid index: `{}` is not a valid module name",
+            entry.name
+        )));
+    }
+    Ok(catalog)
+}
+
+fn join(base: &str, file: &str) -> String {
+    format!("{}/{file}", base.trim_end_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::{CORE, WASTELAND};
+    use survon_test_support::Scratch;
+
+    fn entry(name: &str, version: &str, tags: &[&str]) -> Entry {
+        Entry {
+            name: name.to_string(),
+            version: version.parse().unwrap(),
+            description: Some(format!("The {name} module")),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            url: format!("{name}-{version}.{EXTENSION}"),
+            artifact: Artifact {
+                sha256: "0".repeat(64),
+                size: 0,
+            },
+        }
+    }
+
+    fn source(location: &str, modules: Vec<Entry>) -> Source {
+        Source {
+            location: location.to_string(),
+            catalog: Catalog {
+                name: None,
+                modules,
+            },
+        }
+    }
+
+    fn index() -> Index {
+        Index {
+            sources: vec![
+                source(
+                    "/media/usb/survon-modules",
+                    vec![
+                        entry("weather", "1.3.0", &["sensors", "ble"]),
+                        entry("maps", "2.0.0", &[]),
+                    ],
+                ),
+                source(
+                    "https://mirror.example/modules/",
+                    vec![
+                        entry("weather", "1.10.0", &["sensors"]),
+                        entry("radio", "0.2.0", &["BLE"]),
+                    ],
+                ),
+            ],
+            failed: Vec::new(),
+        }
+    }
+
+    fn names(found: &[(&Source, &Entry)]) -> Vec<String> {
+        found
+            .iter()
+            .map(|(_, entry)| format!("{} {}", entry.name, entry.version))
+            .collect()
+    }
+
+    fn installed(root: &Path, group: &'static str, name: &str, version: Option<&str>) -> Module {
+        let dir = root.join(group).join(name);
+        fs::create_dir_all(&dir).unwrap();
+        let version = version.map_or(String::new(), |v| format!("version: {v}\n"));
+        fs::write(
+            dir.join(crate::modules::CONFIG_FILE),
+            format!("name: {name}\n{version}"),
+        )
+        .unwrap();
+        crate::modules::read(group, true, &dir).unwrap()
+    }
+
+    #[test]
+    fn search_finds_the_newest_version_of_each_match() {
+        let index = index();
+        let (source, weather) = index.newest("weather").unwrap();
+        assert_eq!(weather.version, Version::new(1, 10, 0));
+        assert_eq!(
+            source.package_location(weather),
+            "https://mirror.example/modules/weather-1.10.0.survonmod"
+        );
+        assert!(index.newest("compass").is_none());
+
+        assert_eq!(
+            names(&index.search("ble")),
+            ["radio 0.2.0", "weather 1.10.0"]
+        );
+        assert_eq!(names(&index.search("MAPS")), ["maps 2.0.0"]);
+        assert_eq!(names(&index.search("module")).len(), 3);
+        assert!(index.search("compass").is_empty());
+    }
+
+    #[test]
+    fn upgrades_skip_current_and_core_modules() {
+        let root = Scratch::new("index-upgrades");
+        let modules = [
+            installed(&root, WASTELAND, "weather", Some("1.9.2")),
+            installed(&root, WASTELAND, "maps", Some("2.0.0")),
+            installed(&root, WASTELAND, "radio", None),
+            installed(&root, CORE, "radio", Some("0.1.0")),
+            installed(&root, WASTELAND, "compass", Some("0.1.0")),
+        ];
+        let index = index();
+        let upgrades: Vec<String> = index
+            .upgrades(&modules)
+            .iter()
+            .map(|(module, _, entry)| format!("{} {} {}", module.group, module.name, entry.version))
+            .collect();
+        assert_eq!(
+            upgrades,
+            ["wasteland weather 1.10.0", "wasteland radio 0.2.0"]
+        );
+    }
+
+    #[test]
+    fn indexes_with_unsafe_module_names_are_rejected() {
+        let json = |name: &str| {
+            let catalog = Catalog {
+                name: None,
+                modules: vec![entry(name, "1.0.0", &[])],
+            };
+            serde_json::to_vec(&catalog).unwrap()
+        };
+        assert_eq!(parse("usb", &json("weather")).unwrap().modules.len(), 1);
+        for name in ["../escape", "a/b", ".hidden", ""] {
+            let Err(Error::InvalidModule(message)) = parse("usb", &json(name)) else {
+                panic!("{name:?} was accepted");
+            };
+            assert!(message.starts_with("usb: invalid index:"), "{message}");
+        }
+        assert!(parse("usb", b"{").is_err());
+    }
+
+    #[test]
+    fn indexes_need_a_trusted_signature() {
+        let dir = Scratch::new("index-signature");
+        let location = dir.to_str().unwrap();
+        let key = dir.join("stranger.key");
+        let pair = minisign::KeyPair::generate_encrypted_keypair(Some(String::new())).unwrap();
+        fs::write(&key, pair.sk.to_box(None).unwrap().to_string()).unwrap();
+        assert_eq!(
+            build(&dir, Some("Stranger"), &key, Some(String::new())).unwrap(),
+            0
+        );
+
+        let error = Source::load(location, &dir.join("scratch")).unwrap_err();
+        assert!(
+            matches!(error, Error::Update(survon_update::Error::Signature(_))),
+            "{error:?}"
+        );
+
+        fs::remove_file(dir.join(SIGNATURE_FILE)).unwrap();
+        assert!(matches!(
+            Source::load(location, &dir.join("scratch")),
+            Err(Error::Io { path, .. }) if path == dir.join(SIGNATURE_FILE)
+        ));
+    }
+
+    #[test]
+    fn packages_must_match_their_index_entry() {
+        let dir = Scratch::new("index-install");
+        let package = dir.join(format!("weather-1.3.0.{EXTENSION}"));
+        fs::write(&package, "not the package the index lists").unwrap();
+        let source = source(dir.to_str().unwrap(), Vec::new());
+        let mut entry = entry("weather", "1.3.0", &[]);
+        entry.artifact.size = fs::metadata(&package).unwrap().len();
+
+        let root = dir.join("modules");
+        let error = install(&root, &source, &entry, &InstallOptions::default()).unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                Error::Update(survon_update::Error::Checksum { expected, .. })
+                    if *expected == format!("sha256 {}", "0".repeat(64))
+            ),
+            "{error:?}"
+        );
+        assert!(!root.exists());
+    }
+}
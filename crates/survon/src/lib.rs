@@ -0,0 +1,29 @@
+//! What the `survon` command does, as functions.
+//!
+//! The command-line subcommands and the interactive menu are both thin
+//! layers over this crate, so anything the menu can do can also be
+//! scripted: updating and launching the runtime ([`runtime`]), installing
+//! and configuring the council seat ([`council`]), keeping either running
+//! ([`supervisor`]), managing Wasteland modules ([`modules`], [`package`],
+//! [`index`]) and writing them ([`authoring`]), choosing the LLM model
+//! ([`model`]), re-running the installer ([`actions`]) and choosing what
+//! the console boots into ([`boot`]).
+//! Failures come back as an [`Error`], whose [`Error::exit_code`] is what
+//! the command exits with.
+
+pub mod actions;
+pub mod authoring;
+pub mod boot;
+pub mod council;
+pub mod crash_loop;
+pub mod error;
+pub mod index;
+pub mod model;
+pub mod modules;
+pub mod package;
+pub mod requirements;
+pub mod runtime;
+pub mod status;
+pub mod supervisor;
+
+pub use error::{exit, Error, Result};
@@ -0,0 +1,1562 @@
+mod menu;
+mod selector;
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use survon::boot::{self, Target};
+use survon::status::Status;
+use survon::supervisor::{self, Program};
+use survon::{
+    actions, authoring, council, exit, index, model, modules, package, requirements, runtime, Error,
+};
+use survon_config::history::Entry;
+use survon_config::schema::{self, COUNCIL_STRATEGIES, KEYS};
+use survon_config::{Config, Source, CONFIG_PATH};
+use survon_installer::log::timestamp;
+use survon_installer::models;
+use survon_installer::system::Mode;
+use survon_installer::units::{self, LaunchMode};
+use survon_installer::Context;
+use survon_update::runtime::{RuntimeUpdate, Store, RUNTIME_RELEASES_URL};
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  failure
+  2  bad arguments, or a value a setting does not accept
+  3  the runtime, council seat, module or setting is not there
+  4  `runtime update --check` or `module upgrade --check` found an upgrade
+A launched runtime or council seat exits with its own code.";
+
+fn cli() -> Command {
+    Command::new("survon")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Manage a Survon OS unit (opens the menu when run without a command)")
+        .after_help(EXIT_CODES)
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .global(true)
+                .default_value(CONFIG_PATH)
+                .value_parser(value_parser!(PathBuf))
+                .help("Settings file to use"),
+        )
+        .subcommand(Command::new("menu").about("Open the interactive menu"))
+        .subcommand(
+            Command::new("boot")
+                .about("Choose what to start on the console, counting down to BOOT_TARGET")
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .value_name("TARGET")
+                        .value_parser(Target::ALL.map(Target::name))
+                        .help("Start this straight away instead of showing the choices"),
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("SECS")
+                        .value_parser(value_parser!(u64))
+                        .help("Seconds to count down (default: BOOT_TIMEOUT)"),
+                ),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Print the unit's state, and what the supervisor is doing")
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the supervisor states and last crash as JSON"),
+                ),
+        )
+        .subcommand(
+            Command::new("crashes")
+                .about("List the failed runs the supervisor recorded, with their last output")
+                .arg(
+                    Arg::new("program")
+                        .value_name("PROGRAM")
+                        .value_parser(Program::ALL.map(Program::name))
+                        .help("Only this program's crashes"),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .short('n')
+                        .value_name("N")
+                        .default_value("5")
+                        .value_parser(value_parser!(usize))
+                        .help("Show the N most recent"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .help("Print them as JSON lines"),
+                ),
+        )
+        .subcommand(
+            Command::new("launch-mode")
+                .about("Print how the runtime and council seat are started, or switch it")
+                .arg(
+                    Arg::new("mode")
+                        .value_name("MODE")
+                        .value_parser(LaunchMode::ALL.map(LaunchMode::name))
+                        .help("console (boot selector) or systemd (services); applies at the next boot"),
+                )
+                .arg(
+                    Arg::new("units")
+                        .long("units")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("mode")
+                        .help("Print the unit files the systemd mode installs"),
+                ),
+        )
+        .subcommand(
+            Command::new("runtime")
+                .about("Update and launch the Survon runtime")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("update")
+                        .about("Install the latest runtime release")
+                        .arg(
+                            Arg::new("check")
+                                .long("check")
+                                .action(ArgAction::SetTrue)
+                                .help(
+                                "Only report whether a newer release exists (exit code 4 if so)",
+                            ),
+                        )
+                        .arg(
+                            Arg::new("from")
+                                .long("from")
+                                .value_name("URL")
+                                .default_value(RUNTIME_RELEASES_URL)
+                                .help("Where the release manifest and assets are published"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("launch")
+                        .about("Run the runtime with the current settings until it exits"),
+                )
+                .subcommand(
+                    Command::new("version")
+                        .about("Print the current runtime version")
+                        .arg(
+                            Arg::new("all")
+                                .long("all")
+                                .action(ArgAction::SetTrue)
+                                .help("List every installed version, marking the current one"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Read and change Survon settings")
+                .subcommand_required(true)
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .global(true)
+                        .hide(true)
+                        .default_value("cli")
+                        .value_parser(["cli", "menu"])
+                        .help("Who is making the change, for the history"),
+                )
+                .subcommand(
+                    Command::new("get")
+                        .about("Print a setting's value, or its default when unset")
+                        .arg(Arg::new("key").value_name("KEY").required(true)),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Change a setting")
+                        .arg(Arg::new("key").value_name("KEY").required(true))
+                        .arg(Arg::new("value").value_name("VALUE").required(true))
+                        .arg(
+                            Arg::new("custom")
+                                .long("custom")
+                                .action(ArgAction::SetTrue)
+                                .help("Store a setting the schema does not know, as text"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("unset")
+                        .about("Remove a setting")
+                        .arg(Arg::new("key").value_name("KEY").required(true)),
+                )
+                .subcommand(Command::new("list").about("Print every setting as KEY=VALUE"))
+                .subcommand(
+                    Command::new("describe")
+                        .about("Explain every known setting and show its effective value")
+                        .arg(
+                            Arg::new("key")
+                                .value_name("KEY")
+                                .help("Describe only this setting"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Regenerate the environment file from the settings file"),
+                )
+                .subcommand(
+                    Command::new("history")
+                        .about("List every recorded settings change")
+                        .arg(
+                            Arg::new("key")
+                                .value_name("KEY")
+                                .help("Only list changes to this setting"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("revert")
+                        .about("Undo a recorded change, restoring the value it replaced")
+                        .arg(
+                            Arg::new("id")
+                                .value_name("ID")
+                                .required(true)
+                                .value_parser(value_parser!(u64))
+                                .help("Change number, as shown by `survon config history`"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("module")
+                .about("Manage Wasteland modules under ~/modules")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("List the installed modules, enabled or not")
+                        .arg(
+                            Arg::new("json")
+                                .long("json")
+                                .action(ArgAction::SetTrue)
+                                .help("Print them as a JSON array"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("search")
+                        .about("Search the module indexes by name, description and tag")
+                        .arg(Arg::new("term").value_name("TERM").default_value("").help("Word to look for; lists every indexed module if left out"))
+                        .arg(
+                            Arg::new("json")
+                                .long("json")
+                                .action(ArgAction::SetTrue)
+                                .help("Print the matches as a JSON array"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("info")
+                        .about("Show a module's version, description and requirements, and which are not met, or what an index says about it")
+                        .arg(Arg::new("name").value_name("NAME").required(true)),
+                )
+                .subcommand(
+                    Command::new("install")
+                        .about("Install, upgrade or downgrade a module from an index, a package, a directory or an archive")
+                        .arg(
+                            Arg::new("path")
+                                .value_name("PATH|NAME")
+                                .required(true)
+                                .value_parser(value_parser!(PathBuf))
+                                .help("A .survonmod package, a module directory holding its config.yml, a tar archive of one, or the name of an indexed module"),
+                        )
+                        .arg(
+                            Arg::new("replace")
+                                .long("replace")
+                                .action(ArgAction::SetTrue)
+                                .help("Overwrite an installed module of the same name that did not come from a package"),
+                        )
+                        .arg(
+                            Arg::new("downgrade")
+                                .long("downgrade")
+                                .action(ArgAction::SetTrue)
+                                .help("Allow installing an older version of a package"),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .action(ArgAction::SetTrue)
+                                .help("Overwrite files edited since the package was installed, keeping the old module under ~/modules/.backup"),
+                        )
+                        .arg(
+                            Arg::new("ignore-missing")
+                                .long("ignore-missing")
+                                .action(ArgAction::SetTrue)
+                                .help("Install although modules, assets or the runtime it needs are missing (conflicts are still refused)"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("upgrade")
+                        .about("Upgrade installed modules to the newest version in the module indexes")
+                        .arg(Arg::new("names").value_name("NAME").num_args(0..).help("Modules to upgrade [default: all]"))
+                        .arg(
+                            Arg::new("check")
+                                .long("check")
+                                .action(ArgAction::SetTrue)
+                                .help("Only list the upgrades; exit with 4 if there are any"),
+                        )
+                        .arg(
+                            Arg::new("replace")
+                                .long("replace")
+                                .action(ArgAction::SetTrue)
+                                .help("Also upgrade modules that did not come from a package"),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .action(ArgAction::SetTrue)
+                                .help("Overwrite local edits, keeping the old module under ~/modules/.backup"),
+                        )
+                        .arg(
+                            Arg::new("ignore-missing")
+                                .long("ignore-missing")
+                                .action(ArgAction::SetTrue)
+                                .help("Upgrade although modules, assets or the runtime the new version needs are missing"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("index")
+                        .about("Write and sign an index of the .survonmod packages in a directory")
+                        .arg(
+                            Arg::new("dir")
+                                .value_name("DIR")
+                                .required(true)
+                                .value_parser(value_parser!(PathBuf)),
+                        )
+                        .arg(
+                            Arg::new("key")
+                                .long("key")
+                                .value_name("FILE")
+                                .required(true)
+                                .value_parser(value_parser!(PathBuf))
+                                .help("Minisign secret key to sign it with (password from $SURVON_INDEX_PASSWORD or the terminal)"),
+                        )
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .value_name("NAME")
+                                .help("Name of the index, shown with its modules"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("new")
+                        .about("Start a new module in a directory of its own")
+                        .arg(Arg::new("name").value_name("NAME").required(true))
+                        .arg(
+                            Arg::new("template")
+                                .long("template")
+                                .value_name("KIND")
+                                .value_parser(authoring::Template::ALL.map(authoring::Template::name))
+                                .default_value("basic")
+                                .help("basic, sensor (BLE), llm (uses survon_llm and its model) or audio (ships sounds)"),
+                        )
+                        .arg(
+                            Arg::new("dir")
+                                .long("dir")
+                                .value_name("DIR")
+                                .default_value(".")
+                                .value_parser(value_parser!(PathBuf))
+                                .help("Where to create the module's directory"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("lint")
+                        .about("Check a module directory's config.yml; exits with 1 if there are errors")
+                        .arg(
+                            Arg::new("dir")
+                                .value_name("DIR")
+                                .default_value(".")
+                                .value_parser(value_parser!(PathBuf)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("pack")
+                        .about("Pack a module directory into a .survonmod package")
+                        .arg(
+                            Arg::new("dir")
+                                .value_name("DIR")
+                                .required(true)
+                                .value_parser(value_parser!(PathBuf))
+                                .help("Module directory; its config.yml must give a name and a semver version"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .short('o')
+                                .long("output")
+                                .value_name("FILE")
+                                .value_parser(value_parser!(PathBuf))
+                                .help("Where to write the package [default: NAME-VERSION.survonmod]"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Delete a Wasteland module")
+                        .arg(Arg::new("name").value_name("NAME").required(true))
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .action(ArgAction::SetTrue)
+                                .help("Even if enabled modules depend on it"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("enable")
+                        .about("Let the runtime load a disabled module again")
+                        .arg(Arg::new("name").value_name("NAME").required(true)),
+                )
+                .subcommand(
+                    Command::new("disable")
+                        .about("Stop the runtime loading a module, keeping its files")
+                        .arg(Arg::new("name").value_name("NAME").required(true))
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .action(ArgAction::SetTrue)
+                                .help("Even if enabled modules depend on it"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("council")
+                .about("Install, configure and launch the council seat")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("install")
+                        .about("Install the council seat")
+                        .arg(
+                            Arg::new("strategy")
+                                .long("strategy")
+                                .value_name("NAME")
+                                .value_parser(COUNCIL_STRATEGIES.to_vec())
+                                .help("Strategy to set it up for (default: the configured one)"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("strategy")
+                        .about("Print the council strategy, or change it")
+                        .arg(
+                            Arg::new("name")
+                                .value_name("NAME")
+                                .value_parser(COUNCIL_STRATEGIES.to_vec()),
+                        ),
+                )
+                .subcommand(
+                    Command::new("launch")
+                        .about("Run the council seat with the current settings until it exits"),
+                ),
+        )
+        .subcommand(
+            Command::new("model")
+                .about("Manage the GGUF models survon_llm summarizes with")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("List the catalog and the installed models, marking the one in use"),
+                )
+                .subcommand(
+                    Command::new("install")
+                        .about("Download a catalog model, or a GGUF model from a URL")
+                        .arg(Arg::new("model").value_name("NAME|URL").required(true))
+                        .arg(
+                            Arg::new("name")
+                                .long("name")
+                                .value_name("FILE")
+                                .help("File name to save a URL's model as (default: the URL's)"),
+                        )
+                        .arg(
+                            Arg::new("sha256")
+                                .long("sha256")
+                                .value_name("HEX")
+                                .help("SHA-256 to check a URL's model against"),
+                        )
+                        .arg(
+                            Arg::new("use")
+                                .long("use")
+                                .action(ArgAction::SetTrue)
+                                .help("Switch to the model once it is installed"),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .action(ArgAction::SetTrue)
+                                .help("Install a catalog model even if this unit has too little RAM for it"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Delete an installed model")
+                        .arg(Arg::new("model").value_name("NAME").required(true))
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .action(ArgAction::SetTrue)
+                                .help("Delete the model in use too, switching survon_llm to search-only mode"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("use")
+                        .about("Make the runtime load an installed model, or `none` for search-only mode")
+                        .arg(Arg::new("model").value_name("NAME").required(true)),
+                ),
+        )
+        .subcommand(
+            Command::new("install")
+                .about("Re-install Survon OS with the latest installer")
+                .arg(
+                    Arg::new("args")
+                        .value_name("ARGS")
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true)
+                        .help("Passed to survon-installer, e.g. --provision FILE or -y"),
+                ),
+        )
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    match matches.subcommand() {
+        Some(("config", sub)) => run_config(sub),
+        Some(("runtime", sub)) => run_runtime(sub),
+        Some(("module", sub)) => run_module(sub),
+        Some(("council", sub)) => run_council(sub),
+        Some(("model", sub)) => run_model(sub),
+        Some(("install", sub)) => {
+            let args: Vec<String> = sub
+                .get_many::<String>("args")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            done(actions::install(&args), "Install failed")
+        }
+        Some(("boot", sub)) => {
+            let path = sub
+                .get_one::<PathBuf>("config")
+                .expect("config has a default");
+            let target = sub
+                .get_one::<String>("target")
+                .and_then(|name| Target::from_name(name));
+            let timeout = sub
+                .get_one::<u64>("timeout")
+                .copied()
+                .map(Duration::from_secs);
+            selector::run(path, target, timeout)
+        }
+        Some(("status", sub)) => run_status(sub),
+        Some(("crashes", sub)) => run_crashes(sub),
+        Some(("launch-mode", sub)) => run_launch_mode(sub),
+        Some(("menu", sub)) => run_menu(sub),
+        _ => run_menu(&matches),
+    }
+}
+
+/// `result` as the command's exit code, printing the error with `what`.
+fn done(result: survon::Result<()>, what: &str) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => failed(e, what),
+    }
+}
+
+fn failed(e: Error, what: &str) -> ExitCode {
+    // A program that failed has said why itself.
+    if !matches!(e, Error::Failed { .. }) {
+        eprintln!("{what}: {e}");
+    }
+    ExitCode::from(e.exit_code())
+}
+
+fn load_config(matches: &ArgMatches) -> Result<Config, ExitCode> {
+    let path = matches
+        .get_one::<PathBuf>("config")
+        .expect("config has a default");
+    Config::load(path).map_err(|e| failed(e.into(), "Could not read settings"))
+}
+
+fn run_menu(matches: &ArgMatches) -> ExitCode {
+    let path = matches
+        .get_one::<PathBuf>("config")
+        .expect("config has a default");
+    match menu::run(path) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Menu failed: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_status(matches: &ArgMatches) -> ExitCode {
+    let config = match load_config(matches) {
+        Ok(config) => config,
+        Err(code) => return code,
+    };
+    let status = Status::gather(&config);
+    if matches.get_flag("json") {
+        let json = serde_json::json!({
+            "supervised": status.supervised,
+            "last_crash": status.last_crash,
+        });
+        println!("{json}");
+        return ExitCode::SUCCESS;
+    }
+    let row = |label: &str, value: Option<String>, missing: &str| {
+        let label = format!("{label}:");
+        println!(
+            "{label:<26}{}",
+            value.unwrap_or_else(|| missing.to_string())
+        );
+    };
+    row(
+        "Runtime",
+        status.runtime.map(|v| v.to_string()),
+        "not installed",
+    );
+    row("Model", status.model, "none");
+    row("Council seat", status.council, "not installed");
+    for program in Program::ALL {
+        let state = status
+            .supervised
+            .iter()
+            .find(|s| s.program == program)
+            .map(|s| s.describe());
+        row(&format!("Supervised {program}"), state, "never launched");
+    }
+    if let Some(crash) = &status.last_crash {
+        let crash = format!(
+            "{} {} at {}",
+            crash.program,
+            crash.describe(),
+            timestamp(crash.time)
+        );
+        row("Last crash", Some(crash), "");
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_crashes(matches: &ArgMatches) -> ExitCode {
+    let program = matches
+        .get_one::<String>("program")
+        .and_then(|name| Program::from_name(name));
+    let limit = *matches
+        .get_one::<usize>("limit")
+        .expect("limit has a default");
+    let crashes = supervisor::crashes(program);
+    let shown = &crashes[crashes.len().saturating_sub(limit)..];
+    if matches.get_flag("json") {
+        for crash in shown {
+            println!(
+                "{}",
+                serde_json::to_string(crash).expect("crash records serialize")
+            );
+        }
+        return ExitCode::SUCCESS;
+    }
+    if shown.is_empty() {
+        println!("No crashes recorded.");
+    }
+    for (i, crash) in shown.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!(
+            "{}  {} {} (failure {} in a row)",
+            timestamp(crash.time),
+            crash.program,
+            crash.describe(),
+            crash.failures
+        );
+        for line in &crash.log {
+            println!("  | {line}");
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_launch_mode(matches: &ArgMatches) -> ExitCode {
+    let mut config = match load_config(matches) {
+        Ok(config) => config,
+        Err(code) => return code,
+    };
+    let ctx = Context::from_env(Mode::Live);
+    if matches.get_flag("units") {
+        for unit in units::UNITS {
+            println!("# {}", unit.path().display());
+            print!("{}", unit.render(&ctx.user, &ctx.home));
+            println!();
+        }
+        return ExitCode::SUCCESS;
+    }
+    match matches.get_one::<String>("mode") {
+        Some(name) => {
+            let mode = LaunchMode::from_name(name).expect("clap checked the mode");
+            let result = boot::set_launch_mode(&mut config, mode, Source::Cli);
+            if result.is_ok() {
+                println!("Launch mode set to {mode}; it applies at the next boot.");
+            }
+            done(result, "Could not switch the launch mode")
+        }
+        None => {
+            println!("{}", LaunchMode::configured(&config));
+            for unit in units::UNITS {
+                println!("  {}: {}", unit.name, unit.state(&ctx));
+            }
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+fn run_config(matches: &ArgMatches) -> ExitCode {
+    let mut config = match load_config(matches) {
+        Ok(config) => config,
+        Err(code) => return code,
+    };
+    let source = match matches.get_one::<String>("source").map(String::as_str) {
+        Some("menu") => Source::Menu,
+        _ => Source::Cli,
+    };
+    let key = |sub: &ArgMatches| {
+        sub.get_one::<String>("key")
+            .expect("key is required")
+            .clone()
+    };
+
+    let result = match matches.subcommand() {
+        Some(("get", sub)) => {
+            let key = key(sub);
+            return match config.effective(&key) {
+                Some(value) => {
+                    println!("{value}");
+                    ExitCode::SUCCESS
+                }
+                None => {
+                    eprintln!("{key} is not set.");
+                    ExitCode::from(exit::NOT_FOUND)
+                }
+            };
+        }
+        Some(("describe", sub)) => return describe(&config, sub.get_one::<String>("key")),
+        Some(("list", _)) => {
+            for (key, value) in config.iter() {
+                println!("{key}={value}");
+            }
+            return ExitCode::SUCCESS;
+        }
+        Some(("set", sub)) => {
+            let value = sub.get_one::<String>("value").expect("value is required");
+            let set = if sub.get_flag("custom") {
+                Config::set_custom
+            } else {
+                Config::set
+            };
+            set(&mut config, &key(sub), value).and_then(|_| config.save(source))
+        }
+        Some(("unset", sub)) => {
+            let key = key(sub);
+            if config.unset(&key).is_none() {
+                println!("{key} was not set.");
+                return ExitCode::SUCCESS;
+            }
+            config.save(source)
+        }
+        Some(("export", _)) => config.export(),
+        Some(("history", sub)) => return history(&config, sub.get_one::<String>("key")),
+        Some(("revert", sub)) => {
+            let id = *sub.get_one::<u64>("id").expect("id is required");
+            revert(&mut config, id, source)
+        }
+        _ => unreachable!("a subcommand is required"),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let unknown = matches!(e, survon_config::Error::UnknownKey { .. });
+            let code = failed(e.into(), "Could not update settings");
+            if unknown {
+                eprintln!("See `survon config describe` for the known settings, or pass --custom to store it anyway.");
+            }
+            code
+        }
+    }
+}
+
+fn describe(config: &Config, only: Option<&String>) -> ExitCode {
+    let keys: Vec<_> = match only {
+        Some(name) => match schema::require(name) {
+            Ok(key) => vec![key],
+            Err(e) => return failed(e.into(), "Cannot describe it"),
+        },
+        None => KEYS.iter().collect(),
+    };
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let effective = match (config.get(key.name), key.default_value()) {
+            (Some(value), _) => value.to_string(),
+            (None, Some(default)) => format!("{default} (default)"),
+            (None, None) => "(not set)".to_string(),
+        };
+        println!("{} = {effective}", key.name);
+        println!("  {}", key.description);
+        match key.default {
+            Some(default) => println!("  Type: {} (default: {default})", key.kind),
+            None => println!("  Type: {}", key.kind),
+        }
+        println!("  Read by: {}", key.read_by.join(", "));
+    }
+
+    let custom: Vec<_> = config
+        .iter()
+        .filter(|(name, _)| schema::key(name).is_none())
+        .collect();
+    if only.is_none() && !custom.is_empty() {
+        println!();
+        println!("Other settings (not in the schema):");
+        for (name, value) in custom {
+            println!("  {name} = {value}");
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn history(config: &Config, only: Option<&String>) -> ExitCode {
+    let entries = match config.history().entries() {
+        Ok(entries) => entries,
+        Err(e) => return failed(e.into(), "Could not read the settings history"),
+    };
+    let entries: Vec<&Entry> = entries
+        .iter()
+        .filter(|e| only.is_none_or(|key| &e.key == key))
+        .collect();
+    if entries.is_empty() {
+        println!("No settings changes recorded.");
+        return ExitCode::SUCCESS;
+    }
+    println!("{:>4}  {:<20}  {:<9}  CHANGE", "ID", "TIME", "SOURCE");
+    for entry in entries {
+        let mut change = format!(
+            "{}: {} -> {}",
+            entry.key,
+            shown(entry.old.as_ref()),
+            shown(entry.new.as_ref())
+        );
+        if let Some(id) = entry.reverts {
+            change.push_str(&format!(" (reverts #{id})"));
+        }
+        println!(
+            "{:>4}  {:<20}  {:<9}  {change}",
+            entry.id,
+            timestamp(entry.time),
+            entry.source.to_string()
+        );
+    }
+    ExitCode::SUCCESS
+}
+
+fn shown(value: Option<&survon_config::Value>) -> String {
+    value.map_or_else(|| "(unset)".to_string(), ToString::to_string)
+}
+
+fn revert(config: &mut Config, id: u64, source: Source) -> survon_config::Result<()> {
+    let entry = config.history().entry(id)?;
+    if config.get(&entry.key) != entry.new.as_ref() {
+        println!(
+            "Note: {} has changed since #{id}; it is now {}.",
+            entry.key,
+            shown(config.get(&entry.key))
+        );
+    }
+    config.revert(&entry)?;
+    config.save(source)?;
+    println!("{} is {} again.", entry.key, shown(entry.old.as_ref()));
+    Ok(())
+}
+
+fn run_runtime(matches: &ArgMatches) -> ExitCode {
+    match matches.subcommand() {
+        Some(("update", sub)) => {
+            let from = sub.get_one::<String>("from").expect("from has a default");
+            let check = sub.get_flag("check");
+            if !check {
+                println!("Updating the Survon runtime...");
+            }
+            match runtime::update(from, check) {
+                Ok(update) => {
+                    println!("{}", runtime::summary(&update));
+                    if let RuntimeUpdate::Available { .. } = update {
+                        return ExitCode::from(exit::UPDATE_AVAILABLE);
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Runtime update failed"),
+            }
+        }
+        Some(("launch", _)) => match load_config(matches) {
+            Ok(config) => done(runtime::launch(&config), "Could not launch the runtime"),
+            Err(code) => code,
+        },
+        Some(("version", sub)) => {
+            let store = Store::default();
+            let Some(current) = store.current() else {
+                eprintln!("No runtime installed under {}.", store.root().display());
+                return ExitCode::from(exit::NOT_FOUND);
+            };
+            if !sub.get_flag("all") {
+                println!("{current}");
+                return ExitCode::SUCCESS;
+            }
+            let previous = store.previous();
+            for version in store.installed().unwrap_or_default() {
+                let note = if version == current {
+                    "  (current)"
+                } else if Some(&version) == previous.as_ref() {
+                    "  (previous)"
+                } else {
+                    ""
+                };
+                println!("{version}{note}");
+            }
+            ExitCode::SUCCESS
+        }
+        _ => unreachable!("runtime requires a subcommand"),
+    }
+}
+
+fn run_module(matches: &ArgMatches) -> ExitCode {
+    let root = modules::dir();
+    let name = |sub: &ArgMatches| {
+        sub.get_one::<String>("name")
+            .expect("name is required")
+            .clone()
+    };
+    match matches.subcommand() {
+        Some(("list", sub)) => {
+            let list = match modules::list(&root) {
+                Ok(list) => list,
+                Err(e) => return failed(e, "Could not list modules"),
+            };
+            if sub.get_flag("json") {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&list).expect("modules serialize")
+                );
+                return ExitCode::SUCCESS;
+            }
+            if list.is_empty() {
+                println!("No modules installed under {}.", root.display());
+            }
+            for module in list {
+                let state = match (module.group, module.enabled) {
+                    (modules::CORE, _) => modules::CORE,
+                    (_, true) => modules::WASTELAND,
+                    (_, false) => "disabled",
+                };
+                println!(
+                    "{:<24}  {:<9}  {:<9}  {}",
+                    module.name,
+                    module.version.as_deref().unwrap_or("-"),
+                    state,
+                    module.description.as_deref().unwrap_or("")
+                );
+            }
+            ExitCode::SUCCESS
+        }
+        Some(("search", sub)) => {
+            let index = match load_index(sub) {
+                Ok(index) => index,
+                Err(code) => return code,
+            };
+            let term = sub.get_one::<String>("term").expect("term has a default");
+            let found = index.search(term);
+            if sub.get_flag("json") {
+                let entries: Vec<&index::Entry> = found.iter().map(|(_, entry)| *entry).collect();
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&entries).expect("entries serialize")
+                );
+                return ExitCode::SUCCESS;
+            }
+            if found.is_empty() && term.is_empty() {
+                println!("No modules are indexed.");
+                return ExitCode::SUCCESS;
+            }
+            if found.is_empty() {
+                println!("No indexed module matches `{term}`.");
+                return ExitCode::SUCCESS;
+            }
+            let installed = modules::list(&root).unwrap_or_default();
+            for (_, entry) in found {
+                let state = match installed.iter().find(|m| m.name == entry.name) {
+                    Some(module) => format!(
+                        "installed {}",
+                        module.version.as_deref().unwrap_or("unversioned")
+                    ),
+                    None => String::new(),
+                };
+                println!(
+                    "{:<24}  {:<9}  {:<20}  {}",
+                    entry.name,
+                    entry.version.to_string(),
+                    state,
+                    entry.description.as_deref().unwrap_or("")
+                );
+            }
+            ExitCode::SUCCESS
+        }
+        Some(("info", sub)) => {
+            let name = name(sub);
+            let index = match load_index(sub) {
+                Ok(index) => index,
+                Err(code) => return code,
+            };
+            let indexed = index.newest(&name);
+            match modules::find(&root, &name) {
+                Ok(module) => {
+                    print_module(&root, &module);
+                    if let Some((source, entry)) = indexed {
+                        println!("Indexed:      {} in {}", entry.version, source.location);
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(Error::NoSuchModule(_)) if indexed.is_some() => {
+                    let (source, entry) = indexed.expect("checked above");
+                    print_entry(source, entry);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Could not show the module"),
+            }
+        }
+        Some(("install", sub)) => {
+            let from = sub.get_one::<PathBuf>("path").expect("path is required");
+            let options = modules::InstallOptions {
+                replace: sub.get_flag("replace"),
+                downgrade: sub.get_flag("downgrade"),
+                force: sub.get_flag("force"),
+                ignore_missing: sub.get_flag("ignore-missing"),
+            };
+            if package::is_package(from) {
+                return report_installed(&root, package::install(&root, from, &options));
+            }
+            if !from.exists() && from.components().count() == 1 {
+                let index = match load_index(sub) {
+                    Ok(index) => index,
+                    Err(code) => return code,
+                };
+                let name = from.to_string_lossy();
+                let Some((source, entry)) = index.newest(&name) else {
+                    return failed(
+                        Error::NoSuchModule(name.into_owned()),
+                        "Could not install the module",
+                    );
+                };
+                return report_installed(&root, index::install(&root, source, entry, &options));
+            }
+            match modules::install(&root, from, &options) {
+                Ok(module) => {
+                    println!("Installed {} to {}.", module.name, module.path.display());
+                    warn_problems(&root, &module);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Could not install the module"),
+            }
+        }
+        Some(("upgrade", sub)) => run_module_upgrade(&root, sub),
+        Some(("index", sub)) => {
+            let dir = sub.get_one::<PathBuf>("dir").expect("dir is required");
+            let key = sub.get_one::<PathBuf>("key").expect("key is required");
+            let title = sub.get_one::<String>("name").map(String::as_str);
+            let password = std::env::var("SURVON_INDEX_PASSWORD").ok();
+            match index::build(dir, title, key, password) {
+                Ok(count) => {
+                    println!(
+                        "Indexed {count} packages in {}.",
+                        dir.join(index::INDEX_FILE).display()
+                    );
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Could not write the index"),
+            }
+        }
+        Some(("new", sub)) => {
+            let template = sub
+                .get_one::<String>("template")
+                .and_then(|t| authoring::Template::from_name(t))
+                .expect("clap checks the template");
+            let parent = sub.get_one::<PathBuf>("dir").expect("dir has a default");
+            match authoring::new(parent, &name(sub), template) {
+                Ok(dir) => {
+                    println!("Created the {template} module {}.", dir.display());
+                    println!(
+                        "Edit its config.yml, then check it with `survon module lint {}`.",
+                        dir.display()
+                    );
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Could not create the module"),
+            }
+        }
+        Some(("lint", sub)) => {
+            let dir = sub.get_one::<PathBuf>("dir").expect("dir has a default");
+            let findings = match authoring::lint(dir) {
+                Ok(findings) => findings,
+                Err(e) => return failed(e, "Could not check the module"),
+            };
+            for finding in &findings {
+                println!("{finding}");
+            }
+            let errors = findings
+                .iter()
+                .filter(|f| f.severity == authoring::Severity::Error)
+                .count();
+            let warnings = findings.len() - errors;
+            if findings.is_empty() {
+                println!("{} looks good.", dir.display());
+            } else {
+                println!("{errors} errors, {warnings} warnings.");
+            }
+            if errors > 0 {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Some(("pack", sub)) => {
+            let dir = sub.get_one::<PathBuf>("dir").expect("dir is required");
+            let out = sub.get_one::<PathBuf>("output");
+            match package::pack(dir, out.map(PathBuf::as_path)) {
+                Ok(path) => {
+                    println!("Packed {} into {}.", dir.display(), path.display());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Could not pack the module"),
+            }
+        }
+        Some(("remove", sub)) => match modules::remove(&root, &name(sub), sub.get_flag("force")) {
+            Ok(module) => {
+                println!("Removed {}.", module.name);
+                ExitCode::SUCCESS
+            }
+            Err(e) => failed(e, "Could not remove the module"),
+        },
+        Some(("enable", sub)) => match modules::enable(&root, &name(sub)) {
+            Ok(module) => {
+                println!("Enabled {}; restart the runtime to load it.", module.name);
+                warn_problems(&root, &module);
+                ExitCode::SUCCESS
+            }
+            Err(e) => failed(e, "Could not enable the module"),
+        },
+        Some(("disable", sub)) => {
+            match modules::disable(&root, &name(sub), sub.get_flag("force")) {
+                Ok(module) => {
+                    println!(
+                        "Disabled {}; it is kept in {}.",
+                        module.name,
+                        module.path.display()
+                    );
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Could not disable the module"),
+            }
+        }
+        _ => unreachable!("module requires a subcommand"),
+    }
+}
+
+fn report_installed(root: &Path, result: survon::Result<package::Installed>) -> ExitCode {
+    let installed = match result {
+        Ok(installed) => installed,
+        Err(e) => return failed(e, "Could not install the package"),
+    };
+    let module = &installed.module;
+    let version = module.version.as_deref().unwrap_or("");
+    match &installed.change {
+        package::Change::Installed => {
+            println!(
+                "Installed {} {version} to {}.",
+                module.name,
+                module.path.display()
+            );
+        }
+        package::Change::Upgraded(from) => {
+            println!("Upgraded {} from {from} to {version}.", module.name);
+        }
+        package::Change::Downgraded(from) => {
+            println!("Downgraded {} from {from} to {version}.", module.name);
+        }
+        package::Change::Reinstalled => println!("Reinstalled {} {version}.", module.name),
+        package::Change::Replaced => {
+            println!("Replaced {} with {version} from the package.", module.name);
+        }
+        package::Change::UpToDate => {
+            println!("{} {version} is already installed.", module.name);
+        }
+    }
+    if let Some(backup) = &installed.backup {
+        println!(
+            "The old module, with its local changes, is in {}.",
+            backup.display()
+        );
+    }
+    warn_problems(root, module);
+    ExitCode::SUCCESS
+}
+
+fn run_module_upgrade(root: &Path, matches: &ArgMatches) -> ExitCode {
+    let index = match load_index(matches) {
+        Ok(index) => index,
+        Err(code) => return code,
+    };
+    let installed = match modules::list(root) {
+        Ok(list) => list,
+        Err(e) => return failed(e, "Could not list modules"),
+    };
+    let names: Vec<&String> = matches
+        .get_many::<String>("names")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    for name in &names {
+        if !installed.iter().any(|m| &&m.name == name) {
+            return failed(
+                Error::NoSuchModule(name.to_string()),
+                "Could not upgrade the module",
+            );
+        }
+    }
+    let upgrades: Vec<_> = index
+        .upgrades(&installed)
+        .into_iter()
+        .filter(|(module, ..)| names.is_empty() || names.contains(&&module.name))
+        .collect();
+    if upgrades.is_empty() {
+        println!("Every module is up to date with the indexes.");
+        return ExitCode::SUCCESS;
+    }
+    if matches.get_flag("check") {
+        for (module, _, entry) in &upgrades {
+            println!(
+                "{}: {} -> {}",
+                module.name,
+                module.version.as_deref().unwrap_or("unversioned"),
+                entry.version
+            );
+        }
+        return ExitCode::from(exit::UPDATE_AVAILABLE);
+    }
+    let options = modules::InstallOptions {
+        replace: matches.get_flag("replace"),
+        downgrade: false,
+        force: matches.get_flag("force"),
+        ignore_missing: matches.get_flag("ignore-missing"),
+    };
+    let mut code = ExitCode::SUCCESS;
+    for (_, source, entry) in upgrades {
+        let result = index::install(root, source, entry, &options);
+        if result.is_err() {
+            eprint!("{}: ", entry.name);
+        }
+        let reported = report_installed(root, result);
+        if reported != ExitCode::SUCCESS {
+            code = reported;
+        }
+    }
+    code
+}
+
+/// The module indexes, warning about those that could not be read.
+fn load_index(matches: &ArgMatches) -> Result<index::Index, ExitCode> {
+    let config = load_config(matches)?;
+    let index = index::Index::load(&config);
+    for (location, e) in &index.failed {
+        eprintln!("Warning: skipped the module index at {location}: {e}");
+    }
+    Ok(index)
+}
+
+fn print_entry(source: &index::Source, entry: &index::Entry) {
+    println!("Name:         {}", entry.name);
+    println!("Version:      {}", entry.version);
+    println!(
+        "Description:  {}",
+        entry.description.as_deref().unwrap_or("(not given)")
+    );
+    println!("State:        not installed");
+    if !entry.tags.is_empty() {
+        println!("Tags:         {}", entry.tags.join(", "));
+    }
+    println!("Index:        {}", source.location);
+    println!("Package:      {}", source.package_location(entry));
+}
+
+fn print_module(root: &Path, module: &modules::Module) {
+    println!("Name:         {}", module.name);
+    println!(
+        "Version:      {}",
+        module.version.as_deref().unwrap_or("(not given)")
+    );
+    println!(
+        "Description:  {}",
+        module.description.as_deref().unwrap_or("(not given)")
+    );
+    if !module.tags.is_empty() {
+        println!("Tags:         {}", module.tags.join(", "));
+    }
+    let state = if module.group == modules::CORE {
+        "bundled with the runtime"
+    } else if module.enabled {
+        "enabled"
+    } else {
+        "disabled"
+    };
+    println!("State:        {state}");
+    println!("Path:         {}", module.path.display());
+    if let Some(runtime) = &module.runtime {
+        println!("Runtime:      {runtime}");
+    }
+    match package::modified(module) {
+        None if module.group == modules::CORE => {}
+        None => println!("Package:      none (installed from a directory or archive)"),
+        Some(files) if files.is_empty() => println!("Package:      installed, unmodified"),
+        Some(files) => println!("Package:      installed, modified: {}", files.join(", ")),
+    }
+    let dependencies: Vec<String> = module
+        .dependencies
+        .iter()
+        .map(|dep| match &dep.version {
+            Some(version) => format!("{} {version}", dep.name),
+            None => dep.name.clone(),
+        })
+        .collect();
+    let assets: Vec<&str> = module.assets.iter().map(|a| a.path.as_str()).collect();
+    for (label, items) in [
+        ("Dependencies:", dependencies.join(", ")),
+        ("Assets:", assets.join(", ")),
+        ("Capabilities:", module.capabilities.join(", ")),
+    ] {
+        let items = if items.is_empty() { "none" } else { &items };
+        println!("{label:<13} {items}");
+    }
+    match requirements::check(root, module) {
+        Ok(problems) if problems.is_empty() => println!("Requirements: all met"),
+        Ok(problems) => {
+            println!("Requirements:");
+            for problem in problems {
+                println!("  - {problem}");
+            }
+        }
+        Err(e) => println!("Requirements: {e}"),
+    }
+}
+
+/// Warns about what `module` needs that is not there.
+fn warn_problems(root: &Path, module: &modules::Module) {
+    for problem in requirements::check(root, module).unwrap_or_default() {
+        eprintln!("Warning: {} {problem}.", module.name);
+    }
+}
+
+fn run_model(matches: &ArgMatches) -> ExitCode {
+    let mut config = match load_config(matches) {
+        Ok(config) => config,
+        Err(code) => return code,
+    };
+    let target = |sub: &ArgMatches| {
+        sub.get_one::<String>("model")
+            .expect("model is required")
+            .clone()
+    };
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            let list = match model::list(&config) {
+                Ok(list) => list,
+                Err(e) => return failed(e, "Could not list models"),
+            };
+            let ram = models::total_ram_mb();
+            println!("  NAME            QUANT       SIZE  CONTEXT      RAM  STATE");
+            for entry in &list {
+                let marker = if entry.in_use { "* " } else { "  " };
+                let size_mb = match (&entry.installed, entry.known) {
+                    (Some(installed), _) => installed.size / (1024 * 1024),
+                    (None, Some(known)) => known.size_mb,
+                    (None, None) => 0,
+                };
+                let state = match (&entry.installed, entry.known) {
+                    (Some(_), _) if entry.in_use => "in use",
+                    (Some(_), _) => "installed",
+                    (None, Some(known)) if known.min_ram_mb > ram => "too big for this unit",
+                    (None, _) => "",
+                };
+                let (quant, context, min_ram) = match entry.known {
+                    Some(known) => (
+                        known.quantization.to_string(),
+                        known.context.to_string(),
+                        format!("{}MB", known.min_ram_mb),
+                    ),
+                    None => ("-".to_string(), "-".to_string(), "-".to_string()),
+                };
+                println!(
+                    "{marker}{:<14}  {:<7}  {:>7}  {:>7}  {:>7}  {state}",
+                    entry.name(),
+                    quant,
+                    format!("{size_mb}MB"),
+                    context,
+                    min_ram
+                );
+            }
+            if model::in_use(&config).is_none() {
+                println!("survon_llm is in search-only mode.");
+            }
+            ExitCode::SUCCESS
+        }
+        Some(("install", sub)) => {
+            let target = target(sub);
+            let request = model::Request {
+                target: &target,
+                file: sub.get_one::<String>("name").map(String::as_str),
+                sha256: sub.get_one::<String>("sha256").map(String::as_str),
+                force: sub.get_flag("force"),
+            };
+            let path = match model::install(&request) {
+                Ok(path) => path,
+                Err(e) => return failed(e, "Could not install the model"),
+            };
+            println!("Installed {}.", path.display());
+            let file = path
+                .file_name()
+                .map(|f| f.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if !sub.get_flag("use") {
+                println!("Switch to it with `survon model use {file}`.");
+                return ExitCode::SUCCESS;
+            }
+            match model::use_model(&mut config, Some(&file), Source::Cli) {
+                Ok(()) => {
+                    println!(
+                        "The runtime now summarizes with {file}. Restart it to load the model."
+                    );
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Could not switch to the model"),
+            }
+        }
+        Some(("remove", sub)) => {
+            match model::remove(&config, &target(sub), sub.get_flag("force")) {
+                Ok(path) => {
+                    println!("Removed {}.", path.display());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Could not remove the model"),
+            }
+        }
+        Some(("use", sub)) => {
+            let target = target(sub);
+            let name = (target != "none").then_some(target.as_str());
+            match model::use_model(&mut config, name, Source::Cli) {
+                Ok(()) => {
+                    match name {
+                        Some(name) => println!("The runtime now summarizes with {name}."),
+                        None => println!("survon_llm is now in search-only mode."),
+                    }
+                    println!("Restart the runtime for the change to take effect.");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => failed(e, "Could not switch models"),
+            }
+        }
+        _ => unreachable!("model requires a subcommand"),
+    }
+}
+
+fn run_council(matches: &ArgMatches) -> ExitCode {
+    let mut config = match load_config(matches) {
+        Ok(config) => config,
+        Err(code) => return code,
+    };
+    match matches.subcommand() {
+        Some(("install", sub)) => {
+            let strategy = sub
+                .get_one::<String>("strategy")
+                .cloned()
+                .unwrap_or_else(|| council::strategy(&config));
+            println!("Installing the council seat ({strategy})...");
+            done(
+                council::install(&mut config, &strategy, Source::Cli),
+                "Could not install the council seat",
+            )
+        }
+        Some(("strategy", sub)) => match sub.get_one::<String>("name") {
+            Some(name) => done(
+                council::set_strategy(&mut config, name, Source::Cli),
+                "Could not change the strategy",
+            ),
+            None => {
+                println!("{}", council::strategy(&config));
+                ExitCode::SUCCESS
+            }
+        },
+        Some(("launch", _)) => done(
+            council::launch(&config),
+            "Could not launch the council seat",
+        ),
+        _ => unreachable!("council requires a subcommand"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<ArgMatches, clap::Error> {
+        cli().try_get_matches_from(std::iter::once("survon").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn the_command_line_is_consistent() {
+        cli().debug_assert();
+    }
+
+    #[test]
+    fn subcommands_parse_their_arguments() {
+        let matches = parse(&["runtime", "update", "--check"]).unwrap();
+        let (_, update) = matches.subcommand().unwrap().1.subcommand().unwrap();
+        assert!(update.get_flag("check"));
+        assert_eq!(
+            update.get_one::<String>("from").map(String::as_str),
+            Some(RUNTIME_RELEASES_URL)
+        );
+
+        let matches = parse(&["module", "install", "./weather", "--replace"]).unwrap();
+        let (_, install) = matches.subcommand().unwrap().1.subcommand().unwrap();
+        assert_eq!(
+            install.get_one::<PathBuf>("path"),
+            Some(&PathBuf::from("./weather"))
+        );
+        assert!(install.get_flag("replace"));
+
+        let matches = parse(&["install", "--provision", "unit.toml", "-y"]).unwrap();
+        let args: Vec<&String> = matches
+            .subcommand_matches("install")
+            .unwrap()
+            .get_many("args")
+            .unwrap()
+            .collect();
+        assert_eq!(args, ["--provision", "unit.toml", "-y"]);
+    }
+
+    #[test]
+    fn bad_arguments_are_usage_errors() {
+        for args in [
+            &["council", "strategy", "nonsense"][..],
+            &["runtime"],
+            &["module", "remove"],
+        ] {
+            let error = parse(args).unwrap_err();
+            assert_eq!(error.exit_code(), i32::from(exit::USAGE), "{args:?}");
+        }
+    }
+}
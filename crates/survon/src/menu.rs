@@ -0,0 +1,711 @@
+//! The interactive launcher shown on the Pi's console.
+//!
+//! Everything is reachable with the arrow keys, Enter and Esc (or the
+//! number shown next to an entry), so no mouse or SSH session is needed.
+//! Settings, the council strategy and modules are managed in place.
+//! Everything else leaves the TUI, runs in the plain terminal and comes
+//! back to the menu. Each entry calls the same library function as the
+//! matching `survon` subcommand.
+
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use survon::modules::{self, Module};
+use survon::status::{human_size, Ble, Status};
+use survon::supervisor::State;
+use survon::{actions, council, runtime};
+use survon_config::schema::{Key, Kind, COUNCIL_STRATEGIES, KEYS};
+use survon_config::{Config, Source};
+use survon_installer::log::timestamp;
+use survon_update::runtime::RUNTIME_RELEASES_URL;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    Reinstall,
+    Settings,
+    UpdateRuntime,
+    LaunchRuntime,
+    Modules,
+    InstallCouncilSeat,
+    CouncilStrategy,
+    LaunchCouncilSeat,
+    ImportBundle,
+    Exit,
+}
+
+const ENTRIES: [(Entry, &str); 10] = [
+    (Entry::Reinstall, "Re-install latest Survon OS"),
+    (Entry::Settings, "Settings"),
+    (Entry::UpdateRuntime, "Update Survon runtime"),
+    (Entry::LaunchRuntime, "Launch Survon runtime"),
+    (Entry::Modules, "Wasteland modules"),
+    (Entry::InstallCouncilSeat, "Council seat: install"),
+    (Entry::CouncilStrategy, "Council seat: strategy"),
+    (Entry::LaunchCouncilSeat, "Council seat: launch"),
+    (Entry::ImportBundle, "Import update bundle (USB)"),
+    (Entry::Exit, "Exit"),
+];
+
+/// Work that runs outside the TUI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Reinstall,
+    UpdateRuntime,
+    LaunchRuntime,
+    InstallCouncilSeat(String),
+    LaunchCouncilSeat,
+    ImportBundle,
+}
+
+enum Screen {
+    Main,
+    Settings {
+        list: ListState,
+        edit: Option<Edit>,
+    },
+    Strategy {
+        list: ListState,
+        install: bool,
+    },
+    Modules {
+        list: ListState,
+        modules: Vec<Module>,
+        /// Waiting for `y` to remove the selected module.
+        confirm: bool,
+    },
+}
+
+/// A setting being edited.
+struct Edit {
+    key: &'static Key,
+    input: String,
+}
+
+enum Flow {
+    Continue,
+    Run(Action),
+    Quit,
+}
+
+struct App {
+    config_path: PathBuf,
+    config: Config,
+    status: Status,
+    screen: Screen,
+    main: ListState,
+    /// Feedback from the last thing done, and whether it was an error.
+    message: Option<(String, bool)>,
+}
+
+/// Shows the menu until the user picks Exit.
+pub fn run(config_path: &Path) -> io::Result<()> {
+    let config = Config::load(config_path).map_err(io::Error::other)?;
+    let mut app = App {
+        config_path: config_path.to_path_buf(),
+        status: Status::gather(&config),
+        config,
+        screen: Screen::Main,
+        main: ListState::default().with_selected(Some(0)),
+        message: None,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| app.draw(frame)) {
+            break Err(e);
+        }
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(_) => continue,
+            Err(e) => break Err(e),
+        };
+        match app.handle(key) {
+            Flow::Continue => {}
+            Flow::Quit => break Ok(()),
+            Flow::Run(action) => {
+                ratatui::restore();
+                app.run(action);
+                terminal = ratatui::init();
+                terminal.clear()?;
+            }
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn handle(&mut self, key: KeyEvent) -> Flow {
+        match &mut self.screen {
+            Screen::Main => self.handle_main(key),
+            Screen::Settings { .. } => self.handle_settings(key),
+            Screen::Strategy { .. } => self.handle_strategy(key),
+            Screen::Modules { .. } => self.handle_modules(key),
+        }
+    }
+
+    fn handle_main(&mut self, key: KeyEvent) -> Flow {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Flow::Quit,
+            KeyCode::Char(c @ '0'..='9') => {
+                let index = (c as usize - '0' as usize + 9) % 10;
+                self.main.select(Some(index));
+                return self.activate(ENTRIES[index].0);
+            }
+            KeyCode::Enter => {
+                let index = self.main.selected().unwrap_or(0);
+                return self.activate(ENTRIES[index].0);
+            }
+            code => navigate(&mut self.main, code, ENTRIES.len()),
+        }
+        Flow::Continue
+    }
+
+    fn activate(&mut self, entry: Entry) -> Flow {
+        self.message = None;
+        match entry {
+            Entry::Reinstall => Flow::Run(Action::Reinstall),
+            Entry::Settings => {
+                self.screen = Screen::Settings {
+                    list: ListState::default().with_selected(Some(0)),
+                    edit: None,
+                };
+                Flow::Continue
+            }
+            Entry::UpdateRuntime => Flow::Run(Action::UpdateRuntime),
+            Entry::LaunchRuntime => Flow::Run(Action::LaunchRuntime),
+            Entry::Modules => {
+                self.screen = Screen::Modules {
+                    list: ListState::default().with_selected(Some(0)),
+                    modules: self.modules(),
+                    confirm: false,
+                };
+                Flow::Continue
+            }
+            Entry::InstallCouncilSeat => self.pick_strategy(true),
+            Entry::CouncilStrategy => self.pick_strategy(false),
+            Entry::LaunchCouncilSeat if self.status.council.is_none() => {
+                self.error("The council seat is not installed; install it first.");
+                Flow::Continue
+            }
+            Entry::LaunchCouncilSeat => Flow::Run(Action::LaunchCouncilSeat),
+            Entry::ImportBundle => Flow::Run(Action::ImportBundle),
+            Entry::Exit => Flow::Quit,
+        }
+    }
+
+    fn pick_strategy(&mut self, install: bool) -> Flow {
+        let current = council::strategy(&self.config);
+        let index = COUNCIL_STRATEGIES
+            .iter()
+            .position(|s| *s == current)
+            .unwrap_or(0);
+        self.screen = Screen::Strategy {
+            list: ListState::default().with_selected(Some(index)),
+            install,
+        };
+        Flow::Continue
+    }
+
+    fn handle_strategy(&mut self, key: KeyEvent) -> Flow {
+        let Screen::Strategy { list, install } = &mut self.screen else {
+            unreachable!("called on the strategy screen");
+        };
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.screen = Screen::Main,
+            KeyCode::Enter => {
+                let strategy = COUNCIL_STRATEGIES[list.selected().unwrap_or(0)];
+                let install = *install;
+                self.screen = Screen::Main;
+                if install {
+                    return Flow::Run(Action::InstallCouncilSeat(strategy.to_string()));
+                }
+                match council::set_strategy(&mut self.config, strategy, Source::Menu) {
+                    Ok(()) => self.info(format!("COUNCIL_STRATEGY set to {strategy}.")),
+                    Err(e) => {
+                        self.reload();
+                        self.error(e.to_string());
+                    }
+                }
+            }
+            code => navigate(list, code, COUNCIL_STRATEGIES.len()),
+        }
+        Flow::Continue
+    }
+
+    fn modules(&mut self) -> Vec<Module> {
+        modules::list(&modules::dir()).unwrap_or_else(|e| {
+            self.error(e.to_string());
+            Vec::new()
+        })
+    }
+
+    fn handle_modules(&mut self, key: KeyEvent) -> Flow {
+        let Screen::Modules {
+            list,
+            modules,
+            confirm,
+        } = &mut self.screen
+        else {
+            unreachable!("called on the modules screen");
+        };
+        let selected = list.selected().and_then(|i| modules.get(i)).cloned();
+        if std::mem::take(confirm) {
+            match (key.code, selected) {
+                (KeyCode::Char('y'), Some(module)) => {
+                    match modules::remove(&modules::dir(), &module.name, false) {
+                        Ok(_) => self.info(format!("Removed {}.", module.name)),
+                        Err(e) => self.error(e.to_string()),
+                    }
+                    let fresh = self.modules();
+                    if let Screen::Modules { list, modules, .. } = &mut self.screen {
+                        let last = fresh.len().saturating_sub(1);
+                        list.select(Some(list.selected().unwrap_or(0).min(last)));
+                        *modules = fresh;
+                    }
+                }
+                _ => self.message = None,
+            }
+            return Flow::Continue;
+        }
+
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.screen = Screen::Main,
+            KeyCode::Char('e') => match selected {
+                Some(module) if module.group == modules::WASTELAND => {
+                    let (result, done) = if module.enabled {
+                        (
+                            modules::disable(&modules::dir(), &module.name, false),
+                            "Disabled",
+                        )
+                    } else {
+                        (modules::enable(&modules::dir(), &module.name), "Enabled")
+                    };
+                    match result {
+                        Ok(_) => self.info(format!(
+                            "{done} {}; restart the runtime for it to take effect.",
+                            module.name
+                        )),
+                        Err(e) => self.error(e.to_string()),
+                    }
+                    let fresh = self.modules();
+                    if let Screen::Modules { modules, .. } = &mut self.screen {
+                        *modules = fresh;
+                    }
+                }
+                Some(module) => self.error(format!(
+                    "{} is bundled with the runtime and cannot be disabled.",
+                    module.name
+                )),
+                None => {}
+            },
+            KeyCode::Delete | KeyCode::Char('d') => match selected {
+                Some(module) if module.group == modules::WASTELAND => {
+                    *confirm = true;
+                    self.error(format!("Remove {}? Press y to confirm.", module.name));
+                }
+                Some(module) => self.error(format!(
+                    "{} is bundled with the runtime and cannot be removed.",
+                    module.name
+                )),
+                None => {}
+            },
+            code if !modules.is_empty() => navigate(list, code, modules.len()),
+            _ => {}
+        }
+        Flow::Continue
+    }
+
+    fn handle_settings(&mut self, key: KeyEvent) -> Flow {
+        let Screen::Settings { list, edit } = &mut self.screen else {
+            unreachable!("called on the settings screen");
+        };
+        let selected = &KEYS[list.selected().unwrap_or(0)];
+
+        if let Some(editing) = edit {
+            match key.code {
+                KeyCode::Esc => *edit = None,
+                KeyCode::Enter => {
+                    let (name, input) = (editing.key.name, editing.input.clone());
+                    *edit = None;
+                    if input.is_empty() {
+                        self.unset(name);
+                    } else {
+                        self.set(name, &input);
+                    }
+                }
+                KeyCode::Left | KeyCode::Right => {
+                    if let Some(choices) = choices(editing.key.kind) {
+                        let at = choices.iter().position(|c| *c == editing.input);
+                        let next = match (at, key.code) {
+                            (None, _) => 0,
+                            (Some(i), KeyCode::Left) => (i + choices.len() - 1) % choices.len(),
+                            (Some(i), _) => (i + 1) % choices.len(),
+                        };
+                        editing.input = choices[next].to_string();
+                    }
+                }
+                KeyCode::Backspace => {
+                    editing.input.pop();
+                }
+                KeyCode::Char(c) if choices(editing.key.kind).is_none() => editing.input.push(c),
+                _ => {}
+            }
+            return Flow::Continue;
+        }
+
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.screen = Screen::Main,
+            KeyCode::Enter => {
+                let input = self
+                    .config
+                    .effective(selected.name)
+                    .map_or_else(String::new, |v| v.to_string());
+                *edit = Some(Edit {
+                    key: selected,
+                    input,
+                });
+                self.message = None;
+            }
+            KeyCode::Delete | KeyCode::Char('d') => self.unset(selected.name),
+            code => navigate(list, code, KEYS.len()),
+        }
+        Flow::Continue
+    }
+
+    fn set(&mut self, name: &str, raw: &str) {
+        let result = self
+            .config
+            .set(name, raw)
+            .and_then(|_| self.config.save(Source::Menu));
+        match result {
+            Ok(()) => self.info(format!("{name} set to {raw}.")),
+            Err(e) => {
+                self.reload();
+                self.error(e.to_string());
+            }
+        }
+    }
+
+    fn unset(&mut self, name: &str) {
+        if self.config.unset(name).is_none() {
+            self.info(format!("{name} is not set."));
+            return;
+        }
+        match self.config.save(Source::Menu) {
+            Ok(()) => self.info(format!("{name} removed; its default applies.")),
+            Err(e) => {
+                self.reload();
+                self.error(e.to_string());
+            }
+        }
+    }
+
+    fn info(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), false));
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), true));
+    }
+
+    /// Re-reads the settings and the status, which an action or a failed
+    /// save may have left out of date.
+    fn reload(&mut self) {
+        match Config::load(&self.config_path) {
+            Ok(config) => self.config = config,
+            Err(e) => self.error(e.to_string()),
+        }
+        self.status = Status::gather(&self.config);
+    }
+
+    /// Runs `action` in the plain terminal. Waits for Enter afterwards
+    /// unless a launched program simply exited, so its output can be read.
+    fn run(&mut self, action: Action) {
+        println!();
+        let result = match &action {
+            Action::Reinstall => actions::install(&[]),
+            Action::UpdateRuntime => {
+                println!("Updating the Survon runtime...");
+                runtime::update(RUNTIME_RELEASES_URL, false)
+                    .map(|update| println!("{}", runtime::summary(&update)))
+            }
+            Action::LaunchRuntime => runtime::launch(&self.config),
+            Action::InstallCouncilSeat(strategy) => {
+                council::install(&mut self.config, strategy, Source::Menu)
+            }
+            Action::LaunchCouncilSeat => council::launch(&self.config),
+            Action::ImportBundle => actions::import_bundle(None),
+        };
+        let launched = matches!(action, Action::LaunchRuntime | Action::LaunchCouncilSeat);
+        match &result {
+            Ok(()) if launched => {}
+            Ok(()) => {
+                println!("\nDone.");
+                pause();
+            }
+            Err(e) => {
+                println!("\nFailed: {e}");
+                pause();
+            }
+        }
+        self.reload();
+        if let Err(e) = result {
+            self.error(format!("Last action failed: {e}"));
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let status = status_lines(&self.status);
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(status.len() as u16 + 2),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Paragraph::new(status).block(Block::bordered().title(" Survon OS ".bold())),
+            header,
+        );
+
+        let help = match &self.screen {
+            Screen::Main => "↑/↓ move · Enter select · 1-9, 0 jump · q quit",
+            Screen::Settings { edit: Some(_), .. } => {
+                "type or ←/→ choose · Enter save (empty removes) · Esc cancel"
+            }
+            Screen::Settings { .. } => "↑/↓ move · Enter edit · d remove · Esc back",
+            Screen::Strategy { .. } => "↑/↓ move · Enter choose · Esc back",
+            Screen::Modules { .. } => "↑/↓ move · e enable/disable · d remove · Esc back",
+        };
+        let footer_line = match &self.message {
+            Some((message, true)) => Line::from(message.as_str().red()),
+            Some((message, false)) => Line::from(message.as_str().green()),
+            None => Line::from(help.dark_gray()),
+        };
+        frame.render_widget(Paragraph::new(footer_line), footer);
+
+        let highlight = Style::new().reversed();
+        match &mut self.screen {
+            Screen::Main => {
+                let items = ENTRIES
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (_, label))| ListItem::new(format!("{}  {label}", (i + 1) % 10)));
+                let list = List::new(items)
+                    .block(Block::bordered().title(" Menu "))
+                    .highlight_style(highlight)
+                    .highlight_symbol("> ");
+                frame.render_stateful_widget(list, body, &mut self.main);
+            }
+            Screen::Strategy { list, install } => {
+                let title = if *install {
+                    " Install council seat with strategy "
+                } else {
+                    " Council strategy "
+                };
+                let current = self.config.effective("COUNCIL_STRATEGY");
+                let items = COUNCIL_STRATEGIES.iter().map(|s| {
+                    let marker = if current.as_ref().is_some_and(|c| c.to_string() == *s) {
+                        " (current)"
+                    } else {
+                        ""
+                    };
+                    ListItem::new(format!("{s}{marker}"))
+                });
+                let widget = List::new(items)
+                    .block(Block::bordered().title(title))
+                    .highlight_style(highlight)
+                    .highlight_symbol("> ");
+                frame.render_stateful_widget(widget, body, list);
+            }
+            Screen::Modules { list, modules, .. } => {
+                let block = Block::bordered().title(" Modules ");
+                if modules.is_empty() {
+                    frame.render_widget(
+                        Paragraph::new(vec![
+                            Line::from("No modules installed."),
+                            Line::from("Install one with `survon module install DIR`.".dark_gray()),
+                        ])
+                        .block(block),
+                        body,
+                    );
+                    return;
+                }
+                let items = modules.iter().map(|module| {
+                    let version = module.version.as_deref().unwrap_or("");
+                    let line = Line::from(vec![
+                        Span::raw(format!("{:<24}", module.name)),
+                        Span::raw(format!("{version:<10}")),
+                        format!("{:<11}", module.group).dark_gray(),
+                        Span::raw(module.description.clone().unwrap_or_default()),
+                    ]);
+                    if module.enabled {
+                        ListItem::new(line)
+                    } else {
+                        ListItem::new(line.dark_gray().crossed_out())
+                    }
+                });
+                let widget = List::new(items)
+                    .block(block)
+                    .highlight_style(highlight)
+                    .highlight_symbol("> ");
+                frame.render_stateful_widget(widget, body, list);
+            }
+            Screen::Settings { list, edit } => {
+                let [keys, detail] =
+                    Layout::vertical([Constraint::Min(0), Constraint::Length(5)]).areas(body);
+                let items = KEYS.iter().map(|key| {
+                    let value = match (self.config.get(key.name), key.default_value()) {
+                        (Some(value), _) => Span::raw(value.to_string()),
+                        (None, Some(default)) => format!("{default} (default)").dark_gray(),
+                        (None, None) => "(not set)".dark_gray(),
+                    };
+                    ListItem::new(Line::from(vec![
+                        Span::raw(format!("{:<18}", key.name)),
+                        value,
+                    ]))
+                });
+                let widget = List::new(items)
+                    .block(Block::bordered().title(" Settings "))
+                    .highlight_style(highlight)
+                    .highlight_symbol("> ");
+                frame.render_stateful_widget(widget, keys, list);
+
+                let key = KEYS[list.selected().unwrap_or(0)];
+                frame.render_widget(
+                    Paragraph::new(vec![
+                        Line::from(key.description),
+                        Line::from(format!("Type: {}", key.kind)),
+                        Line::from(format!("Read by: {}", key.read_by.join(", "))),
+                    ])
+                    .block(Block::bordered().title(format!(" {} ", key.name))),
+                    detail,
+                );
+
+                if let Some(edit) = edit {
+                    let area = popup(body, 60, 3);
+                    frame.render_widget(Clear, area);
+                    frame.render_widget(
+                        Paragraph::new(format!("{}_", edit.input))
+                            .block(Block::bordered().title(format!(" {} ", edit.key.name))),
+                        area,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn status_lines(status: &Status) -> Vec<Line<'static>> {
+    let runtime = match &status.runtime {
+        Some(version) => version.to_string().green(),
+        None => "not installed".red(),
+    };
+    let model = match &status.model {
+        Some(name) => name.clone().green(),
+        None => "none (search-only)".yellow(),
+    };
+    let council = match &status.council {
+        Some(strategy) => strategy.clone().green(),
+        None => "not installed".dark_gray(),
+    };
+    let ble = match &status.ble {
+        Ble::Available(adapter) => adapter.clone().green(),
+        Ble::Blocked(adapter) => format!("{adapter} blocked").yellow(),
+        Ble::NoAdapter => "no adapter".red(),
+    };
+    let disk = match status.disk {
+        Some((free, total)) if free < total / 10 => {
+            format!("{} free of {}", human_size(free), human_size(total)).red()
+        }
+        Some((free, total)) => Span::raw(format!(
+            "{} free of {}",
+            human_size(free),
+            human_size(total)
+        )),
+        None => "unknown".dark_gray(),
+    };
+    let mut lines = vec![
+        Line::from(vec![
+            "Runtime: ".bold(),
+            runtime,
+            "   Model: ".bold(),
+            model,
+            "   Council seat: ".bold(),
+            council,
+        ]),
+        Line::from(vec!["BLE: ".bold(), ble, "   Disk: ".bold(), disk]),
+    ];
+    // Only a supervisor at work, or one that gave up, is worth the room.
+    for supervised in &status.supervised {
+        let shown = match supervised.state {
+            _ if !supervised.is_live() => None,
+            State::Running { .. } | State::Restarting { .. } => Some(supervised.describe().green()),
+            State::GaveUp { .. } => Some(supervised.describe().red()),
+            State::Stopped { .. } => None,
+        };
+        if let Some(state) = shown {
+            lines.push(Line::from(vec![
+                format!("Supervised {}: ", supervised.program).bold(),
+                state,
+            ]));
+        }
+    }
+    if let Some(crash) = &status.last_crash {
+        lines.push(Line::from(vec![
+            "Last crash: ".bold(),
+            format!(
+                "{} {} at {} (survon crashes)",
+                crash.program,
+                crash.describe(),
+                timestamp(crash.time)
+            )
+            .yellow(),
+        ]));
+    }
+    lines
+}
+
+/// The values ←/→ cycle through for `kind`, if it has a fixed set.
+fn choices(kind: Kind) -> Option<&'static [&'static str]> {
+    match kind {
+        Kind::Enum(values) => Some(values),
+        Kind::Bool => Some(&["true", "false"]),
+        Kind::Text | Kind::Path | Kind::Number { .. } => None,
+    }
+}
+
+pub(crate) fn navigate(list: &mut ListState, code: KeyCode, len: usize) {
+    let selected = list.selected().unwrap_or(0);
+    let next = match code {
+        KeyCode::Up | KeyCode::Char('k') => (selected + len - 1) % len,
+        KeyCode::Down | KeyCode::Char('j') | KeyCode::Tab => (selected + 1) % len,
+        KeyCode::Home => 0,
+        KeyCode::End => len - 1,
+        _ => return,
+    };
+    list.select(Some(next));
+}
+
+fn popup(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Percentage(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
+
+fn pause() {
+    print!("Press Enter to return to the menu...");
+    let _ = io::stdout().flush();
+    let _ = io::stdin().lock().read_line(&mut String::new());
+}
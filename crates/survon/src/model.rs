@@ -0,0 +1,160 @@
+//! The GGUF model survon_llm summarizes with: listing, installing,
+//! removing and choosing models. The catalog and the files are the
+//! installer's ([`survon_installer::models`]); this keeps `LLM_MODEL_NAME`,
+//! `LLM_MODEL_PATH` and survon_llm's mode in step with them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use survon_config::{Config, Source};
+use survon_installer::models::{self, Installed, Known, LlmMode};
+use survon_installer::system::Mode;
+use survon_installer::Context;
+
+use crate::actions;
+use crate::error::{Error, Result};
+
+/// A model in the catalog or the model directory, or both.
+#[derive(Debug, Clone)]
+pub struct Model {
+    pub file: String,
+    pub known: Option<&'static Known>,
+    pub installed: Option<Installed>,
+    /// Whether the runtime loads it: it is `LLM_MODEL_PATH` and survon_llm
+    /// is in summarizer mode.
+    pub in_use: bool,
+}
+
+impl Model {
+    /// The catalog name, or the file name of a model not in the catalog.
+    pub fn name(&self) -> &str {
+        self.known.map_or(&self.file, |known| known.name)
+    }
+}
+
+/// The catalog followed by the other models in the model directory.
+pub fn list(config: &Config) -> Result<Vec<Model>> {
+    let installed = models::installed(&actions::home())?;
+    let current = in_use(config);
+    let is_current = |path: &Path| current.as_deref() == Some(path);
+    let mut list: Vec<Model> = models::CATALOG
+        .iter()
+        .map(|known| {
+            let installed = installed.iter().find(|m| m.file == known.file).cloned();
+            Model {
+                file: known.file.to_string(),
+                known: Some(known),
+                in_use: installed.as_ref().is_some_and(|m| is_current(&m.path)),
+                installed,
+            }
+        })
+        .collect();
+    list.extend(
+        installed
+            .into_iter()
+            .filter(|m| m.known.is_none())
+            .map(|m| Model {
+                file: m.file.clone(),
+                known: None,
+                in_use: is_current(&m.path),
+                installed: Some(m),
+            }),
+    );
+    Ok(list)
+}
+
+/// The model file the runtime loads, if it loads one.
+pub fn in_use(config: &Config) -> Option<PathBuf> {
+    if models::llm_mode(&actions::home().join("modules")) != Some(LlmMode::Summarizer) {
+        return None;
+    }
+    let path = PathBuf::from(config.effective("LLM_MODEL_PATH")?.to_string());
+    path.is_file().then_some(path)
+}
+
+/// What to install: a catalog model by name, or a URL.
+#[derive(Debug, Clone)]
+pub struct Request<'a> {
+    pub target: &'a str,
+    /// The file name to save a URL's model as, instead of the URL's own.
+    pub file: Option<&'a str>,
+    pub sha256: Option<&'a str>,
+    /// Installs a catalog model even if this unit has too little RAM.
+    pub force: bool,
+}
+
+/// Downloads the model `request` names into the model directory.
+pub fn install(request: &Request) -> Result<PathBuf> {
+    let ctx = Context::from_env(Mode::Live);
+    if let Some(known) = models::known(request.target) {
+        if request.file.is_some() || request.sha256.is_some() {
+            return Err(Error::InvalidModel(
+                "a file name and SHA-256 can only be given for a URL".to_string(),
+            ));
+        }
+        let ram = models::total_ram_mb();
+        if known.min_ram_mb > ram && !request.force {
+            return Err(Error::InvalidModel(format!(
+                "{} needs {}MB of RAM and this unit has {ram}MB; pass --force to install it anyway",
+                known.name, known.min_ram_mb
+            )));
+        }
+        return Ok(models::download(&ctx, known.url, known.file, known.sha256)?);
+    }
+    if !request.target.contains("://") {
+        return Err(Error::NoSuchModel(request.target.to_string()));
+    }
+    let file = request
+        .file
+        .unwrap_or_else(|| models::url_file_name(request.target));
+    Ok(models::download(
+        &ctx,
+        request.target,
+        file,
+        request.sha256,
+    )?)
+}
+
+/// Makes the runtime load the installed model `name`, or run search-only
+/// with `None`.
+pub fn use_model(config: &mut Config, name: Option<&str>, source: Source) -> Result<()> {
+    let ctx = Context::from_env(Mode::Live);
+    let Some(name) = name else {
+        models::set_llm_mode(&ctx, LlmMode::Search)?;
+        return Ok(());
+    };
+    let model = find_installed(name)?;
+    for (key, value) in models::settings(&model.path) {
+        config.set(key, &value)?;
+    }
+    config.save(source)?;
+    models::set_llm_mode(&ctx, LlmMode::Summarizer)?;
+    Ok(())
+}
+
+/// Deletes the installed model `name`. The one in use is only removed with
+/// `force`, and survon_llm goes back to search-only mode.
+pub fn remove(config: &Config, name: &str, force: bool) -> Result<PathBuf> {
+    let model = find_installed(name)?;
+    let current = in_use(config).is_some_and(|path| path == model.path);
+    if current && !force {
+        return Err(Error::InvalidModel(format!(
+            "{} is the model in use; choose another with `survon model use` or pass --force",
+            model.file
+        )));
+    }
+    fs::remove_file(&model.path).map_err(|e| Error::io(&model.path, e))?;
+    if current {
+        models::set_llm_mode(&Context::from_env(Mode::Live), LlmMode::Search)?;
+    }
+    Ok(model.path)
+}
+
+/// The installed model with the catalog name or file name `name`.
+fn find_installed(name: &str) -> Result<Installed> {
+    let file = models::known(name).map_or(name, |known| known.file);
+    models::installed(&actions::home())?
+        .into_iter()
+        .find(|m| m.file == file || m.file == format!("{name}.{}", models::EXTENSION))
+        .ok_or_else(|| Error::NoSuchModel(name.to_string()))
+}
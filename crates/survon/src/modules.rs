@@ -0,0 +1,703 @@
+//! The runtime's modules under `~/modules`.
+//!
+//! Modules bundled with the runtime live in `core/` and are replaced by
+//! the installer. Wasteland modules, the ones added on the unit, live in
+//! `wasteland/`; only those are installed, removed, enabled and disabled
+//! here. A disabled module is moved to `.disabled/`, where the runtime does
+//! not look, and keeps its files and settings until it is enabled again.
+//!
+//! A module is a directory holding a `config.yml`. Its name, version,
+//! description and requirements are read from there; see [`Manifest`] and
+//! [`requirements`].
+//! Modules distributed as `.survonmod` packages are installed by
+//! [`crate::package`].
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+use survon_installer::fsutil;
+
+use crate::actions;
+use crate::error::{Error, Result};
+use crate::requirements;
+
+pub const CORE: &str = "core";
+pub const WASTELAND: &str = "wasteland";
+/// Where disabled Wasteland modules are kept, under `~/modules`.
+pub const DISABLED: &str = ".disabled";
+pub const CONFIG_FILE: &str = "config.yml";
+
+/// What a module's `config.yml` says about it. Everything else in the file
+/// is the module's own business.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Defaults to the module's directory name.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "text")]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Words to find the module by in an index.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// A requirement on the runtime's version such as `>=2.1`, if it gives
+    /// one.
+    #[serde(default, deserialize_with = "text")]
+    pub runtime: Option<String>,
+    #[serde(default, deserialize_with = "dependencies")]
+    pub dependencies: Vec<Dependency>,
+    #[serde(default, deserialize_with = "assets")]
+    pub assets: Vec<Asset>,
+    /// What the module needs of the unit, e.g. `ble`; see
+    /// [`crate::requirements::Capability`].
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+/// Another module this one needs, with the versions it works with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    pub name: String,
+    /// A version requirement such as `>=1.2`, if it gives one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// A file the module needs but does not ship, such as a model.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Asset {
+    /// Relative to the home directory if it starts with `~/`, else to the
+    /// module's directory.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl Manifest {
+    /// Parses the `config.yml` in `dir`.
+    pub fn load(dir: &Path) -> Result<Manifest> {
+        let path = dir.join(CONFIG_FILE);
+        let text = fs::read_to_string(&path).map_err(|e| Error::io(&path, e))?;
+        // An empty file is a module that says nothing about itself.
+        if text.trim().is_empty() {
+            return Ok(Manifest::default());
+        }
+        serde_yaml::from_str(&text)
+            .map_err(|e| Error::InvalidModule(format!("{}: {e}", path.display())))
+    }
+}
+
+/// Dependencies may be written as a list of names, a list of
+/// `{name, version}`, or a map of name to version requirement.
+fn dependencies<'de, D>(de: D) -> std::result::Result<Vec<Dependency>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Item {
+        Name(String),
+        Full(Dependency),
+    }
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Form {
+        List(Vec<Item>),
+        Map(BTreeMap<String, Option<serde_yaml::Value>>),
+    }
+    Ok(match Option::<Form>::deserialize(de)? {
+        None => Vec::new(),
+        Some(Form::List(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                Item::Name(name) => Dependency {
+                    name,
+                    version: None,
+                },
+                Item::Full(dependency) => dependency,
+            })
+            .collect(),
+        Some(Form::Map(map)) => map
+            .into_iter()
+            .map(|(name, version)| Dependency {
+                name,
+                version: version.as_ref().and_then(scalar),
+            })
+            .collect(),
+    })
+}
+
+/// Assets may be written as a path or as `{path, description}`.
+fn assets<'de, D>(de: D) -> std::result::Result<Vec<Asset>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Item {
+        Path(String),
+        Full(Asset),
+    }
+    Ok(Option::<Vec<Item>>::deserialize(de)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item| match item {
+            Item::Path(path) => Asset {
+                path,
+                description: None,
+            },
+            Item::Full(asset) => asset,
+        })
+        .collect())
+}
+
+/// A version may be written as a number (`version: 1.2`).
+fn text<'de, D>(de: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<serde_yaml::Value>::deserialize(de)?
+        .as_ref()
+        .and_then(scalar))
+}
+
+fn scalar(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Module {
+    pub name: String,
+    /// [`CORE`] or [`WASTELAND`].
+    pub group: &'static str,
+    pub enabled: bool,
+    pub path: PathBuf,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub runtime: Option<String>,
+    pub dependencies: Vec<Dependency>,
+    pub assets: Vec<Asset>,
+    pub capabilities: Vec<String>,
+}
+
+impl Module {
+    fn new(group: &'static str, enabled: bool, path: &Path, manifest: Manifest) -> Option<Module> {
+        let name = match manifest.name {
+            Some(name) => name,
+            None => path.file_name()?.to_str()?.to_string(),
+        };
+        Some(Module {
+            name,
+            group,
+            enabled,
+            path: path.to_path_buf(),
+            version: manifest.version,
+            description: manifest.description,
+            tags: manifest.tags,
+            runtime: manifest.runtime,
+            dependencies: manifest.dependencies,
+            assets: manifest.assets,
+            capabilities: manifest.capabilities,
+        })
+    }
+}
+
+/// `~/modules`.
+pub fn dir() -> PathBuf {
+    actions::home().join("modules")
+}
+
+/// Every module under `root`, core modules first, then Wasteland modules
+/// whether enabled or not, each group by name. Modules whose config.yml
+/// cannot be read are left out.
+pub fn list(root: &Path) -> Result<Vec<Module>> {
+    let mut modules = Vec::new();
+    for (group, dir, enabled) in [
+        (CORE, CORE, true),
+        (WASTELAND, WASTELAND, true),
+        (WASTELAND, DISABLED, false),
+    ] {
+        let dir = root.join(dir);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(Error::io(dir, e)),
+        };
+        modules.extend(
+            entries
+                .flatten()
+                .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+                .filter_map(|entry| read(group, enabled, &entry.path()).ok()),
+        );
+    }
+    modules.sort_by(|a, b| (a.group, &a.name).cmp(&(b.group, &b.name)));
+    Ok(modules)
+}
+
+/// The module called `name`.
+pub fn find(root: &Path, name: &str) -> Result<Module> {
+    list(root)?
+        .into_iter()
+        .find(|module| module.name == name)
+        .ok_or_else(|| Error::NoSuchModule(name.to_string()))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    /// Replace a module of the same name that did not come from a package.
+    pub replace: bool,
+    /// Install an older version of a package than the installed one.
+    pub downgrade: bool,
+    /// Overwrite files edited since a package was installed.
+    pub force: bool,
+    /// Install although modules, assets or the runtime it needs are
+    /// missing. Conflicts are refused regardless.
+    pub ignore_missing: bool,
+}
+
+/// Installs the module in `from`, a module directory or an archive of one
+/// (anything `tar` unpacks, such as `.tar.gz` or `.tar.zst`), into
+/// `wasteland/`. An installed module of the same name, enabled or not, is
+/// only replaced with `options.replace`; the new one is enabled. The
+/// module's requirements are checked first; see [`requirements`].
+pub fn install(root: &Path, from: &Path, options: &InstallOptions) -> Result<Module> {
+    if from.is_dir() {
+        return install_dir(root, from, options);
+    }
+    if !from.is_file() {
+        return Err(Error::io(from, ErrorKind::NotFound.into()));
+    }
+    let scratch = env::temp_dir().join(format!("survon-module-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&scratch);
+    fs::create_dir_all(&scratch).map_err(|e| Error::io(&scratch, e))?;
+    let result = unpack(from, &scratch)
+        .and_then(|()| module_root(&scratch, from))
+        .and_then(|dir| install_dir(root, &dir, options));
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+pub(crate) fn unpack(archive: &Path, dest: &Path) -> Result<()> {
+    let output = Command::new("tar")
+        .arg("-xf")
+        .arg(archive)
+        .arg("-C")
+        .arg(dest)
+        .arg("--no-same-owner")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| Error::io("tar", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::InvalidModule(format!(
+            "could not unpack {}: {}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// The module directory in an unpacked archive: the archive's top level if
+/// config.yml is there, else its only directory.
+fn module_root(unpacked: &Path, archive: &Path) -> Result<PathBuf> {
+    if unpacked.join(CONFIG_FILE).is_file() {
+        // Named after the archive, for a module that does not name itself.
+        let stem = archive
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.split('.').next())
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or("module");
+        let named = unpacked.join(stem);
+        if named.exists() {
+            return Err(Error::InvalidModule(format!(
+                "{} has {CONFIG_FILE} at its top level and also holds `{stem}`, \
+                 the name it would be unpacked under; put the module in a directory of its own",
+                archive.display()
+            )));
+        }
+        let inner: Vec<PathBuf> = fs::read_dir(unpacked)
+            .map_err(|e| Error::io(unpacked, e))?
+            .flatten()
+            .map(|entry| entry.path())
+            .collect();
+        fs::create_dir(&named).map_err(|e| Error::io(&named, e))?;
+        for path in inner {
+            let to = named.join(path.file_name().expect("directory entries have names"));
+            fs::rename(&path, &to).map_err(|e| Error::io(&path, e))?;
+        }
+        return Ok(named);
+    }
+    let dirs: Vec<PathBuf> = fs::read_dir(unpacked)
+        .map_err(|e| Error::io(unpacked, e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    match dirs.as_slice() {
+        [dir] if dir.join(CONFIG_FILE).is_file() => Ok(dir.clone()),
+        _ => Err(Error::InvalidModule(format!(
+            "{} does not hold a module (no {CONFIG_FILE} at its top level or in its only directory)",
+            archive.display()
+        ))),
+    }
+}
+
+fn install_dir(root: &Path, from: &Path, options: &InstallOptions) -> Result<Module> {
+    if !from.join(CONFIG_FILE).is_file() {
+        return Err(Error::InvalidModule(format!(
+            "{} is not a module directory (it has no {CONFIG_FILE})",
+            from.display()
+        )));
+    }
+    let from = from.canonicalize().map_err(|e| Error::io(from, e))?;
+    let incoming = read(WASTELAND, true, &from)?;
+    let name = incoming.name.clone();
+    check_name(&name)?;
+    let existing = match find(root, &name) {
+        Ok(module) if module.group == CORE => {
+            return Err(Error::InvalidModule(format!(
+                "`{name}` is the name of a module bundled with the runtime"
+            )));
+        }
+        Ok(module) => Some(module.path),
+        Err(Error::NoSuchModule(_)) => None,
+        Err(e) => return Err(e),
+    };
+    if existing.is_some() && !options.replace {
+        return Err(Error::InvalidModule(format!(
+            "module `{name}` is already installed; pass --replace to overwrite it"
+        )));
+    }
+    let dest = root.join(WASTELAND).join(&name);
+    if dest.exists() && existing.as_ref() != Some(&dest) {
+        return Err(Error::InvalidModule(format!(
+            "{} already exists and is not module `{name}`",
+            dest.display()
+        )));
+    }
+    let problems = requirements::check(root, &incoming)?;
+    requirements::ensure(&incoming, &problems, options.ignore_missing)?;
+    // Copy next to the destination first, so a failed copy leaves the
+    // installed module as it was.
+    let staging = root.join(WASTELAND).join(format!(".{name}.new"));
+    let _ = fs::remove_dir_all(&staging);
+    fsutil::copy_dir_all(&from, &staging)?;
+    // Swap it in with renames alone: the installed module moves aside, the
+    // copy takes its place, and only then is the old one deleted.
+    let old = root.join(WASTELAND).join(format!(".{name}.old"));
+    if let Some(path) = &existing {
+        let _ = fs::remove_dir_all(&old);
+        fs::rename(path, &old).map_err(|e| Error::io(path, e))?;
+    }
+    if let Err(e) = fs::rename(&staging, &dest) {
+        if let Some(path) = &existing {
+            let _ = fs::rename(&old, path);
+        }
+        let _ = fs::remove_dir_all(&staging);
+        return Err(Error::io(&dest, e));
+    }
+    let _ = fs::remove_dir_all(&old);
+    read(WASTELAND, true, &dest)
+}
+
+/// Deletes the Wasteland module `name`, enabled or not. A module other
+/// enabled modules depend on is only removed with `force`.
+pub fn remove(root: &Path, name: &str, force: bool) -> Result<Module> {
+    let module = wasteland(root, name, "removed")?;
+    check_dependents(root, &module, force)?;
+    fs::remove_dir_all(&module.path).map_err(|e| Error::io(&module.path, e))?;
+    Ok(module)
+}
+
+/// Moves the Wasteland module `name` back where the runtime loads it.
+/// Enabling an enabled module does nothing.
+pub fn enable(root: &Path, name: &str) -> Result<Module> {
+    set_enabled(root, name, true)
+}
+
+/// Moves the Wasteland module `name` out of the runtime's way, keeping its
+/// files. Disabling a disabled module does nothing. A module other enabled
+/// modules depend on is only disabled with `force`.
+pub fn disable(root: &Path, name: &str, force: bool) -> Result<Module> {
+    check_dependents(root, &wasteland(root, name, "disabled")?, force)?;
+    set_enabled(root, name, false)
+}
+
+fn check_dependents(root: &Path, module: &Module, force: bool) -> Result<()> {
+    if force || !module.enabled {
+        return Ok(());
+    }
+    let dependents = requirements::dependents(root, &module.name)?;
+    if dependents.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<&str> = dependents.iter().map(|m| m.name.as_str()).collect();
+    Err(Error::InvalidModule(format!(
+        "{} is needed by enabled modules: {}",
+        module.name,
+        names.join(", ")
+    )))
+}
+
+fn set_enabled(root: &Path, name: &str, enabled: bool) -> Result<Module> {
+    let verb = if enabled { "enabled" } else { "disabled" };
+    let module = wasteland(root, name, verb)?;
+    if module.enabled == enabled {
+        return Ok(module);
+    }
+    let dir = root.join(if enabled { WASTELAND } else { DISABLED });
+    fs::create_dir_all(&dir).map_err(|e| Error::io(&dir, e))?;
+    let dest = dir.join(module.path.file_name().expect("module paths have names"));
+    if dest.exists() {
+        return Err(Error::InvalidModule(format!(
+            "{} is in the way of the module `{name}`",
+            dest.display()
+        )));
+    }
+    fs::rename(&module.path, &dest).map_err(|e| Error::io(&module.path, e))?;
+    read(WASTELAND, enabled, &dest)
+}
+
+/// The Wasteland module `name`, or why it cannot be `verb`.
+fn wasteland(root: &Path, name: &str, verb: &str) -> Result<Module> {
+    check_name(name).map_err(|_| Error::NoSuchModule(name.to_string()))?;
+    let module = find(root, name)?;
+    if module.group == CORE {
+        return Err(Error::InvalidModule(format!(
+            "`{name}` is bundled with the runtime and cannot be {verb}"
+        )));
+    }
+    Ok(module)
+}
+
+pub(crate) fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\0']) {
+        return Err(Error::InvalidModule(format!(
+            "`{name}` is not a valid module name"
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn read(group: &'static str, enabled: bool, path: &Path) -> Result<Module> {
+    let manifest = Manifest::load(path)?;
+    Module::new(group, enabled, path, manifest)
+        .ok_or_else(|| Error::InvalidModule(format!("{} has no usable name", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use survon_test_support::Scratch;
+
+    /// Writes a module directory `<parent>/<dir>` with `config`.
+    fn module(parent: &Path, dir: &str, config: &str) -> PathBuf {
+        let path = parent.join(dir);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join(CONFIG_FILE), config).unwrap();
+        path
+    }
+
+    fn names(root: &Path) -> Vec<String> {
+        list(root)
+            .unwrap()
+            .iter()
+            .map(|m| {
+                format!(
+                    "{}/{}{}",
+                    m.group,
+                    m.name,
+                    if m.enabled { "" } else { " (disabled)" }
+                )
+            })
+            .collect()
+    }
+
+    fn invalid(result: Result<Module>) -> String {
+        match result {
+            Err(Error::InvalidModule(message)) => message,
+            other => panic!("expected InvalidModule, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn manifests_accept_every_dependency_form() {
+        let dir = Scratch::new("manifest");
+        for config in [
+            "dependencies: [maps, radio]\n",
+            "dependencies:\n  - name: maps\n    version: \">=1.2\"\n  - name: radio\n",
+            "dependencies:\n  maps: \">=1.2\"\n  radio:\n",
+        ] {
+            let path = module(&dir, "weather", &format!("version: 1.4\n{config}"));
+            let manifest = Manifest::load(&path).unwrap();
+            assert_eq!(manifest.version.as_deref(), Some("1.4"));
+            let names: Vec<&str> = manifest
+                .dependencies
+                .iter()
+                .map(|d| d.name.as_str())
+                .collect();
+            assert_eq!(names, ["maps", "radio"], "{config}");
+        }
+        let empty = module(&dir, "blank", "");
+        assert_eq!(Manifest::load(&empty).unwrap(), Manifest::default());
+        let broken = module(&dir, "broken", "dependencies: 7\n");
+        assert!(matches!(
+            Manifest::load(&broken),
+            Err(Error::InvalidModule(_))
+        ));
+    }
+
+    #[test]
+    fn modules_are_listed_by_group_and_name() {
+        let root = Scratch::new("list");
+        module(&root, "core/radio", "description: Radio\n");
+        module(&root, "wasteland/zeta", "name: weather\n");
+        module(&root, "wasteland/maps", "");
+        module(&root, ".disabled/compass", "");
+        module(&root, "wasteland/.maps.new", "");
+        fs::create_dir_all(root.join("wasteland/notes")).unwrap();
+        assert_eq!(
+            names(&root),
+            [
+                "core/radio",
+                "wasteland/compass (disabled)",
+                "wasteland/maps",
+                "wasteland/weather"
+            ]
+        );
+        assert_eq!(
+            find(&root, "weather").unwrap().path,
+            root.join("wasteland/zeta")
+        );
+        assert!(matches!(find(&root, "notes"), Err(Error::NoSuchModule(_))));
+    }
+
+    #[test]
+    fn installs_replace_only_when_asked() {
+        let dir = Scratch::new("install");
+        let root = dir.join("modules");
+        module(&root, "core/radio", "");
+        let weather = module(&dir, "src", "name: weather\nversion: 1.0.0\n");
+
+        let installed = install(&root, &weather, &InstallOptions::default()).unwrap();
+        assert_eq!(installed.path, root.join("wasteland/weather"));
+        assert!(installed.enabled);
+        assert!(
+            invalid(install(&root, &weather, &InstallOptions::default()))
+                .ends_with("pass --replace to overwrite it")
+        );
+
+        fs::write(weather.join(CONFIG_FILE), "name: weather\nversion: 1.1.0\n").unwrap();
+        let replace = InstallOptions {
+            replace: true,
+            ..InstallOptions::default()
+        };
+        let installed = install(&root, &weather, &replace).unwrap();
+        assert_eq!(installed.version.as_deref(), Some("1.1.0"));
+        assert_eq!(names(&root), ["core/radio", "wasteland/weather"]);
+        assert!(!root.join("wasteland/.weather.old").exists());
+        assert!(!root.join("wasteland/.weather.new").exists());
+
+        let radio = module(&dir, "radio", "");
+        assert!(invalid(install(&root, &radio, &replace)).contains("bundled with the runtime"));
+        fs::remove_file(radio.join(CONFIG_FILE)).unwrap();
+        assert!(invalid(install(&root, &radio, &replace)).contains("not a module directory"));
+        let hidden = module(&dir, "hidden", "name: .hidden\n");
+        assert!(invalid(install(&root, &hidden, &replace)).contains("not a valid module name"));
+    }
+
+    #[test]
+    fn archives_install_the_module_inside() {
+        let dir = Scratch::new("archive");
+        let root = dir.join("modules");
+        module(&dir, "src/compass", "version: 0.3.0\n");
+        let archive = dir.join("compass.tar.gz");
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(dir.join("src"))
+            .arg("compass")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let installed = install(&root, &archive, &InstallOptions::default()).unwrap();
+        assert_eq!(installed.name, "compass");
+        assert_eq!(installed.version.as_deref(), Some("0.3.0"));
+        assert!(root.join("wasteland/compass").join(CONFIG_FILE).is_file());
+
+        let not_a_module = dir.join("notes.txt");
+        fs::write(&not_a_module, "not an archive").unwrap();
+        assert!(
+            invalid(install(&root, &not_a_module, &InstallOptions::default()))
+                .starts_with("could not unpack")
+        );
+    }
+
+    #[test]
+    fn disabling_moves_modules_aside() {
+        let root = Scratch::new("enable");
+        module(&root, "core/radio", "");
+        module(&root, "wasteland/maps", "");
+        module(&root, "wasteland/weather", "dependencies: [maps]\n");
+
+        assert!(
+            invalid(disable(&root, "maps", false)).contains("needed by enabled modules: weather")
+        );
+        let weather = disable(&root, "weather", false).unwrap();
+        assert!(!weather.enabled);
+        assert_eq!(weather.path, root.join(DISABLED).join("weather"));
+        assert!(!disable(&root, "weather", false).unwrap().enabled);
+        assert!(!disable(&root, "maps", false).unwrap().enabled);
+        assert_eq!(
+            names(&root),
+            [
+                "core/radio",
+                "wasteland/maps (disabled)",
+                "wasteland/weather (disabled)"
+            ]
+        );
+
+        let maps = enable(&root, "maps").unwrap();
+        assert!(maps.enabled);
+        assert_eq!(maps.path, root.join(WASTELAND).join("maps"));
+        assert!(enable(&root, "maps").unwrap().enabled);
+        assert!(invalid(disable(&root, "radio", true)).contains("cannot be disabled"));
+        assert!(matches!(
+            enable(&root, "compass"),
+            Err(Error::NoSuchModule(_))
+        ));
+
+        fs::create_dir_all(root.join(WASTELAND).join("weather")).unwrap();
+        assert!(invalid(enable(&root, "weather")).contains("is in the way"));
+    }
+
+    #[test]
+    fn removing_respects_dependents() {
+        let root = Scratch::new("remove");
+        module(&root, "wasteland/maps", "");
+        module(&root, "wasteland/weather", "dependencies: [maps]\n");
+        assert!(invalid(remove(&root, "maps", false)).contains("needed by enabled modules"));
+        remove(&root, "maps", true).unwrap();
+        remove(&root, "weather", false).unwrap();
+        assert!(names(&root).is_empty());
+        assert!(matches!(
+            remove(&root, "../modules", true),
+            Err(Error::NoSuchModule(_))
+        ));
+    }
+}
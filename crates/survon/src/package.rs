@@ -0,0 +1,512 @@
+//! Module packages: `.survonmod` files.
+//!
+//! A package is a zstd-compressed tar archive holding a `survonmod.json`
+//! manifest and the module's directory, named after the module. The
+//! manifest gives the module's name and semver version, the runtime
+//! versions it works with and the SHA-256 of every file in the directory;
+//! a package whose files do not match is refused.
+//!
+//! Installing a package copies its manifest into the module directory as
+//! `.survonmod.json`. That record is how a later install knows whether it
+//! is an upgrade, a downgrade or a reinstall, and which files were edited
+//! on the unit since: those are only overwritten with `force`, and the old
+//! directory is then kept under `~/modules/.backup`. Files added on the
+//! unit that the package does not ship are carried over.
+//!
+//! The module's requirements are checked before anything is changed; see
+//! [`requirements`]. The module is staged next to where it goes and swapped
+//! in with renames, so a failed install leaves the installed version as it
+//! was.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+use survon_installer::steps::MODULE_BACKUP_DIR;
+use survon_installer::{fsutil, journal};
+use survon_update::Version;
+
+use crate::error::{Error, Result};
+use crate::modules::{self, Asset, Dependency, InstallOptions, Module, CORE, WASTELAND};
+use crate::requirements;
+
+pub const EXTENSION: &str = "survonmod";
+/// The manifest at the top of a package.
+pub const MANIFEST_FILE: &str = "survonmod.json";
+/// The manifest of the package a module was installed from, in the module
+/// directory.
+pub const RECORD_FILE: &str = ".survonmod.json";
+
+/// What a package holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: Version,
+    /// The runtime versions the module works with.
+    #[serde(default = "any_version")]
+    pub runtime: VersionReq,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// What the module needs, as its config.yml says; see
+    /// [`requirements`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<Dependency>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assets: Vec<Asset>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<String>,
+    /// The SHA-256 of each file, by its path in the module directory.
+    pub files: BTreeMap<String, String>,
+}
+
+fn any_version() -> VersionReq {
+    VersionReq::STAR
+}
+
+impl Package {
+    /// The record of the package the module in `dir` was installed from, if
+    /// it came from one.
+    pub fn installed(dir: &Path) -> Option<Package> {
+        let text = fs::read_to_string(dir.join(RECORD_FILE)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    fn parse(path: &Path) -> Result<Package> {
+        let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| Error::InvalidModule(format!("{}: {e}", path.display())))
+    }
+}
+
+/// Whether `path` names a package.
+pub fn is_package(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == EXTENSION)
+}
+
+/// What [`install`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Installed,
+    Upgraded(Version),
+    Downgraded(Version),
+    /// The same version again, over local edits.
+    Reinstalled,
+    /// A module that was not installed from a package was replaced.
+    Replaced,
+    /// The same version was installed and unmodified; nothing was done.
+    UpToDate,
+}
+
+#[derive(Debug, Clone)]
+pub struct Installed {
+    pub module: Module,
+    pub change: Change,
+    /// Where the replaced module was kept, if it had local edits.
+    pub backup: Option<PathBuf>,
+}
+
+/// Installs, upgrades or downgrades a Wasteland module from the package at
+/// `archive`. A disabled module stays disabled.
+pub fn install(root: &Path, archive: &Path, options: &InstallOptions) -> Result<Installed> {
+    install_checked(root, archive, options, |_| Ok(()))
+}
+
+/// Like [`install`], but only if the package is `name` at `version`, as
+/// the module index it was picked from lists it.
+pub fn install_listed(
+    root: &Path,
+    archive: &Path,
+    name: &str,
+    version: &Version,
+    options: &InstallOptions,
+) -> Result<Installed> {
+    install_checked(root, archive, options, |package| {
+        if package.name != name || package.version != *version {
+            return Err(Error::InvalidModule(format!(
+                "{} is `{}` {}, but the index lists `{name}` {version}",
+                archive.display(),
+                package.name,
+                package.version
+            )));
+        }
+        Ok(())
+    })
+}
+
+fn install_checked(
+    root: &Path,
+    archive: &Path,
+    options: &InstallOptions,
+    check: impl FnOnce(&Package) -> Result<()>,
+) -> Result<Installed> {
+    let scratch = env::temp_dir().join(format!("survon-package-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&scratch);
+    fs::create_dir_all(&scratch).map_err(|e| Error::io(&scratch, e))?;
+    let result = open(archive, &scratch).and_then(|(package, dir)| {
+        check(&package)?;
+        install_unpacked(root, &package, &dir, options)
+    });
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+/// Unpacks `archive` into `scratch` and checks its files against its
+/// manifest. Returns the manifest and the module directory.
+pub fn open(archive: &Path, scratch: &Path) -> Result<(Package, PathBuf)> {
+    if !archive.is_file() {
+        return Err(Error::io(archive, std::io::ErrorKind::NotFound.into()));
+    }
+    modules::unpack(archive, scratch)?;
+    let package = Package::parse(&scratch.join(MANIFEST_FILE))?;
+    modules::check_name(&package.name)?;
+    let dir = scratch.join(&package.name);
+    let damaged =
+        |what: String| Error::InvalidModule(format!("{} is damaged: {what}", archive.display()));
+    if !dir.join(modules::CONFIG_FILE).is_file() {
+        return Err(damaged(format!(
+            "it has no {}/{}",
+            package.name,
+            modules::CONFIG_FILE
+        )));
+    }
+    let actual = fsutil::hash_tree(&dir)?;
+    if let Some(file) = package
+        .files
+        .keys()
+        .find(|file| !actual.contains_key(*file))
+    {
+        return Err(damaged(format!("{file} is missing")));
+    }
+    for (file, hash) in &actual {
+        match package.files.get(file) {
+            None => return Err(damaged(format!("{file} is not in its manifest"))),
+            Some(expected) if !expected.eq_ignore_ascii_case(hash) => {
+                return Err(damaged(format!("{file} does not match its checksum")));
+            }
+            Some(_) => {}
+        }
+    }
+    let named = modules::read(WASTELAND, true, &dir)?.name;
+    if named != package.name {
+        return Err(damaged(format!(
+            "its manifest names `{}` but its {} names `{named}`",
+            package.name,
+            modules::CONFIG_FILE
+        )));
+    }
+    Ok((package, dir))
+}
+
+fn install_unpacked(
+    root: &Path,
+    package: &Package,
+    from: &Path,
+    options: &InstallOptions,
+) -> Result<Installed> {
+    let name = &package.name;
+    let existing = match modules::find(root, name) {
+        Ok(module) if module.group == CORE => {
+            return Err(Error::InvalidModule(format!(
+                "`{name}` is the name of a module bundled with the runtime"
+            )));
+        }
+        Ok(module) => Some(module),
+        Err(Error::NoSuchModule(_)) => None,
+        Err(e) => return Err(e),
+    };
+
+    // Files that would be overwritten although they were edited here, and
+    // files added here that the package does not ship.
+    let mut modified = Vec::new();
+    let mut added = Vec::new();
+    let change = match &existing {
+        None => Change::Installed,
+        Some(module) => match Package::installed(&module.path) {
+            None if !options.replace => {
+                return Err(Error::InvalidModule(format!(
+                    "module `{name}` is already installed and did not come from a package; \
+                     pass --replace to overwrite it"
+                )));
+            }
+            None => Change::Replaced,
+            Some(old) => {
+                modified = fsutil::changed_files(&module.path, &old.files);
+                for (file, hash) in fsutil::hash_tree(&module.path)? {
+                    if file == RECORD_FILE || old.files.contains_key(&file) {
+                        continue;
+                    }
+                    match package.files.get(&file) {
+                        None => added.push(file),
+                        Some(shipped) if *shipped != hash => modified.push(file),
+                        Some(_) => {}
+                    }
+                }
+                match package.version.cmp(&old.version) {
+                    std::cmp::Ordering::Greater => Change::Upgraded(old.version),
+                    std::cmp::Ordering::Less if !options.downgrade => {
+                        return Err(Error::InvalidModule(format!(
+                            "{name} {} is installed, which is newer than {}; \
+                             pass --downgrade to install it anyway",
+                            old.version, package.version
+                        )));
+                    }
+                    std::cmp::Ordering::Less => Change::Downgraded(old.version),
+                    std::cmp::Ordering::Equal if modified.is_empty() => {
+                        return Ok(Installed {
+                            module: module.clone(),
+                            change: Change::UpToDate,
+                            backup: None,
+                        });
+                    }
+                    std::cmp::Ordering::Equal => Change::Reinstalled,
+                }
+            }
+        },
+    };
+    let mut incoming = modules::read(WASTELAND, true, from)?;
+    if package.runtime != VersionReq::STAR {
+        incoming.runtime = Some(package.runtime.to_string());
+    }
+    let problems = requirements::check(root, &incoming)?;
+    requirements::ensure(&incoming, &problems, options.ignore_missing)?;
+
+    if !modified.is_empty() && !options.force {
+        modified.sort();
+        return Err(Error::InvalidModule(format!(
+            "{name} has local changes that would be overwritten: {}; \
+             pass --force to replace them (the old module is kept under ~/modules/{MODULE_BACKUP_DIR})",
+            modified.join(", ")
+        )));
+    }
+
+    let (dest, enabled) = match &existing {
+        Some(module) => (module.path.clone(), module.enabled),
+        None => (root.join(WASTELAND).join(name), true),
+    };
+    let parent = dest.parent().expect("module paths have a parent");
+    fs::create_dir_all(parent).map_err(|e| Error::io(parent, e))?;
+
+    // Build the new directory next to the old one.
+    let staging = parent.join(format!(".{name}.new"));
+    let _ = fs::remove_dir_all(&staging);
+    fsutil::copy_dir_all(from, &staging)?;
+    if let Some(module) = &existing {
+        for file in &added {
+            let to = staging.join(file);
+            if let Some(dir) = to.parent() {
+                fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+            }
+            fs::copy(module.path.join(file), &to).map_err(|e| Error::io(&to, e))?;
+        }
+    }
+    let record = serde_json::to_string_pretty(package).expect("packages serialize");
+    let record_path = staging.join(RECORD_FILE);
+    fs::write(&record_path, record).map_err(|e| Error::io(&record_path, e))?;
+
+    let mut backup = None;
+    if let Some(module) = &existing {
+        let old = parent.join(format!(".{name}.old"));
+        let _ = fs::remove_dir_all(&old);
+        fs::rename(&module.path, &old).map_err(|e| Error::io(&module.path, e))?;
+        if let Err(e) = fs::rename(&staging, &dest) {
+            let _ = fs::rename(&old, &module.path);
+            let _ = fs::remove_dir_all(&staging);
+            return Err(Error::io(&dest, e));
+        }
+        if modified.is_empty() {
+            let _ = fs::remove_dir_all(&old);
+        } else {
+            let version = module.version.as_deref().unwrap_or("unversioned");
+            let kept = root
+                .join(MODULE_BACKUP_DIR)
+                .join(format!("{name}-{version}-{}", journal::now()));
+            fs::create_dir_all(root.join(MODULE_BACKUP_DIR))
+                .and_then(|()| fs::rename(&old, &kept))
+                .map_err(|e| Error::io(&kept, e))?;
+            backup = Some(kept);
+        }
+    } else {
+        fs::rename(&staging, &dest).map_err(|e| Error::io(&dest, e))?;
+    }
+    Ok(Installed {
+        module: modules::read(WASTELAND, enabled, &dest)?,
+        change,
+        backup,
+    })
+}
+
+/// The files of `module` edited since it was installed from a package, or
+/// `None` if it did not come from one.
+pub fn modified(module: &Module) -> Option<Vec<String>> {
+    let package = Package::installed(&module.path)?;
+    Some(fsutil::changed_files(&module.path, &package.files))
+}
+
+/// Packs the module directory `dir` into `out`, or `<name>-<version>.survonmod`
+/// in the current directory. The module's config.yml must give its name
+/// and a semver version; its `runtime` requirement, if any, is carried
+/// into the manifest.
+pub fn pack(dir: &Path, out: Option<&Path>) -> Result<PathBuf> {
+    let module = modules::read(WASTELAND, true, dir)?;
+    let version = module.version.as_deref().ok_or_else(|| {
+        Error::InvalidModule(format!(
+            "{} gives no version",
+            dir.join(modules::CONFIG_FILE).display()
+        ))
+    })?;
+    let version = Version::parse(version).map_err(|e| {
+        Error::InvalidModule(format!(
+            "{}: version `{version}` is not semver: {e}",
+            module.name
+        ))
+    })?;
+    let runtime = match &module.runtime {
+        Some(req) => VersionReq::parse(req).map_err(|e| {
+            Error::InvalidModule(format!(
+                "{}: runtime `{req}` is not a version requirement: {e}",
+                module.name
+            ))
+        })?,
+        None => any_version(),
+    };
+    let mut files = fsutil::hash_tree(dir)?;
+    files.remove(RECORD_FILE);
+    let package = Package {
+        name: module.name.clone(),
+        version,
+        runtime,
+        description: module.description,
+        tags: module.tags,
+        dependencies: module.dependencies,
+        assets: module.assets,
+        capabilities: module.capabilities,
+        files,
+    };
+    let out = out.map(Path::to_path_buf).unwrap_or_else(|| {
+        PathBuf::from(format!("{}-{}.{EXTENSION}", package.name, package.version))
+    });
+
+    let scratch = env::temp_dir().join(format!("survon-pack-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&scratch);
+    let result = stage(&scratch, dir, &package).and_then(|()| {
+        let output = Command::new("tar")
+            .arg("--zstd")
+            .arg("-cf")
+            .arg(&out)
+            .arg("-C")
+            .arg(&scratch)
+            .args([MANIFEST_FILE, &package.name])
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| Error::io("tar", e))?;
+        if output.status.success() {
+            Ok(out.clone())
+        } else {
+            Err(Error::InvalidModule(format!(
+                "could not write {}: {}",
+                out.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    });
+    let _ = fs::remove_dir_all(&scratch);
+    result
+}
+
+fn stage(scratch: &Path, dir: &Path, package: &Package) -> Result<()> {
+    let staged = scratch.join(&package.name);
+    fsutil::copy_dir_all(dir, &staged)?;
+    let _ = fs::remove_file(staged.join(RECORD_FILE));
+    let manifest = serde_json::to_string_pretty(package).expect("packages serialize");
+    let path = scratch.join(MANIFEST_FILE);
+    fs::write(&path, manifest).map_err(|e| Error::io(&path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use survon_test_support::Scratch;
+
+    fn tar(args: &[&std::ffi::OsStr]) {
+        let status = Command::new("tar").args(args).status().unwrap();
+        assert!(status.success());
+    }
+
+    /// Unpacks `package`, lets `tamper` change the unpacked files and packs
+    /// them up again as `out`, without touching the manifest's checksums.
+    fn repack(package: &Path, out: &Path, tamper: impl FnOnce(&Path)) {
+        let dir = out.with_extension("d");
+        fs::create_dir_all(&dir).unwrap();
+        tar(&[
+            "-xf".as_ref(),
+            package.as_os_str(),
+            "-C".as_ref(),
+            dir.as_os_str(),
+        ]);
+        tamper(&dir);
+        tar(&[
+            "-cf".as_ref(),
+            out.as_os_str(),
+            "-C".as_ref(),
+            dir.as_os_str(),
+            ".".as_ref(),
+        ]);
+    }
+
+    fn open_error(package: &Path) -> String {
+        let unpacked = package.with_extension("open");
+        fs::create_dir_all(&unpacked).unwrap();
+        match open(package, &unpacked) {
+            Err(Error::InvalidModule(message)) => message,
+            other => panic!(
+                "{}: expected InvalidModule, got {other:?}",
+                package.display()
+            ),
+        }
+    }
+
+    #[test]
+    fn tampered_packages_are_rejected() {
+        let dir = Scratch::new("tamper");
+        let module = dir.join("beacon");
+        fs::create_dir_all(&module).unwrap();
+        fs::write(
+            module.join(modules::CONFIG_FILE),
+            "name: beacon\nversion: 1.0.0\ndescription: Blinks\n",
+        )
+        .unwrap();
+        fs::write(module.join("main.lua"), "blink()\n").unwrap();
+        let package = pack(&module, Some(&dir.join("beacon-1.0.0.survonmod"))).unwrap();
+
+        let unpacked = dir.join("unpacked");
+        fs::create_dir_all(&unpacked).unwrap();
+        let (manifest, files) = open(&package, &unpacked).unwrap();
+        assert_eq!(manifest.name, "beacon");
+        assert_eq!(manifest.version, Version::new(1, 0, 0));
+        assert!(files.join("main.lua").is_file());
+
+        let edited = dir.join("edited.tar");
+        repack(&package, &edited, |d| {
+            fs::write(d.join("beacon/main.lua"), "steal()\n").unwrap();
+        });
+        assert!(open_error(&edited).ends_with("main.lua does not match its checksum"));
+
+        let added = dir.join("added.tar");
+        repack(&package, &added, |d| {
+            fs::write(d.join("beacon/extra.lua"), "steal()\n").unwrap();
+        });
+        assert!(open_error(&added).ends_with("extra.lua is not in its manifest"));
+
+        let removed = dir.join("removed.tar");
+        repack(&package, &removed, |d| {
+            fs::remove_file(d.join("beacon/main.lua")).unwrap();
+        });
+        assert!(open_error(&removed).ends_with("main.lua is missing"));
+    }
+}
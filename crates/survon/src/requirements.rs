@@ -0,0 +1,460 @@
+//! What a module needs to work, and whether this unit has it.
+//!
+//! A module's `config.yml` can ask for other modules (`dependencies`), for
+//! files it does not ship itself such as a model or audio (`assets`), for
+//! hardware or services of the unit (`capabilities`) and for a runtime
+//! version (`runtime`):
+//!
+//! ```yaml
+//! runtime: ">=2.1"
+//! dependencies:
+//!   survon_llm: ">=2.0"
+//! assets:
+//!   - ~/models/phi-2.Q4_K_M.gguf
+//!   - path: sounds/
+//!     description: Big band recordings
+//! capabilities: [ble, audio]
+//! ```
+//!
+//! [`check`] lists what is not met. Some of it is merely missing and can be
+//! added after the module ([`Problem::is_missing`]); the rest makes the
+//! module incompatible with the unit or with the modules already on it.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use semver::VersionReq;
+use survon_update::runtime::Store;
+use survon_update::Version;
+
+use crate::actions;
+use crate::error::{Error, Result};
+use crate::modules::{self, Module};
+
+/// Something of the unit's a module can ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Ble,
+    Audio,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 2] = [Capability::Ble, Capability::Audio];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Ble => "ble",
+            Capability::Audio => "audio",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Capability> {
+        let name = name.to_ascii_lowercase();
+        Capability::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Whether the unit has it.
+    pub fn present(self) -> bool {
+        match self {
+            // One entry per Bluetooth adapter, e.g. hci0.
+            Capability::Ble => fs::read_dir("/sys/class/bluetooth")
+                .is_ok_and(|mut adapters| adapters.next().is_some()),
+            Capability::Audio => fs::read_to_string("/proc/asound/cards")
+                .is_ok_and(|cards| !cards.trim().is_empty() && !cards.contains("no soundcards")),
+        }
+    }
+
+    fn lacking(self) -> &'static str {
+        match self {
+            Capability::Ble => "this unit has no Bluetooth adapter",
+            Capability::Audio => "this unit has no sound card",
+        }
+    }
+}
+
+/// A requirement that is not met.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A module it depends on is not installed, or is disabled.
+    MissingModule {
+        name: String,
+        version: Option<String>,
+        disabled: bool,
+    },
+    /// A module it depends on is installed in a version it does not accept.
+    WrongVersion {
+        name: String,
+        required: String,
+        installed: Option<String>,
+    },
+    /// An installed module depends on it, but not in this version.
+    Breaks {
+        dependent: String,
+        required: String,
+    },
+    MissingAsset {
+        path: PathBuf,
+        description: Option<String>,
+    },
+    /// No runtime is installed yet.
+    NoRuntime {
+        required: String,
+    },
+    WrongRuntime {
+        required: String,
+        installed: Version,
+    },
+    Capability(Capability),
+    UnknownCapability(String),
+}
+
+impl Problem {
+    /// Whether this can be put right by adding something later, rather than
+    /// being a conflict.
+    pub fn is_missing(&self) -> bool {
+        matches!(
+            self,
+            Problem::MissingModule { .. }
+                | Problem::MissingAsset { .. }
+                | Problem::NoRuntime { .. }
+        )
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::MissingModule {
+                name,
+                version,
+                disabled,
+            } => {
+                write!(f, "needs the module {name}")?;
+                if let Some(version) = version {
+                    write!(f, " {version}")?;
+                }
+                if *disabled {
+                    write!(f, ", which is disabled")
+                } else {
+                    write!(f, ", which is not installed")
+                }
+            }
+            Problem::WrongVersion {
+                name,
+                required,
+                installed,
+            } => write!(
+                f,
+                "needs the module {name} {required}, but {} is installed",
+                installed.as_deref().unwrap_or("an unversioned copy")
+            ),
+            Problem::Breaks {
+                dependent,
+                required,
+            } => write!(f, "{dependent} needs it at {required}"),
+            Problem::MissingAsset { path, description } => {
+                write!(f, "needs {}", path.display())?;
+                if let Some(description) = description {
+                    write!(f, " ({description})")?;
+                }
+                write!(f, ", which is missing")
+            }
+            Problem::NoRuntime { required } => {
+                write!(f, "needs runtime {required}, and no runtime is installed")
+            }
+            Problem::WrongRuntime {
+                required,
+                installed,
+            } => write!(f, "needs runtime {required}, but {installed} is installed"),
+            Problem::Capability(capability) => write!(
+                f,
+                "needs {}, but {}",
+                capability.name(),
+                capability.lacking()
+            ),
+            Problem::UnknownCapability(name) => {
+                write!(
+                    f,
+                    "needs `{name}`, which is not a capability this tool knows"
+                )
+            }
+        }
+    }
+}
+
+/// What `module` needs that is not there, checked against the modules
+/// under `root` other than itself.
+pub fn check(root: &Path, module: &Module) -> Result<Vec<Problem>> {
+    let installed: Vec<Module> = modules::list(root)?
+        .into_iter()
+        .filter(|m| m.name != module.name)
+        .collect();
+    let mut problems = Vec::new();
+
+    for dep in &module.dependencies {
+        let required = dep.version.as_deref().map(requirement).transpose()?;
+        let Some(found) = installed.iter().find(|m| m.name == dep.name) else {
+            problems.push(Problem::MissingModule {
+                name: dep.name.clone(),
+                version: dep.version.clone(),
+                disabled: false,
+            });
+            continue;
+        };
+        if let Some(required) = required {
+            let matches = found.version.as_deref().and_then(version);
+            if !matches.is_some_and(|v| required.matches(&v)) {
+                problems.push(Problem::WrongVersion {
+                    name: dep.name.clone(),
+                    required: required.to_string(),
+                    installed: found.version.clone(),
+                });
+                continue;
+            }
+        }
+        if !found.enabled {
+            problems.push(Problem::MissingModule {
+                name: dep.name.clone(),
+                version: dep.version.clone(),
+                disabled: true,
+            });
+        }
+    }
+
+    let own = module.version.as_deref().and_then(version);
+    for other in installed.iter().filter(|m| m.enabled) {
+        for dep in other.dependencies.iter().filter(|d| d.name == module.name) {
+            let Some(required) = dep.version.as_deref().and_then(|r| requirement(r).ok()) else {
+                continue;
+            };
+            if !own.as_ref().is_some_and(|v| required.matches(v)) {
+                problems.push(Problem::Breaks {
+                    dependent: other.name.clone(),
+                    required: required.to_string(),
+                });
+            }
+        }
+    }
+
+    for asset in &module.assets {
+        let path = asset_path(&module.path, &asset.path);
+        if !path.exists() {
+            problems.push(Problem::MissingAsset {
+                path,
+                description: asset.description.clone(),
+            });
+        }
+    }
+
+    if let Some(required) = &module.runtime {
+        let required = requirement(required)?;
+        match Store::default().current() {
+            None => problems.push(Problem::NoRuntime {
+                required: required.to_string(),
+            }),
+            Some(installed) if !required.matches(&installed) => {
+                problems.push(Problem::WrongRuntime {
+                    required: required.to_string(),
+                    installed,
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    for name in &module.capabilities {
+        match Capability::from_name(name) {
+            Some(capability) if capability.present() => {}
+            Some(capability) => problems.push(Problem::Capability(capability)),
+            None => problems.push(Problem::UnknownCapability(name.clone())),
+        }
+    }
+    Ok(problems)
+}
+
+/// Fails with an explanation of `problems` unless there are none, or
+/// `ignore_missing` is set and they are all things that can be added later.
+pub fn ensure(module: &Module, problems: &[Problem], ignore_missing: bool) -> Result<()> {
+    if problems.is_empty() || (ignore_missing && problems.iter().all(Problem::is_missing)) {
+        return Ok(());
+    }
+    let mut message = format!("{} cannot be installed:", title(module));
+    for problem in problems {
+        message.push_str(&format!("\n  - {problem}"));
+    }
+    if problems.iter().all(Problem::is_missing) {
+        message.push_str("\nInstall what is missing first, or pass --ignore-missing.");
+    }
+    Err(Error::InvalidModule(message))
+}
+
+/// The enabled modules under `root` that depend on the module `name`.
+pub fn dependents(root: &Path, name: &str) -> Result<Vec<Module>> {
+    Ok(modules::list(root)?
+        .into_iter()
+        .filter(|m| m.enabled && m.name != name)
+        .filter(|m| m.dependencies.iter().any(|d| d.name == name))
+        .collect())
+}
+
+fn title(module: &Module) -> String {
+    match &module.version {
+        Some(version) => format!("{} {version}", module.name),
+        None => module.name.clone(),
+    }
+}
+
+/// Where an asset is: relative to the home directory for `~/...`, to the
+/// module's directory for other relative paths.
+fn asset_path(module_dir: &Path, path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        return actions::home().join(rest);
+    }
+    module_dir.join(path)
+}
+
+fn requirement(text: &str) -> Result<VersionReq> {
+    VersionReq::parse(text)
+        .map_err(|e| Error::InvalidModule(format!("`{text}` is not a version requirement: {e}")))
+}
+
+/// A module version as semver, reading `1.2` as `1.2.0`.
+pub fn version(text: &str) -> Option<Version> {
+    let text = text.trim().trim_start_matches('v');
+    Version::parse(text).ok().or_else(|| {
+        let parts = text.split('.').count();
+        let padded = match parts {
+            1 => format!("{text}.0.0"),
+            2 => format!("{text}.0"),
+            _ => return None,
+        };
+        Version::parse(&padded).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::{DISABLED, WASTELAND};
+    use survon_test_support::Scratch;
+
+    fn module(root: &Path, group: &str, name: &str, config: &str) -> Module {
+        let dir = root.join(group).join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(modules::CONFIG_FILE),
+            format!("name: {name}\n{config}"),
+        )
+        .unwrap();
+        modules::read(WASTELAND, group == WASTELAND, &dir).unwrap()
+    }
+
+    #[test]
+    fn versions_read_short_forms() {
+        assert_eq!(version("1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(version("v2"), Some(Version::new(2, 0, 0)));
+        assert_eq!(version(" 1.4 "), Some(Version::new(1, 4, 0)));
+        assert_eq!(
+            version("1.0.0-beta.2").map(|v| v.pre.to_string()),
+            Some("beta.2".to_string())
+        );
+        assert_eq!(version("1.2.3.4"), None);
+        assert_eq!(version("latest"), None);
+    }
+
+    #[test]
+    fn dependencies_must_be_installed_enabled_and_matching() {
+        let root = Scratch::new("dependencies");
+        module(&root, WASTELAND, "maps", "version: \"1.4\"\n");
+        module(&root, WASTELAND, "radio", "version: 0.9.0\n");
+        module(&root, DISABLED, "weather", "version: 2.0.0\n");
+        let incoming = module(
+            &root,
+            "incoming",
+            "scout",
+            "dependencies:\n  maps: \">=1.2\"\n  radio: \"^1\"\n  weather: \"2\"\n  compass:\n",
+        );
+
+        let problems = check(&root, &incoming).unwrap();
+        assert_eq!(
+            problems,
+            [
+                Problem::MissingModule {
+                    name: "compass".into(),
+                    version: None,
+                    disabled: false,
+                },
+                Problem::WrongVersion {
+                    name: "radio".into(),
+                    required: "^1".into(),
+                    installed: Some("0.9.0".into()),
+                },
+                Problem::MissingModule {
+                    name: "weather".into(),
+                    version: Some("2".into()),
+                    disabled: true,
+                },
+            ]
+        );
+        assert!(ensure(&incoming, &problems, true).is_err());
+    }
+
+    #[test]
+    fn upgrades_must_not_break_dependents() {
+        let root = Scratch::new("dependents");
+        module(
+            &root,
+            WASTELAND,
+            "scout",
+            "dependencies:\n  maps: \"^1.2\"\n",
+        );
+        module(&root, WASTELAND, "maps", "version: 1.3.0\n");
+        let newer = module(&root, "incoming", "maps", "version: 2.0.0\n");
+        assert_eq!(
+            check(&root, &newer).unwrap(),
+            [Problem::Breaks {
+                dependent: "scout".into(),
+                required: "^1.2".into(),
+            }]
+        );
+        let patch = module(&root, "patch", "maps", "version: 1.3.1\n");
+        assert!(check(&root, &patch).unwrap().is_empty());
+    }
+
+    #[test]
+    fn assets_and_capabilities() {
+        let root = Scratch::new("assets");
+        let incoming = module(
+            &root,
+            "incoming",
+            "jukebox",
+            "assets:\n  - path: sounds/\n    description: Recordings\n  - notes.txt\ncapabilities: [sonar]\n",
+        );
+        fs::write(incoming.path.join("notes.txt"), "").unwrap();
+        let problems = check(&root, &incoming).unwrap();
+        assert_eq!(
+            problems,
+            [
+                Problem::MissingAsset {
+                    path: incoming.path.join("sounds/"),
+                    description: Some("Recordings".into()),
+                },
+                Problem::UnknownCapability("sonar".into()),
+            ]
+        );
+        assert!(!problems[1].is_missing());
+        assert!(ensure(&incoming, &problems[..1], true).is_ok());
+        assert!(ensure(&incoming, &problems[..1], false).is_err());
+    }
+
+    #[test]
+    fn bad_requirements_are_errors() {
+        let root = Scratch::new("bad");
+        let incoming = module(&root, "incoming", "scout", "dependencies:\n  maps: soon\n");
+        assert!(matches!(
+            check(&root, &incoming),
+            Err(Error::InvalidModule(_))
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+//! The Survon runtime (runtime-base-rust): its installed versions, updates
+//! and launching it.
+
+use std::path::Path;
+
+use survon_config::Config;
+use survon_installer::steps::update_runtime;
+use survon_installer::system::Mode;
+use survon_installer::Context;
+use survon_update::arch;
+use survon_update::runtime::{RuntimeUpdate, Store, RUNTIME_LINK};
+use survon_update::Version;
+
+use crate::error::{Error, Result};
+use crate::supervisor::{self, Program};
+
+/// Updates the runtime from the release at `from`, or with `check` only
+/// reports whether there is a newer one. See [`update_runtime`].
+pub fn update(from: &str, check: bool) -> Result<RuntimeUpdate> {
+    if check {
+        return Ok(Store::default().update(from, arch::system(), true)?);
+    }
+    let ctx = Context::from_env(Mode::Live);
+    let update = update_runtime(&ctx.system, &ctx.user, from)?;
+    Ok(update.expect("live runs report what they did"))
+}
+
+/// Runs the current runtime under the [`supervisor`], which restarts it
+/// when it fails.
+pub fn launch(config: &Config) -> Result<()> {
+    if !Path::new(RUNTIME_LINK).exists() {
+        return Err(Error::NotInstalled("the runtime"));
+    }
+    supervisor::run(Program::Runtime, config)
+}
+
+/// One line saying what `update` found or did.
+pub fn summary(update: &RuntimeUpdate) -> String {
+    let installed = |from: &Option<Version>| {
+        from.as_ref()
+            .map_or_else(|| "none".to_string(), Version::to_string)
+    };
+    match update {
+        RuntimeUpdate::UpToDate(version) => format!("Runtime {version} is up to date."),
+        RuntimeUpdate::Available { from, to } => {
+            format!(
+                "Runtime {to} is available (installed: {}).",
+                installed(from)
+            )
+        }
+        RuntimeUpdate::Updated { from, to } => {
+            format!("Updated runtime {} -> {to}.", installed(from))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(text: &str) -> Version {
+        Version::parse(text).unwrap()
+    }
+
+    #[test]
+    fn summaries_name_both_versions() {
+        let current = RuntimeUpdate::UpToDate(version("1.2.0"));
+        assert_eq!(summary(&current), "Runtime 1.2.0 is up to date.");
+        let available = RuntimeUpdate::Available {
+            from: Some(version("1.2.0")),
+            to: version("1.3.0"),
+        };
+        assert_eq!(
+            summary(&available),
+            "Runtime 1.3.0 is available (installed: 1.2.0)."
+        );
+        let first = RuntimeUpdate::Updated {
+            from: None,
+            to: version("1.3.0"),
+        };
+        assert_eq!(summary(&first), "Updated runtime none -> 1.3.0.");
+    }
+}
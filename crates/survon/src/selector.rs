@@ -0,0 +1,400 @@
+//! The boot selector, shown on the console after login.
+//!
+//! Counts down `BOOT_TIMEOUT` seconds and then starts `BOOT_TARGET`. Any
+//! key stops the countdown; the entries can then be picked with the arrow
+//! keys and Enter, or by number. The chosen program replaces the selector,
+//! except the menu, after which the selector comes back without a
+//! countdown.
+//!
+//! The runtime is waited for too, so that failed starts can be counted
+//! (see [`survon::crash_loop`]). In a crash loop safe mode becomes the
+//! default and the screen says why.
+
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+use survon::boot::{self, Target};
+use survon::crash_loop::{self, Record};
+use survon::error::status_code;
+use survon::{council, Error};
+use survon_config::Config;
+use survon_installer::log::timestamp;
+use survon_update::runtime::Store;
+use survon_update::Version;
+
+use crate::menu::navigate;
+
+/// The least time the safe-mode screen stays up before safe mode starts,
+/// even with a shorter `BOOT_TIMEOUT`, so the reason can be read.
+const CRASH_LOOP_COUNTDOWN: Duration = Duration::from_secs(15);
+const MAX_FAILURES_SHOWN: usize = 5;
+
+/// Boots `forced` straight away, or lets the user choose, waiting
+/// `timeout` (default: `BOOT_TIMEOUT`) before starting the default.
+/// Returns with the exit code of a forced target, or if a forced target
+/// could not be started.
+pub fn run(config_path: &Path, forced: Option<Target>, timeout: Option<Duration>) -> ExitCode {
+    let mut countdown = true;
+    let mut message = None;
+    loop {
+        // A broken settings file must not keep the unit from booting.
+        let config = Config::load(config_path).unwrap_or_else(|e| {
+            message = Some(format!("Ignoring settings: {e}"));
+            Config::parse(config_path, "").expect("an empty file is valid")
+        });
+        let timeout = timeout.unwrap_or_else(|| boot::timeout(&config));
+        let record = Record::load();
+        let crash_loop = record.in_crash_loop(&config) && Target::SafeMode.unavailable().is_none();
+        let (default, timeout) =
+            default_countdown(crash_loop, timeout, || boot::default_target(&config));
+        let target = match forced {
+            Some(target) => target,
+            None if countdown && timeout.is_zero() => default,
+            None => {
+                let screen = Screen {
+                    config: &config,
+                    default,
+                    crash_loop: crash_loop.then_some(&record),
+                };
+                match screen.choose(countdown.then_some(timeout), message.take()) {
+                    Ok(target) => target,
+                    Err(e) => {
+                        eprintln!("Boot selector failed: {e}");
+                        default
+                    }
+                }
+            }
+        };
+        // Safe mode chosen for the user says nothing about what they want
+        // next time.
+        if !(crash_loop && target == Target::SafeMode) {
+            if let Err(e) = boot::remember(target) {
+                eprintln!("Could not remember the boot choice: {e}");
+            }
+        }
+
+        let result = start(target, &config);
+        let runtime_ended = result.is_ok() && boot::version(target).is_some();
+        match result {
+            Ok(code) if forced.is_some() => return code,
+            Ok(_) => {}
+            Err(e) if forced.is_some() => {
+                eprintln!("{e}");
+                return ExitCode::from(e.exit_code());
+            }
+            Err(e) => message = Some(e.to_string()),
+        }
+        // The runtime ending used to mean a logout and a fresh autologin,
+        // which counted down to the next start; keep doing that. Back from
+        // anything else, someone is at the console, so wait for them.
+        countdown = runtime_ended;
+    }
+}
+
+/// The target to count down to, and for how long. After repeated failed
+/// starts safe mode is the default whatever `BOOT_TARGET` says, and the
+/// countdown is long enough to read why.
+fn default_countdown(
+    crash_loop: bool,
+    timeout: Duration,
+    configured: impl FnOnce() -> Target,
+) -> (Target, Duration) {
+    if crash_loop {
+        (Target::SafeMode, timeout.max(CRASH_LOOP_COUNTDOWN))
+    } else {
+        (configured(), timeout)
+    }
+}
+
+/// The entry highlighted first. The countdown starts the default, so that
+/// is what is highlighted; without one, the last choice is the likelier
+/// pick.
+fn first_selected(
+    countdown: bool,
+    default: Target,
+    last: impl FnOnce() -> Option<Target>,
+) -> Target {
+    if countdown {
+        default
+    } else {
+        last().unwrap_or(default)
+    }
+}
+
+/// Whole seconds left until `deadline`, rounded up, or `None` once it has
+/// passed.
+fn seconds_left(deadline: Instant, now: Instant) -> Option<u64> {
+    let left = deadline
+        .checked_duration_since(now)
+        .filter(|d| !d.is_zero())?;
+    Some(left.as_secs() + u64::from(left.subsec_nanos() > 0))
+}
+
+/// The entry `code` starts, by number or with Enter on `selected`; `None`
+/// for keys that only move around.
+fn picked(code: KeyCode, selected: Option<usize>) -> Option<usize> {
+    match code {
+        KeyCode::Char(c @ '1'..='6') => Some(c as usize - '1' as usize),
+        KeyCode::Enter => Some(selected.unwrap_or(0)),
+        _ => None,
+    }
+}
+
+/// Starts `target` and waits for it, except for the targets that replace
+/// the selector. Starts of the installed runtime are recorded for
+/// crash-loop detection.
+fn start(target: Target, config: &Config) -> survon::Result<ExitCode> {
+    let mut cmd = boot::command(target, config)?;
+    println!("Starting {}...", target.title());
+    let status = match target {
+        Target::Runtime | Target::PreviousRuntime => {
+            crash_loop::run(&mut cmd, boot::version(target), config)?
+        }
+        Target::Menu | Target::SafeMode => {
+            cmd.status().map_err(|e| Error::io(cmd.get_program(), e))?
+        }
+        Target::CouncilSeat | Target::Shell => {
+            let program = cmd.get_program().to_owned();
+            return Err(Error::io(program, cmd.exec()));
+        }
+    };
+    Ok(ExitCode::from(status_code(status)))
+}
+
+/// What the selector shows.
+struct Screen<'a> {
+    config: &'a Config,
+    default: Target,
+    /// The failed starts that made safe mode the default, if they did.
+    crash_loop: Option<&'a Record>,
+}
+
+impl Screen<'_> {
+    /// Shows the entries until one is picked, or the countdown runs out.
+    fn choose(
+        &self,
+        countdown: Option<Duration>,
+        mut message: Option<String>,
+    ) -> io::Result<Target> {
+        let selected = first_selected(countdown.is_some(), self.default, || {
+            boot::last().filter(|t| t.unavailable().is_none())
+        });
+        let mut list =
+            ListState::default().with_selected(Target::ALL.iter().position(|t| *t == selected));
+        let mut deadline = countdown.map(|timeout| Instant::now() + timeout);
+
+        let mut terminal = ratatui::init();
+        let result = loop {
+            let left = deadline.map(|deadline| seconds_left(deadline, Instant::now()));
+            if left == Some(None) {
+                break Ok(self.default);
+            }
+            let footer = match (left.flatten(), &message) {
+                (Some(left), _) => Line::from(vec![
+                    format!("Starting {} in {left}s", self.default.title()).bold(),
+                    " · press any key to choose something else".dark_gray(),
+                ]),
+                (None, Some(message)) => Line::from(message.as_str().red()),
+                (None, None) => Line::from("↑/↓ move · Enter start · 1-6 jump".dark_gray()),
+            };
+            if let Err(e) = terminal.draw(|frame| self.draw(frame, &mut list, footer)) {
+                break Err(e);
+            }
+            match event::poll(Duration::from_millis(100)) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => break Err(e),
+            }
+            let key = match event::read() {
+                Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+                Ok(_) => continue,
+                Err(e) => break Err(e),
+            };
+            deadline = None;
+            message = None;
+            let Some(index) = picked(key.code, list.selected()) else {
+                navigate(&mut list, key.code, Target::ALL.len());
+                continue;
+            };
+            list.select(Some(index));
+            let picked = Target::ALL[index];
+            match picked.unavailable() {
+                Some(reason) => message = Some(format!("{}: {reason}.", picked.title())),
+                None => break Ok(picked),
+            }
+        };
+        ratatui::restore();
+        result
+    }
+
+    fn draw(&self, frame: &mut Frame, list: &mut ListState, footer: Line) {
+        let diagnosis = self.crash_loop.map(|record| self.diagnosis(record));
+        let height = diagnosis.as_ref().map_or(0, |lines| lines.len() as u16 + 2);
+        let [top, body, bottom] = Layout::vertical([
+            Constraint::Length(height),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        if let Some(lines) = diagnosis {
+            frame.render_widget(
+                Paragraph::new(lines)
+                    .wrap(Wrap { trim: false })
+                    .block(Block::bordered().title(" Safe mode ".red().bold())),
+                top,
+            );
+        }
+
+        let items =
+            Target::ALL
+                .iter()
+                .zip(self.details())
+                .enumerate()
+                .map(|(i, (target, detail))| {
+                    let mut title = target.title().to_string();
+                    if *target == self.default {
+                        title.push_str(" (default)");
+                    }
+                    let line = match target.unavailable() {
+                        Some(reason) => Line::from(vec![
+                            format!("{}  {title:<28}", i + 1).dark_gray(),
+                            reason.dark_gray(),
+                        ]),
+                        None => Line::from(vec![
+                            Span::raw(format!("{}  {title:<28}", i + 1)),
+                            detail.dark_gray(),
+                        ]),
+                    };
+                    ListItem::new(line)
+                });
+        let widget = List::new(items)
+            .block(Block::bordered().title(" Survon OS boot ".bold()))
+            .highlight_style(Style::new().reversed())
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(widget, body, list);
+        frame.render_widget(Paragraph::new(footer), bottom);
+    }
+
+    /// Why safe mode is the default: the failed starts, and how to get
+    /// out of it.
+    fn diagnosis(&self, record: &Record) -> Vec<Line<'static>> {
+        let safe = record.known_good.as_ref().map_or_else(String::new, |v| {
+            format!(" (runtime {v}, the last that started fine)")
+        });
+        let mut lines = vec![Line::from(format!(
+            "The runtime failed to start {} times in a row, so the unit boots safe mode{safe}: \
+             debug logging on, no Wasteland modules.",
+            record.failures.len()
+        ))];
+        let shown = record.failures.len().saturating_sub(MAX_FAILURES_SHOWN);
+        for failure in &record.failures[shown..] {
+            let version = failure
+                .version
+                .as_ref()
+                .map_or_else(|| "runtime".to_string(), |v| format!("runtime {v}"));
+            lines.push(Line::from(vec![
+                format!("  {}  ", timestamp(failure.time)).dark_gray(),
+                Span::raw(format!("{version} {}", failure.describe())),
+            ]));
+        }
+        lines.push(Line::from(
+            format!(
+                "A start fails if the runtime exits with an error or within {}s. \
+                 Pick \"{}\" to try it again. Safe mode's debug log is in {}.",
+                boot::number(self.config, "CRASH_LOOP_SECONDS", 30),
+                Target::Runtime.title(),
+                boot::state_dir().join("safe-mode/logs").display()
+            )
+            .dark_gray(),
+        ));
+        lines
+    }
+
+    /// A line of detail for each of [`Target::ALL`].
+    fn details(&self) -> Vec<String> {
+        let store = Store::default();
+        let version = |v: Option<Version>| v.map_or_else(String::new, |v| v.to_string());
+        Target::ALL
+            .iter()
+            .map(|target| match target {
+                Target::Runtime => version(store.current()),
+                Target::CouncilSeat => council::strategy(self.config),
+                Target::Menu => "settings, updates and modules".to_string(),
+                Target::Shell => "command line".to_string(),
+                Target::SafeMode => "debug logging, no Wasteland modules".to_string(),
+                Target::PreviousRuntime => version(store.previous()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_crash_loop_counts_down_to_safe_mode() {
+        let five = Duration::from_secs(5);
+        assert_eq!(
+            default_countdown(false, five, || Target::Menu),
+            (Target::Menu, five)
+        );
+        assert_eq!(
+            default_countdown(true, five, || unreachable!()),
+            (Target::SafeMode, CRASH_LOOP_COUNTDOWN)
+        );
+        let minute = Duration::from_secs(60);
+        assert_eq!(
+            default_countdown(true, minute, || Target::Menu),
+            (Target::SafeMode, minute)
+        );
+    }
+
+    #[test]
+    fn the_countdown_highlights_the_default() {
+        assert_eq!(
+            first_selected(true, Target::Runtime, || Some(Target::Shell)),
+            Target::Runtime
+        );
+        assert_eq!(
+            first_selected(false, Target::Runtime, || Some(Target::Shell)),
+            Target::Shell
+        );
+        assert_eq!(
+            first_selected(false, Target::Runtime, || None),
+            Target::Runtime
+        );
+    }
+
+    #[test]
+    fn seconds_left_round_up_until_the_deadline() {
+        let now = Instant::now();
+        let at = |millis| now + Duration::from_millis(millis);
+        assert_eq!(seconds_left(at(5_000), now), Some(5));
+        assert_eq!(seconds_left(at(4_001), now), Some(5));
+        assert_eq!(seconds_left(at(1), now), Some(1));
+        assert_eq!(seconds_left(now, now), None);
+        assert_eq!(seconds_left(now, at(10)), None);
+    }
+
+    #[test]
+    fn numbers_and_enter_pick_entries() {
+        assert_eq!(picked(KeyCode::Char('1'), Some(3)), Some(0));
+        assert_eq!(picked(KeyCode::Char('6'), None), Some(5));
+        assert_eq!(picked(KeyCode::Char('7'), None), None);
+        assert_eq!(picked(KeyCode::Enter, Some(3)), Some(3));
+        assert_eq!(picked(KeyCode::Enter, None), Some(0));
+        assert_eq!(picked(KeyCode::Down, Some(3)), None);
+        assert_eq!(Target::ALL.len(), 6);
+    }
+}
@@ -0,0 +1,157 @@
+//! The unit's state at a glance, for the menu's header.
+
+use std::ffi::CString;
+use std::fs;
+use std::mem::MaybeUninit;
+use std::path::Path;
+
+use survon_config::Config;
+use survon_update::runtime::Store;
+use survon_update::Version;
+
+use crate::council;
+use crate::supervisor::{self, Crash, Program};
+
+#[derive(Debug, Clone)]
+pub struct Status {
+    pub runtime: Option<Version>,
+    /// The model's file name, if the file is there.
+    pub model: Option<String>,
+    /// The configured strategy, if the council seat is installed.
+    pub council: Option<String>,
+    pub ble: Ble,
+    /// Free and total bytes on the root filesystem.
+    pub disk: Option<(u64, u64)>,
+    /// What the supervisor last recorded, for each program it has run.
+    pub supervised: Vec<supervisor::Status>,
+    pub last_crash: Option<Crash>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ble {
+    NoAdapter,
+    /// Switched off with rfkill (soft) or a hardware switch.
+    Blocked(String),
+    Available(String),
+}
+
+impl Status {
+    pub fn gather(config: &Config) -> Self {
+        let model = config
+            .effective("LLM_MODEL_PATH")
+            .map(|path| path.to_string())
+            .filter(|path| Path::new(path).is_file())
+            .and_then(|path| Some(Path::new(&path).file_name()?.to_str()?.to_string()));
+        let council = council::is_installed().then(|| council::strategy(config));
+        Status {
+            runtime: Store::default().current(),
+            model,
+            council,
+            ble: ble(),
+            disk: disk_space(Path::new("/")),
+            supervised: Program::ALL
+                .into_iter()
+                .filter_map(supervisor::Status::load)
+                .collect(),
+            last_crash: supervisor::crashes(None).pop(),
+        }
+    }
+}
+
+/// The first Bluetooth adapter and whether rfkill blocks it.
+fn ble() -> Ble {
+    let Some(adapter) = first_entry(Path::new("/sys/class/bluetooth")) else {
+        return Ble::NoAdapter;
+    };
+    let blocked = fs::read_dir("/sys/class/rfkill")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|dir| read_trimmed(&dir.join("type")).as_deref() == Some("bluetooth"))
+        .any(|dir| {
+            read_trimmed(&dir.join("soft")).as_deref() == Some("1")
+                || read_trimmed(&dir.join("hard")).as_deref() == Some("1")
+        });
+    if blocked {
+        Ble::Blocked(adapter)
+    } else {
+        Ble::Available(adapter)
+    }
+}
+
+fn first_entry(dir: &Path) -> Option<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.contains(':'))
+        .collect();
+    names.sort();
+    names.into_iter().next()
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    Some(fs::read_to_string(path).ok()?.trim().to_string())
+}
+
+fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    let path = CString::new(path.to_str()?).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stat` is only read after
+    // statvfs reports success.
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    let block = stat.f_frsize as u64;
+    Some((stat.f_bavail as u64 * block, stat.f_blocks as u64 * block))
+}
+
+/// `bytes` in the largest unit that keeps it at or above 1.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use survon_test_support::Scratch;
+
+    use super::*;
+
+    #[test]
+    fn sizes_use_the_largest_whole_unit() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1024), "1.0 KB");
+        assert_eq!(human_size(1536 * 1024), "1.5 MB");
+        assert_eq!(human_size(32 << 30), "32.0 GB");
+        assert_eq!(human_size(3 << 50), "3072.0 TB");
+    }
+
+    #[test]
+    fn the_first_entry_skips_mac_aliases() {
+        let dir = Scratch::new("status");
+        assert_eq!(first_entry(&dir), None);
+        assert_eq!(first_entry(&dir.join("missing")), None);
+
+        fs::create_dir(dir.join("hci1")).unwrap();
+        fs::create_dir(dir.join("hci0:64")).unwrap();
+        assert_eq!(first_entry(&dir).as_deref(), Some("hci1"));
+        fs::create_dir(dir.join("hci0")).unwrap();
+        assert_eq!(first_entry(&dir).as_deref(), Some("hci0"));
+    }
+}
@@ -0,0 +1,655 @@
+//! Runs the runtime or the council seat in the foreground and restarts it
+//! when it fails.
+//!
+//! A program that exits with an error or is killed is started again after a
+//! delay that doubles with each failure in a row, from one second up to a
+//! minute. A run that lasted [`HEALTHY_AFTER`] counts as healthy and resets
+//! the delay. After `RESTART_LIMIT` restarts in a row the supervisor gives
+//! up. A clean exit, or Ctrl-C, ends supervision.
+//!
+//! The program finds a heartbeat file's path in `SURVON_HEARTBEAT`. Once it
+//! has touched that file, it must keep touching it at least every
+//! [`HEARTBEAT_TIMEOUT`]; a program whose heartbeat goes stale is taken to
+//! be hung, killed, and restarted like one that crashed. Programs that never
+//! touch the file are not probed.
+//!
+//! What the program writes to stderr still reaches the terminal, and its
+//! last [`LOG_LINES`] lines go into the crash record kept for every failure
+//! in `~/.local/state/survon/crashes.jsonl`. The supervisor's state is kept
+//! in `~/.local/state/survon/supervisor/`, for `survon status` and the
+//! menu.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use survon_config::Config;
+use survon_installer::units;
+
+use crate::actions;
+use crate::boot;
+use crate::error::{Error, Result};
+
+/// Lines of stderr kept for a crash record.
+pub const LOG_LINES: usize = 20;
+/// A run this long is healthy: the next failure starts the backoff over.
+pub const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+/// How old the heartbeat file may get before the program counts as hung.
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+/// The variable telling the program where its heartbeat file is.
+pub const HEARTBEAT_VAR: &str = "SURVON_HEARTBEAT";
+const FIRST_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+/// How long to wait for the last of a program's output once it has exited.
+const READER_GRACE: Duration = Duration::from_secs(1);
+/// How often a running program is checked on.
+const POLL: Duration = Duration::from_millis(100);
+/// Crash records kept in `crashes.jsonl`, newest last.
+const KEPT_CRASHES: usize = 100;
+const CRASHES_FILE: &str = "crashes.jsonl";
+const STATUS_DIR: &str = "supervisor";
+
+/// The signal that asked the supervisor to stop, or 0.
+static STOP: AtomicI32 = AtomicI32::new(0);
+/// The supervised process, for passing SIGTERM and SIGHUP on to it.
+static CHILD: AtomicI32 = AtomicI32::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Program {
+    Runtime,
+    CouncilSeat,
+}
+
+impl Program {
+    pub const ALL: [Program; 2] = [Program::Runtime, Program::CouncilSeat];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Program::Runtime => "runtime",
+            Program::CouncilSeat => "council-seat",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Program> {
+        Program::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    /// The executable's name, as errors and messages call it.
+    pub fn title(self) -> &'static str {
+        match self {
+            Program::Runtime => "runtime-base-rust",
+            Program::CouncilSeat => "survon-council-seat",
+        }
+    }
+
+    pub fn binary(self) -> &'static Path {
+        Path::new(match self {
+            Program::Runtime => units::RUNTIME_BINARY,
+            Program::CouncilSeat => units::COUNCIL_SEAT_BINARY,
+        })
+    }
+
+    /// A log the program writes itself, read for a crash record when it
+    /// wrote nothing to stderr: the runtime's debug log.
+    fn log_file(self) -> Option<PathBuf> {
+        match self {
+            Program::Runtime => Some(actions::home().join("logs/debug.log")),
+            Program::CouncilSeat => None,
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One failed run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Crash {
+    pub program: Program,
+    /// When it ended, in seconds since the epoch.
+    pub time: u64,
+    pub exit_code: Option<i32>,
+    /// The signal that killed it, if one did.
+    pub signal: Option<i32>,
+    /// How long it ran, in seconds.
+    pub seconds: u64,
+    /// Which failure in a row this was.
+    pub failures: u32,
+    /// Whether it was killed for letting its heartbeat go stale.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hung: bool,
+    /// Its last lines of output.
+    pub log: Vec<String>,
+}
+
+impl Crash {
+    /// What ended it, e.g. "exited with code 101 after 3s".
+    pub fn describe(&self) -> String {
+        let hung = if self.hung {
+            "stopped its heartbeat and "
+        } else {
+            ""
+        };
+        format!(
+            "{hung}{} after {}s",
+            ending(self.exit_code, self.signal),
+            self.seconds
+        )
+    }
+}
+
+/// What a supervisor is doing with its program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "kebab-case")]
+pub enum State {
+    Running {
+        pid: u32,
+        since: u64,
+    },
+    /// Waiting to start the program again after a failure.
+    Restarting {
+        at: u64,
+    },
+    /// The program exited cleanly or was stopped.
+    Stopped {
+        time: u64,
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+    },
+    /// The program failed `RESTART_LIMIT` times after its last healthy run.
+    GaveUp {
+        time: u64,
+    },
+}
+
+/// The last state a supervisor recorded for its program.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub program: Program,
+    #[serde(flatten)]
+    pub state: State,
+    /// Restarts since the supervisor started.
+    pub restarts: u32,
+    /// The supervisor's process.
+    pub supervisor: u32,
+}
+
+impl Status {
+    fn path(program: Program) -> PathBuf {
+        boot::state_dir()
+            .join(STATUS_DIR)
+            .join(format!("{program}.json"))
+    }
+
+    /// The file `program` touches to show it is not hung.
+    fn heartbeat(program: Program) -> PathBuf {
+        boot::state_dir()
+            .join(STATUS_DIR)
+            .join(format!("{program}.heartbeat"))
+    }
+
+    /// The last recorded status, if `program` was ever supervised.
+    pub fn load(program: Program) -> Option<Status> {
+        let text = fs::read_to_string(Status::path(program)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    fn save(&self) {
+        let path = Status::path(self.program);
+        let json = serde_json::to_string_pretty(self).expect("statuses serialize");
+        // The status is for display; failing to write it must not stop
+        // the program being supervised.
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&path, json));
+        if let Err(e) = written {
+            eprintln!(
+                "Could not record the supervisor status: {}: {e}",
+                path.display()
+            );
+        }
+    }
+
+    /// Whether the recorded state is current: a running or restarting
+    /// state whose supervisor has gone is not.
+    pub fn is_live(&self) -> bool {
+        match self.state {
+            State::Running { .. } | State::Restarting { .. } => alive(self.supervisor),
+            State::Stopped { .. } | State::GaveUp { .. } => true,
+        }
+    }
+
+    /// One line for `survon status` and the menu.
+    pub fn describe(&self) -> String {
+        let restarts = match self.restarts {
+            0 => String::new(),
+            1 => ", restarted once".to_string(),
+            n => format!(", restarted {n} times"),
+        };
+        let state = match &self.state {
+            _ if !self.is_live() => "not running (its supervisor is gone)".to_string(),
+            State::Running { pid, since } => {
+                format!("running since {} (pid {pid})", clock(*since))
+            }
+            State::Restarting { at } => format!("restarting at {}", clock(*at)),
+            State::Stopped {
+                time,
+                exit_code,
+                signal,
+            } => format!("{} at {}", ending(*exit_code, *signal), clock(*time)),
+            State::GaveUp { time } => format!("gave up restarting at {}", clock(*time)),
+        };
+        format!("{state}{restarts}")
+    }
+}
+
+/// The recorded crashes, oldest first; only `program`'s if given.
+pub fn crashes(program: Option<Program>) -> Vec<Crash> {
+    let text = fs::read_to_string(crashes_path()).unwrap_or_default();
+    text.lines()
+        .filter_map(|line| serde_json::from_str::<Crash>(line).ok())
+        .filter(|crash| program.is_none_or(|p| crash.program == p))
+        .collect()
+}
+
+fn crashes_path() -> PathBuf {
+    boot::state_dir().join(CRASHES_FILE)
+}
+
+fn record_crash(crash: &Crash) -> Result<()> {
+    let path = crashes_path();
+    let dir = boot::state_dir();
+    fs::create_dir_all(&dir).map_err(|e| Error::io(&dir, e))?;
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    let record = serde_json::to_string(crash).expect("crash records serialize");
+    let text = append_record(&existing, &record);
+    fs::write(&path, text).map_err(|e| Error::io(&path, e))
+}
+
+/// `existing` crash records with `record` added, keeping the newest
+/// [`KEPT_CRASHES`].
+fn append_record(existing: &str, record: &str) -> String {
+    let mut lines: Vec<&str> = existing.lines().collect();
+    lines.push(record);
+    let excess = lines.len().saturating_sub(KEPT_CRASHES);
+    lines[excess..].iter().flat_map(|l| [*l, "\n"]).collect()
+}
+
+/// Failures in a row and the delay before the next restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Backoff {
+    failures: u32,
+    delay: Duration,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Backoff {
+            failures: 0,
+            delay: FIRST_DELAY,
+        }
+    }
+
+    /// Counts a failure after a run of `ran` and returns how long to wait
+    /// before restarting. A healthy run starts the count over.
+    fn failed(&mut self, ran: Duration) -> Duration {
+        if ran >= HEALTHY_AFTER {
+            *self = Backoff::new();
+        }
+        self.failures += 1;
+        let delay = self.delay;
+        self.delay = (delay * 2).min(MAX_DELAY);
+        delay
+    }
+}
+
+/// Runs `program` with the settings in its environment, restarting it as
+/// described above, until it exits cleanly, is stopped, or the restarts
+/// run out. Fails with the last exit status if the program did not end
+/// cleanly.
+pub fn run(program: Program, config: &Config) -> Result<()> {
+    let limit = boot::number(config, "RESTART_LIMIT", 5);
+    let supervisor = std::process::id();
+    let _signals = SignalGuard::install();
+    let mut status = Status {
+        program,
+        state: State::Restarting { at: now() },
+        restarts: 0,
+        supervisor,
+    };
+    let mut backoff = Backoff::new();
+
+    loop {
+        let started = Instant::now();
+        let (exit, hung, log) = run_once(program, config, &mut status)?;
+        let ran = started.elapsed();
+        let stopped = STOP.load(Ordering::SeqCst) != 0;
+        if exit.success() || stopped || stopped_by_request(exit) {
+            status.state = State::Stopped {
+                time: now(),
+                exit_code: exit.code(),
+                signal: exit.signal(),
+            };
+            status.save();
+            return Ok(());
+        }
+
+        let delay = backoff.failed(ran);
+        let failures = backoff.failures;
+        let crash = Crash {
+            program,
+            time: now(),
+            exit_code: exit.code(),
+            signal: exit.signal(),
+            seconds: ran.as_secs(),
+            failures,
+            hung,
+            log: if log.is_empty() {
+                program.log_file().map(|f| tail(&f)).unwrap_or_default()
+            } else {
+                log
+            },
+        };
+        if let Err(e) = record_crash(&crash) {
+            eprintln!("Could not record the crash: {e}");
+        }
+
+        if u64::from(failures) > limit {
+            status.state = State::GaveUp { time: now() };
+            status.save();
+            eprintln!(
+                "{} {}. It failed {failures} times in a row; not restarting it. \
+                 See `survon crashes {program}`.",
+                program.title(),
+                crash.describe(),
+            );
+            return Err(Error::Failed {
+                program: program.title(),
+                status: exit,
+            });
+        }
+
+        status.state = State::Restarting {
+            at: now() + delay.as_secs(),
+        };
+        status.save();
+        eprintln!(
+            "{} {}. Restarting it in {}s ({failures} of {limit}); Ctrl-C to stop.",
+            program.title(),
+            crash.describe(),
+            delay.as_secs(),
+        );
+        if !wait(delay) {
+            status.state = State::Stopped {
+                time: now(),
+                exit_code: exit.code(),
+                signal: exit.signal(),
+            };
+            status.save();
+            return Ok(());
+        }
+        status.restarts += 1;
+    }
+}
+
+/// Starts `program` once and waits for it, passing its stderr through.
+/// Returns how it ended, whether it was killed for a stale heartbeat, and
+/// the last lines of its stderr.
+fn run_once(
+    program: Program,
+    config: &Config,
+    status: &mut Status,
+) -> Result<(ExitStatus, bool, Vec<String>)> {
+    let heartbeat = Status::heartbeat(program);
+    // A heartbeat left from an earlier run must not count for this one.
+    let _ = fs::remove_file(&heartbeat);
+    if let Some(dir) = heartbeat.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let mut cmd = Command::new(program.binary());
+    cmd.current_dir(actions::home())
+        .envs(config.iter().map(|(key, value)| (key, value.to_string())))
+        .env(HEARTBEAT_VAR, &heartbeat)
+        .stderr(Stdio::piped());
+    // SAFETY: signal() is async-signal-safe. The child gets the default
+    // dispositions back, so Ctrl-C reaches it as usual.
+    unsafe {
+        cmd.pre_exec(|| {
+            for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+            Ok(())
+        });
+    }
+    let mut child = cmd.spawn().map_err(|e| Error::io(program.binary(), e))?;
+    CHILD.store(child.id() as i32, Ordering::SeqCst);
+    status.state = State::Running {
+        pid: child.id(),
+        since: now(),
+    };
+    status.save();
+
+    let lines = Arc::new(Mutex::new(VecDeque::with_capacity(LOG_LINES)));
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let (done, finished) = mpsc::channel();
+    {
+        let lines = Arc::clone(&lines);
+        thread::spawn(move || {
+            let mut out = io::stderr();
+            for line in BufReader::new(stderr).lines().map_while(|l| l.ok()) {
+                let _ = writeln!(out, "{line}");
+                let mut lines = lines.lock().expect("no panics while holding the log");
+                if lines.len() == LOG_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            }
+            let _ = done.send(());
+        });
+    }
+    let mut hung = false;
+    let exit = loop {
+        match child.try_wait() {
+            Ok(Some(exit)) => break Ok(exit),
+            Ok(None) => {}
+            Err(e) => break Err(Error::io(program.binary(), e)),
+        }
+        let beat = fs::metadata(&heartbeat).and_then(|m| m.modified()).ok();
+        if !hung && stale(beat, SystemTime::now()) {
+            eprintln!(
+                "{} has not updated its heartbeat in {}s; killing it.",
+                program.title(),
+                HEARTBEAT_TIMEOUT.as_secs(),
+            );
+            hung = true;
+            let _ = child.kill();
+        }
+        thread::sleep(POLL);
+    };
+    CHILD.store(0, Ordering::SeqCst);
+    // A process the program left behind may still hold its stderr open;
+    // keep passing its output on, but do not wait for it.
+    let _ = finished.recv_timeout(READER_GRACE);
+    let log = lines
+        .lock()
+        .expect("no panics while holding the log")
+        .iter()
+        .cloned()
+        .collect();
+    Ok((exit?, hung, log))
+}
+
+/// Whether a heartbeat last touched at `beat` is too old by `now`. No
+/// heartbeat yet is not stale: the program may not send any.
+fn stale(beat: Option<SystemTime>, now: SystemTime) -> bool {
+    beat.and_then(|beat| now.duration_since(beat).ok())
+        .is_some_and(|age| age > HEARTBEAT_TIMEOUT)
+}
+
+/// Sleeps for `delay` unless asked to stop first. Returns whether it slept
+/// the whole time.
+fn wait(delay: Duration) -> bool {
+    let until = Instant::now() + delay;
+    while Instant::now() < until {
+        if STOP.load(Ordering::SeqCst) != 0 {
+            return false;
+        }
+        thread::sleep(POLL);
+    }
+    STOP.load(Ordering::SeqCst) == 0
+}
+
+/// The last [`LOG_LINES`] lines of `path`.
+fn tail(path: &Path) -> Vec<String> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    let mut lines = VecDeque::with_capacity(LOG_LINES);
+    for line in BufReader::new(file).lines().map_while(|l| l.ok()) {
+        if lines.len() == LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+    lines.into()
+}
+
+/// Whether `status` is the program obeying a request to stop.
+fn stopped_by_request(status: ExitStatus) -> bool {
+    matches!(
+        status.signal(),
+        Some(libc::SIGINT | libc::SIGTERM | libc::SIGHUP)
+    )
+}
+
+fn ending(exit_code: Option<i32>, signal: Option<i32>) -> String {
+    match (exit_code, signal) {
+        (_, Some(signal)) => format!("was killed by signal {signal}"),
+        (Some(0), _) => "exited".to_string(),
+        (Some(code), _) => format!("exited with code {code}"),
+        (None, None) => "ended".to_string(),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// `secs` as a UTC date and time.
+fn clock(secs: u64) -> String {
+    survon_installer::log::timestamp(secs)
+}
+
+fn alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists.
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+/// Catches Ctrl-C, SIGTERM and SIGHUP while supervising, so the supervisor
+/// outlives its program and can record how it ended. Restores the previous
+/// handlers when dropped.
+struct SignalGuard {
+    previous: Vec<(libc::c_int, libc::sighandler_t)>,
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    STOP.store(signal, Ordering::SeqCst);
+    // Ctrl-C reaches the program from the terminal already.
+    let child = CHILD.load(Ordering::SeqCst);
+    if signal != libc::SIGINT && child > 0 {
+        // SAFETY: kill() is async-signal-safe.
+        unsafe {
+            libc::kill(child, signal);
+        }
+    }
+}
+
+impl SignalGuard {
+    fn install() -> Self {
+        STOP.store(0, Ordering::SeqCst);
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        let previous = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP]
+            .into_iter()
+            // SAFETY: the handler only touches atomics and calls kill().
+            .map(|signal| (signal, unsafe { libc::signal(signal, handler) }))
+            .collect();
+        SignalGuard { previous }
+    }
+}
+
+impl Drop for SignalGuard {
+    fn drop(&mut self) {
+        for (signal, handler) in &self.previous {
+            // SAFETY: restores a disposition signal() returned earlier.
+            unsafe {
+                libc::signal(*signal, *handler);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_a_minute() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<u64> = (0..9)
+            .map(|_| backoff.failed(Duration::from_secs(2)).as_secs())
+            .collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60, 60]);
+        assert_eq!(backoff.failures, 9);
+    }
+
+    #[test]
+    fn a_healthy_run_resets_the_backoff() {
+        let mut backoff = Backoff::new();
+        for _ in 0..4 {
+            backoff.failed(Duration::from_secs(5));
+        }
+        assert_eq!(backoff.failed(HEALTHY_AFTER), FIRST_DELAY);
+        assert_eq!(backoff.failures, 1);
+        assert_eq!(backoff.failed(Duration::ZERO), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn crash_records_are_trimmed_to_the_newest() {
+        let existing: String = (0..KEPT_CRASHES + 5).map(|n| format!("{n}\n")).collect();
+        let text = append_record(&existing, "new");
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), KEPT_CRASHES);
+        assert_eq!(lines[0], "6");
+        assert_eq!(lines[KEPT_CRASHES - 1], "new");
+        assert_eq!(append_record("", "first"), "first\n");
+    }
+
+    #[test]
+    fn only_an_old_heartbeat_is_stale() {
+        let now = SystemTime::now();
+        assert!(!stale(None, now));
+        assert!(!stale(Some(now - Duration::from_secs(5)), now));
+        assert!(stale(
+            Some(now - HEARTBEAT_TIMEOUT - Duration::from_secs(1)),
+            now
+        ));
+        // A clock that went backwards is not a hang.
+        assert!(!stale(Some(now + Duration::from_secs(5)), now));
+    }
+}
@@ -0,0 +1,29 @@
+#!/bin/bash
+
+# Writes a release manifest (see crates/survon-update) to stdout.
+#
+# Usage: release-manifest.sh NAME VERSION FILE...
+#
+# The manifest lists each FILE by base name with its size and sha256. Sign
+# the result with the release key to publish it:
+#   minisign -S -s release.key -m manifest.json -t "NAME VERSION"
+
+set -e
+
+if [ $# -lt 3 ]; then
+  echo "Usage: $0 NAME VERSION FILE..." >&2
+  exit 1
+fi
+
+NAME="$1"
+VERSION="$2"
+shift 2
+
+printf '{\n  "name": "%s",\n  "version": "%s",\n  "artifacts": {' "$NAME" "$VERSION"
+SEP=""
+for FILE in "$@"; do
+  printf '%s\n    "%s": { "sha256": "%s", "size": %s }' \
+    "$SEP" "$(basename "$FILE")" "$(sha256sum "$FILE" | cut -d' ' -f1)" "$(stat -c %s "$FILE")"
+  SEP=","
+done
+printf '\n  }\n}\n'